@@ -1,6 +1,9 @@
 #![doc = include_str!("../README.md")]
 
-use std::{env, fs, path::PathBuf};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
@@ -134,3 +137,53 @@ impl Config {
         Ok(())
     }
 }
+
+/// Holds the contents of a project's configuration file, `tree-sitter.json`.
+///
+/// Unlike the user's [`Config`], this file is checked into a project's repository, so that
+/// everyone working on the project shares the same settings. Like the user's configuration,
+/// individual tree-sitter components use the [`ProjectConfig::get`][] method to extract the
+/// fields that are specific to that component.
+#[derive(Debug)]
+pub struct ProjectConfig {
+    pub location: PathBuf,
+    pub config: Value,
+}
+
+impl ProjectConfig {
+    pub const FILE_NAME: &'static str = "tree-sitter.json";
+
+    /// Locates and loads the project's configuration file, which is the `tree-sitter.json` file
+    /// in the given directory, or in the nearest of its ancestors that contains one.
+    pub fn find(dir: &Path) -> Result<Option<Self>> {
+        let Some(location) = dir
+            .ancestors()
+            .map(|dir| dir.join(Self::FILE_NAME))
+            .find(|path| path.is_file())
+        else {
+            return Ok(None);
+        };
+        let content = fs::read_to_string(&location)
+            .with_context(|| format!("Failed to read {}", &location.to_string_lossy()))?;
+        let config = serde_json::from_str(&content)
+            .with_context(|| format!("Bad JSON config {}", &location.to_string_lossy()))?;
+        Ok(Some(Self { location, config }))
+    }
+
+    /// The directory that contains the project's configuration file.
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        self.location.parent().unwrap()
+    }
+
+    /// Parses a component-specific configuration from the project's configuration file, in the
+    /// same way as [`Config::get`][].
+    pub fn get<C>(&self) -> Result<C>
+    where
+        C: for<'de> Deserialize<'de>,
+    {
+        let config = serde_json::from_value(self.config.clone())
+            .with_context(|| format!("Bad project config {}", self.location.to_string_lossy()))?;
+        Ok(config)
+    }
+}
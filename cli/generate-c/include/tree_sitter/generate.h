@@ -0,0 +1,39 @@
+#ifndef TREE_SITTER_GENERATE_H_
+#define TREE_SITTER_GENERATE_H_
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#include <stdint.h>
+
+typedef struct TSGeneratedParser TSGeneratedParser;
+
+// Generate a parser from the contents of a `grammar.json` file, with the
+// default options of `tree-sitter generate`. This always returns a
+// `TSGeneratedParser`, which must be deleted with `ts_generated_parser_delete`.
+TSGeneratedParser *ts_generate_parser(
+  const char *grammar_json,
+  uint32_t grammar_json_len
+);
+
+// Delete the result of generating a parser.
+void ts_generated_parser_delete(TSGeneratedParser *);
+
+// Get the message describing why generation failed, or NULL if it succeeded.
+// A panic in the generator is reported as a failure.
+const char *ts_generated_parser_error(const TSGeneratedParser *);
+
+// Access the generated files, whose paths are relative to the grammar's `src`
+// directory. `ts_generated_parser_file_name` returns NULL if the index is out
+// of range, and `ts_generated_parser_file` returns NULL if the given file
+// wasn't generated. The strings are owned by the `TSGeneratedParser`.
+uint32_t ts_generated_parser_file_count(const TSGeneratedParser *);
+const char *ts_generated_parser_file_name(const TSGeneratedParser *, uint32_t index);
+const char *ts_generated_parser_file(const TSGeneratedParser *, const char *file_name);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif  // TREE_SITTER_GENERATE_H_
@@ -0,0 +1,256 @@
+use std::{
+    any::Any,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    process::abort,
+    ptr, slice, str,
+};
+
+use anyhow::{anyhow, Result};
+use tree_sitter_cli::generate::{generate_parser, GenerateOptions, GeneratedOutput, ALLOC_HEADER};
+
+/// The files that generating a parser produced, or the reason that it failed.
+pub struct TSGeneratedParser {
+    files: Vec<(CString, CString)>,
+    error: Option<CString>,
+}
+
+/// Generate a parser from the contents of a `grammar.json` file, with the default options
+/// of `tree-sitter generate`.
+///
+/// Always returns a [`TSGeneratedParser`] instance, which must be deleted with
+/// [`ts_generated_parser_delete`]. If generation failed, [`ts_generated_parser_error`]
+/// returns the error message. A panic in the generator is reported the same way, instead
+/// of unwinding into the caller.
+///
+/// # Safety
+///
+/// `grammar_json` must be non-null and must point to at least `grammar_json_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn ts_generate_parser(
+    grammar_json: *const c_char,
+    grammar_json_len: u32,
+) -> *mut TSGeneratedParser {
+    let grammar_json = slice::from_raw_parts(grammar_json.cast::<u8>(), grammar_json_len as usize);
+    let generated_parser = generated_parser(|| {
+        let grammar_json =
+            str::from_utf8(grammar_json).map_err(|_| anyhow!("The grammar is not valid UTF-8"))?;
+        generate_parser(grammar_json, &GenerateOptions::default())
+    });
+    Box::into_raw(Box::new(generated_parser))
+}
+
+/// Deletes a [`TSGeneratedParser`] instance.
+///
+/// # Safety
+///
+/// `this` must be non-null and must be a valid pointer to a [`TSGeneratedParser`] instance
+/// created by [`ts_generate_parser`].
+///
+/// It cannot be used after this function is called.
+#[no_mangle]
+pub unsafe extern "C" fn ts_generated_parser_delete(this: *mut TSGeneratedParser) {
+    drop(Box::from_raw(this));
+}
+
+/// Get the message describing why generation failed, as a C-style string, or null if it
+/// succeeded.
+///
+/// # Safety
+///
+/// `this` must be non-null and must be a valid pointer to a [`TSGeneratedParser`] instance
+/// created by [`ts_generate_parser`].
+///
+/// The returned pointer must not outlive the [`TSGeneratedParser`] instance.
+#[no_mangle]
+pub unsafe extern "C" fn ts_generated_parser_error(
+    this: *const TSGeneratedParser,
+) -> *const c_char {
+    let this = unwrap_ptr(this);
+    this.error
+        .as_ref()
+        .map_or(ptr::null(), |error| error.as_ptr())
+}
+
+/// Get the number of generated files, which is zero if generation failed.
+///
+/// # Safety
+///
+/// `this` must be non-null and must be a valid pointer to a [`TSGeneratedParser`] instance
+/// created by [`ts_generate_parser`].
+#[no_mangle]
+pub unsafe extern "C" fn ts_generated_parser_file_count(this: *const TSGeneratedParser) -> u32 {
+    let this = unwrap_ptr(this);
+    this.files.len() as u32
+}
+
+/// Get the path of a generated file, relative to the grammar's `src` directory, as a
+/// C-style string, or null if `index` is not less than the number of files.
+///
+/// # Safety
+///
+/// `this` must be non-null and must be a valid pointer to a [`TSGeneratedParser`] instance
+/// created by [`ts_generate_parser`].
+///
+/// The returned pointer must not outlive the [`TSGeneratedParser`] instance.
+#[no_mangle]
+pub unsafe extern "C" fn ts_generated_parser_file_name(
+    this: *const TSGeneratedParser,
+    index: u32,
+) -> *const c_char {
+    let this = unwrap_ptr(this);
+    this.files
+        .get(index as usize)
+        .map_or(ptr::null(), |(name, _)| name.as_ptr())
+}
+
+/// Get the contents of a generated file, as a C-style string, or null if that file wasn't
+/// generated.
+///
+/// # Safety
+///
+/// `this` must be non-null and must be a valid pointer to a [`TSGeneratedParser`] instance
+/// created by [`ts_generate_parser`], and `file_name` must be a non-null C-style string.
+///
+/// The returned pointer must not outlive the [`TSGeneratedParser`] instance.
+#[no_mangle]
+pub unsafe extern "C" fn ts_generated_parser_file(
+    this: *const TSGeneratedParser,
+    file_name: *const c_char,
+) -> *const c_char {
+    let this = unwrap_ptr(this);
+    let file_name = CStr::from_ptr(unwrap_ptr(file_name));
+    this.files
+        .iter()
+        .find(|(name, _)| name.as_c_str() == file_name)
+        .map_or(ptr::null(), |(_, contents)| contents.as_ptr())
+}
+
+/// Run the generator, catching a panic so that it doesn't unwind across the C API.
+fn generated_parser(generate: impl FnOnce() -> Result<GeneratedOutput>) -> TSGeneratedParser {
+    let result = panic::catch_unwind(AssertUnwindSafe(generate)).unwrap_or_else(|payload| {
+        Err(anyhow!(
+            "The parser generator panicked: {}",
+            panic_message(&*payload)
+        ))
+    });
+    match result {
+        Ok(output) => {
+            let mut files = vec![
+                ("parser.c", Some(output.parser_c)),
+                ("lexer.c", output.lexer_c),
+                ("node-types.json", Some(output.node_types_json)),
+                ("tree_sitter/alloc.h", Some(ALLOC_HEADER.to_string())),
+                (
+                    "tree_sitter/array.h",
+                    Some(tree_sitter::ARRAY_HEADER.to_string()),
+                ),
+                (
+                    "tree_sitter/parser.h",
+                    Some(tree_sitter::PARSER_HEADER.to_string()),
+                ),
+            ];
+            files.retain(|(_, contents)| contents.is_some());
+            TSGeneratedParser {
+                files: files
+                    .into_iter()
+                    .map(|(name, contents)| (to_c_string(name), to_c_string(&contents.unwrap())))
+                    .collect(),
+                error: None,
+            }
+        }
+        Err(error) => TSGeneratedParser {
+            files: Vec::new(),
+            error: Some(to_c_string(&format!("{error:#}"))),
+        },
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("unknown error")
+}
+
+// Generated files don't contain null bytes, but an error message might quote one from the
+// grammar.
+fn to_c_string(value: &str) -> CString {
+    CString::new(value.replace('\0', "\\0")).unwrap()
+}
+
+unsafe fn unwrap_ptr<'a, T>(result: *const T) -> &'a T {
+    result.as_ref().unwrap_or_else(|| {
+        eprintln!("{}:{} - pointer must not be null", file!(), line!());
+        abort();
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_parser_through_c_api() {
+        let file_contents = |generated_parser, name: &str| unsafe {
+            let name = CString::new(name).unwrap();
+            let contents = ts_generated_parser_file(generated_parser, name.as_ptr());
+            (!contents.is_null()).then(|| CStr::from_ptr(contents).to_str().unwrap().to_string())
+        };
+
+        let grammar_json =
+            r#"{"name": "c_api", "rules": {"program": {"type": "STRING", "value": "a"}}}"#;
+        unsafe {
+            let generated_parser =
+                ts_generate_parser(grammar_json.as_ptr().cast(), grammar_json.len() as u32);
+            assert!(ts_generated_parser_error(generated_parser).is_null());
+            let file_names = (0..ts_generated_parser_file_count(generated_parser))
+                .map(|i| {
+                    CStr::from_ptr(ts_generated_parser_file_name(generated_parser, i))
+                        .to_str()
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                file_names,
+                [
+                    "parser.c",
+                    "node-types.json",
+                    "tree_sitter/alloc.h",
+                    "tree_sitter/array.h",
+                    "tree_sitter/parser.h"
+                ]
+            );
+            assert!(file_contents(generated_parser, "parser.c")
+                .unwrap()
+                .contains("tree_sitter_c_api"));
+            assert_eq!(file_contents(generated_parser, "lexer.c"), None);
+            assert!(ts_generated_parser_file_name(generated_parser, 5).is_null());
+            ts_generated_parser_delete(generated_parser);
+
+            let grammar_json =
+                r#"{"name": "c_api", "rules": {"program": {"type": "SYMBOL", "name": "missing"}}}"#;
+            let generated_parser =
+                ts_generate_parser(grammar_json.as_ptr().cast(), grammar_json.len() as u32);
+            let error = CStr::from_ptr(ts_generated_parser_error(generated_parser));
+            assert_eq!(error.to_str().unwrap(), "Undefined symbol `missing`");
+            assert_eq!(ts_generated_parser_file_count(generated_parser), 0);
+            ts_generated_parser_delete(generated_parser);
+        }
+    }
+
+    #[test]
+    fn test_panic_in_generator() {
+        let generated_parser = generated_parser(|| panic!("no more states"));
+        unsafe {
+            let error = CStr::from_ptr(ts_generated_parser_error(&generated_parser));
+            assert_eq!(
+                error.to_str().unwrap(),
+                "The parser generator panicked: no more states"
+            );
+            assert_eq!(ts_generated_parser_file_count(&generated_parser), 0);
+        }
+    }
+}
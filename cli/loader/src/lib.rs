@@ -1,5 +1,7 @@
 #![doc = include_str!("../README.md")]
 
+#[cfg(not(feature = "tree-sitter-highlight"))]
+use std::marker::PhantomData;
 #[cfg(any(feature = "tree-sitter-highlight", feature = "tree-sitter-tags"))]
 use std::ops::Range;
 #[cfg(feature = "tree-sitter-highlight")]
@@ -45,6 +47,12 @@ pub struct Config {
         deserialize_with = "deserialize_parser_directories"
     )]
     pub parser_directories: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(
+        rename = "library-directories",
+        deserialize_with = "deserialize_parser_directories"
+    )]
+    pub library_directories: Vec<PathBuf>,
 }
 
 // Replace `~` or `$HOME` with home path string.
@@ -88,6 +96,7 @@ impl Config {
                 home_dir.join("dev"),
                 home_dir.join("git"),
             ],
+            library_directories: Vec::new(),
         }
     }
 }
@@ -116,6 +125,8 @@ pub struct LanguageConfiguration<'a> {
     highlight_names: &'a Mutex<Vec<String>>,
     #[cfg(feature = "tree-sitter-highlight")]
     use_all_highlight_names: bool,
+    #[cfg(not(feature = "tree-sitter-highlight"))]
+    _phantom: PhantomData<&'a ()>,
 }
 
 pub struct Loader {
@@ -125,6 +136,7 @@ pub struct Loader {
     language_configuration_ids_by_file_type: HashMap<String, Vec<usize>>,
     language_configuration_in_current_path: Option<usize>,
     language_configuration_ids_by_first_line_regex: HashMap<String, Vec<usize>>,
+    library_directories: Vec<PathBuf>,
     #[cfg(feature = "tree-sitter-highlight")]
     highlight_names: Box<Mutex<Vec<String>>>,
     #[cfg(feature = "tree-sitter-highlight")]
@@ -141,6 +153,7 @@ pub struct CompileConfig<'a> {
     pub header_paths: Vec<&'a Path>,
     pub parser_path: PathBuf,
     pub scanner_path: Option<PathBuf>,
+    pub lexer_path: Option<PathBuf>,
     pub external_files: Option<&'a [PathBuf]>,
     pub output_path: Option<PathBuf>,
     pub flags: &'a [&'a str],
@@ -160,6 +173,7 @@ impl<'a> CompileConfig<'a> {
             header_paths: vec![src_path],
             parser_path: src_path.join("parser.c"),
             scanner_path: None,
+            lexer_path: None,
             external_files: externals,
             output_path,
             flags: &[],
@@ -193,6 +207,7 @@ impl Loader {
             language_configuration_ids_by_file_type: HashMap::new(),
             language_configuration_in_current_path: None,
             language_configuration_ids_by_first_line_regex: HashMap::new(),
+            library_directories: Vec::new(),
             #[cfg(feature = "tree-sitter-highlight")]
             highlight_names: Box::new(Mutex::new(Vec::new())),
             #[cfg(feature = "tree-sitter-highlight")]
@@ -220,12 +235,14 @@ impl Loader {
     }
 
     pub fn find_all_languages(&mut self, config: &Config) -> Result<()> {
-        if config.parser_directories.is_empty() {
+        if config.parser_directories.is_empty() && config.library_directories.is_empty() {
             eprintln!("Warning: You have not configured any parser directories!");
             eprintln!("Please run `tree-sitter init-config` and edit the resulting");
             eprintln!("configuration file to indicate where we should look for");
             eprintln!("language grammars.\n");
         }
+        self.library_directories
+            .clone_from(&config.library_directories);
         for parser_container_dir in &config.parser_directories {
             if let Ok(entries) = fs::read_dir(parser_container_dir) {
                 for entry in entries {
@@ -283,28 +300,71 @@ impl Loader {
         Ok(None)
     }
 
+    pub fn language_configuration_for_name(
+        &self,
+        name: &str,
+    ) -> Result<Option<(Language, &LanguageConfiguration<'_>)>> {
+        for configuration in &self.language_configurations {
+            if configuration.language_name == name {
+                let language = self.language_for_id(configuration.language_id)?;
+                return Ok(Some((language, configuration)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Find a language by its name, either among the grammars in the parser directories,
+    /// or among the compiled libraries in the library directories. A library for a
+    /// language named `foo` is called `foo`, `tree-sitter-foo` or `libtree-sitter-foo`,
+    /// with the platform's extension for dynamic libraries.
+    pub fn language_for_name(&self, name: &str) -> Result<Option<Language>> {
+        if let Some((language, _)) = self.language_configuration_for_name(name)? {
+            return Ok(Some(language));
+        }
+        for library_dir in &self.library_directories {
+            for file_name in [
+                name.to_string(),
+                format!("tree-sitter-{name}"),
+                format!("libtree-sitter-{name}"),
+            ] {
+                let library_path = library_dir
+                    .join(file_name)
+                    .with_extension(env::consts::DLL_EXTENSION);
+                if library_path.is_file() {
+                    let language_fn_name =
+                        format!("tree_sitter_{}", replace_dashes_with_underscores(name));
+                    return Self::load_language_from_dylib(&library_path, &language_fn_name)
+                        .map(Some);
+                }
+            }
+        }
+        Ok(None)
+    }
+
     pub fn language_configuration_for_first_line_regex(
         &self,
         path: &Path,
     ) -> Result<Option<(Language, &LanguageConfiguration)>> {
-        self.language_configuration_ids_by_first_line_regex
-            .iter()
-            .try_fold(None, |_, (regex, ids)| {
-                if let Some(regex) = Self::regex(Some(regex)) {
-                    let file = fs::File::open(path)?;
-                    let reader = BufReader::new(file);
-                    let first_line = reader.lines().next().transpose()?;
-                    if let Some(first_line) = first_line {
-                        if regex.is_match(&first_line) && !ids.is_empty() {
-                            let configuration = &self.language_configurations[ids[0]];
-                            let language = self.language_for_id(configuration.language_id)?;
-                            return Ok(Some((language, configuration)));
-                        }
-                    }
+        if self
+            .language_configuration_ids_by_first_line_regex
+            .is_empty()
+        {
+            return Ok(None);
+        }
+        let file = fs::File::open(path)?;
+        let Some(first_line) = BufReader::new(file).lines().next().transpose()? else {
+            return Ok(None);
+        };
+        for (regex, ids) in &self.language_configuration_ids_by_first_line_regex {
+            if let Some(regex) = Self::regex(Some(regex)) {
+                if regex.is_match(&first_line) && !ids.is_empty() {
+                    let configuration = &self.language_configurations[ids[0]];
+                    let language = self.language_for_id(configuration.language_id)?;
+                    return Ok(Some((language, configuration)));
                 }
-
-                Ok(None)
-            })
+            }
+        }
+        Ok(None)
     }
 
     pub fn language_configuration_for_file_name(
@@ -488,6 +548,7 @@ impl Loader {
 
         let parser_path = config.src_path.join("parser.c");
         config.scanner_path = self.get_scanner_path(config.src_path);
+        config.lexer_path = self.get_lexer_path(config.src_path);
 
         let mut paths_to_check = vec![parser_path];
 
@@ -495,6 +556,10 @@ impl Loader {
             paths_to_check.push(scanner_path.clone());
         }
 
+        if let Some(lexer_path) = config.lexer_path.as_ref() {
+            paths_to_check.push(lexer_path.clone());
+        }
+
         paths_to_check.extend(
             config
                 .external_files
@@ -583,8 +648,12 @@ impl Loader {
             }
         }
 
-        let library = unsafe { Library::new(&output_path) }
-            .with_context(|| format!("Error opening dynamic library {output_path:?}"))?;
+        Self::load_language_from_dylib(&output_path, &language_fn_name)
+    }
+
+    fn load_language_from_dylib(library_path: &Path, language_fn_name: &str) -> Result<Language> {
+        let library = unsafe { Library::new(library_path) }
+            .with_context(|| format!("Error opening dynamic library {library_path:?}"))?;
         let language = unsafe {
             let language_fn = library
                 .get::<Symbol<unsafe extern "C" fn() -> Language>>(language_fn_name.as_bytes())
@@ -600,7 +669,7 @@ impl Loader {
         config: &CompileConfig,
         lock_file: &fs::File,
         lock_path: &Path,
-    ) -> Result<(), Error> {
+    ) -> Result<()> {
         let mut cc_config = cc::Build::new();
         cc_config
             .cargo_metadata(false)
@@ -616,6 +685,10 @@ impl Loader {
             cc_config.file(scanner_path);
         }
 
+        if let Some(lexer_path) = config.lexer_path.as_ref() {
+            cc_config.file(lexer_path);
+        }
+
         if self.debug_build {
             cc_config.opt_level(0).extra_warnings(true);
         } else {
@@ -763,7 +836,7 @@ impl Loader {
         scanner_filename: Option<&Path>,
         output_path: &Path,
         force_docker: bool,
-    ) -> Result<(), Error> {
+    ) -> Result<()> {
         #[derive(PartialEq, Eq)]
         enum EmccSource {
             Native,
@@ -880,6 +953,10 @@ impl Loader {
             command.arg(scanner_filename);
         }
 
+        if self.get_lexer_path(src_path).is_some() {
+            command.arg("lexer.c");
+        }
+
         command.arg("parser.c");
         let status = command
             .spawn()
@@ -978,114 +1055,149 @@ impl Loader {
             tree_sitter: Vec<LanguageConfigurationJSON>,
         }
 
+        #[derive(Deserialize)]
+        struct ProjectJSON {
+            grammars: Option<Vec<LanguageConfigurationJSON>>,
+        }
+
         #[derive(Deserialize)]
         struct GrammarJSON {
             name: String,
         }
 
+        // The languages at a path are only added once, even if the path is both in a parser
+        // directory and the current directory.
+        if let Some(start) = self
+            .language_configurations
+            .iter()
+            .position(|c| c.root_path == parser_path)
+        {
+            let count = self.language_configurations[start..]
+                .iter()
+                .take_while(|c| c.root_path == parser_path)
+                .count();
+            if set_current_path_config && self.language_configuration_in_current_path.is_none() {
+                self.language_configuration_in_current_path = Some(start);
+            }
+            return Ok(&self.language_configurations[start..start + count]);
+        }
+
         let initial_language_configuration_count = self.language_configurations.len();
 
-        if let Ok(package_json_contents) = fs::read_to_string(parser_path.join("package.json")) {
-            let package_json = serde_json::from_str::<PackageJSON>(&package_json_contents);
-            if let Ok(package_json) = package_json {
-                let language_count = self.languages_by_id.len();
-                for config_json in package_json.tree_sitter {
-                    // Determine the path to the parser directory. This can be specified in
-                    // the package.json, but defaults to the directory containing the package.json.
-                    let language_path = parser_path.join(config_json.path);
-
-                    let grammar_path = language_path.join("src").join("grammar.json");
-                    let mut grammar_file = fs::File::open(grammar_path)
-                        .with_context(|| "Failed to read grammar.json")?;
-                    let grammar_json: GrammarJSON =
-                        serde_json::from_reader(BufReader::new(&mut grammar_file))
-                            .with_context(|| "Failed to parse grammar.json")?;
-
-                    // Determine if a previous language configuration in this package.json file
-                    // already uses the same language.
-                    let mut language_id = None;
-                    for (id, (path, _, _)) in
-                        self.languages_by_id.iter().enumerate().skip(language_count)
-                    {
-                        if language_path == *path {
-                            language_id = Some(id);
-                        }
-                    }
+        // A project's tree-sitter.json lists its grammars, in place of the package.json.
+        let project_json = fs::read_to_string(parser_path.join("tree-sitter.json"))
+            .ok()
+            .map(|contents| {
+                serde_json::from_str::<ProjectJSON>(&contents)
+                    .with_context(|| "Failed to parse tree-sitter.json")
+            })
+            .transpose()?;
+        let config_jsons = if let Some(grammars) = project_json.and_then(|p| p.grammars) {
+            grammars
+        } else if let Ok(package_json_contents) =
+            fs::read_to_string(parser_path.join("package.json"))
+        {
+            serde_json::from_str::<PackageJSON>(&package_json_contents)
+                .map(|package_json| package_json.tree_sitter)
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
 
-                    // If not, add a new language path to the list.
-                    let language_id = if let Some(language_id) = language_id {
-                        language_id
-                    } else {
-                        self.languages_by_id.push((
-                            language_path,
-                            OnceCell::new(),
-                            config_json.external_files.clone().into_vec().map(|files| {
-                                files.into_iter()
-                                    .map(|path| {
-                                       let path = parser_path.join(path);
-                                        // prevent p being above/outside of parser_path
-                                        if path.starts_with(parser_path) {
-                                            Ok(path)
-                                        } else {
-                                            Err(anyhow!("External file path {path:?} is outside of parser directory {parser_path:?}"))
-                                        }
-                                    })
-                                    .collect::<Result<Vec<_>>>()
-                            }).transpose()?,
-                        ));
-                        self.languages_by_id.len() - 1
-                    };
-
-                    let configuration = LanguageConfiguration {
-                        root_path: parser_path.to_path_buf(),
-                        language_name: grammar_json.name.clone(),
-                        scope: config_json.scope,
-                        language_id,
-                        file_types: config_json.file_types.unwrap_or_default(),
-                        content_regex: Self::regex(config_json.content_regex.as_deref()),
-                        first_line_regex: Self::regex(config_json.first_line_regex.as_deref()),
-                        injection_regex: Self::regex(config_json.injection_regex.as_deref()),
-                        injections_filenames: config_json.injections.into_vec(),
-                        locals_filenames: config_json.locals.into_vec(),
-                        tags_filenames: config_json.tags.into_vec(),
-                        highlights_filenames: config_json.highlights.into_vec(),
-                        #[cfg(feature = "tree-sitter-highlight")]
-                        highlight_config: OnceCell::new(),
-                        #[cfg(feature = "tree-sitter-tags")]
-                        tags_config: OnceCell::new(),
-                        #[cfg(feature = "tree-sitter-highlight")]
-                        highlight_names: &self.highlight_names,
-                        #[cfg(feature = "tree-sitter-highlight")]
-                        use_all_highlight_names: self.use_all_highlight_names,
-                    };
-
-                    for file_type in &configuration.file_types {
-                        self.language_configuration_ids_by_file_type
-                            .entry(file_type.to_string())
-                            .or_default()
-                            .push(self.language_configurations.len());
-                    }
-                    if let Some(first_line_regex) = &configuration.first_line_regex {
-                        self.language_configuration_ids_by_first_line_regex
-                            .entry(first_line_regex.to_string())
-                            .or_default()
-                            .push(self.language_configurations.len());
-                    }
+        let language_count = self.languages_by_id.len();
+        for config_json in config_jsons {
+            // Determine the path to the parser directory. This can be specified in
+            // the configuration, but defaults to the directory containing it.
+            let language_path = parser_path.join(config_json.path);
 
-                    self.language_configurations.push(unsafe {
-                        mem::transmute::<LanguageConfiguration<'_>, LanguageConfiguration<'static>>(
-                            configuration,
-                        )
-                    });
-
-                    if set_current_path_config
-                        && self.language_configuration_in_current_path.is_none()
-                    {
-                        self.language_configuration_in_current_path =
-                            Some(self.language_configurations.len() - 1);
-                    }
+            let grammar_path = language_path.join("src").join("grammar.json");
+            let mut grammar_file =
+                fs::File::open(grammar_path).with_context(|| "Failed to read grammar.json")?;
+            let grammar_json: GrammarJSON =
+                serde_json::from_reader(BufReader::new(&mut grammar_file))
+                    .with_context(|| "Failed to parse grammar.json")?;
+
+            // Determine if a previous language configuration in this file
+            // already uses the same language.
+            let mut language_id = None;
+            for (id, (path, _, _)) in self.languages_by_id.iter().enumerate().skip(language_count) {
+                if language_path == *path {
+                    language_id = Some(id);
                 }
             }
+
+            // If not, add a new language path to the list.
+            let language_id = if let Some(language_id) = language_id {
+                language_id
+            } else {
+                self.languages_by_id.push((
+                    language_path,
+                    OnceCell::new(),
+                    config_json.external_files.clone().into_vec().map(|files| {
+                        files.into_iter()
+                            .map(|path| {
+                               let path = parser_path.join(path);
+                                // prevent p being above/outside of parser_path
+                                if path.starts_with(parser_path) {
+                                    Ok(path)
+                                } else {
+                                    Err(anyhow!("External file path {path:?} is outside of parser directory {parser_path:?}"))
+                                }
+                            })
+                            .collect::<Result<Vec<_>>>()
+                    }).transpose()?,
+                ));
+                self.languages_by_id.len() - 1
+            };
+
+            let configuration = LanguageConfiguration {
+                root_path: parser_path.to_path_buf(),
+                language_name: grammar_json.name.clone(),
+                scope: config_json.scope,
+                language_id,
+                file_types: config_json.file_types.unwrap_or_default(),
+                content_regex: Self::regex(config_json.content_regex.as_deref()),
+                first_line_regex: Self::regex(config_json.first_line_regex.as_deref()),
+                injection_regex: Self::regex(config_json.injection_regex.as_deref()),
+                injections_filenames: config_json.injections.into_vec(),
+                locals_filenames: config_json.locals.into_vec(),
+                tags_filenames: config_json.tags.into_vec(),
+                highlights_filenames: config_json.highlights.into_vec(),
+                #[cfg(feature = "tree-sitter-highlight")]
+                highlight_config: OnceCell::new(),
+                #[cfg(feature = "tree-sitter-tags")]
+                tags_config: OnceCell::new(),
+                #[cfg(feature = "tree-sitter-highlight")]
+                highlight_names: &self.highlight_names,
+                #[cfg(feature = "tree-sitter-highlight")]
+                use_all_highlight_names: self.use_all_highlight_names,
+                #[cfg(not(feature = "tree-sitter-highlight"))]
+                _phantom: PhantomData,
+            };
+
+            for file_type in &configuration.file_types {
+                self.language_configuration_ids_by_file_type
+                    .entry(file_type.to_string())
+                    .or_default()
+                    .push(self.language_configurations.len());
+            }
+            if let Some(first_line_regex) = &configuration.first_line_regex {
+                self.language_configuration_ids_by_first_line_regex
+                    .entry(first_line_regex.to_string())
+                    .or_default()
+                    .push(self.language_configurations.len());
+            }
+
+            self.language_configurations.push(unsafe {
+                mem::transmute::<LanguageConfiguration<'_>, LanguageConfiguration<'static>>(
+                    configuration,
+                )
+            });
+
+            if set_current_path_config && self.language_configuration_in_current_path.is_none() {
+                self.language_configuration_in_current_path =
+                    Some(self.language_configurations.len() - 1);
+            }
         }
 
         if self.language_configurations.len() == initial_language_configuration_count
@@ -1118,6 +1230,8 @@ impl Loader {
                 highlight_names: &self.highlight_names,
                 #[cfg(feature = "tree-sitter-highlight")]
                 use_all_highlight_names: self.use_all_highlight_names,
+                #[cfg(not(feature = "tree-sitter-highlight"))]
+                _phantom: PhantomData,
             };
             self.language_configurations.push(unsafe {
                 mem::transmute::<LanguageConfiguration<'_>, LanguageConfiguration<'static>>(
@@ -1160,6 +1274,8 @@ impl Loader {
             })?
         {
             Ok(lang)
+        } else if let Some(lang) = self.language_configuration_for_first_line_regex(path)? {
+            Ok(lang.0)
         } else if let Some(id) = self.language_configuration_in_current_path {
             Ok(self.language_for_id(self.language_configurations[id].language_id)?)
         } else if let Some(lang) = self
@@ -1169,8 +1285,6 @@ impl Loader {
             .cloned()
         {
             Ok(lang.0)
-        } else if let Some(lang) = self.language_configuration_for_first_line_regex(path)? {
-            Ok(lang.0)
         } else {
             Err(anyhow!("No language found"))
         }
@@ -1194,6 +1308,13 @@ impl Loader {
         let path = src_path.join("scanner.c");
         path.exists().then_some(path)
     }
+
+    /// The lexer that `tree-sitter generate --split-lexer` writes alongside the parser.
+    #[must_use]
+    pub fn get_lexer_path(&self, src_path: &Path) -> Option<PathBuf> {
+        let path = src_path.join("lexer.c");
+        path.exists().then_some(path)
+    }
 }
 
 impl<'a> LanguageConfiguration<'a> {
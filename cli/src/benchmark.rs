@@ -0,0 +1,236 @@
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Language, Parser, Tree};
+
+use crate::{
+    fuzz::{edits::get_random_edit, random::Rand},
+    parse::perform_edit,
+};
+
+pub struct BenchmarkOptions {
+    pub iterations: usize,
+    pub edits: usize,
+}
+
+/// The results of benchmarking one file. Durations are medians, in microseconds.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FileBenchmark {
+    pub path: String,
+    pub bytes: usize,
+    pub parse_us: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reparse_us: Option<f64>,
+}
+
+impl FileBenchmark {
+    #[must_use]
+    pub fn bytes_per_ms(&self) -> f64 {
+        if self.parse_us > 0.0 {
+            self.bytes as f64 * 1000.0 / self.parse_us
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Measure how long it takes to parse the file from scratch, and to re-parse it
+/// incrementally after a random edit. The edits are seeded by their index, so that
+/// each run of the benchmark performs the same edits.
+pub fn benchmark_file(
+    parser: &mut Parser,
+    language: &Language,
+    path: &Path,
+    options: &BenchmarkOptions,
+) -> Result<FileBenchmark> {
+    parser.set_language(language)?;
+    let source_code =
+        fs::read(path).with_context(|| format!("Error reading source file {path:?}"))?;
+    let parse = |parser: &mut Parser, input: &[u8], old_tree: Option<&Tree>| {
+        parser
+            .parse(input, old_tree)
+            .ok_or_else(|| anyhow!("Failed to parse {path:?}"))
+    };
+
+    let mut parse_durations = Vec::with_capacity(options.iterations);
+    let mut tree = None;
+    for _ in 0..options.iterations.max(1) {
+        let time = Instant::now();
+        tree = Some(parse(parser, &source_code, None)?);
+        parse_durations.push(time.elapsed());
+    }
+    let tree = tree.unwrap();
+
+    let mut reparse_durations = Vec::with_capacity(options.edits);
+    for seed in 0..options.edits {
+        let mut rand = Rand::new(seed);
+        let mut input = source_code.clone();
+        let mut edited_tree = tree.clone();
+        let edit = get_random_edit(&mut rand, &input);
+        perform_edit(&mut edited_tree, &mut input, &edit)?;
+
+        let time = Instant::now();
+        parse(parser, &input, Some(&edited_tree))?;
+        reparse_durations.push(time.elapsed());
+    }
+
+    Ok(FileBenchmark {
+        path: path.to_string_lossy().to_string(),
+        bytes: source_code.len(),
+        parse_us: median_us(&mut parse_durations).unwrap_or_default(),
+        reparse_us: median_us(&mut reparse_durations),
+    })
+}
+
+fn median_us(durations: &mut [Duration]) -> Option<f64> {
+    if durations.is_empty() {
+        return None;
+    }
+    durations.sort_unstable();
+    Some((durations[durations.len() / 2].as_secs_f64() * 1e7).round() / 10.0)
+}
+
+/// Print a table of the results, with the change from the baseline's result for the
+/// same file, if there is one.
+pub fn print_results(results: &[FileBenchmark], baseline: Option<&[FileBenchmark]>) {
+    let path_width = results
+        .iter()
+        .map(|result| result.path.chars().count())
+        .chain(["path".len()])
+        .max()
+        .unwrap();
+    println!(
+        "{:path_width$}  {:>10}  {:>20}  {:>10}  {:>20}",
+        "path", "bytes", "parse (µs)", "bytes/ms", "reparse (µs)"
+    );
+    for result in results {
+        let baseline_result = baseline_result(baseline, result);
+        let cell = |value: Option<f64>, baseline_value: Option<f64>| {
+            let Some(value) = value else {
+                return "-".to_string();
+            };
+            match baseline_value.and_then(|baseline_value| percent_change(baseline_value, value)) {
+                Some(change) => format!("{value} ({change:+.1}%)"),
+                None => value.to_string(),
+            }
+        };
+        println!(
+            "{:path_width$}  {:>10}  {:>20}  {:>10.0}  {:>20}",
+            result.path,
+            result.bytes,
+            cell(
+                Some(result.parse_us),
+                baseline_result.map(|result| result.parse_us)
+            ),
+            result.bytes_per_ms(),
+            cell(
+                result.reparse_us,
+                baseline_result.and_then(|result| result.reparse_us)
+            ),
+        );
+    }
+
+    let total_bytes = results.iter().map(|result| result.bytes).sum::<usize>();
+    let total_us = results.iter().map(|result| result.parse_us).sum::<f64>();
+    if total_us > 0.0 {
+        println!(
+            "\nTotal: {} files, {total_bytes} bytes, {:.0} bytes/ms",
+            results.len(),
+            total_bytes as f64 * 1000.0 / total_us
+        );
+    }
+}
+
+/// Describe each file that took more than `max_regression` percent longer to parse, or
+/// to re-parse, than in the baseline.
+#[must_use]
+pub fn find_regressions(
+    results: &[FileBenchmark],
+    baseline: &[FileBenchmark],
+    max_regression: f64,
+) -> Vec<String> {
+    let mut regressions = Vec::new();
+    for result in results {
+        let Some(baseline_result) = baseline_result(Some(baseline), result) else {
+            continue;
+        };
+        let measurements = [
+            (
+                "parse",
+                Some(result.parse_us),
+                Some(baseline_result.parse_us),
+            ),
+            ("reparse", result.reparse_us, baseline_result.reparse_us),
+        ];
+        for (name, value, baseline_value) in measurements {
+            if let (Some(value), Some(baseline_value)) = (value, baseline_value) {
+                if let Some(change) = percent_change(baseline_value, value) {
+                    if change > max_regression {
+                        regressions.push(format!(
+                            "{}: {name} time went from {baseline_value}µs to {value}µs ({change:+.1}%)",
+                            result.path
+                        ));
+                    }
+                }
+            }
+        }
+    }
+    regressions
+}
+
+fn baseline_result<'a>(
+    baseline: Option<&'a [FileBenchmark]>,
+    result: &FileBenchmark,
+) -> Option<&'a FileBenchmark> {
+    baseline?
+        .iter()
+        .find(|baseline_result| baseline_result.path == result.path)
+}
+
+fn percent_change(old: f64, new: f64) -> Option<f64> {
+    (old > 0.0).then(|| (new - old) * 100.0 / old)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_benchmark(path: &str, parse_us: f64, reparse_us: Option<f64>) -> FileBenchmark {
+        FileBenchmark {
+            path: path.to_string(),
+            bytes: 1000,
+            parse_us,
+            reparse_us,
+        }
+    }
+
+    #[test]
+    fn test_find_regressions() {
+        let baseline = [
+            file_benchmark("a.js", 100.0, Some(10.0)),
+            file_benchmark("b.js", 200.0, None),
+        ];
+        let results = [
+            file_benchmark("a.js", 105.0, Some(20.0)),
+            file_benchmark("b.js", 300.0, Some(10.0)),
+            file_benchmark("c.js", 300.0, Some(10.0)),
+        ];
+        assert_eq!(
+            find_regressions(&results, &baseline, 10.0),
+            [
+                "a.js: reparse time went from 10µs to 20µs (+100.0%)",
+                "b.js: parse time went from 200µs to 300µs (+50.0%)",
+            ]
+        );
+        assert_eq!(
+            find_regressions(&results, &baseline, 100.0),
+            Vec::<String>::new()
+        );
+        assert_eq!(results[0].bytes_per_ms(), 1000.0 * 1000.0 / 105.0);
+    }
+}
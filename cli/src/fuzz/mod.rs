@@ -1,4 +1,8 @@
-use std::{collections::HashMap, env, fs, path::Path};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+};
 
 use lazy_static::lazy_static;
 use rand::Rng;
@@ -59,6 +63,7 @@ pub struct FuzzOptions {
     pub exclude: Option<Regex>,
     pub log_graphs: bool,
     pub log: bool,
+    pub save_failures: Option<PathBuf>,
 }
 
 pub fn fuzz_language_corpus(
@@ -182,6 +187,7 @@ pub fn fuzz_language_corpus(
 
         for trial in 0..options.iterations {
             let seed = start_seed + trial;
+            let mut edited_input = Vec::new();
             let passed = allocations::record(|| {
                 let mut rand = Rand::new(seed);
                 let mut log_session = None;
@@ -201,6 +207,7 @@ pub fn fuzz_language_corpus(
                     undo_stack.push(invert_edit(&input, &edit));
                     perform_edit(&mut tree, &mut input, &edit).unwrap();
                 }
+                edited_input.clone_from(&input);
 
                 if log_seed {
                     println!("   {test_index}.{trial:<2} seed: {seed}");
@@ -269,6 +276,9 @@ pub fn fuzz_language_corpus(
             });
 
             if !passed {
+                if let Some(failures_dir) = &options.save_failures {
+                    save_failing_input(failures_dir, &test_name, seed, &edited_input);
+                }
                 failure_count += 1;
                 break;
             }
@@ -292,6 +302,21 @@ pub fn fuzz_language_corpus(
     }
 }
 
+/// Save an edited input that failed fuzzing, named after its test and seed, so that
+/// the failure can be reproduced with `tree-sitter parse`, or by fuzzing again with
+/// `TREE_SITTER_SEED` set to the seed.
+fn save_failing_input(failures_dir: &Path, test_name: &str, seed: usize, input: &[u8]) {
+    let file_name = test_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    let path = failures_dir.join(format!("{file_name}.{seed}"));
+    match fs::create_dir_all(failures_dir).and_then(|()| fs::write(&path, input)) {
+        Ok(()) => println!("Saved the failing input to {}", path.display()),
+        Err(e) => eprintln!("Error: failed to save the failing input to {path:?}: {e}"),
+    }
+}
+
 pub struct FlattenedTest {
     pub name: String,
     pub input: Vec<u8>,
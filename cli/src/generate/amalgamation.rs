@@ -0,0 +1,105 @@
+use std::collections::HashSet;
+
+use super::{
+    render::{to_block_comments, CStandard},
+    ALLOC_HEADER,
+};
+
+pub const AMALGAMATION_FILE_HEADER: &str =
+    "// This file is generated by `tree-sitter generate --amalgamate`.";
+
+/// Combine the generated parser and the grammar's external scanner into a single
+/// translation unit, replacing their includes of Tree-sitter's headers with the
+/// contents of those headers, so that the parser can be vendored as one file.
+pub fn render_amalgamation(
+    parser_c_code: &str,
+    scanner_c_code: Option<&str>,
+    c_standard: CStandard,
+) -> String {
+    let mut result = if c_standard == CStandard::C89 {
+        to_block_comments(AMALGAMATION_FILE_HEADER)
+    } else {
+        format!("{AMALGAMATION_FILE_HEADER}\n")
+    };
+    result.push('\n');
+    let mut inlined_headers = HashSet::new();
+    inline_headers(parser_c_code, &mut inlined_headers, &mut result);
+    if let Some(scanner_c_code) = scanner_c_code {
+        result.push('\n');
+        inline_headers(scanner_c_code, &mut inlined_headers, &mut result);
+    }
+    result
+}
+
+fn inline_headers(code: &str, inlined_headers: &mut HashSet<&str>, result: &mut String) {
+    for line in code.lines() {
+        if let Some((name, contents)) = included_header(line) {
+            // The headers have include guards, so each of them only needs to be
+            // inlined the first time that it is included.
+            if inlined_headers.insert(name) {
+                inline_headers(contents, inlined_headers, result);
+            }
+            continue;
+        }
+        result.push_str(line);
+        result.push('\n');
+    }
+}
+
+/// The name and contents of the Tree-sitter header that the given line includes,
+/// if any. Other includes are left as they are.
+fn included_header(line: &str) -> Option<(&'static str, &'static str)> {
+    let path = line
+        .trim()
+        .strip_prefix("#include")?
+        .trim()
+        .strip_prefix('"')?
+        .strip_suffix('"')?;
+    match path {
+        "tree_sitter/parser.h" => Some(("parser.h", tree_sitter::PARSER_HEADER)),
+        // The array header includes the allocation header from its own directory.
+        "tree_sitter/alloc.h" | "./alloc.h" => Some(("alloc.h", ALLOC_HEADER)),
+        "tree_sitter/array.h" => Some(("array.h", tree_sitter::ARRAY_HEADER)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_amalgamation() {
+        let amalgamation = render_amalgamation(
+            "#include \"tree_sitter/parser.h\"\n\nenum ts_symbol_identifiers {};\n",
+            Some(
+                "#include \"tree_sitter/array.h\"\n#include \"tree_sitter/parser.h\"\n#include \"tag.h\"\n\nvoid *scanner;\n",
+            ),
+            CStandard::C99,
+        );
+
+        assert!(amalgamation.starts_with(AMALGAMATION_FILE_HEADER));
+        assert!(!amalgamation.contains("#include \"tree_sitter/"));
+        assert!(!amalgamation.contains("#include \"./alloc.h\""));
+        assert!(amalgamation.contains("#include \"tag.h\"\n"));
+        assert_eq!(
+            amalgamation
+                .matches("#define TREE_SITTER_PARSER_H_")
+                .count(),
+            1
+        );
+        assert_eq!(
+            amalgamation.matches("#define TREE_SITTER_ALLOC_H_").count(),
+            1
+        );
+        assert_eq!(
+            amalgamation.matches("#define TREE_SITTER_ARRAY_H_").count(),
+            1
+        );
+        assert!(
+            amalgamation.find("enum ts_symbol_identifiers").unwrap()
+                < amalgamation.find("#define TREE_SITTER_ARRAY_H_").unwrap()
+        );
+        assert!(amalgamation.ends_with("void *scanner;\n"));
+    }
+}
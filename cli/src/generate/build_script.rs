@@ -0,0 +1,133 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use super::{
+    default_grammar_path, generate_parser, grammar_source_hashes, load_grammar_file,
+    watched_grammar_files, write_file, write_lexer_file, write_manifest, GenerateOptions,
+    ALLOC_HEADER,
+};
+
+/// Keep the generated parser of a grammar that is vendored in a Rust project up to date,
+/// from the project's `build.rs`. The parser is generated again if any of the files that
+/// the grammar is generated from changed since it was last generated, and Cargo is told to
+/// run the build script again when one of them changes. Returns whether the parser was
+/// generated.
+///
+/// Whether the files changed is decided by the hashes in `src/manifest.json`, which is
+/// written along with the parser, so a checkout whose generated files are up to date can
+/// be built without a JavaScript runtime for evaluating `grammar.js`.
+pub fn generate_parser_in_build_script(
+    repo_path: &Path,
+    options: &GenerateOptions,
+) -> Result<bool> {
+    generate_if_changed(repo_path, options, &mut io::stdout())
+}
+
+fn generate_if_changed(
+    repo_path: &Path,
+    options: &GenerateOptions,
+    cargo_directives: &mut impl Write,
+) -> Result<bool> {
+    let grammar_path = default_grammar_path(repo_path);
+    for path in watched_grammar_files(&grammar_path).into_keys() {
+        writeln!(
+            cargo_directives,
+            "cargo:rerun-if-changed={}",
+            path.display()
+        )?;
+    }
+
+    let src_path = repo_path.join("src");
+    let manifest = fs::read_to_string(src_path.join("manifest.json"))
+        .ok()
+        .and_then(|json| serde_json::from_str::<serde_json::Value>(&json).ok());
+    let source_hashes = grammar_source_hashes(&grammar_path)?;
+    if src_path.join("parser.c").exists()
+        && manifest
+            .is_some_and(|manifest| manifest["sources"] == serde_json::Value::from(source_hashes))
+    {
+        return Ok(false);
+    }
+
+    let grammar_json = load_grammar_file(&grammar_path, None)?;
+    let output = generate_parser(&grammar_json, options)
+        .with_context(|| format!("Failed to generate the parser for {grammar_path:?}"))?;
+    let header_path = src_path.join("tree_sitter");
+    fs::create_dir_all(&header_path)?;
+
+    let mut generated_files = vec!["parser.c", "node-types.json"];
+    if grammar_path.extension().is_some_and(|e| e != "json") {
+        write_file(&src_path.join("grammar.json"), &grammar_json)?;
+        generated_files.push("grammar.json");
+    }
+    write_file(&src_path.join("parser.c"), output.parser_c)?;
+    if output.lexer_c.is_some() {
+        generated_files.push("lexer.c");
+    }
+    write_lexer_file(&src_path.join("lexer.c"), output.lexer_c)?;
+    write_file(&src_path.join("node-types.json"), output.node_types_json)?;
+    write_file(&header_path.join("alloc.h"), ALLOC_HEADER)?;
+    write_file(&header_path.join("array.h"), tree_sitter::ARRAY_HEADER)?;
+    write_file(&header_path.join("parser.h"), tree_sitter::PARSER_HEADER)?;
+    generated_files.extend([
+        "tree_sitter/alloc.h",
+        "tree_sitter/array.h",
+        "tree_sitter/parser.h",
+    ]);
+    write_manifest(&src_path, &grammar_path, &grammar_json, &generated_files)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_parser_in_build_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let grammar_path = dir.path().join("grammar.toml");
+        let write_grammar = |value: &str| {
+            fs::write(
+                &grammar_path,
+                format!("name = \"vendored\"\n\n[rules.program]\ntype = \"STRING\"\nvalue = \"{value}\"\n"),
+            )
+            .unwrap();
+        };
+        let generate = || {
+            let mut cargo_directives = Vec::new();
+            let generated = generate_if_changed(
+                dir.path(),
+                &GenerateOptions::default(),
+                &mut cargo_directives,
+            )
+            .unwrap();
+            (generated, String::from_utf8(cargo_directives).unwrap())
+        };
+
+        write_grammar("a");
+        let (generated, cargo_directives) = generate();
+        assert!(generated);
+        assert_eq!(
+            cargo_directives,
+            format!("cargo:rerun-if-changed={}\n", grammar_path.display())
+        );
+        let parser_c = fs::read_to_string(dir.path().join("src").join("parser.c")).unwrap();
+        assert!(parser_c.contains("tree_sitter_vendored"));
+        assert!(dir.path().join("src/tree_sitter/parser.h").exists());
+        assert!(dir.path().join("src/grammar.json").exists());
+
+        assert!(!generate().0);
+
+        write_grammar("b");
+        assert!(generate().0);
+        assert!(!generate().0);
+
+        fs::remove_file(dir.path().join("src").join("parser.c")).unwrap();
+        assert!(generate().0);
+    }
+}
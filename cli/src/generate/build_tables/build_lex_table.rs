@@ -9,7 +9,7 @@ use super::{coincident_tokens::CoincidentTokenIndex, token_conflicts::TokenConfl
 use crate::generate::{
     dedup::split_state_id_groups,
     grammars::{LexicalGrammar, SyntaxGrammar},
-    nfa::{CharacterSet, NfaCursor},
+    nfa::{CharacterSet, CharacterSetInterner, NfaCursor, NfaStateSet},
     prepare_grammar::symbol_is_used,
     rules::{Symbol, TokenSet},
     tables::{AdvanceAction, LexState, LexTable, ParseStateId, ParseTable},
@@ -39,7 +39,11 @@ pub fn build_lex_table(
         LexTable::default()
     };
 
+    // Many parse states have exactly the same valid tokens. Once a state's tokens have been
+    // merged into a group, the group contains all of them, so another state with the same
+    // tokens can join that group without comparing its tokens to each group again.
     let mut parse_state_ids_by_token_set = Vec::<(TokenSet, Vec<ParseStateId>)>::new();
+    let mut group_ids_by_token_set = HashMap::<TokenSet, usize>::new();
     for (i, state) in parse_table.states.iter().enumerate() {
         let tokens = state
             .terminal_entries
@@ -57,26 +61,30 @@ pub fn build_lex_table(
                     None
                 }
             })
-            .collect();
+            .collect::<TokenSet>();
 
-        let mut did_merge = false;
-        for entry in &mut parse_state_ids_by_token_set {
-            if merge_token_set(
-                &mut entry.0,
-                &tokens,
-                lexical_grammar,
-                token_conflict_map,
-                coincident_token_index,
-            ) {
-                did_merge = true;
-                entry.1.push(i);
-                break;
-            }
+        if let Some(group_id) = group_ids_by_token_set.get(&tokens) {
+            parse_state_ids_by_token_set[*group_id].1.push(i);
+            continue;
         }
 
-        if !did_merge {
-            parse_state_ids_by_token_set.push((tokens, vec![i]));
-        }
+        let group_id = parse_state_ids_by_token_set
+            .iter_mut()
+            .position(|entry| {
+                merge_token_set(
+                    &mut entry.0,
+                    &tokens,
+                    lexical_grammar,
+                    token_conflict_map,
+                    coincident_token_index,
+                )
+            })
+            .unwrap_or_else(|| {
+                parse_state_ids_by_token_set.push((tokens.clone(), Vec::new()));
+                parse_state_ids_by_token_set.len() - 1
+            });
+        parse_state_ids_by_token_set[group_id].1.push(i);
+        group_ids_by_token_set.insert(tokens, group_id);
     }
 
     let mut builder = LexTableBuilder::new(lexical_grammar);
@@ -131,9 +139,19 @@ pub fn build_lex_table(
     }
 }
 
+/// Convert the NFA of a lexical grammar into a DFA that recognizes the given tokens, which
+/// are indices into the grammar's variables. When more than one token matches the same
+/// text, the DFA accepts the one that the generated lexer would prefer. State 0 is the
+/// start state.
+pub fn build_dfa(lexical_grammar: &LexicalGrammar, tokens: &[usize]) -> LexTable {
+    let mut builder = LexTableBuilder::new(lexical_grammar);
+    builder.add_state_for_tokens(&tokens.iter().copied().map(Symbol::terminal).collect());
+    builder.table
+}
+
 struct QueueEntry {
     state_id: usize,
-    nfa_states: Vec<u32>,
+    nfa_states: NfaStateSet,
     eof_valid: bool,
 }
 
@@ -142,17 +160,19 @@ struct LexTableBuilder<'a> {
     cursor: NfaCursor<'a>,
     table: LexTable,
     state_queue: VecDeque<QueueEntry>,
-    state_ids_by_nfa_state_set: HashMap<(Vec<u32>, bool), usize>,
+    state_ids_by_nfa_state_set: HashMap<(NfaStateSet, bool), usize>,
+    character_sets: CharacterSetInterner,
 }
 
 impl<'a> LexTableBuilder<'a> {
     fn new(lexical_grammar: &'a LexicalGrammar) -> Self {
         Self {
             lexical_grammar,
-            cursor: NfaCursor::new(&lexical_grammar.nfa, vec![]),
+            cursor: NfaCursor::new(&lexical_grammar.nfa, NfaStateSet::new()),
             table: LexTable::default(),
             state_queue: VecDeque::new(),
             state_ids_by_nfa_state_set: HashMap::new(),
+            character_sets: CharacterSetInterner::default(),
         }
     }
 
@@ -199,7 +219,7 @@ impl<'a> LexTableBuilder<'a> {
         state_id
     }
 
-    fn add_state(&mut self, nfa_states: Vec<u32>, eof_valid: bool) -> (usize, bool) {
+    fn add_state(&mut self, nfa_states: NfaStateSet, eof_valid: bool) -> (usize, bool) {
         self.cursor.reset(nfa_states);
         match self
             .state_ids_by_nfa_state_set
@@ -220,7 +240,7 @@ impl<'a> LexTableBuilder<'a> {
         }
     }
 
-    fn populate_state(&mut self, state_id: usize, nfa_states: Vec<u32>, eof_valid: bool) {
+    fn populate_state(&mut self, state_id: usize, nfa_states: NfaStateSet, eof_valid: bool) {
         self.cursor.force_reset(nfa_states);
 
         // The EOF state is represented as an empty list of NFA states.
@@ -244,7 +264,7 @@ impl<'a> LexTableBuilder<'a> {
         // If EOF is a valid lookahead token, add a transition predicated on the null
         // character that leads to the empty set of NFA states.
         if eof_valid {
-            let (next_state_id, _) = self.add_state(Vec::new(), false);
+            let (next_state_id, _) = self.add_state(NfaStateSet::new(), false);
             self.table.states[state_id].eof_action = Some(AdvanceAction {
                 state: next_state_id,
                 in_main_token: true,
@@ -267,7 +287,7 @@ impl<'a> LexTableBuilder<'a> {
             let (next_state_id, _) =
                 self.add_state(transition.states, eof_valid && transition.is_separator);
             self.table.states[state_id].advance_actions.push((
-                transition.characters,
+                self.character_sets.intern(transition.characters),
                 AdvanceAction {
                     state: next_state_id,
                     in_main_token: !transition.is_separator,
@@ -428,3 +448,63 @@ fn sort_states(table: &mut LexTable, parse_table: &mut ParseTable) {
         state.lex_state_id = new_ids_by_old_id[state.lex_state_id];
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{grammar_builder::GrammarBuilder, rules::Rule};
+
+    fn run_dfa(table: &LexTable, text: &str) -> Option<Symbol> {
+        let mut state = &table.states[0];
+        for c in text.chars() {
+            let (_, action) = state
+                .advance_actions
+                .iter()
+                .find(|(characters, _)| characters.contains(c))?;
+            state = &table.states[action.state];
+        }
+        state.accept_action
+    }
+
+    #[test]
+    fn test_build_dfa() {
+        let (_, lexical_grammar) = GrammarBuilder::new("test")
+            .rule(
+                "program",
+                Rule::repeat(Rule::choice(vec![
+                    Rule::NamedSymbol("keyword".to_string()),
+                    Rule::NamedSymbol("identifier".to_string()),
+                    Rule::NamedSymbol("number".to_string()),
+                ])),
+            )
+            .rule("keyword", Rule::String("if".to_string()))
+            .rule(
+                "identifier",
+                Rule::Pattern("[a-z]+".to_string(), String::new()),
+            )
+            .rule("number", Rule::Pattern("\\d+".to_string(), String::new()))
+            .build_prepared()
+            .unwrap();
+        let index = |name| {
+            lexical_grammar
+                .variables
+                .iter()
+                .position(|v| v.name == name)
+                .unwrap()
+        };
+
+        let table = build_dfa(&lexical_grammar, &[index("keyword"), index("identifier")]);
+        let accepted = |text| run_dfa(&table, text).map(|symbol| symbol.index);
+        assert_eq!(accepted("if"), Some(index("keyword")));
+        assert_eq!(accepted("iff"), Some(index("identifier")));
+        assert_eq!(accepted("i"), Some(index("identifier")));
+        assert_eq!(accepted(""), None);
+        assert_eq!(accepted("12"), None);
+
+        let table = build_dfa(&lexical_grammar, &[index("number")]);
+        assert_eq!(
+            run_dfa(&table, "12").map(|symbol| symbol.index),
+            Some(index("number"))
+        );
+    }
+}
@@ -5,8 +5,9 @@ use std::{
     hash::BuildHasherDefault,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use indexmap::{map::Entry, IndexMap};
+use log::warn;
 use rustc_hash::FxHasher;
 
 use super::{
@@ -14,6 +15,7 @@ use super::{
     item_set_builder::ParseItemSetBuilder,
 };
 use crate::generate::{
+    error::{GrammarError, GrammarErrorKind},
     grammars::{
         InlinedProductionMap, LexicalGrammar, PrecedenceEntry, SyntaxGrammar, VariableType,
     },
@@ -134,17 +136,18 @@ impl<'a> ParseTableBuilder<'a> {
         }
 
         if !self.actual_conflicts.is_empty() {
-            println!("Warning: unnecessary conflicts");
-            for conflict in &self.actual_conflicts {
-                println!(
-                    "  {}",
+            let conflicts = self
+                .actual_conflicts
+                .iter()
+                .map(|conflict| {
                     conflict
                         .iter()
                         .map(|symbol| format!("`{}`", self.symbol_name(symbol)))
                         .collect::<Vec<_>>()
                         .join(", ")
-                );
-            }
+                })
+                .collect::<Vec<_>>();
+            warn!("unnecessary conflicts\n  {}", conflicts.join("\n  "));
         }
 
         Ok((self.parse_table, self.parse_state_info_by_id))
@@ -392,15 +395,23 @@ impl<'a> ParseTableBuilder<'a> {
                         }
                     })
                     .collect::<HashSet<_>>();
-                let mut message =
-                    "Extra rules must have unambiguous endings. Conflicting rules: ".to_string();
-                for (i, variable_index) in parent_symbols.iter().enumerate() {
-                    if i > 0 {
-                        message += ", ";
-                    }
-                    message += &self.syntax_grammar.variables[*variable_index as usize].name;
-                }
-                return Err(anyhow!(message));
+                let names = parent_symbols
+                    .iter()
+                    .map(|variable_index| {
+                        self.syntax_grammar.variables[*variable_index as usize]
+                            .name
+                            .as_str()
+                    })
+                    .collect::<Vec<_>>();
+                return Err(GrammarError::new(
+                    GrammarErrorKind::AmbiguousExtra,
+                    format!(
+                        "Extra rules must have unambiguous endings. Conflicting rules: {}",
+                        names.join(", ")
+                    ),
+                )
+                .with_symbols(names)
+                .into());
             }
         }
         // Add actions for the start tokens of each non-terminal extra rule.
@@ -755,21 +766,25 @@ impl<'a> ParseTableBuilder<'a> {
             writeln!(&mut msg).unwrap();
         }
 
+        let conflict_names = actual_conflict
+            .iter()
+            .map(|symbol| self.symbol_name(symbol))
+            .collect::<Vec<_>>();
+        let add_conflict = format!(
+            "Add a conflict for these rules: {}",
+            conflict_names
+                .iter()
+                .map(|name| format!("`{name}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
         resolution_count += 1;
-        write!(
-            &mut msg,
-            "  {resolution_count}:  Add a conflict for these rules: ",
-        )
-        .unwrap();
-        for (i, symbol) in actual_conflict.iter().enumerate() {
-            if i > 0 {
-                write!(&mut msg, ", ").unwrap();
-            }
-            write!(&mut msg, "`{}`", self.symbol_name(symbol)).unwrap();
-        }
-        writeln!(&mut msg).unwrap();
+        writeln!(&mut msg, "  {resolution_count}:  {add_conflict}").unwrap();
 
-        Err(anyhow!(msg))
+        Err(GrammarError::new(GrammarErrorKind::UnresolvedConflict, msg)
+            .with_symbols(conflict_names)
+            .with_suggestion(add_conflict)
+            .into())
     }
 
     fn compare_precedence(
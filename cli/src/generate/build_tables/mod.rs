@@ -6,11 +6,15 @@ mod item_set_builder;
 mod minimize_parse_table;
 mod token_conflicts;
 
-use std::collections::{BTreeSet, HashMap};
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::Duration,
+};
 
 use anyhow::Result;
-pub use build_lex_table::LARGE_CHARACTER_RANGE_COUNT;
+pub use build_lex_table::{build_dfa, LARGE_CHARACTER_RANGE_COUNT};
 use log::info;
+use smallvec::smallvec;
 
 use self::{
     build_lex_table::build_lex_table,
@@ -20,11 +24,14 @@ use self::{
     token_conflicts::TokenConflictMap,
 };
 use crate::generate::{
+    checkpoint::Checkpoints,
     grammars::{InlinedProductionMap, LexicalGrammar, SyntaxGrammar},
-    nfa::{CharacterSet, NfaCursor},
+    nfa::{CharacterSet, NfaCursor, NfaStateSet},
     node_types::VariableInfo,
     rules::{AliasMap, Symbol, SymbolType, TokenSet},
+    table_dump::{describe_parse_action, symbol_name},
     tables::{LexTable, ParseAction, ParseTable, ParseTableEntry},
+    timings::Timings,
 };
 
 pub struct Tables {
@@ -33,8 +40,22 @@ pub struct Tables {
     pub keyword_lex_table: LexTable,
     pub word_token: Option<Symbol>,
     pub large_character_sets: Vec<(Option<Symbol>, CharacterSet)>,
+    pub parse_state_sources: Vec<ParseStateSource>,
+}
+
+/// The part of the grammar that a parse state was built from.
+pub struct ParseStateSource {
+    /// The symbols that precede the state, along the path by which it was first reached.
+    pub symbol_sequence: Vec<Symbol>,
+    /// The rules that the state's items belong to.
+    pub variable_indices: Vec<usize>,
+    /// The state's items, each written as a production with a dot at the item's position.
+    /// The kernel items, which the state was reached with, come first.
+    pub items: Vec<String>,
+    pub kernel_item_count: usize,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn build_tables(
     syntax_grammar: &SyntaxGrammar,
     lexical_grammar: &LexicalGrammar,
@@ -42,45 +63,127 @@ pub fn build_tables(
     variable_info: &[VariableInfo],
     inlines: &InlinedProductionMap,
     report_symbol_name: Option<&str>,
+    record_state_sources: bool,
+    conflict_analysis_budget: Option<Duration>,
+    checkpoints: Option<&Checkpoints>,
+    timings: &mut Timings,
 ) -> Result<Tables> {
-    let (mut parse_table, following_tokens, parse_state_info) =
-        build_parse_table(syntax_grammar, lexical_grammar, inlines, variable_info)?;
-    let token_conflict_map = TokenConflictMap::new(lexical_grammar, following_tokens);
-    let coincident_token_index = CoincidentTokenIndex::new(&parse_table, lexical_grammar);
-    let keywords = identify_keywords(
-        lexical_grammar,
-        &parse_table,
-        syntax_grammar.word_token,
-        &token_conflict_map,
-        &coincident_token_index,
-    );
-    populate_error_state(
-        &mut parse_table,
-        syntax_grammar,
-        lexical_grammar,
-        &coincident_token_index,
-        &token_conflict_map,
-        &keywords,
-    );
-    populate_used_symbols(&mut parse_table, syntax_grammar, lexical_grammar);
-    minimize_parse_table(
-        &mut parse_table,
-        syntax_grammar,
-        lexical_grammar,
-        simple_aliases,
-        &token_conflict_map,
-        &keywords,
-    );
-    let lex_tables = build_lex_table(
-        &mut parse_table,
-        syntax_grammar,
-        lexical_grammar,
-        &keywords,
-        &coincident_token_index,
-        &token_conflict_map,
-    );
-    populate_external_lex_states(&mut parse_table, syntax_grammar);
-    mark_fragile_tokens(&mut parse_table, lexical_grammar, &token_conflict_map);
+    // The items that the parse states were built from refer to the grammar, so they aren't
+    // saved in the checkpoint, which can only be used when they aren't needed.
+    let parse_table_checkpoints =
+        checkpoints.filter(|_| report_symbol_name.is_none() && !record_state_sources);
+    let (mut parse_table, following_tokens, parse_state_info) = timings.time(
+        "parse state construction",
+        || match parse_table_checkpoints {
+            Some(checkpoints) => {
+                // The inlined productions and the variable info are derived from the
+                // grammars and the aliases. The aliases are sorted, because the order in
+                // which a map is iterated isn't deterministic.
+                let mut sorted_aliases = simple_aliases.iter().collect::<Vec<_>>();
+                sorted_aliases.sort_unstable();
+                let key = Checkpoints::key(&(syntax_grammar, lexical_grammar, sorted_aliases));
+                let (parse_table, following_tokens) =
+                    checkpoints.resume_or_run("parse-table", &key, || {
+                        let (parse_table, following_tokens, _) = build_parse_table(
+                            syntax_grammar,
+                            lexical_grammar,
+                            inlines,
+                            variable_info,
+                        )?;
+                        Ok((parse_table, following_tokens))
+                    })?;
+                Ok((parse_table, following_tokens, Vec::new()))
+            }
+            None => build_parse_table(syntax_grammar, lexical_grammar, inlines, variable_info),
+        },
+    )?;
+    let (token_conflict_map, coincident_token_index, keywords) =
+        timings.time("conflict analysis", || -> Result<_> {
+            let token_conflict_map = match checkpoints {
+                // Even when the tokens changed, the previous analysis still holds for the
+                // pairs of tokens that didn't, so only the changed tokens are analyzed again.
+                Some(checkpoints) => {
+                    let key = Checkpoints::key(&(
+                        lexical_grammar,
+                        &following_tokens,
+                        conflict_analysis_budget,
+                    ));
+                    let (previous, is_current) = match checkpoints.load("token-conflicts", &key) {
+                        Some(analysis) => (Some(analysis), true),
+                        None => (checkpoints.load_latest("token-conflicts"), false),
+                    };
+                    let token_conflict_map = TokenConflictMap::with_previous_analysis(
+                        lexical_grammar,
+                        following_tokens,
+                        conflict_analysis_budget,
+                        previous.as_ref(),
+                    );
+                    if !is_current {
+                        checkpoints.save(
+                            "token-conflicts",
+                            &key,
+                            &token_conflict_map.analysis(),
+                        )?;
+                    }
+                    token_conflict_map
+                }
+                None => TokenConflictMap::new(
+                    lexical_grammar,
+                    following_tokens,
+                    conflict_analysis_budget,
+                ),
+            };
+            let coincident_token_index = CoincidentTokenIndex::new(&parse_table, lexical_grammar);
+            let keywords = identify_keywords(
+                lexical_grammar,
+                &parse_table,
+                syntax_grammar.word_token,
+                &token_conflict_map,
+                &coincident_token_index,
+            );
+            Ok((token_conflict_map, coincident_token_index, keywords))
+        })?;
+    timings.time("parse table minimization", || {
+        populate_error_state(
+            &mut parse_table,
+            syntax_grammar,
+            lexical_grammar,
+            &coincident_token_index,
+            &token_conflict_map,
+            &keywords,
+        );
+        populate_used_symbols(&mut parse_table, syntax_grammar, lexical_grammar);
+        minimize_parse_table(
+            &mut parse_table,
+            syntax_grammar,
+            lexical_grammar,
+            simple_aliases,
+            &token_conflict_map,
+            &keywords,
+        );
+    });
+    let lex_tables = timings.time("lex table construction", || {
+        let lex_tables = build_lex_table(
+            &mut parse_table,
+            syntax_grammar,
+            lexical_grammar,
+            &keywords,
+            &coincident_token_index,
+            &token_conflict_map,
+        );
+        populate_external_lex_states(&mut parse_table, syntax_grammar);
+        mark_fragile_tokens(&mut parse_table, lexical_grammar, &token_conflict_map);
+        lex_tables
+    });
+
+    if log::log_enabled!(log::Level::Info) {
+        report_conflicting_token_groups(
+            syntax_grammar,
+            lexical_grammar,
+            &parse_table,
+            &token_conflict_map,
+        );
+    }
 
     if let Some(report_symbol_name) = report_symbol_name {
         report_state_info(
@@ -92,15 +195,68 @@ pub fn build_tables(
         );
     }
 
+    let parse_state_sources = if record_state_sources {
+        parse_state_sources(
+            syntax_grammar,
+            lexical_grammar,
+            &parse_table,
+            &parse_state_info,
+        )
+    } else {
+        Vec::new()
+    };
+
     Ok(Tables {
         parse_table,
         main_lex_table: lex_tables.main_lex_table,
         keyword_lex_table: lex_tables.keyword_lex_table,
         large_character_sets: lex_tables.large_character_sets,
         word_token: syntax_grammar.word_token,
+        parse_state_sources,
     })
 }
 
+/// Describe the items that each state of the minimized parse table was built from.
+/// States that were merged during minimization are described by the items of the
+/// state that they were merged into.
+fn parse_state_sources(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    parse_table: &ParseTable,
+    parse_state_info: &[ParseStateInfo],
+) -> Vec<ParseStateSource> {
+    parse_table
+        .states
+        .iter()
+        .map(|state| {
+            let (symbol_sequence, item_set) = &parse_state_info[state.id];
+            let variable_indices = item_set
+                .entries
+                .iter()
+                .filter(|(item, _)| !item.is_augmented())
+                .map(|(item, _)| item.variable_index as usize)
+                .collect::<BTreeSet<_>>();
+            let (kernel_items, closure_items) = item_set
+                .entries
+                .iter()
+                .map(|(item, _)| item)
+                .partition::<Vec<_>, _>(|item| item.step_index > 0 || item.is_augmented());
+            ParseStateSource {
+                symbol_sequence: symbol_sequence.clone(),
+                variable_indices: variable_indices.into_iter().collect(),
+                kernel_item_count: kernel_items.len(),
+                items: kernel_items
+                    .into_iter()
+                    .chain(closure_items)
+                    .map(|item| {
+                        item::ParseItemDisplay(item, syntax_grammar, lexical_grammar).to_string()
+                    })
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
 fn populate_error_state(
     parse_table: &mut ParseTable,
     syntax_grammar: &SyntaxGrammar,
@@ -276,7 +432,7 @@ fn identify_keywords(
     }
 
     let word_token = word_token.unwrap();
-    let mut cursor = NfaCursor::new(&lexical_grammar.nfa, Vec::new());
+    let mut cursor = NfaCursor::new(&lexical_grammar.nfa, NfaStateSet::new());
 
     // First find all of the candidate keyword tokens: tokens that start with
     // letters or underscore and can match the same string as a word token.
@@ -285,7 +441,7 @@ fn identify_keywords(
         .iter()
         .enumerate()
         .filter_map(|(i, variable)| {
-            cursor.reset(vec![variable.start_state]);
+            cursor.reset(smallvec![variable.start_state]);
             if all_chars_are_alphabetical(&cursor)
                 && token_conflict_map.does_match_same_string(i, word_token.index)
                 && !token_conflict_map.does_match_different_string(i, word_token.index)
@@ -399,6 +555,58 @@ fn mark_fragile_tokens(
     }
 }
 
+/// Report each group of three or more tokens that are valid in the same parse states and that
+/// all match some of the same strings, along with the order in which the lexer prefers them.
+fn report_conflicting_token_groups(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    parse_table: &ParseTable,
+    token_conflict_map: &TokenConflictMap,
+) {
+    let mut state_ids_by_valid_tokens = HashMap::<Vec<usize>, Vec<usize>>::new();
+    for (state_id, state) in parse_table.states.iter().enumerate() {
+        let mut valid_tokens = state
+            .terminal_entries
+            .keys()
+            .filter(|symbol| symbol.is_terminal())
+            .map(|symbol| symbol.index)
+            .collect::<Vec<_>>();
+        valid_tokens.sort_unstable();
+        state_ids_by_valid_tokens
+            .entry(valid_tokens)
+            .or_default()
+            .push(state_id);
+    }
+
+    let mut state_ids_by_group = HashMap::<Vec<usize>, BTreeSet<usize>>::new();
+    for (valid_tokens, state_ids) in &state_ids_by_valid_tokens {
+        for group in token_conflict_map.conflicting_token_groups(valid_tokens) {
+            state_ids_by_group
+                .entry(group)
+                .or_default()
+                .extend(state_ids);
+        }
+    }
+
+    let mut groups = state_ids_by_group.into_iter().collect::<Vec<_>>();
+    groups.sort_unstable_by(|a, b| a.1.cmp(&b.1));
+    for (group, state_ids) in groups {
+        let names = group
+            .iter()
+            .map(|index| symbol_name(syntax_grammar, lexical_grammar, &Symbol::terminal(*index)))
+            .collect::<Vec<_>>();
+        let state_ids = state_ids
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        info!(
+            "the tokens {} all match some of the same strings in parse states {}. Where they do, the lexer prefers them in that order",
+            names.join(", "),
+            state_ids.join(", "),
+        );
+    }
+}
+
 fn report_state_info<'a>(
     syntax_grammar: &SyntaxGrammar,
     lexical_grammar: &LexicalGrammar,
@@ -441,7 +649,7 @@ fn report_state_info<'a>(
     }
     eprintln!();
 
-    let state_indices = if report_symbol_name == "*" {
+    let state_indices = if report_symbol_name == "*" || report_symbol_name == "-" {
         Some(&all_state_indices)
     } else {
         symbols_with_state_indices
@@ -479,7 +687,37 @@ fn report_state_info<'a>(
                 "\nitems:\n{}",
                 self::item::ParseItemSetDisplay(item_set, syntax_grammar, lexical_grammar,),
             );
+
+            // The conflicts that remain in the table are the ones that the grammar
+            // declares, which are resolved at runtime by splitting the parse stack.
+            let conflicts = parse_table.states[state_index]
+                .terminal_entries
+                .iter()
+                .filter(|(_, entry)| entry.actions.len() > 1)
+                .collect::<Vec<_>>();
+            if !conflicts.is_empty() {
+                eprintln!("conflicts:");
+                for (symbol, entry) in conflicts {
+                    eprintln!(
+                        "  {}: {}",
+                        symbol_name(syntax_grammar, lexical_grammar, symbol),
+                        entry
+                            .actions
+                            .iter()
+                            .map(|action| describe_parse_action(
+                                syntax_grammar,
+                                lexical_grammar,
+                                action
+                            ))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+                }
+                eprintln!();
+            }
         }
+    } else {
+        eprintln!("Warning: the grammar has no rule named {report_symbol_name:?}");
     }
 }
 
@@ -1,14 +1,27 @@
-use std::{cmp::Ordering, collections::HashSet, fmt};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::HashMap,
+    fmt,
+    hash::BuildHasherDefault,
+    time::{Duration, Instant},
+};
+
+use indexmap::IndexSet;
+use log::{info, trace, warn};
+use rustc_hash::FxHasher;
+use serde::{Deserialize, Serialize};
+use smallvec::smallvec;
 
 use crate::generate::{
     build_tables::item::TokenSetDisplay,
+    fnv1a_hash,
     grammars::{LexicalGrammar, SyntaxGrammar},
-    nfa::{CharacterSet, NfaCursor, NfaTransition},
+    nfa::{CharacterSet, NfaCursor, NfaState, NfaStateSet, NfaTransition},
     rules::TokenSet,
 };
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
-struct TokenConflictStatus {
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenConflictStatus {
     matches_prefix: bool,
     does_match_continuation: bool,
     does_match_valid_continuation: bool,
@@ -17,12 +30,45 @@ struct TokenConflictStatus {
     matches_different_string: bool,
 }
 
+impl TokenConflictStatus {
+    /// The status of a token in a pair of tokens whose analysis was cut short. Tokens with
+    /// this status aren't lexed in the same states, aren't used as keywords, and aren't
+    /// considered free of conflicts during error recovery. Matching separators is left out,
+    /// because that would make the other token *less* likely to be treated as conflicting.
+    ///
+    /// Only the token that the lexer would prefer if both matched the same string is said to
+    /// match the same string, and the strings that the other token doesn't match.
+    const fn conflicting(is_preferred: bool) -> Self {
+        Self {
+            matches_prefix: true,
+            does_match_continuation: true,
+            does_match_valid_continuation: true,
+            does_match_separators: false,
+            matches_same_string: is_preferred,
+            matches_different_string: is_preferred,
+        }
+    }
+}
+
+/// The conflict status of every pair of tokens in a grammar, saved so that analyzing a later
+/// version of the grammar can reuse the status of the pairs of tokens that didn't change.
+/// An analysis made by another version of the CLI is never reused.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TokenConflictAnalysis {
+    cli_version: String,
+    budget: Option<Duration>,
+    token_hashes: Vec<u64>,
+    status_matrix: Vec<TokenConflictStatus>,
+}
+
 pub struct TokenConflictMap<'a> {
     n: usize,
     status_matrix: Vec<TokenConflictStatus>,
     following_tokens: Vec<TokenSet>,
     starting_chars_by_index: Vec<CharacterSet>,
     following_chars_by_index: Vec<CharacterSet>,
+    token_hashes: Vec<u64>,
+    budget: Option<Duration>,
     grammar: &'a LexicalGrammar,
 }
 
@@ -32,32 +78,98 @@ impl<'a> TokenConflictMap<'a> {
     /// immediately after each other token.
     ///
     /// This analyzes the possible kinds of overlap between each pair of tokens and stores
-    /// them in a matrix.
-    pub fn new(grammar: &'a LexicalGrammar, following_tokens: Vec<TokenSet>) -> Self {
-        let mut cursor = NfaCursor::new(&grammar.nfa, Vec::new());
+    /// them in a matrix. At most `budget` is spent on analyzing each pair of tokens; a pair
+    /// whose analysis runs out of time is treated as conflicting in every way that keeps the
+    /// generated lexer correct, at the cost of a larger lex table.
+    pub fn new(
+        grammar: &'a LexicalGrammar,
+        following_tokens: Vec<TokenSet>,
+        budget: Option<Duration>,
+    ) -> Self {
+        Self::with_previous_analysis(grammar, following_tokens, budget, None)
+    }
+
+    /// Create a token conflict map like [`Self::new`], but take the status of each pair of
+    /// tokens that is unaffected by the changes since the `previous` analysis from it, instead
+    /// of analyzing the pair again.
+    ///
+    /// The status of a pair of tokens only depends on the two tokens' own NFA states, their
+    /// implicit precedence, the characters that can follow them, and which of them comes
+    /// first in the grammar. So it can be reused when both tokens hash the same as two tokens
+    /// of the previous analysis that were in the same order, even if other tokens were added,
+    /// removed or changed in between.
+    pub fn with_previous_analysis(
+        grammar: &'a LexicalGrammar,
+        following_tokens: Vec<TokenSet>,
+        budget: Option<Duration>,
+        previous: Option<&TokenConflictAnalysis>,
+    ) -> Self {
+        let mut cursor = NfaCursor::new(&grammar.nfa, NfaStateSet::new());
         let starting_chars = get_starting_chars(&mut cursor, grammar);
         let following_chars = get_following_chars(&starting_chars, &following_tokens);
+        let token_hashes = get_token_hashes(grammar, &following_chars);
 
+        // The index of each token in the previous analysis, if it has one. Tokens whose hash
+        // appears more than once in the previous analysis are analyzed again.
         let n = grammar.variables.len();
-        let mut status_matrix = vec![TokenConflictStatus::default(); n * n];
-        for i in 0..grammar.variables.len() {
-            for j in 0..i {
-                let status = compute_conflict_status(&mut cursor, grammar, &following_chars, i, j);
-                status_matrix[matrix_index(n, i, j)] = status.0;
-                status_matrix[matrix_index(n, j, i)] = status.1;
+        let previous = previous.filter(|previous| {
+            previous.cli_version == env!("CARGO_PKG_VERSION") && previous.budget == budget
+        });
+        let previous_indices = match previous {
+            Some(previous) => {
+                let mut indices_by_hash = HashMap::new();
+                for (index, hash) in previous.token_hashes.iter().enumerate() {
+                    indices_by_hash
+                        .entry(hash)
+                        .and_modify(|i| *i = None)
+                        .or_insert(Some(index));
+                }
+                token_hashes
+                    .iter()
+                    .map(|hash| indices_by_hash.get(hash).copied().flatten())
+                    .collect()
             }
+            None => vec![None; n],
+        };
+        if previous.is_some() {
+            let reused_count = previous_indices.iter().flatten().count();
+            info!("reusing the conflict analysis of {reused_count} of the {n} tokens");
         }
 
+        let status_matrix = compute_status_matrix(grammar, &following_chars, budget, |i, j| {
+            let previous = previous?;
+            let (i, j) = (previous_indices[i]?, previous_indices[j]?);
+            (i > j).then(|| {
+                let m = previous.token_hashes.len();
+                (
+                    previous.status_matrix[matrix_index(m, i, j)].clone(),
+                    previous.status_matrix[matrix_index(m, j, i)].clone(),
+                )
+            })
+        });
         TokenConflictMap {
             n,
             status_matrix,
             following_tokens,
             starting_chars_by_index: starting_chars,
             following_chars_by_index: following_chars,
+            token_hashes,
+            budget,
             grammar,
         }
     }
 
+    /// The analysis behind this map, to be passed to [`Self::with_previous_analysis`] when
+    /// a later version of the grammar is analyzed.
+    pub fn analysis(&self) -> TokenConflictAnalysis {
+        TokenConflictAnalysis {
+            cli_version: env!("CARGO_PKG_VERSION").to_string(),
+            budget: self.budget,
+            token_hashes: self.token_hashes.clone(),
+            status_matrix: self.status_matrix.clone(),
+        }
+    }
+
     /// Does token `i` match any strings that token `j` also matches, such that token `i`
     /// is preferred over token `j`?
     pub fn has_same_conflict_status(&self, a: usize, b: usize, other: usize) -> bool {
@@ -104,6 +216,89 @@ impl<'a> TokenConflictMap<'a> {
             || status.does_match_continuation
     }
 
+    /// Find the groups of three or more of the given tokens in which every token matches some
+    /// of the same strings as every other token. Conflicts are analyzed between pairs of
+    /// tokens, but when several tokens are valid at once, which one gets lexed depends on how
+    /// all of them are ordered, so each group is reported as a whole. The tokens in each group
+    /// are ordered from the one that the lexer prefers most.
+    pub fn conflicting_token_groups(&self, tokens: &[usize]) -> Vec<Vec<usize>> {
+        let mut groups = Vec::new();
+        self.find_conflicting_token_groups(
+            &mut Vec::new(),
+            tokens.to_vec(),
+            Vec::new(),
+            &mut groups,
+        );
+        for group in &mut groups {
+            let members = group.clone();
+            group.sort_unstable_by_key(|i| {
+                let preferred_count = members
+                    .iter()
+                    .filter(|j| self.does_match_same_string(*i, **j))
+                    .count();
+                (Reverse(preferred_count), *i)
+            });
+        }
+        groups.sort_unstable();
+        groups
+    }
+
+    /// Collect the maximal sets of mutually conflicting tokens that extend `group` with
+    /// tokens from `candidates`, using the Bron-Kerbosch algorithm. Tokens in `excluded`
+    /// would extend the group too, but their groups have already been collected.
+    fn find_conflicting_token_groups(
+        &self,
+        group: &mut Vec<usize>,
+        mut candidates: Vec<usize>,
+        mut excluded: Vec<usize>,
+        groups: &mut Vec<Vec<usize>>,
+    ) {
+        let conflicts = |i: usize, j: usize| {
+            i != j && (self.does_match_same_string(i, j) || self.does_match_same_string(j, i))
+        };
+        if candidates.is_empty() {
+            if excluded.is_empty() && group.len() >= 3 {
+                groups.push(group.clone());
+            }
+            return;
+        }
+        if group.len() + candidates.len() < 3 {
+            return;
+        }
+
+        let pivot = candidates
+            .iter()
+            .chain(&excluded)
+            .copied()
+            .max_by_key(|i| candidates.iter().filter(|j| conflicts(*i, **j)).count())
+            .unwrap();
+        let branches = candidates
+            .iter()
+            .copied()
+            .filter(|i| !conflicts(pivot, *i))
+            .collect::<Vec<_>>();
+        for token in branches {
+            group.push(token);
+            self.find_conflicting_token_groups(
+                group,
+                candidates
+                    .iter()
+                    .copied()
+                    .filter(|i| conflicts(token, *i))
+                    .collect(),
+                excluded
+                    .iter()
+                    .copied()
+                    .filter(|i| conflicts(token, *i))
+                    .collect(),
+                groups,
+            );
+            group.pop();
+            candidates.retain(|i| *i != token);
+            excluded.push(token);
+        }
+    }
+
     pub fn prefer_token(grammar: &LexicalGrammar, left: (i32, usize), right: (i32, usize)) -> bool {
         match left.0.cmp(&right.0) {
             Ordering::Less => false,
@@ -208,7 +403,7 @@ const fn matrix_index(variable_count: usize, i: usize, j: usize) -> usize {
 fn get_starting_chars(cursor: &mut NfaCursor, grammar: &LexicalGrammar) -> Vec<CharacterSet> {
     let mut result = Vec::with_capacity(grammar.variables.len());
     for variable in &grammar.variables {
-        cursor.reset(vec![variable.start_state]);
+        cursor.reset(smallvec![variable.start_state]);
         let mut all_chars = CharacterSet::empty();
         for (chars, _) in cursor.transition_chars() {
             all_chars = all_chars.add(chars);
@@ -236,25 +431,143 @@ fn get_following_chars(
         .collect()
 }
 
+/// Hash each token's NFA states, renumbered from zero, together with its implicit precedence
+/// and the characters that can follow it. The hashes are saved with the analysis, so they're
+/// computed from the JSON of these values, which is the same on every platform.
+fn get_token_hashes(grammar: &LexicalGrammar, following_chars: &[CharacterSet]) -> Vec<u64> {
+    let mut first_state_id = 0;
+    grammar
+        .variables
+        .iter()
+        .zip(following_chars)
+        .map(|(variable, following_chars)| {
+            let mut json = Vec::new();
+            for state in
+                &grammar.nfa.states[first_state_id as usize..=variable.start_state as usize]
+            {
+                match state {
+                    NfaState::Advance {
+                        chars,
+                        state_id,
+                        is_sep,
+                        precedence,
+                    } => serde_json::to_writer(
+                        &mut json,
+                        &(0, chars, state_id - first_state_id, is_sep, precedence),
+                    ),
+                    NfaState::Split(left, right) => serde_json::to_writer(
+                        &mut json,
+                        &(1, left - first_state_id, right - first_state_id),
+                    ),
+                    NfaState::Accept { precedence, .. } => {
+                        serde_json::to_writer(&mut json, &(2, precedence))
+                    }
+                }
+                .expect("tokens can be serialized");
+            }
+            serde_json::to_writer(&mut json, &(variable.implicit_precedence, following_chars))
+                .expect("tokens can be serialized");
+            first_state_id = variable.start_state + 1;
+            fnv1a_hash(&json)
+        })
+        .collect()
+}
+
+/// Compute the conflict status of every pair of tokens, except for the pairs whose status
+/// `reused` returns.
+fn compute_status_matrix(
+    grammar: &LexicalGrammar,
+    following_chars: &[CharacterSet],
+    budget: Option<Duration>,
+    reused: impl Fn(usize, usize) -> Option<(TokenConflictStatus, TokenConflictStatus)>,
+) -> Vec<TokenConflictStatus> {
+    let mut cursor = NfaCursor::new(&grammar.nfa, NfaStateSet::new());
+    let n = grammar.variables.len();
+    let mut status_matrix = vec![TokenConflictStatus::default(); n * n];
+    for i in 0..n {
+        for j in 0..i {
+            if let Some(status) = reused(i, j) {
+                status_matrix[matrix_index(n, i, j)] = status.0;
+                status_matrix[matrix_index(n, j, i)] = status.1;
+                continue;
+            }
+            let deadline = budget.map(|budget| Instant::now() + budget);
+            let status =
+                compute_conflict_status(&mut cursor, grammar, following_chars, i, j, deadline)
+                    .unwrap_or_else(|| {
+                        warn!(
+                            "analyzing whether the tokens `{}` and `{}` conflict took longer than {} ms, so they are treated as conflicting",
+                            grammar.variables[i].name,
+                            grammar.variables[j].name,
+                            budget.unwrap_or_default().as_millis()
+                        );
+                        let i_is_preferred = TokenConflictMap::prefer_token(
+                            grammar,
+                            (accept_precedence(grammar, i), i),
+                            (accept_precedence(grammar, j), j),
+                        );
+                        (
+                            TokenConflictStatus::conflicting(i_is_preferred),
+                            TokenConflictStatus::conflicting(!i_is_preferred),
+                        )
+                    });
+            trace!(
+                "conflict status of {:?} and {:?}: {:?}, {:?}",
+                grammar.variables[i].name,
+                grammar.variables[j].name,
+                status.0,
+                status.1
+            );
+            status_matrix[matrix_index(n, i, j)] = status.0;
+            status_matrix[matrix_index(n, j, i)] = status.1;
+        }
+    }
+    status_matrix
+}
+
+/// The highest precedence with which a token can be accepted.
+fn accept_precedence(grammar: &LexicalGrammar, index: usize) -> i32 {
+    grammar
+        .nfa
+        .states
+        .iter()
+        .filter_map(|state| match state {
+            NfaState::Accept {
+                variable_index,
+                precedence,
+            } if *variable_index == index => Some(*precedence),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
 fn compute_conflict_status(
     cursor: &mut NfaCursor,
     grammar: &LexicalGrammar,
     following_chars: &[CharacterSet],
     i: usize,
     j: usize,
-) -> (TokenConflictStatus, TokenConflictStatus) {
-    let mut visited_state_sets = HashSet::new();
-    let mut state_set_queue = vec![vec![
+    deadline: Option<Instant>,
+) -> Option<(TokenConflictStatus, TokenConflictStatus)> {
+    // Each state set that is reached is stored once, and is queued by its index.
+    let mut state_sets = IndexSet::<NfaStateSet, BuildHasherDefault<FxHasher>>::default();
+    state_sets.insert(smallvec![
         grammar.variables[i].start_state,
         grammar.variables[j].start_state,
-    ]];
+    ]);
+    let mut state_set_queue = vec![0];
     let mut result = (
         TokenConflictStatus::default(),
         TokenConflictStatus::default(),
     );
 
-    while let Some(state_set) = state_set_queue.pop() {
-        let mut live_variable_indices = grammar.variable_indices_for_nfa_states(&state_set);
+    while let Some(state_set_id) = state_set_queue.pop() {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return None;
+        }
+        let state_set = &state_sets[state_set_id];
+        let mut live_variable_indices = grammar.variable_indices_for_nfa_states(state_set);
 
         // If only one of the two tokens could possibly match from this state, then
         // there is no reason to analyze any of its successors. Just record the fact
@@ -270,7 +583,7 @@ fn compute_conflict_status(
         }
 
         // Don't pursue states where there's no potential for conflict.
-        cursor.reset(state_set);
+        cursor.reset(state_set.clone());
         let within_separator = cursor.transition_chars().any(|(_, sep)| sep);
 
         // Examine each possible completed token in this state.
@@ -362,12 +675,15 @@ fn compute_conflict_status(
                 }
             }
 
-            if can_advance && visited_state_sets.insert(transition.states.clone()) {
-                state_set_queue.push(transition.states);
+            if can_advance {
+                let (state_set_id, is_new) = state_sets.insert_full(transition.states);
+                if is_new {
+                    state_set_queue.push(state_set_id);
+                }
             }
         }
     }
-    result
+    Some(result)
 }
 
 #[cfg(test)]
@@ -398,7 +714,7 @@ mod tests {
         })
         .unwrap();
 
-        let token_map = TokenConflictMap::new(&grammar, Vec::new());
+        let token_map = TokenConflictMap::new(&grammar, Vec::new(), None);
 
         assert_eq!(
             token_map.starting_chars_by_index[0],
@@ -449,6 +765,7 @@ mod tests {
                     .copied()
                     .collect(),
             ],
+            None,
         );
 
         // Given the string "in", the `in` token is preferred over the `identifier` token
@@ -486,12 +803,79 @@ mod tests {
 
         let var = |name| index_of_var(&grammar, name);
 
-        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 4]);
+        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 4], None);
 
         assert!(token_map.does_conflict(var("newline"), var("x")));
         assert!(!token_map.does_conflict(var("x"), var("newline")));
     }
 
+    #[test]
+    fn test_token_conflicts_with_exhausted_time_budget() {
+        let tokens = |y_precedence: i32| {
+            expand_tokens(ExtractedLexicalGrammar {
+                separators: vec![Rule::pattern("\\s", "")],
+                variables: vec![
+                    Variable {
+                        name: "x".to_string(),
+                        kind: VariableType::Named,
+                        rule: Rule::string("x"),
+                    },
+                    Variable {
+                        name: "y".to_string(),
+                        kind: VariableType::Named,
+                        rule: Rule::prec(Precedence::Integer(y_precedence), Rule::string("y")),
+                    },
+                ],
+            })
+            .unwrap()
+        };
+        let grammar = tokens(0);
+
+        let var = |name| index_of_var(&grammar, name);
+
+        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 2], None);
+        assert!(!token_map.does_conflict(var("x"), var("y")));
+        assert!(!token_map.does_overlap(var("y"), var("x")));
+
+        // Without any time to analyze them, the tokens are assumed to conflict.
+        let token_map =
+            TokenConflictMap::new(&grammar, vec![TokenSet::new(); 2], Some(Duration::ZERO));
+        for (i, j) in [(var("x"), var("y")), (var("y"), var("x"))] {
+            assert!(token_map.does_conflict(i, j));
+            assert!(token_map.does_overlap(i, j));
+            assert!(token_map.does_match_shorter_or_longer(i, j));
+        }
+
+        // Only the token that would be preferred is assumed to match the same strings as the
+        // other, and strings that the other doesn't match. With equal precedence, that's the
+        // token that comes first.
+        let preferred = TokenConflictStatus::conflicting(true);
+        let other = TokenConflictStatus::conflicting(false);
+        assert!(preferred.matches_same_string && preferred.matches_different_string);
+        assert!(!other.matches_same_string && !other.matches_different_string);
+        assert_eq!(
+            token_map.status_matrix[matrix_index(2, var("x"), var("y"))],
+            preferred
+        );
+        assert_eq!(
+            token_map.status_matrix[matrix_index(2, var("y"), var("x"))],
+            other
+        );
+
+        // A token with a higher precedence is preferred.
+        let grammar = tokens(1);
+        let token_map =
+            TokenConflictMap::new(&grammar, vec![TokenSet::new(); 2], Some(Duration::ZERO));
+        assert_eq!(
+            token_map.status_matrix[matrix_index(2, var("x"), var("y"))],
+            other
+        );
+        assert_eq!(
+            token_map.status_matrix[matrix_index(2, var("y"), var("x"))],
+            preferred
+        );
+    }
+
     #[test]
     fn test_token_conflicts_with_open_ended_tokens() {
         let grammar = expand_tokens(ExtractedLexicalGrammar {
@@ -513,12 +897,215 @@ mod tests {
 
         let var = |name| index_of_var(&grammar, name);
 
-        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 4]);
+        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 4], None);
 
         assert!(token_map.does_match_shorter_or_longer(var("anything"), var("x")));
         assert!(!token_map.does_match_shorter_or_longer(var("x"), var("anything")));
     }
 
+    #[test]
+    fn test_conflicting_token_groups() {
+        let grammar = expand_tokens(ExtractedLexicalGrammar {
+            separators: Vec::new(),
+            variables: vec![
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+                Variable {
+                    name: "hex_digits".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::prec(Precedence::Integer(1), Rule::pattern("[a-f0-9]+", "")),
+                },
+                Variable {
+                    name: "abc".to_string(),
+                    kind: VariableType::Anonymous,
+                    rule: Rule::string("abc"),
+                },
+                Variable {
+                    name: "x".to_string(),
+                    kind: VariableType::Anonymous,
+                    rule: Rule::string("x"),
+                },
+                Variable {
+                    name: "number".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[0-9]+", ""),
+                },
+            ],
+        })
+        .unwrap();
+
+        let var = |name| index_of_var(&grammar, name);
+        let token_map = TokenConflictMap::new(&grammar, vec![TokenSet::new(); 5], None);
+
+        // Each pair of these tokens conflicts, and the lexer prefers them by precedence, then
+        // by whether they are strings.
+        assert_eq!(
+            token_map.conflicting_token_groups(&[
+                var("identifier"),
+                var("hex_digits"),
+                var("abc"),
+                var("x"),
+                var("number"),
+            ]),
+            vec![vec![var("hex_digits"), var("abc"), var("identifier")]]
+        );
+
+        // A single pair isn't reported as a group.
+        assert!(token_map
+            .conflicting_token_groups(&[var("identifier"), var("abc"), var("number")])
+            .is_empty());
+    }
+
+    #[test]
+    fn test_token_conflicts_with_previous_analysis() {
+        let tokens = |tokens: &[(&str, Rule)]| {
+            expand_tokens(ExtractedLexicalGrammar {
+                separators: vec![Rule::pattern("\\s", "")],
+                variables: tokens
+                    .iter()
+                    .map(|(name, rule)| Variable {
+                        name: (*name).to_string(),
+                        kind: VariableType::Named,
+                        rule: rule.clone(),
+                    })
+                    .collect(),
+            })
+            .unwrap()
+        };
+        let old_grammar = tokens(&[
+            ("in", Rule::string("in")),
+            ("identifier", Rule::pattern("\\w+", "")),
+            ("instanceof", Rule::string("instanceof")),
+        ]);
+        let new_grammar = tokens(&[
+            ("if", Rule::string("if")),
+            ("in", Rule::string("in")),
+            ("identifier", Rule::pattern("\\w+", "")),
+            ("instanceof", Rule::string("instance_of")),
+        ]);
+
+        // Mark the status of a pair of tokens that didn't change, so that reusing it can be
+        // told apart from analyzing it again.
+        let mut previous =
+            TokenConflictMap::new(&old_grammar, vec![TokenSet::new(); 3], None).analysis();
+        let var = |name| index_of_var(&old_grammar, name);
+        previous.status_matrix[matrix_index(3, var("in"), var("identifier"))] =
+            TokenConflictStatus::conflicting(true);
+
+        let following_tokens = vec![TokenSet::new(); 4];
+        let expected = TokenConflictMap::new(&new_grammar, following_tokens.clone(), None);
+        let token_map = TokenConflictMap::with_previous_analysis(
+            &new_grammar,
+            following_tokens.clone(),
+            None,
+            Some(&previous),
+        );
+        let var = |name| index_of_var(&new_grammar, name);
+        for i in 0..4 {
+            for j in 0..4 {
+                let status = &token_map.status_matrix[matrix_index(4, i, j)];
+                if (i, j) == (var("in"), var("identifier")) {
+                    assert_eq!(*status, TokenConflictStatus::conflicting(true));
+                } else {
+                    assert_eq!(*status, expected.status_matrix[matrix_index(4, i, j)]);
+                }
+            }
+        }
+
+        // An analysis that was done with a different time budget isn't reused.
+        let token_map = TokenConflictMap::with_previous_analysis(
+            &new_grammar,
+            following_tokens,
+            Some(Duration::from_secs(1)),
+            Some(&previous),
+        );
+        assert_eq!(token_map.status_matrix, expected.status_matrix);
+    }
+
+    #[test]
+    fn test_token_conflicts_with_one_changed_token() {
+        let tokens = |number_pattern: &'static str| {
+            expand_tokens(ExtractedLexicalGrammar {
+                separators: vec![Rule::pattern("\\s", "")],
+                variables: [
+                    ("in", Rule::string("in")),
+                    ("identifier", Rule::pattern("[a-z]+", "")),
+                    ("number", Rule::pattern(number_pattern, "")),
+                    ("dot", Rule::string(".")),
+                ]
+                .into_iter()
+                .map(|(name, rule)| Variable {
+                    name: name.to_string(),
+                    kind: VariableType::Named,
+                    rule,
+                })
+                .collect(),
+            })
+            .unwrap()
+        };
+        let old_grammar = tokens("\\d+");
+        let new_grammar = tokens("\\d+(\\.\\d+)?");
+        let following_tokens = vec![TokenSet::new(); 4];
+
+        // Mark the status of every pair of tokens, so that reusing it can be told apart from
+        // analyzing it again.
+        let mut previous =
+            TokenConflictMap::new(&old_grammar, following_tokens.clone(), None).analysis();
+        let marked = TokenConflictStatus {
+            matches_prefix: true,
+            does_match_continuation: true,
+            does_match_valid_continuation: true,
+            does_match_separators: true,
+            matches_same_string: true,
+            matches_different_string: true,
+        };
+        previous.status_matrix.fill(marked.clone());
+
+        let expected = TokenConflictMap::new(&new_grammar, following_tokens.clone(), None);
+        let token_map = TokenConflictMap::with_previous_analysis(
+            &new_grammar,
+            following_tokens.clone(),
+            None,
+            Some(&previous),
+        );
+        let number = index_of_var(&new_grammar, "number");
+        assert_eq!(
+            token_map
+                .token_hashes
+                .iter()
+                .zip(&previous.token_hashes)
+                .filter(|(a, b)| a != b)
+                .count(),
+            1
+        );
+        for i in 0..4 {
+            for j in 0..4 {
+                let status = &token_map.status_matrix[matrix_index(4, i, j)];
+                if i == j || i == number || j == number {
+                    assert_eq!(*status, expected.status_matrix[matrix_index(4, i, j)]);
+                } else {
+                    assert_eq!(*status, marked, "{i} {j}");
+                }
+            }
+        }
+
+        // An analysis that was made by another version of the CLI isn't reused.
+        previous.cli_version = "0.0.0".to_string();
+        let token_map = TokenConflictMap::with_previous_analysis(
+            &new_grammar,
+            following_tokens,
+            None,
+            Some(&previous),
+        );
+        assert_eq!(token_map.status_matrix, expected.status_matrix);
+
+        // The hashes don't depend on the platform or on the version of Rust.
+        assert_eq!(token_map.token_hashes[0], 0x49e6_f278_cd45_eab8);
+    }
+
     fn index_of_var(grammar: &LexicalGrammar, name: &str) -> usize {
         grammar
             .variables
@@ -0,0 +1,216 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use log::{info, warn};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::content_hash;
+
+/// The intermediate results of generating a parser, kept so that a later generation can
+/// reuse the result of a phase instead of running it again. Each result is stored with a
+/// key computed from the inputs of its phase, and a result with a different key is ignored.
+///
+/// Results saved in a directory let a generation that was interrupted resume after the last
+/// phase that finished. Results kept in memory let a process that generates the same grammar
+/// repeatedly, like `tree-sitter generate --watch`, skip the phases whose inputs an edit
+/// didn't change.
+pub struct Checkpoints {
+    store: Store,
+}
+
+enum Store {
+    Directory(PathBuf),
+    Memory(Mutex<PhaseResults>),
+}
+
+/// The latest result of each phase, with its key.
+type PhaseResults = HashMap<&'static str, (String, Box<dyn Any + Send>)>;
+
+impl Checkpoints {
+    pub fn in_directory(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create the checkpoint directory {dir:?}"))?;
+        Ok(Self {
+            store: Store::Directory(dir.to_path_buf()),
+        })
+    }
+
+    #[must_use]
+    pub fn in_memory() -> Self {
+        Self {
+            store: Store::Memory(Mutex::default()),
+        }
+    }
+
+    /// The key of a phase's result, computed from everything that the result depends on.
+    pub fn key(inputs: &impl Serialize) -> String {
+        let json = serde_json::to_vec(&(env!("CARGO_PKG_VERSION"), inputs))
+            .expect("phase inputs can be serialized");
+        content_hash(&json)
+    }
+
+    /// Load the result of the phase with the given name, if it was saved with this key.
+    pub fn load<T>(&self, name: &'static str, key: &str) -> Option<T>
+    where
+        T: DeserializeOwned + Clone + Send + 'static,
+    {
+        let value = self.load_with_key(name, Some(key))?;
+        match &self.store {
+            Store::Directory(dir) => {
+                info!(
+                    "resuming from the checkpoint {:?}",
+                    dir.join(format!("{name}.json"))
+                );
+            }
+            Store::Memory(_) => info!("reusing the {name} from the previous generation"),
+        }
+        Some(value)
+    }
+
+    /// Load the latest result of the phase with the given name, whatever inputs it was saved
+    /// for, so that the parts of it that are still valid can be reused.
+    pub fn load_latest<T>(&self, name: &'static str) -> Option<T>
+    where
+        T: DeserializeOwned + Clone + Send + 'static,
+    {
+        self.load_with_key(name, None)
+    }
+
+    fn load_with_key<T>(&self, name: &'static str, key: Option<&str>) -> Option<T>
+    where
+        T: DeserializeOwned + Clone + Send + 'static,
+    {
+        match &self.store {
+            Store::Directory(dir) => {
+                let path = dir.join(format!("{name}.json"));
+                let mut reader = BufReader::new(File::open(&path).ok()?);
+                let mut saved_key = String::new();
+                reader.read_line(&mut saved_key).ok()?;
+                if key.is_some_and(|key| saved_key.trim_end() != key) {
+                    return None;
+                }
+                serde_json::from_reader(reader)
+                    .map_err(|error| {
+                        warn!("ignoring the checkpoint {path:?}, which can't be read: {error}");
+                    })
+                    .ok()
+            }
+            Store::Memory(results) => {
+                let results = results.lock().unwrap();
+                let (saved_key, value) = results.get(name)?;
+                if key.is_some_and(|key| saved_key != key) {
+                    return None;
+                }
+                value.downcast_ref::<T>().cloned()
+            }
+        }
+    }
+
+    pub fn save<T>(&self, name: &'static str, key: &str, value: &T) -> Result<()>
+    where
+        T: Serialize + Clone + Send + 'static,
+    {
+        match &self.store {
+            Store::Directory(dir) => {
+                let path = dir.join(format!("{name}.json"));
+                // Write to another file first, so that an interruption can't leave a
+                // truncated checkpoint behind.
+                let temp_path = path.with_extension("json.tmp");
+                let write = || -> Result<()> {
+                    let mut writer = BufWriter::new(File::create(&temp_path)?);
+                    writeln!(writer, "{key}")?;
+                    serde_json::to_writer(&mut writer, value)?;
+                    writer.flush()?;
+                    fs::rename(&temp_path, &path)?;
+                    Ok(())
+                };
+                write().with_context(|| format!("Failed to write the checkpoint {path:?}"))
+            }
+            Store::Memory(results) => {
+                results
+                    .lock()
+                    .unwrap()
+                    .insert(name, (key.to_string(), Box::new(value.clone())));
+                Ok(())
+            }
+        }
+    }
+
+    /// Load the result of a phase, or run the phase and save its result.
+    pub fn resume_or_run<T>(
+        &self,
+        name: &'static str,
+        key: &str,
+        run: impl FnOnce() -> Result<T>,
+    ) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned + Clone + Send + 'static,
+    {
+        if let Some(value) = self.load(name, key) {
+            return Ok(value);
+        }
+        let value = run()?;
+        self.save(name, key, &value)?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn test_checkpoints() {
+        let dir = tempfile::tempdir().unwrap();
+        for checkpoints in [
+            Checkpoints::in_directory(dir.path()).unwrap(),
+            Checkpoints::in_memory(),
+        ] {
+            let key_1 = Checkpoints::key(&"inputs-1");
+            assert_eq!(checkpoints.load::<Vec<u32>>("phase", &key_1), None);
+
+            let runs = Cell::new(0);
+            let run = || {
+                runs.set(runs.get() + 1);
+                Ok(vec![1, 2, 3])
+            };
+            assert_eq!(
+                checkpoints.resume_or_run("phase", &key_1, run).unwrap(),
+                [1, 2, 3]
+            );
+            assert_eq!(
+                checkpoints.resume_or_run("phase", &key_1, run).unwrap(),
+                [1, 2, 3]
+            );
+            assert_eq!(runs.get(), 1);
+
+            // A result that was saved for different inputs is ignored, unless the latest
+            // result is asked for.
+            let key_2 = Checkpoints::key(&"inputs-2");
+            assert_eq!(checkpoints.load::<Vec<u32>>("phase", &key_2), None);
+            assert_eq!(checkpoints.load_latest("phase"), Some(vec![1, 2, 3]));
+            assert_eq!(checkpoints.load_latest::<Vec<u32>>("other-phase"), None);
+            assert_eq!(
+                checkpoints.resume_or_run("phase", &key_2, run).unwrap(),
+                [1, 2, 3]
+            );
+            assert_eq!(runs.get(), 2);
+        }
+        assert!(!dir.path().join("phase.json.tmp").exists());
+
+        // So is a checkpoint that can't be read.
+        let checkpoints = Checkpoints::in_directory(dir.path()).unwrap();
+        let key = Checkpoints::key(&"inputs-2");
+        fs::write(dir.path().join("phase.json"), format!("{key}\n[1, 2,")).unwrap();
+        assert_eq!(checkpoints.load::<Vec<u32>>("phase", &key), None);
+    }
+}
@@ -0,0 +1,298 @@
+use std::{collections::HashSet, env, fmt::Write, fs, path::Path};
+
+use anyhow::{anyhow, Error};
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use super::{error::GrammarError, grammars::InputGrammar, rules::Rule};
+
+lazy_static! {
+    static ref MENTIONED_NAME_REGEX: Regex =
+        Regex::new(r"[`']([a-zA-Z_][a-zA-Z0-9_]*)[`']").unwrap();
+    static ref RULES_SECTION_REGEX: Regex =
+        Regex::new(r#"(?m)(\brules\s*:|"rules"\s*:|^\[rules\b)"#).unwrap();
+}
+
+/// The place in a grammar file where a rule is defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleLocation {
+    pub row: usize,
+    pub column: usize,
+    pub len: usize,
+}
+
+/// A problem with a grammar, as reported by `tree-sitter lsp`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct GrammarDiagnostic {
+    pub message: String,
+    pub is_error: bool,
+    /// The definitions of the rules that the problem concerns. The problem is reported at
+    /// the first of them, or at the start of the grammar file if there are none.
+    pub rules: Vec<(String, RuleLocation)>,
+}
+
+impl GrammarDiagnostic {
+    pub fn error(message: &str, source: &str) -> Self {
+        Self {
+            message: message.trim_end().to_string(),
+            is_error: true,
+            rules: mentioned_rules(message, source),
+        }
+    }
+}
+
+/// Add excerpts of the grammar file to an error from processing the grammar, pointing at
+/// the definitions of the rules that the error mentions. If the grammar file can't be read,
+/// or the error doesn't mention any of its rules, the error is returned unchanged. A
+/// [`GrammarError`] stays one, with its `span` set to the definition of its first symbol.
+pub fn annotate_error(error: Error, grammar_path: &Path) -> Error {
+    if error.chain().count() > 1 {
+        return error;
+    }
+    let Ok(source) = fs::read_to_string(grammar_path) else {
+        return error;
+    };
+    let display_path = env::current_dir()
+        .ok()
+        .and_then(|dir| grammar_path.strip_prefix(dir).ok())
+        .unwrap_or(grammar_path);
+    let message = error.to_string();
+    let notes = render_notes(&message, &display_path.display().to_string(), &source);
+    match error.downcast::<GrammarError>() {
+        Ok(mut error) => {
+            error.span = error
+                .symbols
+                .first()
+                .and_then(|name| find_rule_definition(&source, name));
+            if !notes.is_empty() {
+                error.message = format!("{}\n{notes}", message.trim_end());
+            }
+            error.into()
+        }
+        Err(error) if notes.is_empty() => error,
+        Err(_) => anyhow!("{}\n{notes}", message.trim_end()),
+    }
+}
+
+fn render_notes(message: &str, path: &str, source: &str) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+    let mut notes = String::new();
+    for (name, location) in mentioned_rules(message, source) {
+        let line_number = (location.row + 1).to_string();
+        let gutter = " ".repeat(line_number.len());
+        writeln!(notes, "\nnote: `{name}` is defined here").unwrap();
+        writeln!(
+            notes,
+            "{gutter}--> {path}:{line_number}:{}",
+            location.column + 1
+        )
+        .unwrap();
+        writeln!(notes, "{gutter} |").unwrap();
+        writeln!(notes, "{line_number} | {}", lines[location.row]).unwrap();
+        writeln!(
+            notes,
+            "{gutter} | {}{}",
+            " ".repeat(location.column),
+            "^".repeat(location.len)
+        )
+        .unwrap();
+    }
+    notes
+}
+
+/// The rules that a message names in quotes or backticks, along with their definitions,
+/// in the order that they are first named.
+fn mentioned_rules(message: &str, source: &str) -> Vec<(String, RuleLocation)> {
+    let mut rules = Vec::<(String, RuleLocation)>::new();
+    for captures in MENTIONED_NAME_REGEX.captures_iter(message) {
+        let name = captures.get(1).unwrap().as_str();
+        if rules.iter().any(|(rule, _)| rule == name) {
+            continue;
+        }
+        if let Some(location) = find_rule_definition(source, name) {
+            rules.push((name.to_string(), location));
+        }
+    }
+    rules
+}
+
+/// Warn about the rules that can't be reached from the start rule, the extras, the
+/// external tokens or the word token, and so are never used.
+pub fn unused_rule_diagnostics(grammar: &InputGrammar, source: &str) -> Vec<GrammarDiagnostic> {
+    let mut stack = Vec::new();
+    stack.extend(grammar.variables.first().map(|v| v.name.as_str()));
+    stack.extend(grammar.word_token.as_deref());
+    for rule in grammar.extra_symbols.iter().chain(&grammar.external_tokens) {
+        collect_symbol_names(rule, &mut stack);
+    }
+
+    let mut used = HashSet::new();
+    while let Some(name) = stack.pop() {
+        if used.insert(name) {
+            if let Some(variable) = grammar.variables.iter().find(|v| v.name == name) {
+                collect_symbol_names(&variable.rule, &mut stack);
+            }
+        }
+    }
+
+    grammar
+        .variables
+        .iter()
+        .filter(|variable| !used.contains(variable.name.as_str()))
+        .map(|variable| GrammarDiagnostic {
+            message: format!("The rule `{}` is never used", variable.name),
+            is_error: false,
+            rules: find_rule_definition(source, &variable.name)
+                .map(|location| (variable.name.clone(), location))
+                .into_iter()
+                .collect(),
+        })
+        .collect()
+}
+
+fn collect_symbol_names<'a>(rule: &'a Rule, names: &mut Vec<&'a str>) {
+    match rule {
+        Rule::NamedSymbol(name) => names.push(name),
+        Rule::Choice(elements) | Rule::Seq(elements) => {
+            for element in elements {
+                collect_symbol_names(element, names);
+            }
+        }
+        Rule::Metadata { rule, .. } | Rule::Repeat(rule) => collect_symbol_names(rule, names),
+        _ => {}
+    }
+}
+
+/// Find the definition of a rule in the source of a `grammar.js`, `grammar.json` or
+/// `grammar.toml` file, by looking for a line that starts with the rule's name as a key,
+/// after the start of the `rules` section.
+fn find_rule_definition(source: &str, name: &str) -> Option<RuleLocation> {
+    let rules_start = RULES_SECTION_REGEX.find(source)?.start();
+    let name = regex::escape(name);
+    let definition_regex = Regex::new(&format!(
+        r#"(?m)^[ \t]*(?:(\[rules\.{name}\])|(["']?{name}["']?)\s*[:=])"#
+    ))
+    .unwrap();
+    let captures = definition_regex.captures(&source[rules_start..])?;
+    let key = captures.get(1).or_else(|| captures.get(2))?;
+    let start = rules_start + key.start();
+    let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+    Some(RuleLocation {
+        row: source[..start].matches('\n').count(),
+        column: source[line_start..start].chars().count(),
+        len: key.as_str().chars().count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::parse_grammar::parse_grammar;
+
+    const GRAMMAR_JS: &str = r"module.exports = grammar({
+  name: 'arithmetic',
+
+  rules: {
+    program: $ => repeat($._expression),
+
+    _expression: $ => choice($.number, $.sum),
+
+    sum: $ => seq($._expression, '+', $._expression),
+
+    number: _ => /\d+/,
+  },
+});
+";
+
+    #[test]
+    fn test_find_rule_definition() {
+        assert_eq!(
+            find_rule_definition(GRAMMAR_JS, "sum"),
+            Some(RuleLocation {
+                row: 8,
+                column: 4,
+                len: 3
+            })
+        );
+        assert_eq!(find_rule_definition(GRAMMAR_JS, "name"), None);
+        assert_eq!(find_rule_definition(GRAMMAR_JS, "missing"), None);
+
+        let grammar_json = "{\n  \"name\": \"a\",\n  \"rules\": {\n    \"sum\": {}\n  }\n}";
+        assert_eq!(
+            find_rule_definition(grammar_json, "sum"),
+            Some(RuleLocation {
+                row: 3,
+                column: 4,
+                len: 5
+            })
+        );
+
+        let grammar_toml = "name = \"a\"\n\n[rules.program]\ntype = \"BLANK\"\n";
+        assert_eq!(
+            find_rule_definition(grammar_toml, "program"),
+            Some(RuleLocation {
+                row: 2,
+                column: 0,
+                len: 15
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_notes_for_mentioned_rules() {
+        let message = "Unresolved conflict for symbol sequence:\n\n  _expression  '+'  _expression  •  '+'  …\n\nPossible resolutions:\n\n  1:  Specify a left or right associativity in `sum`\n  2:  Add a conflict for these rules: `sum`\n";
+        assert_eq!(
+            render_notes(message, "grammar.js", GRAMMAR_JS),
+            "
+note: `sum` is defined here
+ --> grammar.js:9:5
+  |
+9 |     sum: $ => seq($._expression, '+', $._expression),
+  |     ^^^
+"
+        );
+        assert_eq!(
+            render_notes("Undefined symbol `other`", "grammar.js", GRAMMAR_JS),
+            ""
+        );
+    }
+
+    #[test]
+    fn test_unused_rule_diagnostics() {
+        let grammar = parse_grammar(
+            r##"{
+                "name": "arithmetic",
+                "extras": [{"type": "SYMBOL", "name": "comment"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "number"}},
+                    "number": {"type": "PATTERN", "value": "\\d+"},
+                    "comment": {"type": "PATTERN", "value": "#.*"},
+                    "sum": {
+                        "type": "SEQ",
+                        "members": [
+                            {"type": "SYMBOL", "name": "number"},
+                            {"type": "STRING", "value": "+"},
+                            {"type": "SYMBOL", "name": "sum"}
+                        ]
+                    }
+                }
+            }"##,
+        )
+        .unwrap();
+        assert_eq!(
+            unused_rule_diagnostics(&grammar, GRAMMAR_JS),
+            [GrammarDiagnostic {
+                message: "The rule `sum` is never used".to_string(),
+                is_error: false,
+                rules: vec![(
+                    "sum".to_string(),
+                    RuleLocation {
+                        row: 8,
+                        column: 4,
+                        len: 3
+                    }
+                )],
+            }]
+        );
+    }
+}
@@ -235,7 +235,10 @@ function grammar(baseGrammar, options) {
       externals: [],
       inline: [],
       supertypes: [],
+      deprecated: [],
       precedences: [],
+      injections: [],
+      display_names: {},
     };
   } else {
     baseGrammar = baseGrammar.grammar;
@@ -406,6 +409,27 @@ function grammar(baseGrammar, options) {
     });
   }
 
+  let deprecated = baseGrammar.deprecated || [];
+  if (options.deprecated) {
+    if (typeof options.deprecated !== "function") {
+      throw new Error("Grammar's 'deprecated' property must be a function.");
+    }
+
+    const baseDeprecatedRules = deprecated.map(sym);
+    const deprecatedRules = options.deprecated.call(ruleBuilder, ruleBuilder, baseDeprecatedRules);
+
+    if (!Array.isArray(deprecatedRules)) {
+      throw new Error("Grammar's deprecated must be an array of rules.");
+    }
+
+    deprecated = deprecatedRules.map(symbol => {
+      if (symbol.name === 'ReferenceError') {
+        throw new Error(`Deprecated rule \`${symbol.symbol.name}\` is not defined.`);
+      }
+      return symbol.name;
+    });
+  }
+
   let precedences = baseGrammar.precedences;
   if (options.precedences) {
     if (typeof options.precedences !== "function") {
@@ -423,12 +447,60 @@ function grammar(baseGrammar, options) {
     });
   }
 
+  let injections = baseGrammar.injections || [];
+  if (options.injections) {
+    if (typeof options.injections !== "function") {
+      throw new Error("Grammar's 'injections' property must be a function.");
+    }
+
+    const injectionList = options.injections.call(ruleBuilder, ruleBuilder, injections);
+    if (!Array.isArray(injectionList)) {
+      throw new Error("Grammar's injections must be an array of objects.");
+    }
+
+    injections = injectionList.map(injection => {
+      if (typeof injection !== "object" || !injection.node) {
+        throw new Error("Grammar's injections must be objects with a 'node' property.");
+      }
+
+      let node = injection.node;
+      if (typeof node !== "string") {
+        if (node.name === 'ReferenceError') {
+          throw new Error(`Injection rule \`${node.symbol.name}\` is not defined.`);
+        }
+        node = normalize(node).name;
+      }
+
+      if ((injection.language === undefined) === (injection.language_field === undefined)) {
+        throw new Error(`Injection for rule \`${node}\` must have either a 'language' or a 'language_field'.`);
+      }
+
+      return Object.assign({}, injection, { node });
+    });
+  }
+
+  let displayNames = baseGrammar.display_names || {};
+  if (options.display_names) {
+    if (typeof options.display_names !== "object" || Array.isArray(options.display_names)) {
+      throw new Error("Grammar's 'display_names' property must be an object.");
+    }
+
+    for (const [name, displayName] of Object.entries(options.display_names)) {
+      if (typeof displayName !== "string") {
+        throw new Error(`Display name for token \`${name}\` must be a string.`);
+      }
+    }
+
+    displayNames = Object.assign({}, displayNames, options.display_names);
+  }
+
   if (Object.keys(rules).length === 0) {
     throw new Error("Grammar must have at least one rule.");
   }
 
   return {
     grammar: {
+      schema_version: 1,
       name,
       inherits,
       word,
@@ -439,6 +511,9 @@ function grammar(baseGrammar, options) {
       externals,
       inline,
       supertypes,
+      deprecated: deprecated.length > 0 ? deprecated : undefined,
+      injections: injections.length > 0 ? injections : undefined,
+      display_names: Object.keys(displayNames).length > 0 ? displayNames : undefined,
     },
   };
 }
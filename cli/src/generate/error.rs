@@ -0,0 +1,89 @@
+use std::{error, fmt};
+
+use super::diagnostics::RuleLocation;
+
+/// The kind of problem that a [`GrammarError`] reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GrammarErrorKind {
+    /// A rule refers to a rule or token that isn't defined.
+    UndefinedSymbol,
+    /// The start rule's name begins with an underscore.
+    HiddenStartRule,
+    /// An entry in `extras` is neither a token nor a reference to a rule, or is listed twice.
+    InvalidExtra,
+    /// A rule can't be renamed as requested.
+    InvalidRename,
+    /// A token uses a rule or a regex feature that can't be compiled into the lexer.
+    UnsupportedToken,
+    /// A rule other than the start rule matches the empty string.
+    EmptyRule,
+    /// A rule listed in `inline` can't be inlined.
+    InvalidInline,
+    /// An entry in `externals` can't be used as an external token.
+    InvalidExternalToken,
+    /// The `word` rule isn't a token.
+    InvalidWordToken,
+    /// A rule uses a named precedence that isn't in any of the `precedences` lists.
+    UndeclaredPrecedence,
+    /// Two of the `precedences` lists order the same precedences differently.
+    ConflictingPrecedences,
+    /// An extra rule can end in more than one way.
+    AmbiguousExtra,
+    /// The grammar is ambiguous at some point, and the conflict isn't resolved by
+    /// precedence, associativity or an entry in `conflicts`.
+    UnresolvedConflict,
+}
+
+/// A problem with a grammar that prevents a parser from being generated. The functions
+/// that prepare a grammar and build its parse table return these inside of an
+/// [`anyhow::Error`], which can be found with [`GrammarError::find`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GrammarError {
+    pub kind: GrammarErrorKind,
+    /// The description of the problem, followed by excerpts of the grammar file if the
+    /// error has been located in it.
+    pub message: String,
+    /// The names of the rules, tokens and precedences that the problem concerns.
+    pub symbols: Vec<String>,
+    /// The definition of the first of the `symbols` in the grammar file, if it's known.
+    pub span: Option<RuleLocation>,
+    /// A change to the grammar that would fix the problem.
+    pub suggestion: Option<String>,
+}
+
+impl GrammarError {
+    pub fn new(kind: GrammarErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            symbols: Vec::new(),
+            span: None,
+            suggestion: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_symbols<T: ToString>(mut self, symbols: impl IntoIterator<Item = T>) -> Self {
+        self.symbols = symbols.into_iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    #[must_use]
+    pub fn with_suggestion(mut self, suggestion: impl Into<String>) -> Self {
+        self.suggestion = Some(suggestion.into());
+        self
+    }
+
+    /// Find the grammar error in an error's chain of causes.
+    pub fn find(error: &anyhow::Error) -> Option<&Self> {
+        error.chain().find_map(|cause| cause.downcast_ref())
+    }
+}
+
+impl fmt::Display for GrammarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for GrammarError {}
@@ -0,0 +1,270 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+
+use super::{
+    grammars::{
+        InputGrammar, LexicalGrammar, PrecedenceEntry, SyntaxGrammar, Variable, VariableType,
+    },
+    prepare_grammar::prepare_grammar,
+    rules::Rule,
+    timings::Timings,
+};
+
+/// Builds an [`InputGrammar`] in code, for tools that translate grammars from other formats
+/// and would otherwise have to produce `grammar.json` text. The rules are written with the
+/// same [`Rule`] values that `grammar.json` is parsed into.
+#[derive(Debug, Default)]
+pub struct GrammarBuilder {
+    grammar: InputGrammar,
+}
+
+impl GrammarBuilder {
+    #[must_use]
+    pub fn new(name: &str) -> Self {
+        Self {
+            grammar: InputGrammar {
+                name: name.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Add a rule. The first rule is the start rule.
+    #[must_use]
+    pub fn rule(mut self, name: &str, rule: Rule) -> Self {
+        self.grammar.variables.push(Variable {
+            name: name.to_string(),
+            kind: VariableType::Named,
+            rule,
+        });
+        self
+    }
+
+    #[must_use]
+    pub fn extra(mut self, rule: Rule) -> Self {
+        self.grammar.extra_symbols.push(rule);
+        self
+    }
+
+    #[must_use]
+    pub fn external(mut self, rule: Rule) -> Self {
+        self.grammar.external_tokens.push(rule);
+        self
+    }
+
+    /// Add a list of precedences, from highest to lowest.
+    #[must_use]
+    pub fn precedences(mut self, entries: Vec<PrecedenceEntry>) -> Self {
+        self.grammar.precedence_orderings.push(entries);
+        self
+    }
+
+    #[must_use]
+    pub fn conflict(mut self, rule_names: &[&str]) -> Self {
+        self.grammar
+            .expected_conflicts
+            .push(rule_names.iter().map(ToString::to_string).collect());
+        self
+    }
+
+    #[must_use]
+    pub fn inline(mut self, rule_name: &str) -> Self {
+        self.grammar.variables_to_inline.push(rule_name.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn supertype(mut self, rule_name: &str) -> Self {
+        self.grammar.supertype_symbols.push(rule_name.to_string());
+        self
+    }
+
+    #[must_use]
+    pub fn word(mut self, rule_name: &str) -> Self {
+        self.grammar.word_token = Some(rule_name.to_string());
+        self
+    }
+
+    /// Check that the grammar is well-formed, and return it.
+    pub fn build(self) -> Result<InputGrammar> {
+        let grammar = self.grammar;
+        if grammar.name.is_empty()
+            || grammar.name.starts_with(|c: char| c.is_ascii_digit())
+            || !grammar
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(anyhow!(
+                "Invalid grammar name `{}`. It must be a C identifier.",
+                grammar.name
+            ));
+        }
+        if grammar.variables.is_empty() {
+            return Err(anyhow!("A grammar must have at least one rule"));
+        }
+
+        let mut names = HashSet::new();
+        for variable in &grammar.variables {
+            if !names.insert(variable.name.as_str()) {
+                return Err(anyhow!("Duplicate rule `{}`", variable.name));
+            }
+        }
+        for rule in &grammar.external_tokens {
+            if let Rule::NamedSymbol(name) = rule {
+                names.insert(name);
+            }
+        }
+
+        for variable in &grammar.variables {
+            check_symbols(&variable.rule, &names)
+                .map_err(|e| anyhow!("{e} in the rule `{}`", variable.name))?;
+        }
+        for rule in &grammar.extra_symbols {
+            if matches!(rule, Rule::String(value) if value.is_empty()) {
+                return Err(anyhow!(
+                    "Rules in the `extras` array must not contain empty strings"
+                ));
+            }
+            check_symbols(rule, &names).map_err(|e| anyhow!("{e} in `extras`"))?;
+        }
+        let referenced_names =
+            grammar
+                .expected_conflicts
+                .iter()
+                .flatten()
+                .chain(&grammar.supertype_symbols)
+                .chain(&grammar.variables_to_inline)
+                .chain(&grammar.word_token)
+                .chain(grammar.precedence_orderings.iter().flatten().filter_map(
+                    |entry| match entry {
+                        PrecedenceEntry::Symbol(name) => Some(name),
+                        PrecedenceEntry::Name(_) => None,
+                    },
+                ));
+        for name in referenced_names {
+            if !names.contains(name.as_str()) {
+                return Err(anyhow!("Undefined symbol `{name}`"));
+            }
+        }
+
+        Ok(grammar)
+    }
+
+    /// Check the grammar and prepare it for building parse tables, as `tree-sitter generate`
+    /// does, returning its syntactic and lexical parts.
+    pub fn build_prepared(self) -> Result<(SyntaxGrammar, LexicalGrammar)> {
+        let grammar = self.build()?;
+        let (syntax_grammar, lexical_grammar, _, _) =
+            prepare_grammar(&grammar, None, &HashMap::new(), &mut Timings::default())?;
+        Ok((syntax_grammar, lexical_grammar))
+    }
+}
+
+fn check_symbols(rule: &Rule, names: &HashSet<&str>) -> Result<()> {
+    match rule {
+        Rule::NamedSymbol(name) if !names.contains(name.as_str()) => {
+            Err(anyhow!("Undefined symbol `{name}`"))
+        }
+        Rule::Choice(elements) | Rule::Seq(elements) => elements
+            .iter()
+            .try_for_each(|element| check_symbols(element, names)),
+        Rule::Metadata { rule, .. } | Rule::Repeat(rule) => check_symbols(rule, names),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate_parser_from_grammar, rules::Precedence, GenerateOptions};
+
+    #[test]
+    fn test_grammar_builder() {
+        let builder = || {
+            GrammarBuilder::new("arithmetic")
+                .rule(
+                    "program",
+                    Rule::repeat(Rule::NamedSymbol("_expression".to_string())),
+                )
+                .rule(
+                    "_expression",
+                    Rule::choice(vec![
+                        Rule::NamedSymbol("number".to_string()),
+                        Rule::NamedSymbol("sum".to_string()),
+                    ]),
+                )
+                .rule(
+                    "sum",
+                    Rule::prec_left(
+                        Precedence::Name("sum".to_string()),
+                        Rule::seq(vec![
+                            Rule::NamedSymbol("_expression".to_string()),
+                            Rule::String("+".to_string()),
+                            Rule::NamedSymbol("_expression".to_string()),
+                        ]),
+                    ),
+                )
+                .rule("number", Rule::Pattern("\\d+".to_string(), String::new()))
+                .extra(Rule::Pattern("\\s".to_string(), String::new()))
+                .precedences(vec![PrecedenceEntry::Name("sum".to_string())])
+        };
+
+        let (syntax_grammar, lexical_grammar) = builder().build_prepared().unwrap();
+        assert_eq!(
+            syntax_grammar
+                .variables
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<_>>(),
+            ["program", "_expression", "sum", "program_repeat1"]
+        );
+        assert_eq!(
+            lexical_grammar
+                .variables
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<_>>(),
+            ["+", "number"]
+        );
+
+        let output =
+            generate_parser_from_grammar(&builder().build().unwrap(), &GenerateOptions::default())
+                .unwrap();
+        assert_eq!(output.name, "arithmetic");
+
+        assert_eq!(
+            builder()
+                .rule("sum", Rule::Blank)
+                .build()
+                .unwrap_err()
+                .to_string(),
+            "Duplicate rule `sum`"
+        );
+        assert_eq!(
+            builder()
+                .rule("call", Rule::NamedSymbol("identifier".to_string()))
+                .build()
+                .unwrap_err()
+                .to_string(),
+            "Undefined symbol `identifier` in the rule `call`"
+        );
+        assert_eq!(
+            builder()
+                .word("identifier")
+                .build()
+                .unwrap_err()
+                .to_string(),
+            "Undefined symbol `identifier`"
+        );
+        assert_eq!(
+            GrammarBuilder::new("1st")
+                .rule("program", Rule::Blank)
+                .build()
+                .unwrap_err()
+                .to_string(),
+            "Invalid grammar name `1st`. It must be a C identifier."
+        );
+    }
+}
@@ -1,4 +1,6 @@
 use std::{
+    collections::BTreeSet,
+    fmt::Write as _,
     fs,
     fs::File,
     io::BufReader,
@@ -12,7 +14,7 @@ use indoc::indoc;
 use serde::Deserialize;
 use serde_json::{json, Map, Value};
 
-use super::write_file;
+use super::{grammars::InputGrammar, render::sanitize_identifier, rules::Rule, write_file};
 
 const CLI_VERSION: &str = env!("CARGO_PKG_VERSION");
 const CLI_VERSION_PLACEHOLDER: &str = "CLI_VERSION";
@@ -59,6 +61,12 @@ const TEST_BINDING_PY_TEMPLATE: &str = include_str!("./templates/test_binding.py
 const PACKAGE_SWIFT_TEMPLATE: &str = include_str!("./templates/package.swift");
 const TESTS_SWIFT_TEMPLATE: &str = include_str!("./templates/tests.swift");
 
+const SCANNER_C_TEMPLATE: &str = include_str!("./templates/scanner.c");
+const EXTERNAL_TOKENS_PLACEHOLDER: &str = "EXTERNAL_TOKENS";
+
+const FUZZER_C_TEMPLATE: &str = include_str!("./templates/fuzzer.c");
+const SCANNER_FILES_PLACEHOLDER: &str = "SCANNER_FILES";
+
 #[derive(Deserialize, Debug)]
 struct LanguageConfiguration {}
 
@@ -243,8 +251,9 @@ pub fn generate_grammar_files(
         _ => return Ok(()),
     };
 
-    // Do not create a grammar.js file in a repo with multiple language configs
-    if !package_json.has_multiple_language_configs() {
+    // Do not create a grammar.js file in a repo with multiple language configs, or for a
+    // grammar that is written in TOML
+    if !package_json.has_multiple_language_configs() && !repo_path.join("grammar.toml").exists() {
         missing_path(repo_path.join("grammar.js"), |path| {
             generate_file(path, GRAMMAR_JS_TEMPLATE, language_name)
         })?;
@@ -547,6 +556,38 @@ pub fn generate_grammar_files(
     Ok(())
 }
 
+/// Scaffold a new grammar project in the given directory: a grammar stub, the bindings
+/// and their build files, and the directories for the test corpus and the queries.
+pub fn init_grammar_project(repo_path: &Path, language_name: &str) -> Result<()> {
+    if !is_valid_language_name(language_name) {
+        return Err(anyhow!(
+            "Invalid language name {language_name:?}. The name must start with a letter or an underscore, and only contain letters, digits and underscores"
+        ));
+    }
+    for grammar_file in ["grammar.js", "grammar.toml"] {
+        if repo_path.join(grammar_file).exists() {
+            return Err(anyhow!(
+                "{:?} already contains a grammar. Run `tree-sitter generate` to update its files",
+                repo_path.to_string_lossy()
+            ));
+        }
+    }
+
+    create_dir(repo_path)?;
+    generate_grammar_files(repo_path, language_name, true)?;
+    create_dir(&repo_path.join("test").join("corpus"))?;
+    create_dir(&repo_path.join("queries"))?;
+    Ok(())
+}
+
+fn is_valid_language_name(language_name: &str) -> bool {
+    let mut chars = language_name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 pub fn lookup_package_json_for_path(path: &Path) -> Result<(PathBuf, PackageJSON)> {
     let mut pathbuf = path.to_owned();
     loop {
@@ -576,6 +617,133 @@ pub fn lookup_package_json_for_path(path: &Path) -> Result<(PathBuf, PackageJSON
     }
 }
 
+/// Write a skeleton external scanner for a grammar that declares `externals`, unless
+/// the grammar already has a scanner.
+pub fn generate_scanner_file(
+    src_path: &Path,
+    language_name: &str,
+    external_tokens: &[Rule],
+    export_prefix: &str,
+) -> Result<()> {
+    if external_tokens.is_empty()
+        || src_path.join("scanner.c").exists()
+        || src_path.join("scanner.cc").exists()
+    {
+        return Ok(());
+    }
+
+    let token_names = external_tokens
+        .iter()
+        .enumerate()
+        .map(|(i, token)| match token {
+            Rule::NamedSymbol(name) => name.to_shouty_snake_case(),
+            Rule::String(value) => sanitize_identifier(value).to_ascii_uppercase(),
+            _ => format!("EXTERNAL_TOKEN_{i}"),
+        })
+        .map(|name| format!("  {name},"))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    eprintln!(
+        "Writing a skeleton external scanner to src/scanner.c. Remember to add it to your bindings' build files."
+    );
+    generate_file(
+        &src_path.join("scanner.c"),
+        &SCANNER_C_TEMPLATE
+            .replace(EXTERNAL_TOKENS_PLACEHOLDER, &token_names)
+            .replace(
+                "tree_sitter_PARSER_NAME",
+                &format!("{export_prefix}PARSER_NAME"),
+            ),
+        language_name,
+    )
+}
+
+/// Write a libFuzzer entry point for the parser to `fuzz/fuzzer.c`, along with a
+/// dictionary of the grammar's string tokens for the fuzzer to combine.
+pub fn generate_fuzz_harness(
+    repo_path: &Path,
+    input_grammar: &InputGrammar,
+    export_prefix: &str,
+) -> Result<()> {
+    let fuzz_path = repo_path.join("fuzz");
+    create_dir(&fuzz_path)?;
+
+    let scanner_files = if repo_path.join("src").join("scanner.c").exists() {
+        " src/scanner.c"
+    } else {
+        ""
+    };
+    generate_file(
+        &fuzz_path.join("fuzzer.c"),
+        &FUZZER_C_TEMPLATE
+            .replace(SCANNER_FILES_PLACEHOLDER, scanner_files)
+            .replace(
+                "tree_sitter_PARSER_NAME",
+                &format!("{export_prefix}PARSER_NAME"),
+            ),
+        &input_grammar.name,
+    )?;
+
+    let mut strings = BTreeSet::new();
+    for variable in &input_grammar.variables {
+        collect_strings(&variable.rule, &mut strings);
+    }
+    for rule in input_grammar
+        .extra_symbols
+        .iter()
+        .chain(&input_grammar.external_tokens)
+    {
+        collect_strings(rule, &mut strings);
+    }
+    let mut dictionary = String::new();
+    for string in strings {
+        if string.chars().all(char::is_whitespace) {
+            continue;
+        }
+        dictionary.push('"');
+        for byte in string.bytes() {
+            match byte {
+                b'"' | b'\\' => {
+                    dictionary.push('\\');
+                    dictionary.push(byte as char);
+                }
+                b' '..=b'~' => dictionary.push(byte as char),
+                _ => write!(&mut dictionary, "\\x{byte:02X}").unwrap(),
+            }
+        }
+        dictionary.push_str("\"\n");
+    }
+    write_file(&fuzz_path.join("tokens.dict"), dictionary)?;
+
+    eprintln!("Note: wrote a fuzzing harness to {fuzz_path:?}. The comment at the top of fuzzer.c explains how to build and run it.");
+    Ok(())
+}
+
+/// Collect the strings that appear in a rule, including the names of anonymous aliases.
+fn collect_strings<'a>(rule: &'a Rule, strings: &mut BTreeSet<&'a str>) {
+    match rule {
+        Rule::String(value) => {
+            strings.insert(value);
+        }
+        Rule::Metadata { params, rule } => {
+            if let Some(alias) = &params.alias {
+                if !alias.is_named {
+                    strings.insert(&alias.value);
+                }
+            }
+            collect_strings(rule, strings);
+        }
+        Rule::Repeat(rule) => collect_strings(rule, strings),
+        Rule::Choice(members) | Rule::Seq(members) => {
+            for member in members {
+                collect_strings(member, strings);
+            }
+        }
+        Rule::Blank | Rule::Pattern(..) | Rule::NamedSymbol(_) | Rule::Symbol(_) => {}
+    }
+}
+
 fn generate_file(path: &Path, template: &str, language_name: &str) -> Result<()> {
     write_file(
         path,
@@ -0,0 +1,122 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::{Context, Result};
+use log::debug;
+
+use super::grammars::{LexicalGrammar, SyntaxGrammar};
+
+/// A transformation of a prepared grammar, which runs after the grammar has been split into
+/// its syntactic and lexical parts and before the parse tables are built. Passes can add
+/// tokens, rewrite productions, or reject grammars that break a project's conventions, and
+/// are registered in [`GenerateOptions::grammar_passes`](super::GenerateOptions).
+///
+/// A pass that adds or removes variables or tokens must keep the symbols that refer to them
+/// consistent, since the grammar isn't prepared again afterwards.
+pub trait GrammarPass: Send + Sync {
+    /// The name of the pass, which is used in log messages and in its errors.
+    fn name(&self) -> &str;
+
+    fn run(
+        &self,
+        syntax_grammar: &mut SyntaxGrammar,
+        lexical_grammar: &mut LexicalGrammar,
+    ) -> Result<()>;
+}
+
+impl fmt::Debug for dyn GrammarPass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GrammarPass({:?})", self.name())
+    }
+}
+
+/// Run the passes in the order that they were registered.
+pub fn run_grammar_passes(
+    passes: &[Arc<dyn GrammarPass>],
+    syntax_grammar: &mut SyntaxGrammar,
+    lexical_grammar: &mut LexicalGrammar,
+) -> Result<()> {
+    for pass in passes {
+        debug!("running the grammar pass `{}`", pass.name());
+        pass.run(syntax_grammar, lexical_grammar)
+            .with_context(|| format!("The grammar pass `{}` failed", pass.name()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::generate::{generate_parser, GenerateOptions};
+
+    struct RenameToken;
+
+    impl GrammarPass for RenameToken {
+        fn name(&self) -> &str {
+            "rename-token"
+        }
+
+        fn run(&self, _: &mut SyntaxGrammar, lexical_grammar: &mut LexicalGrammar) -> Result<()> {
+            for variable in &mut lexical_grammar.variables {
+                if variable.name == "word" {
+                    variable.name = "identifier".to_string();
+                }
+            }
+            Ok(())
+        }
+    }
+
+    struct NoHiddenRules;
+
+    impl GrammarPass for NoHiddenRules {
+        fn name(&self) -> &str {
+            "no-hidden-rules"
+        }
+
+        fn run(&self, syntax_grammar: &mut SyntaxGrammar, _: &mut LexicalGrammar) -> Result<()> {
+            match syntax_grammar
+                .variables
+                .iter()
+                .find(|v| v.name.starts_with('_'))
+            {
+                Some(variable) => Err(anyhow!("The rule `{}` is hidden", variable.name)),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_grammar_passes() {
+        let grammar_json = r#"{
+            "name": "test",
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "_item"}},
+                "_item": {"type": "SYMBOL", "name": "word"},
+                "word": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#;
+
+        let options = GenerateOptions {
+            grammar_passes: vec![Arc::new(RenameToken)],
+            ..Default::default()
+        };
+        let output = generate_parser(grammar_json, &options).unwrap();
+        assert!(output.node_types_json.contains("\"identifier\""));
+        assert!(!output.node_types_json.contains("\"word\""));
+
+        let options = GenerateOptions {
+            grammar_passes: vec![Arc::new(RenameToken), Arc::new(NoHiddenRules)],
+            ..Default::default()
+        };
+        assert_eq!(
+            format!("{:?}", options.grammar_passes),
+            "[GrammarPass(\"rename-token\"), GrammarPass(\"no-hidden-rules\")]"
+        );
+        let error = generate_parser(grammar_json, &options).unwrap_err();
+        assert_eq!(
+            format!("{error:#}"),
+            "The grammar pass `no-hidden-rules` failed: The rule `_item` is hidden"
+        );
+    }
+}
@@ -1,11 +1,16 @@
-use std::{collections::HashMap, fmt};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
 
 use super::{
     nfa::Nfa,
     rules::{Alias, Associativity, Precedence, Rule, Symbol},
 };
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum VariableType {
     Hidden,
     Auxiliary,
@@ -15,20 +20,36 @@ pub enum VariableType {
 
 // Input grammar
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Variable {
     pub name: String,
     pub kind: VariableType,
     pub rule: Rule,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum PrecedenceEntry {
     Name(String),
     Symbol(String),
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+/// Where the language of an injected region comes from.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InjectionLanguage {
+    Name(String),
+    Field(String),
+}
+
+/// A node whose content is written in another language.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Injection {
+    pub node: String,
+    pub language: InjectionLanguage,
+    pub content_field: Option<String>,
+    pub include_children: bool,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct InputGrammar {
     pub name: String,
     pub variables: Vec<Variable>,
@@ -38,12 +59,15 @@ pub struct InputGrammar {
     pub external_tokens: Vec<Rule>,
     pub variables_to_inline: Vec<String>,
     pub supertype_symbols: Vec<String>,
+    pub deprecated_symbols: Vec<String>,
     pub word_token: Option<String>,
+    pub injections: Vec<Injection>,
+    pub display_names: BTreeMap<String, String>,
 }
 
 // Extracted lexical grammar
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LexicalVariable {
     pub name: String,
     pub kind: VariableType,
@@ -51,7 +75,7 @@ pub struct LexicalVariable {
     pub start_state: u32,
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct LexicalGrammar {
     pub nfa: Nfa,
     pub variables: Vec<LexicalVariable>,
@@ -59,7 +83,7 @@ pub struct LexicalGrammar {
 
 // Extracted syntax grammar
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct ProductionStep {
     pub symbol: Symbol,
     pub precedence: Precedence,
@@ -68,39 +92,44 @@ pub struct ProductionStep {
     pub field_name: Option<String>,
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Production {
     pub steps: Vec<ProductionStep>,
     pub dynamic_precedence: i32,
 }
 
+/// The productions that result from inlining rules, and the steps that they replace. Each
+/// step is keyed by the address of its production, which is either one of the grammar's
+/// or one of `productions`. The address is stored as an integer rather than a pointer, so
+/// that the map can be shared between the threads that build a parser.
 #[derive(Default)]
 pub struct InlinedProductionMap {
     pub productions: Vec<Production>,
-    pub production_map: HashMap<(*const Production, u32), Vec<usize>>,
+    pub production_map: HashMap<(usize, u32), Vec<usize>>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SyntaxVariable {
     pub name: String,
     pub kind: VariableType,
     pub productions: Vec<Production>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ExternalToken {
     pub name: String,
     pub kind: VariableType,
     pub corresponding_internal_token: Option<Symbol>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SyntaxGrammar {
     pub variables: Vec<SyntaxVariable>,
     pub extra_symbols: Vec<Symbol>,
     pub expected_conflicts: Vec<Vec<Symbol>>,
     pub external_tokens: Vec<ExternalToken>,
     pub supertype_symbols: Vec<Symbol>,
+    pub deprecated_symbols: Vec<Symbol>,
     pub variables_to_inline: Vec<Symbol>,
     pub word_token: Option<Symbol>,
     pub precedence_orderings: Vec<Vec<PrecedenceEntry>>,
@@ -234,13 +263,17 @@ impl SyntaxVariable {
 }
 
 impl InlinedProductionMap {
+    pub fn key(production: &Production, step_index: u32) -> (usize, u32) {
+        (production as *const Production as usize, step_index)
+    }
+
     pub fn inlined_productions<'a>(
         &'a self,
         production: &Production,
         step_index: u32,
     ) -> Option<impl Iterator<Item = &'a Production> + 'a> {
         self.production_map
-            .get(&(production as *const Production, step_index))
+            .get(&Self::key(production, step_index))
             .map(|production_indices| {
                 production_indices
                     .iter()
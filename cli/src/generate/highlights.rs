@@ -0,0 +1,113 @@
+use std::fmt::Write;
+
+use super::node_types::NodeInfoJSON;
+
+pub const HIGHLIGHTS_QUERY_HEADER: &str =
+    "; This file was generated by `tree-sitter generate` as a starting point for highlighting.";
+
+const BUILTIN_CONSTANTS: [&str; 5] = ["true", "false", "null", "nil", "none"];
+
+/// The highlight captures, in the order that their patterns are written.
+const CAPTURES: [&str; 9] = [
+    "keyword",
+    "operator",
+    "punctuation.bracket",
+    "punctuation.delimiter",
+    "constant.builtin",
+    "number",
+    "string.special",
+    "string",
+    "comment",
+];
+
+/// Guess the highlight capture for a node, based on its name.
+fn capture_for_node(kind: &str, named: bool) -> Option<&'static str> {
+    if !named {
+        return if BUILTIN_CONSTANTS.contains(&kind.to_ascii_lowercase().as_str()) {
+            Some("constant.builtin")
+        } else if kind.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && kind.chars().all(|c| c.is_alphanumeric() || c == '_')
+        {
+            Some("keyword")
+        } else if kind.len() == 1 && "()[]{}".contains(kind) {
+            Some("punctuation.bracket")
+        } else if kind.len() == 1 && ",;.:".contains(kind) {
+            Some("punctuation.delimiter")
+        } else if !kind.is_empty() && kind.chars().all(|c| c.is_ascii_punctuation()) {
+            Some("operator")
+        } else {
+            None
+        };
+    }
+
+    let words = kind.split('_').collect::<Vec<_>>();
+    let has_word = |candidates: &[&str]| words.iter().any(|word| candidates.contains(word));
+    if BUILTIN_CONSTANTS.contains(&kind) {
+        Some("constant.builtin")
+    } else if has_word(&["comment"]) {
+        Some("comment")
+    } else if has_word(&["escape"]) {
+        Some("string.special")
+    } else if has_word(&["string", "char", "character", "heredoc"]) {
+        Some("string")
+    } else if has_word(&["number", "integer", "float"]) {
+        Some("number")
+    } else {
+        None
+    }
+}
+
+fn escape_node_name(kind: &str) -> String {
+    let mut result = String::with_capacity(kind.len());
+    for c in kind.chars() {
+        match c {
+            '"' => result += "\\\"",
+            '\\' => result += "\\\\",
+            '\n' => result += "\\n",
+            '\r' => result += "\\r",
+            '\t' => result += "\\t",
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+/// Render a starter highlights query, which assigns the standard highlight captures
+/// to the grammar's keywords, operators, punctuation, and to the nodes whose names
+/// suggest strings, numbers and comments.
+pub fn render_highlights_query(node_types: &[NodeInfoJSON]) -> Option<String> {
+    let mut patterns = CAPTURES.map(|_| Vec::new());
+    for node_type in node_types {
+        if let Some(capture) = capture_for_node(&node_type.kind, node_type.named) {
+            let index = CAPTURES.iter().position(|c| *c == capture).unwrap();
+            patterns[index].push(if node_type.named {
+                format!("({})", node_type.kind)
+            } else {
+                format!("\"{}\"", escape_node_name(&node_type.kind))
+            });
+        }
+    }
+
+    if patterns.iter().all(Vec::is_empty) {
+        return None;
+    }
+
+    let mut result = String::new();
+    writeln!(&mut result, "{HIGHLIGHTS_QUERY_HEADER}").unwrap();
+    for (capture, patterns) in CAPTURES.iter().zip(patterns) {
+        match patterns.as_slice() {
+            [] => {}
+            [pattern] => {
+                writeln!(&mut result, "\n{pattern} @{capture}").unwrap();
+            }
+            patterns => {
+                writeln!(&mut result, "\n[").unwrap();
+                for pattern in patterns {
+                    writeln!(&mut result, "  {pattern}").unwrap();
+                }
+                writeln!(&mut result, "] @{capture}").unwrap();
+            }
+        }
+    }
+    Some(result)
+}
@@ -0,0 +1,93 @@
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+
+use super::{
+    grammars::{Injection, InjectionLanguage, LexicalGrammar, SyntaxGrammar, VariableType},
+    node_types::VariableInfo,
+};
+
+pub const INJECTIONS_QUERY_HEADER: &str =
+    "; This file is generated by `tree-sitter generate` from the grammar's `injections`.";
+
+/// Render the `injections` declared in a grammar as an injections query, using the
+/// captures and properties understood by `tree-sitter-highlight`.
+pub fn render_injections_query(
+    injections: &[Injection],
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    variable_info: &[VariableInfo],
+) -> Result<Option<String>> {
+    if injections.is_empty() {
+        return Ok(None);
+    }
+
+    let mut result = String::new();
+    writeln!(&mut result, "{INJECTIONS_QUERY_HEADER}").unwrap();
+
+    for injection in injections {
+        let node = &injection.node;
+        let fields = if let Some(index) = syntax_grammar
+            .variables
+            .iter()
+            .position(|v| v.name == *node && v.kind == VariableType::Named)
+        {
+            Some(&variable_info[index].fields)
+        } else if lexical_grammar
+            .variables
+            .iter()
+            .any(|v| v.name == *node && v.kind == VariableType::Named)
+        {
+            None
+        } else {
+            return Err(anyhow!(
+                "Injection node `{node}` must be a visible rule in the grammar"
+            ));
+        };
+
+        let language_field = match &injection.language {
+            InjectionLanguage::Field(field) => Some(field),
+            InjectionLanguage::Name(_) => None,
+        };
+        for field in language_field.into_iter().chain(&injection.content_field) {
+            if !fields.is_some_and(|fields| fields.contains_key(field)) {
+                return Err(anyhow!(
+                    "Injection node `{node}` does not have a field named `{field}`"
+                ));
+            }
+        }
+        if language_field.is_some() && language_field == injection.content_field.as_ref() {
+            return Err(anyhow!(
+                "Injection node `{node}` cannot use the field `{}` for both its language and its content",
+                injection.content_field.as_ref().unwrap()
+            ));
+        }
+
+        let mut pattern = format!("({node}");
+        if let Some(field) = language_field {
+            write!(&mut pattern, "\n  {field}: _ @injection.language").unwrap();
+        }
+        if let Some(field) = &injection.content_field {
+            write!(&mut pattern, "\n  {field}: _ @injection.content)").unwrap();
+        } else {
+            pattern.push_str(") @injection.content");
+        }
+
+        let mut predicates = Vec::new();
+        if let InjectionLanguage::Name(language) = &injection.language {
+            predicates.push(format!("(#set! injection.language {language:?})"));
+        }
+        if injection.include_children {
+            predicates.push("(#set! injection.include-children)".to_string());
+        }
+
+        result.push('\n');
+        if predicates.is_empty() {
+            writeln!(&mut result, "{pattern}").unwrap();
+        } else {
+            writeln!(&mut result, "({pattern}\n {})", predicates.join("\n ")).unwrap();
+        }
+    }
+
+    Ok(Some(result))
+}
@@ -0,0 +1,140 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    io::{self, Write},
+    process, slice, str,
+    sync::atomic::{AtomicBool, AtomicIsize, AtomicPtr, AtomicUsize, Ordering},
+};
+
+pub const BYTES_PER_MB: usize = 1 << 20;
+
+static COUNTING: AtomicBool = AtomicBool::new(false);
+// Memory that was allocated before counting started can be freed while counting, so this
+// can go below zero.
+static ALLOCATED: AtomicIsize = AtomicIsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static LIMIT: AtomicUsize = AtomicUsize::new(0);
+static LIMIT_EXCEEDED: AtomicBool = AtomicBool::new(false);
+static PHASE_PTR: AtomicPtr<u8> = AtomicPtr::new(std::ptr::null_mut());
+static PHASE_LEN: AtomicUsize = AtomicUsize::new(0);
+
+/// A global allocator that keeps count of how much memory is allocated, so that the memory
+/// used by each phase of parser generation can be reported, and generation can be stopped
+/// before it uses more than `--max-memory`. The CLI installs it with `#[global_allocator]`,
+/// and only turns the counting on with [`start_counting_memory`] for the `generate` command's
+/// `--timing` and `--max-memory`; until then, it passes allocations straight to the system
+/// allocator. When it isn't installed, no memory usage is reported and no limit is enforced.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() && COUNTING.load(Ordering::Relaxed) {
+            record_allocation(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() && COUNTING.load(Ordering::Relaxed) {
+            record_allocation(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        if COUNTING.load(Ordering::Relaxed) {
+            ALLOCATED.fetch_sub(layout.size() as isize, Ordering::Relaxed);
+        }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() && COUNTING.load(Ordering::Relaxed) {
+            if new_size > layout.size() {
+                record_allocation(new_size - layout.size());
+            } else {
+                ALLOCATED.fetch_sub((layout.size() - new_size) as isize, Ordering::Relaxed);
+            }
+        }
+        new_ptr
+    }
+}
+
+fn record_allocation(size: usize) {
+    let allocated =
+        (ALLOCATED.fetch_add(size as isize, Ordering::Relaxed) + size as isize).max(0) as usize;
+    PEAK.fetch_max(allocated, Ordering::Relaxed);
+    let limit = LIMIT.load(Ordering::Relaxed);
+    if limit != 0 && allocated > limit && !LIMIT_EXCEEDED.swap(true, Ordering::Relaxed) {
+        exit_over_limit(limit);
+    }
+}
+
+/// Stop the process with an error that names the phase that went over the limit. This runs
+/// inside the allocator, so the message is written without allocating, and any allocation
+/// made while exiting goes through without being checked again.
+#[cold]
+fn exit_over_limit(limit: usize) -> ! {
+    let _ = writeln!(
+        io::stderr(),
+        "Error: Parser generation used more than the maximum of {} MB of memory during {}",
+        limit / BYTES_PER_MB,
+        current_phase().unwrap_or("generation"),
+    );
+    process::exit(1);
+}
+
+/// Count the memory that is allocated from now on, when the [`CountingAllocator`] is
+/// installed. Memory that is already allocated isn't counted.
+pub fn start_counting_memory() {
+    ALLOCATED.store(0, Ordering::Relaxed);
+    PEAK.store(0, Ordering::Relaxed);
+    COUNTING.store(true, Ordering::Relaxed);
+}
+
+/// Limit the memory that parser generation may allocate. This only has an effect when the
+/// [`CountingAllocator`] is installed and counting.
+pub fn set_memory_limit(bytes: Option<usize>) {
+    LIMIT.store(bytes.unwrap_or(0), Ordering::Relaxed);
+}
+
+/// The number of bytes that are currently allocated since counting started, or zero when the
+/// [`CountingAllocator`] isn't installed or counting.
+pub fn allocated_bytes() -> usize {
+    ALLOCATED.load(Ordering::Relaxed).max(0) as usize
+}
+
+/// Start measuring the peak memory usage of a phase from the current usage.
+pub fn reset_peak() {
+    PEAK.store(allocated_bytes(), Ordering::Relaxed);
+}
+
+pub fn peak_bytes() -> usize {
+    PEAK.load(Ordering::Relaxed)
+}
+
+/// Record the phase that is running, so that going over the memory limit can be blamed on
+/// it. Returns the phase that was running before, to be restored when this one finishes.
+pub fn set_current_phase(phase: Option<&'static str>) -> Option<&'static str> {
+    let previous = current_phase();
+    let (ptr, len) = phase.map_or((std::ptr::null_mut(), 0), |phase| {
+        (phase.as_ptr().cast_mut(), phase.len())
+    });
+    PHASE_PTR.store(std::ptr::null_mut(), Ordering::Release);
+    PHASE_LEN.store(len, Ordering::Release);
+    PHASE_PTR.store(ptr, Ordering::Release);
+    previous
+}
+
+fn current_phase() -> Option<&'static str> {
+    let ptr = PHASE_PTR.load(Ordering::Acquire);
+    if ptr.is_null() {
+        return None;
+    }
+    let len = PHASE_LEN.load(Ordering::Acquire);
+    // The pointer and length always come from the same `&'static str`, since the pointer
+    // is cleared while the length is changed.
+    unsafe { str::from_utf8(slice::from_raw_parts(ptr, len)).ok() }
+}
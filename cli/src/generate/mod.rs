@@ -1,34 +1,90 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     env, fs,
     io::Write,
     path::{Path, PathBuf},
     process::{Command, Stdio},
+    sync::Arc,
+    thread,
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{anyhow, Context, Result};
+pub use build_tables::build_dfa;
 use build_tables::build_tables;
+pub use checkpoint::Checkpoints;
+use diagnostics::{annotate_error, unused_rule_diagnostics};
 use grammar_files::path_in_ignore;
-use grammars::InputGrammar;
+use grammars::{Injection, VariableType};
+pub use grammars::{InputGrammar, LexicalGrammar, LexicalVariable, PrecedenceEntry, SyntaxGrammar};
 use lazy_static::lazy_static;
+use log::{info, warn};
+pub use nfa::{
+    CharacterSet, CharacterSetInterner, Nfa, NfaCursor, NfaState, NfaStateSet, NfaTransition,
+};
 use parse_grammar::parse_grammar;
-use prepare_grammar::prepare_grammar;
+use prepare_grammar::{prepare_grammar, process_inlines};
 use regex::{Regex, RegexBuilder};
-use render::render_c_code;
+use render::{
+    render_c_code, strip_annotations, to_block_comments, CStandard, LexerStrategy, RenderedParser,
+    DEFAULT_EXPORT_PREFIX, LEXER_FILE_HEADER, SHARED_CHARACTER_SETS_HEADER,
+};
+pub use rules::{Alias, Precedence, Rule, Symbol, SymbolType};
 use semver::Version;
+use serde::{Deserialize, Serialize};
+pub use table_blob::{
+    read_table_blob, BlobLexState, BlobParseAction, BlobParseState, BlobParseTableEntry,
+    BlobSymbol, TableBlob,
+};
+pub use tables::{
+    AdvanceAction, FieldLocation, GotoAction, LexState, LexTable, ParseAction, ParseState,
+    ParseTable, ParseTableEntry, ProductionInfo,
+};
+use timings::Timings;
 
+mod amalgamation;
+mod build_script;
 mod build_tables;
+mod checkpoint;
 mod dedup;
+mod diagnostics;
+mod error;
+mod grammar_builder;
 mod grammar_files;
+mod grammar_passes;
 mod grammars;
+mod highlights;
+mod injections;
+mod memory;
 mod nfa;
 mod node_types;
 pub mod parse_grammar;
 mod prepare_grammar;
+#[cfg(feature = "qjs-rt")]
+mod quickjs;
+mod railroad;
+mod random_programs;
+mod reference_docs;
 mod render;
 mod rules;
+mod rust_ast;
+mod skeleton_corpus;
+mod source_map;
+mod table_blob;
+mod table_dump;
 mod tables;
+mod timings;
 
-pub use grammar_files::lookup_package_json_for_path;
+pub use build_script::generate_parser_in_build_script;
+pub use diagnostics::{GrammarDiagnostic, RuleLocation};
+pub use error::{GrammarError, GrammarErrorKind};
+pub use grammar_builder::GrammarBuilder;
+pub use grammar_files::{init_grammar_project, lookup_package_json_for_path};
+use grammar_passes::run_grammar_passes;
+pub use grammar_passes::GrammarPass;
+pub use memory::{set_memory_limit, start_counting_memory, CountingAllocator};
+pub use railroad::{railroad_diagrams, RailroadGrammar, RailroadNode, RailroadRule};
+pub use reference_docs::{render_reference_docs, DocsExample, DocsFormat};
 
 lazy_static! {
     static ref JSON_COMMENT_REGEX: Regex = RegexBuilder::new("^\\s*//.*")
@@ -39,19 +95,78 @@ lazy_static! {
 
 struct GeneratedParser {
     c_code: String,
+    lexer_c_code: Option<String>,
+    symbols_header: String,
     node_types_json: String,
+    injections_query: Option<String>,
+    highlights_query: Option<String>,
+    table_dump: Option<String>,
+    rust_ast: Option<String>,
+    symbol_ids: Vec<String>,
+    shared_character_sets: Vec<(String, String)>,
+    skeleton_corpus: Option<String>,
+    table_blob: Option<Vec<u8>>,
+    random_programs: Vec<String>,
+    source_map: Option<String>,
 }
 
 pub const ALLOC_HEADER: &str = include_str!("./templates/alloc.h");
 
+/// How often `generate --watch` and `lsp` check whether the grammar's files have changed.
+pub const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How [`generate_parser_in_directory`] reads a grammar and writes the files that are
+/// generated from it. Together with the [`GenerateOptions`] of the generated files, they
+/// correspond to the options of `tree-sitter generate`.
+#[derive(Debug, Clone, Default)]
+pub struct DirectoryOptions {
+    pub generate_bindings: bool,
+    pub js_runtime: Option<String>,
+    /// A JSON file that maps the names of rules to the names that their nodes are
+    /// published under, which are added to [`GenerateOptions::renames`].
+    pub rename_map_path: Option<PathBuf>,
+    /// Keep the ids that the symbols had in the previous generation, which are recorded
+    /// in `src/symbol-ids.json`.
+    pub stable_symbol_ids: bool,
+    pub symbols_header: bool,
+    /// The directory to write the generated files to, instead of `src`.
+    pub output_path: Option<PathBuf>,
+    pub manifest: bool,
+    pub banner_path: Option<PathBuf>,
+    pub shared_character_sets_path: Option<PathBuf>,
+    pub amalgamate: bool,
+    pub fuzz_harness: bool,
+    /// Write a starting point for `queries/highlights.scm`, if the grammar doesn't have
+    /// one yet.
+    pub highlights_query: bool,
+    /// Log the time and memory that each phase of generation took.
+    pub timing: bool,
+}
+
 pub fn generate_parser_in_directory(
     repo_path: &Path,
     grammar_path: Option<&str>,
-    abi_version: usize,
-    generate_bindings: bool,
-    report_symbol_name: Option<&str>,
-    js_runtime: Option<&str>,
+    options: &GenerateOptions,
+    directory_options: &DirectoryOptions,
+    checkpoints: Option<&Checkpoints>,
 ) -> Result<()> {
+    let DirectoryOptions {
+        generate_bindings,
+        js_runtime,
+        rename_map_path,
+        stable_symbol_ids,
+        symbols_header,
+        output_path,
+        manifest,
+        banner_path,
+        shared_character_sets_path,
+        amalgamate,
+        fuzz_harness,
+        highlights_query: write_highlights_query,
+        timing,
+    } = directory_options;
+    let start = Instant::now();
+    let mut timings = Timings::default();
     let mut repo_path = repo_path.to_owned();
     let mut grammar_path = grammar_path;
 
@@ -68,7 +183,11 @@ pub fn generate_parser_in_directory(
         }
     }
 
-    if repo_path.is_dir() && !repo_path.join("grammar.js").exists() && !path_in_ignore(&repo_path) {
+    if repo_path.is_dir()
+        && !repo_path.join("grammar.js").exists()
+        && !repo_path.join("grammar.toml").exists()
+        && !path_in_ignore(&repo_path)
+    {
         if let Some(dir_name) = repo_path
             .file_name()
             .map(|x| x.to_string_lossy().to_ascii_lowercase())
@@ -83,63 +202,748 @@ pub fn generate_parser_in_directory(
     }
 
     // Read the grammar.json.
-    let grammar_json = if let Some(path) = grammar_path {
-        load_grammar_file(path.as_ref(), js_runtime)?
-    } else {
-        let grammar_js_path =
-            grammar_path.map_or(repo_path.join("grammar.js"), std::convert::Into::into);
-        load_grammar_file(&grammar_js_path, js_runtime)?
-    };
+    let grammar_file = grammar_path.map_or_else(|| default_grammar_path(&repo_path), PathBuf::from);
+    let grammar_json = timings.time("grammar loading", || {
+        load_grammar_file(&grammar_file, js_runtime.as_deref())
+    })?;
 
+    // The generated files are written to the output directory, while the files that
+    // are maintained alongside the grammar, like the external scanner, stay in `src`.
     let src_path = repo_path.join("src");
-    let header_path = src_path.join("tree_sitter");
+    let output_path = output_path.clone().unwrap_or_else(|| src_path.clone());
+    let header_path = output_path.join("tree_sitter");
 
     // Ensure that the output directories exist.
     fs::create_dir_all(&src_path)?;
     fs::create_dir_all(&header_path)?;
 
-    if grammar_path.is_none() {
-        fs::write(src_path.join("grammar.json"), &grammar_json)
-            .with_context(|| format!("Failed to write grammar.json to {src_path:?}"))?;
+    if grammar_path.map_or(true, |path| path.ends_with(".toml")) {
+        fs::write(output_path.join("grammar.json"), &grammar_json)
+            .with_context(|| format!("Failed to write grammar.json to {output_path:?}"))?;
+    }
+
+    let export_prefix = options
+        .export_prefix
+        .as_deref()
+        .unwrap_or(DEFAULT_EXPORT_PREFIX);
+    validate_export_prefix(export_prefix)?;
+    if export_prefix != DEFAULT_EXPORT_PREFIX {
+        warn!("the loader and the generated bindings expect the parser's functions to start with `{DEFAULT_EXPORT_PREFIX}`");
     }
+    let c_standard = parse_c_standard(options.c_standard.as_deref())?;
 
     // Parse and preprocess the grammar.
-    let input_grammar = parse_grammar(&grammar_json)?;
+    let input_grammar =
+        parse_grammar(&grammar_json).map_err(|e| annotate_error(e, &grammar_file))?;
+    let banner = banner_path
+        .as_ref()
+        .map(|path| {
+            fs::read_to_string(path).with_context(|| format!("Failed to read banner {path:?}"))
+        })
+        .transpose()?;
+    let mut options = options.clone();
+    if let Some(rename_map_path) = rename_map_path {
+        options.renames.extend(load_rename_map(rename_map_path)?);
+    }
+
+    // Symbol ids are kept stable by recording their order in a sidecar file,
+    // and reusing that order the next time the parser is generated.
+    let symbol_ids_path = src_path.join("symbol-ids.json");
+    if *stable_symbol_ids && symbol_ids_path.exists() {
+        let json = fs::read_to_string(&symbol_ids_path)
+            .with_context(|| format!("Failed to read {symbol_ids_path:?}"))?;
+        options.previous_symbol_ids = serde_json::from_str(&json)
+            .with_context(|| format!("Failed to parse {symbol_ids_path:?}"))?;
+    }
+    // The skeleton corpus never replaces the grammar's own tests.
+    options.skeleton_corpus &= !has_corpus(&repo_path);
+
+    // The lex functions refer to the shared character sets by a path relative to the
+    // generated sources.
+    let shared_character_sets_include = shared_character_sets_path
+        .as_ref()
+        .map(|path| {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            relative_include_path(&output_path, path)
+        })
+        .transpose()?;
 
     // Generate the parser and related files.
     let GeneratedParser {
         c_code,
+        lexer_c_code,
+        symbols_header: symbols_header_code,
         node_types_json,
-    } = generate_parser_for_grammar_with_opts(&input_grammar, abi_version, report_symbol_name)?;
+        injections_query,
+        highlights_query,
+        table_dump,
+        rust_ast: rust_ast_code,
+        symbol_ids,
+        shared_character_sets,
+        skeleton_corpus,
+        table_blob: table_blob_data,
+        random_programs: random_program_texts,
+        source_map: source_map_json,
+    } = generate_parser_for_grammar_with_opts(
+        &input_grammar,
+        &options,
+        shared_character_sets_include.as_deref(),
+        checkpoints,
+        &mut timings,
+    )
+    .map_err(|e| annotate_error(e, &grammar_file))?;
 
-    write_file(&src_path.join("parser.c"), c_code)?;
-    write_file(&src_path.join("node-types.json"), node_types_json)?;
+    // The amalgamation includes the external scanner, so write the skeleton scanner
+    // first if the grammar needs one.
+    grammar_files::generate_scanner_file(
+        &src_path,
+        &input_grammar.name,
+        &input_grammar.external_tokens,
+        export_prefix,
+    )?;
+    if *fuzz_harness {
+        grammar_files::generate_fuzz_harness(&repo_path, &input_grammar, export_prefix)?;
+    }
+
+    let banner = banner.as_deref();
+    if *amalgamate {
+        let scanner_c_code = fs::read_to_string(src_path.join("scanner.c")).ok();
+        if scanner_c_code.is_none() && src_path.join("scanner.cc").exists() {
+            warn!("the C++ external scanner can't be included in the amalgamation, and must be compiled separately");
+        }
+        write_file(
+            &output_path.join("amalgamation.c"),
+            add_c_banner(
+                banner,
+                c_standard,
+                amalgamation::render_amalgamation(&c_code, scanner_c_code.as_deref(), c_standard),
+            ),
+        )?;
+    }
+    write_file(
+        &output_path.join("parser.c"),
+        add_c_banner(banner, c_standard, c_code),
+    )?;
+    write_lexer_file(
+        &output_path.join("lexer.c"),
+        lexer_c_code.map(|code| add_c_banner(banner, c_standard, code)),
+    )?;
+    if let Some(table_dump) = table_dump {
+        write_file(
+            &output_path.join("parser.txt"),
+            add_banner(banner, "", table_dump),
+        )?;
+    }
+    if let Some(table_blob_data) = table_blob_data {
+        write_file(&output_path.join("parser-tables.bin"), table_blob_data)?;
+    }
+    if let Some(source_map_json) = source_map_json {
+        write_file(&output_path.join("source-map.json"), source_map_json)?;
+    }
+    if options.railroad {
+        write_file(
+            &output_path.join("railroad.json"),
+            railroad::render_railroad_json(&input_grammar),
+        )?;
+    }
+    if *symbols_header {
+        write_file(
+            &output_path.join("symbols.h"),
+            add_banner(banner, "// ", symbols_header_code),
+        )?;
+    }
+    write_file(&output_path.join("node-types.json"), node_types_json)?;
     write_file(&header_path.join("alloc.h"), ALLOC_HEADER)?;
     write_file(&header_path.join("array.h"), tree_sitter::ARRAY_HEADER)?;
     write_file(&header_path.join("parser.h"), tree_sitter::PARSER_HEADER)?;
 
+    if let Some(path) = shared_character_sets_path {
+        write_shared_character_sets(path, shared_character_sets)?;
+    }
+
+    if *manifest {
+        let mut generated_files = vec!["parser.c", "node-types.json"];
+        if grammar_path.is_none() {
+            generated_files.push("grammar.json");
+        }
+        if options.split_lexer {
+            generated_files.push("lexer.c");
+        }
+        if *amalgamate {
+            generated_files.push("amalgamation.c");
+        }
+        if options.table_blob {
+            generated_files.push("parser-tables.bin");
+        }
+        if options.dump_tables {
+            generated_files.push("parser.txt");
+        }
+        if options.source_map {
+            generated_files.push("source-map.json");
+        }
+        if options.railroad {
+            generated_files.push("railroad.json");
+        }
+        if *symbols_header {
+            generated_files.push("symbols.h");
+        }
+        generated_files.extend([
+            "tree_sitter/alloc.h",
+            "tree_sitter/array.h",
+            "tree_sitter/parser.h",
+        ]);
+        write_manifest(&output_path, &grammar_file, &grammar_json, &generated_files)?;
+    }
+
+    if *stable_symbol_ids {
+        write_file(
+            &symbol_ids_path,
+            serde_json::to_string_pretty(&symbol_ids).unwrap(),
+        )?;
+    }
+
+    if let Some(injections_query) = injections_query {
+        write_injections_query(&repo_path.join("queries"), injections_query)?;
+    }
+
+    if let Some(skeleton_corpus) = skeleton_corpus {
+        let corpus_path = repo_path.join("test").join("corpus");
+        fs::create_dir_all(&corpus_path)?;
+        write_file(&corpus_path.join("skeleton.txt"), skeleton_corpus)?;
+        info!("wrote a skeleton test corpus to {corpus_path:?}. If a test's expected tree doesn't match the parser's, run `tree-sitter test --update` to accept it.");
+    }
+
+    if let Some((count, _)) = options.random_programs {
+        let random_path = repo_path.join("test").join("random");
+        fs::create_dir_all(&random_path)?;
+        let width = count.to_string().len();
+        for (i, program) in random_program_texts.iter().enumerate() {
+            write_file(&random_path.join(format!("{:0width$}.txt", i + 1)), program)?;
+        }
+        if random_program_texts.len() < count {
+            warn!("no random programs were generated, because the start rule can't be derived without the external scanner");
+        } else {
+            info!("wrote {count} random programs to {random_path:?}");
+        }
+    }
+
+    // The highlights query is only a starting point, so it is never overwritten.
+    let highlights_path = repo_path.join("queries").join("highlights.scm");
+    if let Some(highlights_query) = highlights_query.filter(|_| *write_highlights_query) {
+        if !highlights_path.exists() {
+            fs::create_dir_all(repo_path.join("queries"))?;
+            write_file(&highlights_path, highlights_query)?;
+        }
+    }
+
+    if let Some(rust_ast_code) = rust_ast_code {
+        let bindings_path = repo_path.join("bindings").join("rust");
+        fs::create_dir_all(&bindings_path)?;
+        write_file(&bindings_path.join("ast.rs"), rust_ast_code)?;
+        info!("the typed syntax tree was written to {bindings_path:?}. Declare it with `pub mod ast;` in the crate's lib.rs, which must depend on the `tree-sitter` crate");
+    }
+
     if !path_in_ignore(&repo_path) {
-        grammar_files::generate_grammar_files(&repo_path, &input_grammar.name, generate_bindings)?;
+        grammar_files::generate_grammar_files(&repo_path, &input_grammar.name, *generate_bindings)?;
+    }
+
+    if *timing {
+        info!(
+            "the time and memory that each phase took:\n{timings}Total: {:.2} ms",
+            start.elapsed().as_micros() as f64 / 1e3
+        );
     }
 
     Ok(())
 }
 
+/// Remove the annotations from the parser that was generated into the given directory
+/// with `--annotate`, so that it can be released without them, without generating it
+/// again.
+pub fn strip_annotations_in_directory(repo_path: &Path, output_path: Option<&Path>) -> Result<()> {
+    let output_path = output_path.map_or_else(|| repo_path.join("src"), Path::to_path_buf);
+    for file_name in ["parser.c", "lexer.c"] {
+        let path = output_path.join(file_name);
+        if file_name == "lexer.c" && !path.exists() {
+            continue;
+        }
+        let code = fs::read_to_string(&path).with_context(|| format!("Failed to read {path:?}"))?;
+        let stripped_code = strip_annotations(&code);
+        if stripped_code != code {
+            write_file(&path, stripped_code)?;
+        }
+    }
+    if output_path.join("manifest.json").exists() {
+        warn!("the hashes in {:?} are out of date, because the annotations were stripped from the parser", output_path.join("manifest.json"));
+    }
+    Ok(())
+}
+
+/// Call `generate` once, and then again whenever one of the grammar's source files
+/// changes, until the process is interrupted. Errors are reported as they happen,
+/// without ending the watch, so that they can be fixed in place.
+pub fn watch_grammar(
+    repo_path: &Path,
+    grammar_path: Option<&str>,
+    mut generate: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    let grammar_path = grammar_path.map_or_else(
+        || default_grammar_path(repo_path),
+        |path| repo_path.join(path),
+    );
+    loop {
+        if let Err(error) = generate() {
+            eprintln!("{error:?}");
+        }
+
+        // A grammar path that didn't exist is populated as a new grammar directory by
+        // the first generation.
+        let grammar_path = if grammar_path.is_dir() {
+            grammar_path.join("grammar.js")
+        } else {
+            grammar_path.clone()
+        };
+        let files = watched_grammar_files(&grammar_path);
+        eprintln!("Watching {} file(s) for changes...", files.len());
+        let changed_path = loop {
+            thread::sleep(WATCH_INTERVAL);
+            let new_files = watched_grammar_files(&grammar_path);
+            if new_files != files {
+                break new_files
+                    .iter()
+                    .chain(&files)
+                    .find(|(path, modified)| {
+                        files.get(*path) != Some(modified) || !new_files.contains_key(*path)
+                    })
+                    .map(|(path, _)| path.clone())
+                    .unwrap();
+            }
+        };
+        eprintln!("\nRegenerating after a change to {changed_path:?}");
+    }
+}
+
+/// The files that a grammar is generated from, with their modification times: the
+/// grammar file itself and, for a `grammar.js` file, the other JavaScript files next
+/// to it or in its subdirectories, which it may `require`.
+pub fn watched_grammar_files(grammar_path: &Path) -> BTreeMap<PathBuf, Option<SystemTime>> {
+    let mut paths = vec![grammar_path.to_path_buf()];
+    if grammar_path.extension().is_some_and(|e| e == "js") {
+        if let Some(grammar_dir) = grammar_path.parent() {
+            collect_js_files(grammar_dir, &mut paths);
+        }
+    }
+    paths
+        .into_iter()
+        .map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+            (path, modified)
+        })
+        .collect()
+}
+
+fn collect_js_files(dir: &Path, paths: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name();
+        let file_name = file_name.to_string_lossy();
+        if path.is_dir() {
+            // Skip the generated code, the bindings, and installed or built dependencies.
+            if !file_name.starts_with('.')
+                && !["bindings", "build", "node_modules", "src", "target"]
+                    .contains(&file_name.as_ref())
+            {
+                collect_js_files(&path, paths);
+            }
+        } else if path.extension().is_some_and(|e| e == "js") && !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+}
+
 pub fn generate_parser_for_grammar(grammar_json: &str) -> Result<(String, String)> {
+    let output = generate_parser(grammar_json, &GenerateOptions::default())?;
+    Ok((output.name, output.parser_c))
+}
+
+/// Options for generating a parser in memory with [`generate_parser`]. They correspond to
+/// the options of `tree-sitter generate` that affect the generated files.
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    pub abi_version: usize,
+    /// The rule whose parse states are printed while the parse table is built.
+    pub report_symbol_name: Option<String>,
+    pub auto_inline_threshold: Option<usize>,
+    /// Rules and external tokens whose nodes are published under a different name, by
+    /// their names in the grammar.
+    pub renames: HashMap<String, String>,
+    /// The symbol names from a previous generation, so that the symbols keep their ids.
+    pub previous_symbol_ids: Vec<String>,
+    pub split_lexer: bool,
+    pub freestanding: bool,
+    pub dump_tables: bool,
+    pub export_prefix: Option<String>,
+    pub rust_ast: bool,
+    pub lexer_strategy: Option<String>,
+    pub table_blob: bool,
+    pub source_map: bool,
+    pub railroad: bool,
+    pub c_standard: Option<String>,
+    pub inline_keyword: Option<String>,
+    pub annotate: bool,
+    /// Render a test corpus with an example of each rule, as a starting point for a
+    /// grammar that doesn't have tests yet.
+    pub skeleton_corpus: bool,
+    /// The number of random programs to generate from the grammar, and the seed that they
+    /// are generated with.
+    pub random_programs: Option<(usize, u64)>,
+    /// Passes that transform the prepared grammar before the parse tables are built.
+    pub grammar_passes: Vec<Arc<dyn GrammarPass>>,
+    /// The longest time to spend analyzing whether two tokens conflict. A pair of tokens
+    /// that takes longer is treated as conflicting in every way.
+    pub conflict_analysis_budget: Option<Duration>,
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            abi_version: tree_sitter::LANGUAGE_VERSION,
+            report_symbol_name: None,
+            auto_inline_threshold: None,
+            renames: HashMap::new(),
+            previous_symbol_ids: Vec::new(),
+            split_lexer: false,
+            freestanding: false,
+            dump_tables: false,
+            export_prefix: None,
+            rust_ast: false,
+            lexer_strategy: None,
+            table_blob: false,
+            source_map: false,
+            railroad: false,
+            c_standard: None,
+            inline_keyword: None,
+            annotate: false,
+            skeleton_corpus: false,
+            random_programs: None,
+            grammar_passes: Vec::new(),
+            conflict_analysis_budget: None,
+        }
+    }
+}
+
+/// The files generated for a grammar by [`generate_parser`]. The optional files are only
+/// present when the corresponding option is enabled.
+#[derive(Debug, Clone)]
+pub struct GeneratedOutput {
+    pub name: String,
+    pub parser_c: String,
+    pub lexer_c: Option<String>,
+    pub symbols_header: String,
+    pub node_types_json: String,
+    pub highlights_query: Option<String>,
+    pub injections_query: Option<String>,
+    pub table_dump: Option<String>,
+    pub rust_ast: Option<String>,
+    pub table_blob: Option<Vec<u8>>,
+    pub source_map: Option<String>,
+    pub railroad_json: Option<String>,
+    pub skeleton_corpus: Option<String>,
+    pub random_programs: Vec<String>,
+    /// The names of the symbols in the order of their ids, to pass back in
+    /// [`GenerateOptions::previous_symbol_ids`] next time. The ids of removed symbols
+    /// that are kept unused have empty names.
+    pub symbol_ids: Vec<String>,
+}
+
+/// Generate a parser from the contents of a `grammar.json` file, returning the generated
+/// files instead of writing them to disk. Generation doesn't use any global state, so
+/// several parsers can be generated at once from different threads.
+pub fn generate_parser(grammar_json: &str, options: &GenerateOptions) -> Result<GeneratedOutput> {
     let grammar_json = JSON_COMMENT_REGEX.replace_all(grammar_json, "\n");
-    let input_grammar = parse_grammar(&grammar_json)?;
-    let parser =
-        generate_parser_for_grammar_with_opts(&input_grammar, tree_sitter::LANGUAGE_VERSION, None)?;
-    Ok((input_grammar.name, parser.c_code))
+    generate_parser_from_grammar(&parse_grammar(&grammar_json)?, options)
+}
+
+/// Generate a parser from a grammar that was built in code with a [`GrammarBuilder`],
+/// returning the generated files instead of writing them to disk.
+pub fn generate_parser_from_grammar(
+    input_grammar: &InputGrammar,
+    options: &GenerateOptions,
+) -> Result<GeneratedOutput> {
+    let parser = generate_parser_for_grammar_with_opts(
+        input_grammar,
+        options,
+        None,
+        None,
+        &mut Timings::default(),
+    )?;
+    Ok(GeneratedOutput {
+        name: input_grammar.name.clone(),
+        parser_c: parser.c_code,
+        lexer_c: parser.lexer_c_code,
+        symbols_header: parser.symbols_header,
+        node_types_json: parser.node_types_json,
+        highlights_query: parser.highlights_query,
+        injections_query: parser.injections_query,
+        table_dump: parser.table_dump,
+        rust_ast: parser.rust_ast,
+        table_blob: parser.table_blob,
+        source_map: parser.source_map,
+        railroad_json: options
+            .railroad
+            .then(|| railroad::render_railroad_json(input_grammar)),
+        skeleton_corpus: parser.skeleton_corpus,
+        random_programs: parser.random_programs,
+        symbol_ids: parser.symbol_ids,
+    })
+}
+
+/// The tables that a parser is generated from, for tools that analyze a grammar's parser
+/// or generate code for it in another form.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseTables {
+    pub parse_table: ParseTable,
+    /// The table for lexing tokens, which each parse state starts from a state of.
+    pub main_lex_table: LexTable,
+    /// The table for lexing keywords within the word token, which is empty if the grammar
+    /// has no word token.
+    pub keyword_lex_table: LexTable,
+    pub word_token: Option<Symbol>,
+    /// The metadata of the symbols in [`ParseTable::symbols`], in the same order.
+    pub symbols: Vec<SymbolMetadata>,
+}
+
+/// How a symbol appears in syntax trees.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolMetadata {
+    pub symbol: Symbol,
+    /// The node type, which is the name of the rule or token, or its default alias.
+    pub name: String,
+    pub visible: bool,
+    pub named: bool,
+    pub supertype: bool,
+}
+
+/// Build the parse table and lex tables for a grammar, as `tree-sitter generate` does
+/// before rendering them as C code.
+pub fn build_parse_tables(input_grammar: &InputGrammar) -> Result<ParseTables> {
+    let mut timings = Timings::default();
+    let (syntax_grammar, lexical_grammar, inlines, simple_aliases) =
+        prepare_grammar(input_grammar, None, &HashMap::new(), &mut timings)?;
+    let variable_info =
+        node_types::get_variable_info(&syntax_grammar, &lexical_grammar, &simple_aliases)?;
+    let tables = build_tables(
+        &syntax_grammar,
+        &lexical_grammar,
+        &simple_aliases,
+        &variable_info,
+        &inlines,
+        None,
+        false,
+        None,
+        None,
+        &mut timings,
+    )?;
+
+    let symbols = tables
+        .parse_table
+        .symbols
+        .iter()
+        .map(|symbol| {
+            let (name, kind) = match symbol.kind {
+                SymbolType::End | SymbolType::EndOfNonTerminalExtra => {
+                    ("end", VariableType::Hidden)
+                }
+                SymbolType::NonTerminal => {
+                    let variable = &syntax_grammar.variables[symbol.index];
+                    (variable.name.as_str(), variable.kind)
+                }
+                SymbolType::Terminal => {
+                    let variable = &lexical_grammar.variables[symbol.index];
+                    (variable.name.as_str(), variable.kind)
+                }
+                SymbolType::External => {
+                    let token = &syntax_grammar.external_tokens[symbol.index];
+                    (token.name.as_str(), token.kind)
+                }
+            };
+            if let Some(alias) = simple_aliases.get(symbol) {
+                return SymbolMetadata {
+                    symbol: *symbol,
+                    name: alias.value.clone(),
+                    visible: true,
+                    named: alias.is_named,
+                    supertype: false,
+                };
+            }
+            SymbolMetadata {
+                symbol: *symbol,
+                name: name.to_string(),
+                visible: matches!(kind, VariableType::Named | VariableType::Anonymous),
+                named: matches!(kind, VariableType::Named | VariableType::Hidden),
+                supertype: syntax_grammar.supertype_symbols.contains(symbol),
+            }
+        })
+        .collect();
+
+    Ok(ParseTables {
+        parse_table: tables.parse_table,
+        main_lex_table: tables.main_lex_table,
+        keyword_lex_table: tables.keyword_lex_table,
+        word_token: tables.word_token,
+        symbols,
+    })
+}
+
+fn validate_export_prefix(export_prefix: &str) -> Result<()> {
+    if export_prefix.starts_with(|c: char| c.is_ascii_digit())
+        || !export_prefix
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid export prefix `{export_prefix}`. It must be the start of a C identifier."
+        ));
+    }
+    Ok(())
+}
+
+fn parse_lexer_strategy(lexer_strategy: Option<&str>) -> Result<LexerStrategy> {
+    match lexer_strategy {
+        None | Some("switch") => Ok(LexerStrategy::Switch),
+        Some("jump-table") => Ok(LexerStrategy::JumpTable),
+        Some("range-table") => Ok(LexerStrategy::RangeTable),
+        Some("ascii-bitmap") => Ok(LexerStrategy::AsciiBitmap),
+        Some("direct-coded") => Ok(LexerStrategy::DirectCoded),
+        Some(strategy) => Err(anyhow!(
+            "Invalid lexer strategy `{strategy}`. It must be `switch`, `jump-table`, `range-table`, `ascii-bitmap` or `direct-coded`."
+        )),
+    }
 }
 
+fn parse_c_standard(c_standard: Option<&str>) -> Result<CStandard> {
+    match c_standard {
+        None | Some("c99") => Ok(CStandard::C99),
+        Some("c89") => Ok(CStandard::C89),
+        Some(standard) => Err(anyhow!(
+            "Invalid C standard `{standard}`. It must be `c89` or `c99`."
+        )),
+    }
+}
+
+fn validate_inline_keyword(inline_keyword: Option<&str>) -> Result<()> {
+    if let Some(keyword) = inline_keyword {
+        if keyword.starts_with(|c: char| c.is_ascii_digit())
+            || !keyword
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            return Err(anyhow!(
+                "Invalid inline keyword `{keyword}`. It must be a C identifier, or empty."
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check a grammar for problems by generating its parser without writing any files, and
+/// look for rules that are never used. The problems are located in the grammar file where
+/// possible.
+pub fn check_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Vec<GrammarDiagnostic> {
+    let source = fs::read_to_string(grammar_path).unwrap_or_default();
+    let input_grammar = match load_grammar_file(grammar_path, js_runtime)
+        .and_then(|grammar_json| parse_grammar(&grammar_json))
+    {
+        Ok(input_grammar) => input_grammar,
+        Err(error) => return vec![GrammarDiagnostic::error(&format!("{error:#}"), &source)],
+    };
+    let mut diagnostics = unused_rule_diagnostics(&input_grammar, &source);
+    if let Err(error) = generate_parser_with_default_opts(&input_grammar) {
+        diagnostics.insert(0, GrammarDiagnostic::error(&format!("{error:#}"), &source));
+    }
+    diagnostics
+}
+
+fn generate_parser_with_default_opts(input_grammar: &InputGrammar) -> Result<GeneratedParser> {
+    generate_parser_for_grammar_with_opts(
+        input_grammar,
+        &GenerateOptions::default(),
+        None,
+        None,
+        &mut Timings::default(),
+    )
+}
+
+/// Generate a parser from a grammar. The lex functions include the shared character sets
+/// from `shared_character_sets_include`, if it's given, and the results of the slowest
+/// phases are reused from `checkpoints`.
 fn generate_parser_for_grammar_with_opts(
     input_grammar: &InputGrammar,
-    abi_version: usize,
-    report_symbol_name: Option<&str>,
+    options: &GenerateOptions,
+    shared_character_sets_include: Option<&str>,
+    checkpoints: Option<&Checkpoints>,
+    timings: &mut Timings,
 ) -> Result<GeneratedParser> {
-    let (syntax_grammar, lexical_grammar, inlines, simple_aliases) =
-        prepare_grammar(input_grammar)?;
+    let export_prefix = options
+        .export_prefix
+        .as_deref()
+        .unwrap_or(DEFAULT_EXPORT_PREFIX);
+    validate_export_prefix(export_prefix)?;
+    let lexer_strategy = parse_lexer_strategy(options.lexer_strategy.as_deref())?;
+    let c_standard = parse_c_standard(options.c_standard.as_deref())?;
+    validate_inline_keyword(options.inline_keyword.as_deref())?;
+    let GenerateOptions {
+        abi_version,
+        auto_inline_threshold,
+        ref renames,
+        ..
+    } = *options;
+
+    let (mut syntax_grammar, mut lexical_grammar, mut inlines, simple_aliases) =
+        if let Some(checkpoints) = checkpoints {
+            let key = Checkpoints::key(&(
+                input_grammar,
+                renames.iter().collect::<BTreeMap<_, _>>(),
+                auto_inline_threshold,
+            ));
+            // Symbols aren't strings, so the aliases are saved as a list of pairs.
+            let (syntax_grammar, lexical_grammar, simple_aliases) =
+                checkpoints.resume_or_run("prepared-grammar", &key, || {
+                    let (syntax_grammar, lexical_grammar, _, simple_aliases) =
+                        prepare_grammar(input_grammar, auto_inline_threshold, renames, timings)?;
+                    Ok((
+                        syntax_grammar,
+                        lexical_grammar,
+                        simple_aliases.into_iter().collect::<Vec<_>>(),
+                    ))
+                })?;
+            let inlines = process_inlines(&syntax_grammar, &lexical_grammar)?;
+            (
+                syntax_grammar,
+                lexical_grammar,
+                inlines,
+                simple_aliases.into_iter().collect(),
+            )
+        } else {
+            prepare_grammar(input_grammar, auto_inline_threshold, renames, timings)?
+        };
+    if !options.grammar_passes.is_empty() {
+        timings.time("grammar passes", || {
+            run_grammar_passes(
+                &options.grammar_passes,
+                &mut syntax_grammar,
+                &mut lexical_grammar,
+            )
+        })?;
+        // The inlined productions refer to the productions of the syntax grammar, which
+        // the passes may have changed.
+        inlines = process_inlines(&syntax_grammar, &lexical_grammar)?;
+    }
     let variable_info =
         node_types::get_variable_info(&syntax_grammar, &lexical_grammar, &simple_aliases)?;
     let node_types_json = node_types::generate_node_types_json(
@@ -148,32 +952,387 @@ fn generate_parser_for_grammar_with_opts(
         &simple_aliases,
         &variable_info,
     );
+    let highlights_query = highlights::render_highlights_query(&node_types_json);
+    let rust_ast = options
+        .rust_ast
+        .then(|| rust_ast::render_rust_ast(&input_grammar.name, &node_types_json));
+    let injections = input_grammar
+        .injections
+        .iter()
+        .map(|injection| Injection {
+            node: renames
+                .get(&injection.node)
+                .unwrap_or(&injection.node)
+                .clone(),
+            ..injection.clone()
+        })
+        .collect::<Vec<_>>();
+    let injections_query = injections::render_injections_query(
+        &injections,
+        &syntax_grammar,
+        &lexical_grammar,
+        &variable_info,
+    )?;
+    let token_display_names =
+        token_display_names(input_grammar, renames, &syntax_grammar, &lexical_grammar)?;
     let tables = build_tables(
         &syntax_grammar,
         &lexical_grammar,
         &simple_aliases,
         &variable_info,
         &inlines,
-        report_symbol_name,
+        options.report_symbol_name.as_deref(),
+        options.source_map || options.annotate,
+        options.conflict_analysis_budget,
+        checkpoints,
+        timings,
     )?;
-    let c_code = render_c_code(
-        &input_grammar.name,
-        tables,
-        syntax_grammar,
-        lexical_grammar,
-        simple_aliases,
-        abi_version,
-    );
+    let skeleton_corpus = options
+        .skeleton_corpus
+        .then(|| {
+            skeleton_corpus::render_skeleton_corpus(
+                &syntax_grammar,
+                &lexical_grammar,
+                &simple_aliases,
+            )
+        })
+        .flatten();
+    let random_programs = options
+        .random_programs
+        .map(|(count, seed)| {
+            random_programs::render_random_programs(
+                &syntax_grammar,
+                &lexical_grammar,
+                &simple_aliases,
+                count,
+                seed,
+            )
+        })
+        .unwrap_or_default();
+    let table_dump = options
+        .dump_tables
+        .then(|| table_dump::render_table_dump(&tables, &syntax_grammar, &lexical_grammar));
+    let source_map = options
+        .source_map
+        .then(|| source_map::render_source_map(&tables, &syntax_grammar, &lexical_grammar));
+    let table_blob = options.table_blob.then(|| {
+        table_blob::render_table_blob(
+            &input_grammar.name,
+            &tables,
+            &syntax_grammar,
+            &lexical_grammar,
+            &simple_aliases,
+            abi_version,
+        )
+    });
+    let RenderedParser {
+        c_code,
+        lexer_c_code,
+        symbols_header,
+        symbol_ids,
+        shared_character_sets,
+    } = timings.time("code emission", || {
+        render_c_code(
+            &input_grammar.name,
+            tables,
+            syntax_grammar,
+            lexical_grammar,
+            simple_aliases,
+            token_display_names,
+            abi_version,
+            options.previous_symbol_ids.clone(),
+            options.split_lexer,
+            options.freestanding,
+            export_prefix,
+            shared_character_sets_include,
+            lexer_strategy,
+            c_standard,
+            options.inline_keyword.as_deref(),
+            options.annotate,
+        )
+    });
     Ok(GeneratedParser {
         c_code,
+        lexer_c_code,
+        symbols_header,
         node_types_json: serde_json::to_string_pretty(&node_types_json).unwrap(),
+        injections_query,
+        highlights_query,
+        table_dump,
+        rust_ast,
+        symbol_ids,
+        shared_character_sets,
+        skeleton_corpus,
+        table_blob,
+        random_programs,
+        source_map,
+    })
+}
+
+/// Whether the grammar already has tests in the directory that `tree-sitter test`
+/// reads its corpus from.
+fn has_corpus(repo_path: &Path) -> bool {
+    fs::read_dir(repo_path.join("test").join("corpus"))
+        .is_ok_and(|mut entries| entries.next().is_some())
+}
+
+/// The path of a file to `#include` from the generated sources in the given directory.
+fn relative_include_path(from_dir: &Path, path: &Path) -> Result<String> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("Invalid path {path:?}"))?;
+    let from_dir = fs::canonicalize(from_dir)?;
+    let to_dir = fs::canonicalize(path.parent().unwrap_or(Path::new(".")))?;
+    let common_count = from_dir
+        .components()
+        .zip(to_dir.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let to_dir = if common_count == 0 {
+        to_dir
+    } else {
+        let mut relative_path = PathBuf::new();
+        for _ in from_dir.components().skip(common_count) {
+            relative_path.push("..");
+        }
+        relative_path.extend(to_dir.components().skip(common_count));
+        relative_path
+    };
+    Ok(to_dir.join(file_name).to_string_lossy().replace('\\', "/"))
+}
+
+/// Add the character sets used by a parser to the header that is shared between
+/// parsers. The sets that are already in the header are kept, because other parsers
+/// may use them.
+fn write_shared_character_sets(path: &Path, character_sets: Vec<(String, String)>) -> Result<()> {
+    let mut definitions = BTreeMap::new();
+    if let Ok(existing) = fs::read_to_string(path) {
+        if !existing.starts_with(SHARED_CHARACTER_SETS_HEADER) {
+            return Err(anyhow!(
+                "Not overwriting {path:?}, which was not generated by `tree-sitter generate --shared-character-sets`"
+            ));
+        }
+        let mut definition = String::new();
+        for line in existing[SHARED_CHARACTER_SETS_HEADER.len()..].lines() {
+            if line.is_empty() && definition.is_empty() {
+                continue;
+            }
+            definition += line;
+            definition.push('\n');
+            if line == "#endif" {
+                let name = definition
+                    .lines()
+                    .nth(1)
+                    .and_then(|line| line.split_whitespace().nth(1))
+                    .and_then(|name| name.strip_suffix("[]"))
+                    .unwrap_or_default()
+                    .to_string();
+                definitions.insert(name, std::mem::take(&mut definition));
+            }
+        }
+    }
+    definitions.extend(character_sets);
+
+    let mut contents = SHARED_CHARACTER_SETS_HEADER.to_string();
+    for definition in definitions.values() {
+        contents.push('\n');
+        contents += definition;
+    }
+    write_file(path, contents)
+}
+
+/// Find the tokens that are given display names in the grammar. They can be referred
+/// to by their rule names, or by their text for anonymous tokens.
+fn token_display_names(
+    input_grammar: &InputGrammar,
+    renames: &HashMap<String, String>,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> Result<HashMap<Symbol, String>> {
+    let mut result = HashMap::new();
+    for (name, display_name) in &input_grammar.display_names {
+        let renamed = renames.get(name).unwrap_or(name);
+        let tokens = lexical_grammar
+            .variables
+            .iter()
+            .enumerate()
+            .filter(|(_, variable)| variable.name == *renamed)
+            .map(|(i, _)| Symbol::terminal(i))
+            .chain(
+                syntax_grammar
+                    .external_tokens
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, token)| token.name == *renamed)
+                    .map(|(i, _)| Symbol::external(i)),
+            )
+            .collect::<Vec<_>>();
+        if tokens.is_empty() {
+            if input_grammar.variables.iter().any(|v| v.name == *name) {
+                return Err(anyhow!(
+                    "Invalid display name for `{name}`: only tokens can have display names"
+                ));
+            }
+            return Err(anyhow!("Undefined symbol `{name}` in `display_names`"));
+        }
+        for token in tokens {
+            result.insert(token, display_name.clone());
+        }
+    }
+    Ok(result)
+}
+
+/// Write the lex functions that were split out of the parser, or remove a previously
+/// generated lexer file if the parser was generated as a single file.
+fn write_lexer_file(path: &Path, lexer_c_code: Option<String>) -> Result<()> {
+    if let Some(lexer_c_code) = lexer_c_code {
+        info!("the lexer was written to {path:?}, which must be compiled along with parser.c");
+        return write_file(path, lexer_c_code);
+    }
+    // The header is a block comment in lexers that are generated as C89.
+    let header_text = LEXER_FILE_HEADER.trim_start_matches("// ");
+    if fs::read_to_string(path).is_ok_and(|existing| existing.contains(header_text)) {
+        fs::remove_file(path).with_context(|| format!("Failed to remove {path:?}"))?;
+    }
+    Ok(())
+}
+
+/// Prefix a generated file with the project's banner, commenting out each of its lines.
+fn add_banner(banner: Option<&str>, comment_prefix: &str, contents: String) -> String {
+    let Some(banner) = banner else {
+        return contents;
+    };
+    let mut result = String::with_capacity(banner.len() + contents.len());
+    for line in banner.lines() {
+        result += format!("{comment_prefix}{line}").trim_end();
+        result.push('\n');
+    }
+    result.push('\n');
+    result + &contents
+}
+
+/// Prefix a generated C file with the project's banner, as block comments when the file
+/// is C89, which doesn't have line comments.
+fn add_c_banner(banner: Option<&str>, c_standard: CStandard, contents: String) -> String {
+    if c_standard == CStandard::C89 && banner.is_some() {
+        to_block_comments(&add_banner(banner, "// ", String::new())) + &contents
+    } else {
+        add_banner(banner, "// ", contents)
+    }
+}
+
+/// Write a manifest of the generated files, with a hash of each file's contents and a
+/// hash of the grammar that they were generated from, so that build systems can tell
+/// when the files are stale.
+fn write_manifest(
+    output_path: &Path,
+    grammar_path: &Path,
+    grammar_json: &str,
+    file_names: &[&str],
+) -> Result<()> {
+    let mut files = serde_json::Map::new();
+    for file_name in file_names {
+        let path = output_path.join(file_name);
+        let contents = fs::read(&path).with_context(|| format!("Failed to read {path:?}"))?;
+        files.insert((*file_name).to_string(), content_hash(&contents).into());
+    }
+    let manifest = serde_json::json!({
+        "generator": format!("tree-sitter {}", env!("CARGO_PKG_VERSION")),
+        "grammar": content_hash(grammar_json.as_bytes()),
+        "sources": grammar_source_hashes(grammar_path)?,
+        "files": files,
+    });
+    write_file(
+        &output_path.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest).unwrap() + "\n",
+    )
+}
+
+/// Hashes of the files that a grammar is generated from, by their paths relative to the
+/// directory of the grammar file, so that it can be checked without evaluating the
+/// grammar whether the generated files are stale.
+fn grammar_source_hashes(
+    grammar_path: &Path,
+) -> Result<serde_json::Map<String, serde_json::Value>> {
+    let grammar_dir = grammar_path.parent().unwrap_or(Path::new(""));
+    let mut hashes = serde_json::Map::new();
+    for path in watched_grammar_files(grammar_path).into_keys() {
+        let contents = fs::read(&path).with_context(|| format!("Failed to read {path:?}"))?;
+        let relative_path = path.strip_prefix(grammar_dir).unwrap_or(&path);
+        hashes.insert(
+            relative_path.to_string_lossy().replace('\\', "/"),
+            content_hash(&contents).into(),
+        );
+    }
+    Ok(hashes)
+}
+
+/// A 64-bit FNV-1a hash of the given contents, which is stable across platforms and
+/// versions of the CLI.
+fn fnv1a_hash(contents: &[u8]) -> u64 {
+    contents
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+        })
+}
+
+fn content_hash(contents: &[u8]) -> String {
+    format!("fnv1a64:{:016x}", fnv1a_hash(contents))
+}
+
+/// Write the injections query generated from the grammar, unless the grammar's
+/// existing `injections.scm` was written by hand.
+fn write_injections_query(queries_path: &Path, injections_query: String) -> Result<()> {
+    let path = queries_path.join("injections.scm");
+    if let Ok(existing) = fs::read_to_string(&path) {
+        if !existing.starts_with(injections::INJECTIONS_QUERY_HEADER) {
+            warn!(
+                "not overwriting {path:?}, which was not generated from the grammar's `injections`"
+            );
+            return Ok(());
+        }
+    }
+    fs::create_dir_all(queries_path)?;
+    write_file(&path, injections_query)
+}
+
+/// Read a JSON object that maps rule names to the names under which their nodes
+/// should be published.
+fn load_rename_map(path: &Path) -> Result<HashMap<String, String>> {
+    let json =
+        fs::read_to_string(path).with_context(|| format!("Failed to read rename map {path:?}"))?;
+    serde_json::from_str(&json).with_context(|| {
+        format!("Failed to parse rename map {path:?}. It must be a JSON object mapping rule names to node names")
     })
 }
 
+/// Compare two versions of a grammar's `node-types.json`, returning a description
+/// of each change that can break code written against the old version.
+pub fn get_breaking_node_type_changes(old_json: &str, new_json: &str) -> Result<Vec<String>> {
+    let old = serde_json::from_str::<Vec<node_types::NodeInfoJSON>>(old_json)
+        .with_context(|| "Failed to parse the old node types")?;
+    let new = serde_json::from_str::<Vec<node_types::NodeInfoJSON>>(new_json)
+        .with_context(|| "Failed to parse the new node types")?;
+    Ok(node_types::get_breaking_node_type_changes(&old, &new))
+}
+
+/// The grammar file in the given directory: `grammar.js`, or `grammar.toml` if there is
+/// only a TOML grammar.
+pub fn default_grammar_path(repo_path: &Path) -> PathBuf {
+    let grammar_js_path = repo_path.join("grammar.js");
+    let grammar_toml_path = repo_path.join("grammar.toml");
+    if !grammar_js_path.exists() && grammar_toml_path.exists() {
+        grammar_toml_path
+    } else {
+        grammar_js_path
+    }
+}
+
 pub fn load_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Result<String> {
     if grammar_path.is_dir() {
         return Err(anyhow!(
-            "Path to a grammar file with `.js` or `.json` extension is required"
+            "Path to a grammar file with `.js`, `.json` or `.toml` extension is required"
         ));
     }
     match grammar_path.extension().and_then(|e| e.to_str()) {
@@ -182,20 +1341,42 @@ pub fn load_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Resul
         Some("json") => {
             Ok(fs::read_to_string(grammar_path).with_context(|| "Failed to load grammar.json")?)
         }
+        Some("toml") => load_toml_grammar_file(grammar_path),
         _ => Err(anyhow!("Unknown grammar file extension: {grammar_path:?}",)),
     }
 }
 
+/// Load a grammar that is written in TOML, with the same structure as `grammar.json`,
+/// and convert it to JSON. The rules keep their order, so the first one is still the
+/// start rule.
+fn load_toml_grammar_file(grammar_path: &Path) -> Result<String> {
+    let grammar_toml =
+        fs::read_to_string(grammar_path).with_context(|| "Failed to load grammar.toml")?;
+    let grammar: serde_json::Value =
+        toml::from_str(&grammar_toml).with_context(|| "Failed to parse grammar.toml")?;
+    Ok(serde_json::to_string_pretty(&grammar)?)
+}
+
 fn load_js_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Result<String> {
     let grammar_path = fs::canonicalize(grammar_path)?;
+    let cli_version = Version::parse(env!("CARGO_PKG_VERSION"))
+        .with_context(|| "Could not parse this package's version as semver.")?;
+
+    let js_runtime = js_runtime.unwrap_or("node");
+    if js_runtime == "native" {
+        #[cfg(feature = "qjs-rt")]
+        return format_grammar_json(&quickjs::load_grammar_file(&grammar_path, &cli_version)?);
+        #[cfg(not(feature = "qjs-rt"))]
+        return Err(anyhow!(
+            "The `native` JavaScript runtime isn't available, because this version of Tree-sitter was built without the `qjs-rt` feature"
+        ));
+    }
 
     #[cfg(windows)]
     let grammar_path = url::Url::from_file_path(grammar_path)
         .expect("Failed to convert path to URL")
         .to_string();
 
-    let js_runtime = js_runtime.unwrap_or("node");
-
     let mut js_command = Command::new(js_runtime);
     match js_runtime {
         "node" => {
@@ -221,8 +1402,6 @@ fn load_js_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Result
         .stdin
         .take()
         .with_context(|| format!("Failed to open stdin for {js_runtime}"))?;
-    let cli_version = Version::parse(env!("CARGO_PKG_VERSION"))
-        .with_context(|| "Could not parse this package's version as semver.")?;
     write!(
         js_stdin,
         "globalThis.TREE_SITTER_CLI_VERSION_MAJOR = {};
@@ -258,18 +1437,913 @@ fn load_js_grammar_file(grammar_path: &Path, js_runtime: Option<&str>) -> Result
                 stdout.flush()?;
             }
 
-            Ok(serde_json::to_string_pretty(
-                &serde_json::from_str::<serde_json::Value>(grammar_json)
-                    .with_context(|| "Failed to parse grammar JSON")?,
-            )
-            .with_context(|| "Failed to serialize grammar JSON")?
-                + "\n")
+            format_grammar_json(grammar_json)
         }
         Some(code) => Err(anyhow!("{js_runtime} process exited with status {code}")),
     }
 }
 
+fn format_grammar_json(grammar_json: &str) -> Result<String> {
+    Ok(serde_json::to_string_pretty(
+        &serde_json::from_str::<serde_json::Value>(grammar_json)
+            .with_context(|| "Failed to parse grammar JSON")?,
+    )
+    .with_context(|| "Failed to serialize grammar JSON")?
+        + "\n")
+}
+
 fn write_file(path: &Path, body: impl AsRef<[u8]>) -> Result<()> {
     fs::write(path, body)
         .with_context(|| format!("Failed to write {:?}", path.file_name().unwrap()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stable_symbol_ids() {
+        let generate = |grammar_json: &str, previous_symbol_ids: Vec<String>| {
+            generate_parser_for_grammar_with_opts(
+                &parse_grammar(grammar_json).unwrap(),
+                &GenerateOptions {
+                    previous_symbol_ids,
+                    ..Default::default()
+                },
+                None,
+                None,
+                &mut Timings::default(),
+            )
+            .unwrap()
+            .symbol_ids
+        };
+
+        let grammar = r#"{
+            "name": "test",
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "statement"}},
+                "statement": {"type": "SEQ", "members": [
+                    {"type": "SYMBOL", "name": "identifier"},
+                    {"type": "STRING", "value": ";"}
+                ]},
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#;
+        let extended_grammar = r#"{
+            "name": "test",
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "CHOICE", "members": [
+                    {"type": "SYMBOL", "name": "block"},
+                    {"type": "SYMBOL", "name": "statement"}
+                ]}},
+                "block": {"type": "SEQ", "members": [
+                    {"type": "STRING", "value": "{"},
+                    {"type": "SYMBOL", "name": "statement"},
+                    {"type": "STRING", "value": "}"}
+                ]},
+                "statement": {"type": "SEQ", "members": [
+                    {"type": "SYMBOL", "name": "identifier"},
+                    {"type": "STRING", "value": ";"}
+                ]},
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#;
+
+        let symbol_ids = generate(grammar, Vec::new());
+        assert_eq!(
+            symbol_ids,
+            [
+                "anon_sym_SEMI",
+                "sym_identifier",
+                "sym_program",
+                "sym_statement",
+                "aux_sym_program_repeat1"
+            ]
+        );
+
+        // Without the previous ids, the new rule is numbered in grammar order,
+        // which shifts the ids of the rules that follow it.
+        assert_eq!(
+            generate(extended_grammar, Vec::new())[..6],
+            [
+                "anon_sym_LBRACE",
+                "anon_sym_RBRACE",
+                "anon_sym_SEMI",
+                "sym_identifier",
+                "sym_program",
+                "sym_block",
+            ]
+        );
+
+        // With the previous ids, the existing symbols keep their relative order,
+        // and the new symbols come after them within each category.
+        let extended_symbol_ids = generate(extended_grammar, symbol_ids);
+        assert_eq!(
+            extended_symbol_ids,
+            [
+                "anon_sym_SEMI",
+                "sym_identifier",
+                "anon_sym_LBRACE",
+                "anon_sym_RBRACE",
+                "sym_program",
+                "sym_statement",
+                "aux_sym_program_repeat1",
+                "sym_block",
+            ]
+        );
+
+        // Removing symbols leaves their ids unused, so that the symbols after them keep
+        // their ids.
+        let c_code = generate_parser_for_grammar_with_opts(
+            &parse_grammar(grammar).unwrap(),
+            &GenerateOptions {
+                previous_symbol_ids: extended_symbol_ids.clone(),
+                ..Default::default()
+            },
+            None,
+            None,
+            &mut Timings::default(),
+        )
+        .unwrap()
+        .c_code;
+        assert!(c_code.contains("#define SYMBOL_COUNT 8\n"));
+        assert!(c_code.contains("#define TOKEN_COUNT 5\n"));
+        assert!(c_code.contains("  sym_program = 5,\n"));
+        let symbol_ids = generate(grammar, extended_symbol_ids);
+        assert_eq!(
+            symbol_ids,
+            [
+                "anon_sym_SEMI",
+                "sym_identifier",
+                "",
+                "",
+                "sym_program",
+                "sym_statement",
+                "aux_sym_program_repeat1",
+            ]
+        );
+
+        // The unused ids are kept until new symbols take them.
+        assert_eq!(generate(grammar, symbol_ids.clone()), symbol_ids);
+        assert_eq!(
+            generate(extended_grammar, symbol_ids)[..5],
+            [
+                "anon_sym_SEMI",
+                "sym_identifier",
+                "anon_sym_LBRACE",
+                "anon_sym_RBRACE",
+                "sym_program",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_content_hash() {
+        assert_eq!(content_hash(b""), "fnv1a64:cbf29ce484222325");
+        assert_eq!(content_hash(b"a"), "fnv1a64:af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn test_add_banner() {
+        assert_eq!(add_banner(None, "// ", "int x;\n".into()), "int x;\n");
+        assert_eq!(
+            add_banner(
+                Some("Copyright ACME\n\nGenerated code\n"),
+                "// ",
+                "int x;\n".into()
+            ),
+            "// Copyright ACME\n//\n// Generated code\n\nint x;\n"
+        );
+    }
+
+    #[test]
+    fn test_load_toml_grammar_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let grammar_path = dir.path().join("grammar.toml");
+        fs::write(
+            &grammar_path,
+            r#"
+name = "test"
+extras = [{ type = "PATTERN", value = "\\s" }]
+
+[rules.program]
+type = "REPEAT"
+content = { type = "SYMBOL", name = "identifier" }
+
+[rules.identifier]
+type = "PATTERN"
+value = "[a-z]+"
+"#,
+        )
+        .unwrap();
+        assert_eq!(default_grammar_path(dir.path()), grammar_path);
+
+        let grammar_json = load_grammar_file(&grammar_path, None).unwrap();
+        let input_grammar = parse_grammar(&grammar_json).unwrap();
+        assert_eq!(input_grammar.name, "test");
+        assert_eq!(
+            input_grammar
+                .variables
+                .iter()
+                .map(|variable| variable.name.as_str())
+                .collect::<Vec<_>>(),
+            ["program", "identifier"]
+        );
+        assert_eq!(input_grammar.extra_symbols.len(), 1);
+    }
+
+    #[test]
+    fn test_watched_grammar_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in [
+            "grammar.js",
+            "common/define-grammar.js",
+            "node_modules/tree-sitter-cli/dsl.js",
+            "bindings/node/index.js",
+            "src/grammar.json",
+            "package.json",
+        ] {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+
+        let files = watched_grammar_files(&dir.path().join("grammar.js"));
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            [
+                &dir.path().join("common/define-grammar.js"),
+                &dir.path().join("grammar.js"),
+            ]
+        );
+        assert!(files.values().all(Option::is_some));
+
+        let files = watched_grammar_files(&dir.path().join("src/grammar.json"));
+        assert_eq!(
+            files.keys().collect::<Vec<_>>(),
+            [&dir.path().join("src/grammar.json")]
+        );
+    }
+
+    #[test]
+    fn test_shared_character_sets() {
+        let dir = tempfile::tempdir().unwrap();
+        let src_path = dir.path().join("grammar").join("src");
+        let header_path = dir.path().join("common").join("character_sets.h");
+        fs::create_dir_all(&src_path).unwrap();
+        fs::create_dir_all(header_path.parent().unwrap()).unwrap();
+        assert_eq!(
+            relative_include_path(&src_path, &header_path).unwrap(),
+            "../../common/character_sets.h"
+        );
+
+        let definition = |name: &str| {
+            format!(
+                "#ifdef TS_USE_{}\nTS_SHARED_CHARACTER_SET ts_{name}[] = {{\n  {{'a', 'z'}},\n}};\n#endif\n",
+                name.to_ascii_uppercase()
+            )
+        };
+        write_shared_character_sets(
+            &header_path,
+            vec![("ts_character_set_2".into(), definition("character_set_2"))],
+        )
+        .unwrap();
+        write_shared_character_sets(
+            &header_path,
+            vec![
+                ("ts_character_set_1".into(), definition("character_set_1")),
+                ("ts_character_set_2".into(), definition("character_set_2")),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(&header_path).unwrap(),
+            format!(
+                "{SHARED_CHARACTER_SETS_HEADER}\n{}\n{}",
+                definition("character_set_1"),
+                definition("character_set_2")
+            )
+        );
+
+        fs::write(&header_path, "// Written by hand\n").unwrap();
+        assert!(write_shared_character_sets(&header_path, Vec::new()).is_err());
+    }
+
+    #[test]
+    fn test_split_lexer() {
+        let grammar = parse_grammar(
+            r#"{
+                "name": "test",
+                "word": "identifier",
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "CHOICE", "members": [
+                        {"type": "STRING", "value": "if"},
+                        {"type": "SYMBOL", "name": "identifier"}
+                    ]}},
+                    "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+                }
+            }"#,
+        )
+        .unwrap();
+        let generate = |split_lexer| {
+            generate_parser_for_grammar_with_opts(
+                &grammar,
+                &GenerateOptions {
+                    split_lexer,
+                    ..Default::default()
+                },
+                None,
+                None,
+                &mut Timings::default(),
+            )
+            .unwrap()
+        };
+
+        let parser = generate(false);
+        assert!(parser.lexer_c_code.is_none());
+        assert!(parser.c_code.contains("static bool ts_lex(TSLexer *lexer"));
+
+        let parser = generate(true);
+        let lexer_c_code = parser.lexer_c_code.unwrap();
+        assert!(lexer_c_code.starts_with(LEXER_FILE_HEADER));
+        assert!(
+            lexer_c_code.contains("bool tree_sitter_test_lex(TSLexer *lexer, TSStateId state) {")
+        );
+        assert!(lexer_c_code
+            .contains("bool tree_sitter_test_lex_keywords(TSLexer *lexer, TSStateId state) {"));
+        assert!(!parser.c_code.contains("START_LEXER();"));
+        assert!(parser
+            .c_code
+            .contains("bool tree_sitter_test_lex(TSLexer *lexer, TSStateId state);"));
+        assert!(parser
+            .c_code
+            .contains(".keyword_lex_fn = tree_sitter_test_lex_keywords,"));
+    }
+
+    #[test]
+    fn test_generate_parser() {
+        let grammar_json = r#"{
+            // Comments are allowed, as in the generated `grammar.json` files.
+            "name": "test",
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "word"}},
+                "word": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#;
+
+        let output = generate_parser(grammar_json, &GenerateOptions::default()).unwrap();
+        assert_eq!(output.name, "test");
+        assert!(output
+            .parser_c
+            .contains("TS_PUBLIC const TSLanguage *tree_sitter_test(void) {"));
+        assert!(output.lexer_c.is_none());
+        assert!(output.node_types_json.contains("\"type\": \"program\""));
+        assert!(output.rust_ast.is_none());
+        assert_eq!(
+            output.symbol_ids,
+            ["sym_word", "sym_program", "aux_sym_program_repeat1"]
+        );
+
+        let output = generate_parser(
+            grammar_json,
+            &GenerateOptions {
+                split_lexer: true,
+                rust_ast: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(output.lexer_c.is_some());
+        assert!(output.rust_ast.is_some());
+
+        let error = generate_parser(
+            grammar_json,
+            &GenerateOptions {
+                lexer_strategy: Some("table".to_string()),
+                ..Default::default()
+            },
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid lexer strategy `table`. It must be `switch`, `jump-table`, `range-table`, `ascii-bitmap` or `direct-coded`."
+        );
+    }
+
+    #[test]
+    fn test_renaming_a_symbol_used_in_precedences() {
+        // The conflict between reducing a `word` to a `statement` or to an `expression` is
+        // only resolved by the `precedences`, so they must follow the rename.
+        let grammar_json = r#"{
+            "name": "test",
+            "precedences": [[{"type": "SYMBOL", "name": "statement"}, {"type": "SYMBOL", "name": "expression"}]],
+            "rules": {
+                "program": {"type": "CHOICE", "members": [
+                    {"type": "SYMBOL", "name": "statement"},
+                    {"type": "SYMBOL", "name": "expression"}
+                ]},
+                "statement": {"type": "SYMBOL", "name": "word"},
+                "expression": {"type": "SYMBOL", "name": "word"},
+                "word": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#;
+
+        let output = generate_parser(
+            grammar_json,
+            &GenerateOptions {
+                renames: HashMap::from([("statement".to_string(), "command".to_string())]),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(output.node_types_json.contains("\"type\": \"command\""));
+        assert!(!output.node_types_json.contains("\"type\": \"statement\""));
+    }
+
+    #[test]
+    fn test_build_parse_tables() {
+        let grammar = parse_grammar(
+            r#"{
+                "name": "test",
+                "supertypes": ["_value"],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "_value"}},
+                    "_value": {"type": "CHOICE", "members": [
+                        {"type": "SYMBOL", "name": "word"},
+                        {"type": "STRING", "value": "!"}
+                    ]},
+                    "word": {"type": "PATTERN", "value": "[a-z]+"}
+                }
+            }"#,
+        )
+        .unwrap();
+        let tables = build_parse_tables(&grammar).unwrap();
+
+        let symbols = tables
+            .symbols
+            .iter()
+            .map(|s| (s.name.as_str(), s.visible, s.named, s.supertype))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            symbols,
+            [
+                ("end", false, true, false),
+                ("!", true, false, false),
+                ("word", true, true, false),
+                ("program", true, true, false),
+                ("_value", false, true, true),
+                ("program_repeat1", false, false, false),
+            ]
+        );
+        assert_eq!(tables.symbols.len(), tables.parse_table.symbols.len());
+        assert!(tables.keyword_lex_table.states.is_empty());
+
+        // The lexer starts each state's tokens from a state of the main lex table.
+        for state in &tables.parse_table.states {
+            assert!(state.lex_state_id < tables.main_lex_table.states.len());
+        }
+        let word = tables.symbols[2].symbol;
+        assert!(tables.parse_table.states[1]
+            .terminal_entries
+            .get(&word)
+            .is_some_and(|entry| matches!(entry.actions[..], [ParseAction::Shift { .. }])));
+    }
+
+    #[test]
+    fn test_serialize_intermediate_representations() {
+        let grammar = parse_grammar(
+            r#"{
+                "name": "test",
+                "extras": [{"type": "PATTERN", "value": "\\s"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "word"}},
+                    "word": {"type": "PATTERN", "value": "[a-z]+"}
+                }
+            }"#,
+        )
+        .unwrap();
+        let json = serde_json::to_string(&grammar).unwrap();
+        assert_eq!(
+            serde_json::from_str::<InputGrammar>(&json).unwrap(),
+            grammar
+        );
+
+        let (syntax_grammar, lexical_grammar, _, _) =
+            prepare_grammar(&grammar, None, &HashMap::new(), &mut Timings::default()).unwrap();
+        let json = serde_json::to_string(&syntax_grammar).unwrap();
+        assert_eq!(
+            serde_json::from_str::<SyntaxGrammar>(&json).unwrap(),
+            syntax_grammar
+        );
+        let json = serde_json::to_string(&lexical_grammar).unwrap();
+        assert_eq!(
+            serde_json::from_str::<LexicalGrammar>(&json).unwrap(),
+            lexical_grammar
+        );
+
+        let tables = build_parse_tables(&grammar).unwrap();
+        let json = serde_json::to_string(&tables).unwrap();
+        assert_eq!(serde_json::from_str::<ParseTables>(&json).unwrap(), tables);
+    }
+
+    #[test]
+    fn test_grammar_errors() {
+        let grammar_json = r#"{
+  "name": "test",
+  "rules": {
+    "program": {"type": "SYMBOL", "name": "sum"},
+    "sum": {"type": "SEQ", "members": [
+      {"type": "SYMBOL", "name": "number"},
+      {"type": "REPEAT", "content": {"type": "SEQ", "members": [
+        {"type": "STRING", "value": "+"},
+        {"type": "SYMBOL", "name": "sum"}
+      ]}}
+    ]},
+    "number": {"type": "PATTERN", "value": "[0-9]+"}
+  }
+}"#;
+        let error = generate_parser(grammar_json, &GenerateOptions::default()).unwrap_err();
+        let grammar_error = GrammarError::find(&error).unwrap();
+        assert_eq!(grammar_error.kind, GrammarErrorKind::UnresolvedConflict);
+        assert_eq!(grammar_error.symbols, ["sum"]);
+        assert_eq!(
+            grammar_error.suggestion.as_deref(),
+            Some("Add a conflict for these rules: `sum`")
+        );
+        assert!(grammar_error
+            .message
+            .starts_with("Unresolved conflict for symbol sequence:"));
+
+        let grammar_dir = tempfile::tempdir().unwrap();
+        let grammar_path = grammar_dir.path().join("grammar.json");
+        fs::write(&grammar_path, grammar_json).unwrap();
+        let error = annotate_error(error, &grammar_path);
+        let grammar_error = GrammarError::find(&error).unwrap();
+        assert_eq!(
+            grammar_error.span,
+            Some(RuleLocation {
+                row: 4,
+                column: 4,
+                len: 5
+            })
+        );
+        assert!(grammar_error
+            .message
+            .contains("note: `sum` is defined here"));
+
+        let error = generate_parser(
+            &grammar_json.replace("[0-9]+", "^[0-9]+"),
+            &GenerateOptions::default(),
+        )
+        .unwrap_err();
+        let grammar_error = GrammarError::find(&error).unwrap();
+        assert_eq!(grammar_error.kind, GrammarErrorKind::UnsupportedToken);
+        assert_eq!(grammar_error.symbols, ["number"]);
+        assert_eq!(error.to_string(), "Error processing rule number");
+    }
+
+    #[test]
+    fn test_lexer_strategies() {
+        let grammar = parse_grammar(
+            r#"{
+                "name": "test",
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "word"}},
+                    "word": {"type": "PATTERN", "value": "[a-fA-F0-9_]+"}
+                }
+            }"#,
+        )
+        .unwrap();
+        let generate = |lexer_strategy: &str| {
+            generate_parser_for_grammar_with_opts(
+                &grammar,
+                &GenerateOptions {
+                    lexer_strategy: Some(lexer_strategy.to_string()),
+                    ..Default::default()
+                },
+                None,
+                None,
+                &mut Timings::default(),
+            )
+            .unwrap()
+            .c_code
+        };
+
+        let c_code = generate("switch");
+        assert!(c_code.contains("switch (state) {\n    case 0:"));
+        assert!(!c_code.contains("set_contains("));
+
+        let c_code = generate("jump-table");
+        assert!(c_code.contains("static const void *const lex_states[] = {\n      &&lex_state_0,"));
+        assert!(c_code.contains("goto *lex_states[state];"));
+        assert!(c_code.contains("case 0: goto lex_state_0;"));
+        assert!(c_code.contains("  lex_state_0:\n"));
+
+        let c_code = generate("range-table");
+        assert!(c_code.contains("static TSCharacterRange lex_character_set_1[] = {"));
+        assert!(c_code.contains("set_contains(lex_character_set_1, 4, lookahead)"));
+
+        let c_code = generate("ascii-bitmap");
+        assert!(c_code.contains(
+            "static const uint32_t lex_ascii_bitmap_1[] = {0x00000000, 0x03ff0000, 0x8000007e, 0x0000007e};"
+        ));
+        assert!(c_code.contains(
+            "if ((uint32_t)lookahead < 128 && (lex_ascii_bitmap_1[lookahead >> 5] >> (lookahead & 31)) & 1) ADVANCE("
+        ));
+    }
+
+    #[test]
+    fn test_direct_coded_lexer() {
+        let grammar = parse_grammar(
+            r#"{
+                "name": "test",
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "operator"}},
+                    "operator": {"type": "CHOICE", "members": [
+                        {"type": "STRING", "value": "+"}, {"type": "STRING", "value": "-"},
+                        {"type": "STRING", "value": "*"}, {"type": "STRING", "value": "/"},
+                        {"type": "STRING", "value": "%"}, {"type": "STRING", "value": "<"},
+                        {"type": "STRING", "value": ">"}, {"type": "STRING", "value": "="},
+                        {"type": "STRING", "value": "!"}
+                    ]}
+                }
+            }"#,
+        )
+        .unwrap();
+        let c_code = generate_parser_for_grammar_with_opts(
+            &grammar,
+            &GenerateOptions {
+                lexer_strategy: Some("direct-coded".to_string()),
+                c_standard: Some("c89".to_string()),
+                ..Default::default()
+            },
+            None,
+            None,
+            &mut Timings::default(),
+        )
+        .unwrap()
+        .c_code;
+
+        // The lex states are dispatched with a computed goto, and the characters with a
+        // `switch` statement, which works in C89 too.
+        assert!(c_code.contains("goto *lex_states[state];"));
+        assert!(!c_code.contains("ADVANCE_MAP("));
+        assert!(c_code.contains("switch (lookahead) {\n      case '!':\n        ADVANCE("));
+        assert!(c_code.contains("      case '%':\n        ADVANCE("));
+    }
+
+    #[test]
+    fn test_annotations() {
+        let grammar = parse_grammar(
+            r#"{
+                "name": "test",
+                "extras": [{"type": "PATTERN", "value": "\\s"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "comment"}},
+                    "comment": {"type": "SEQ", "members": [
+                        {"type": "STRING", "value": "/*"},
+                        {"type": "PATTERN", "value": "[a-z]+"},
+                        {"type": "STRING", "value": "*/"}
+                    ]}
+                }
+            }"#,
+        )
+        .unwrap();
+        let generate = |annotate| {
+            generate_parser_for_grammar_with_opts(
+                &grammar,
+                &GenerateOptions {
+                    annotate,
+                    ..Default::default()
+                },
+                None,
+                None,
+                &mut Timings::default(),
+            )
+            .unwrap()
+            .c_code
+        };
+
+        let c_code = generate(false);
+        let annotated_c_code = generate(true);
+        assert!(!c_code.contains("/*|"));
+        assert!(annotated_c_code.contains("  /*| START → • program */\n  [1] = {\n"));
+        assert!(annotated_c_code.contains("  /*| comment → / * • comment_token1 * / */\n"));
+        assert!(annotated_c_code.contains("/*| tokens: \"/ *\" */\n"));
+        assert!(annotated_c_code.contains("/*| reduce comment */\n"));
+        assert_eq!(strip_annotations(&annotated_c_code), c_code);
+    }
+
+    #[test]
+    fn test_c_standards() {
+        let grammar = parse_grammar(
+            r#"{
+                "name": "test",
+                "externals": [{"type": "SYMBOL", "name": "heredoc"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "CHOICE", "members": [
+                        {"type": "FIELD", "name": "body", "content": {"type": "SYMBOL", "name": "heredoc"}},
+                        {"type": "SYMBOL", "name": "operator"}
+                    ]}},
+                    "operator": {"type": "CHOICE", "members": [
+                        {"type": "STRING", "value": "+"}, {"type": "STRING", "value": "-"},
+                        {"type": "STRING", "value": "*"}, {"type": "STRING", "value": "/"},
+                        {"type": "STRING", "value": "%"}, {"type": "STRING", "value": "<"},
+                        {"type": "STRING", "value": ">"}, {"type": "STRING", "value": "="},
+                        {"type": "STRING", "value": "!"}
+                    ]}
+                }
+            }"#,
+        )
+        .unwrap();
+        let generate = |c_standard: &str, inline_keyword: Option<&str>| {
+            generate_parser_for_grammar_with_opts(
+                &grammar,
+                &GenerateOptions {
+                    c_standard: Some(c_standard.to_string()),
+                    inline_keyword: inline_keyword.map(str::to_string),
+                    ..Default::default()
+                },
+                None,
+                None,
+                &mut Timings::default(),
+            )
+            .unwrap()
+            .c_code
+        };
+
+        let c_code = generate("c99", None);
+        assert!(c_code.contains("ADVANCE_MAP("));
+        assert!(c_code.contains("  field_body = 1,\n};"));
+        assert!(!c_code.contains("TS_INLINE"));
+
+        let c_code = generate("c89", Some("__inline"));
+        assert!(!c_code.contains("ADVANCE_MAP("));
+        assert!(!c_code.contains("//"));
+        assert!(c_code.contains("  field_body = 1\n};"));
+        assert!(c_code.contains("  ts_external_token_heredoc = 0\n};"));
+        assert!(c_code.contains("#ifndef TS_INLINE\n#define TS_INLINE __inline\n#endif\n"));
+    }
+
+    #[test]
+    fn test_token_display_names() {
+        let generate = |display_names: &str| {
+            let grammar = parse_grammar(&format!(
+                r#"{{
+                    "name": "test",
+                    "display_names": {display_names},
+                    "rules": {{
+                        "program": {{"type": "REPEAT", "content": {{"type": "SEQ", "members": [
+                            {{"type": "SYMBOL", "name": "identifier"}},
+                            {{"type": "STRING", "value": ";"}}
+                        ]}}}},
+                        "identifier": {{"type": "PATTERN", "value": "[a-z]+"}}
+                    }}
+                }}"#
+            ))
+            .unwrap();
+            let c_code = generate_parser_for_grammar_with_opts(
+                &grammar,
+                &GenerateOptions {
+                    ..Default::default()
+                },
+                None,
+                None,
+                &mut Timings::default(),
+            )?
+            .c_code;
+            let start = c_code.find("ts_token_display_names[SYMBOL_COUNT]").unwrap();
+            let end = start + c_code[start..].find("};").unwrap();
+            Ok::<_, anyhow::Error>(c_code[start..end].to_string())
+        };
+
+        assert_eq!(
+            generate("{}").unwrap(),
+            "ts_token_display_names[SYMBOL_COUNT] = {
+  [ts_builtin_sym_end] = \"end of file\",
+  [anon_sym_SEMI] = \"\\\";\\\"\",
+  [sym_identifier] = \"identifier\",
+"
+        );
+        assert_eq!(
+            generate(r#"{"identifier": "name", ";": "semicolon"}"#).unwrap(),
+            "ts_token_display_names[SYMBOL_COUNT] = {
+  [ts_builtin_sym_end] = \"end of file\",
+  [anon_sym_SEMI] = \"semicolon\",
+  [sym_identifier] = \"name\",
+"
+        );
+        assert_eq!(
+            generate(r#"{"program": "program"}"#)
+                .unwrap_err()
+                .to_string(),
+            "Invalid display name for `program`: only tokens can have display names"
+        );
+        assert_eq!(
+            generate(r#"{"semicolon": ";"}"#).unwrap_err().to_string(),
+            "Undefined symbol `semicolon` in `display_names`"
+        );
+    }
+
+    #[test]
+    fn test_generate_parser_with_checkpoints() {
+        let generate = |identifier: &str, checkpoints: Option<&Checkpoints>| {
+            let grammar = parse_grammar(&format!(
+                r#"{{
+                    "name": "test",
+                    "extras": [{{"type": "PATTERN", "value": "\\s"}}],
+                    "rules": {{
+                        "program": {{"type": "REPEAT", "content": {{"type": "SEQ", "members": [
+                            {identifier},
+                            {{"type": "STRING", "value": ";"}}
+                        ]}}}},
+                        "identifier": {{"type": "PATTERN", "value": "[a-z]+"}}
+                    }}
+                }}"#
+            ))
+            .unwrap();
+            generate_parser_for_grammar_with_opts(
+                &grammar,
+                &GenerateOptions {
+                    ..Default::default()
+                },
+                None,
+                checkpoints,
+                &mut Timings::default(),
+            )
+            .unwrap()
+            .c_code
+        };
+
+        // The second grammar only adds a field, which doesn't change its tokens or the
+        // tokens that can follow them, so the token conflicts of the first are reused for it.
+        // Reusing the result of a phase must not change the generated parser.
+        let symbol = r#"{"type": "SYMBOL", "name": "identifier"}"#;
+        let field = format!(r#"{{"type": "FIELD", "name": "name", "content": {symbol}}}"#);
+        let checkpoints = Checkpoints::in_memory();
+        let dir = tempfile::tempdir().unwrap();
+        let dir_checkpoints = Checkpoints::in_directory(dir.path()).unwrap();
+        for identifier in [symbol, &field, symbol] {
+            let expected = generate(identifier, None);
+            assert_eq!(generate(identifier, Some(&checkpoints)), expected);
+            assert_eq!(generate(identifier, Some(&dir_checkpoints)), expected);
+        }
+    }
+
+    #[test]
+    fn test_generation_types_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<InputGrammar>();
+        assert_send_sync::<SyntaxGrammar>();
+        assert_send_sync::<LexicalGrammar>();
+        assert_send_sync::<grammars::InlinedProductionMap>();
+        assert_send_sync::<GenerateOptions>();
+        assert_send_sync::<GeneratedOutput>();
+        assert_send_sync::<ParseTables>();
+        assert_send_sync::<GrammarError>();
+    }
+
+    #[test]
+    fn test_generate_parsers_concurrently() {
+        let grammars = ["+", "-", "*", "/"].map(|operator| {
+            format!(
+                r#"{{
+                    "name": "concurrent",
+                    "inline": ["_operand"],
+                    "rules": {{
+                        "program": {{"type": "REPEAT", "content": {{"type": "SYMBOL", "name": "operation"}}}},
+                        "operation": {{"type": "SEQ", "members": [
+                            {{"type": "SYMBOL", "name": "_operand"}},
+                            {{"type": "STRING", "value": "{operator}"}},
+                            {{"type": "SYMBOL", "name": "_operand"}}
+                        ]}},
+                        "_operand": {{"type": "CHOICE", "members": [
+                            {{"type": "SYMBOL", "name": "number"}},
+                            {{"type": "SYMBOL", "name": "identifier"}}
+                        ]}},
+                        "number": {{"type": "PATTERN", "value": "\\d+"}},
+                        "identifier": {{"type": "PATTERN", "value": "[a-z]+"}}
+                    }}
+                }}"#
+            )
+        });
+        let options = GenerateOptions::default();
+        let sequential = grammars
+            .iter()
+            .map(|grammar_json| generate_parser(grammar_json, &options).unwrap().parser_c)
+            .collect::<Vec<_>>();
+        let concurrent = thread::scope(|scope| {
+            let handles = grammars
+                .iter()
+                .map(|grammar_json| {
+                    scope.spawn(|| generate_parser(grammar_json, &options).unwrap().parser_c)
+                })
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(concurrent, sequential);
+    }
+}
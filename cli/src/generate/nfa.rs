@@ -1,20 +1,44 @@
 use std::{
     char,
     cmp::{max, Ordering},
+    collections::HashSet,
     fmt,
+    hash::{Hash, Hasher},
     iter::ExactSizeIterator,
     mem::{self, swap},
     ops::{Range, RangeInclusive},
+    sync::Arc,
 };
 
+use serde::{Deserialize, Serialize};
+use smallvec::{smallvec, SmallVec};
+
 /// A set of characters represented as a vector of ranges.
-#[derive(Clone, Default, PartialEq, Eq, Hash)]
+///
+/// The ranges are shared between clones of a set, and are only copied when one of the clones
+/// is modified. Sets that are stored many times can be deduplicated with a
+/// [`CharacterSetInterner`], after which equal sets can be compared by address.
+#[derive(Clone, Default, Eq, Serialize, Deserialize)]
+#[serde(from = "CharacterRanges", into = "CharacterRanges")]
 pub struct CharacterSet {
+    ranges: Arc<Vec<Range<u32>>>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename = "CharacterSet")]
+struct CharacterRanges {
     ranges: Vec<Range<u32>>,
 }
 
+/// Keeps a single copy of each distinct [`CharacterSet`], so that sets which are stored many
+/// times, like the large Unicode classes that are used by several tokens, share their ranges.
+#[derive(Default)]
+pub struct CharacterSetInterner {
+    sets: HashSet<CharacterSet>,
+}
+
 /// A state in an NFA representing a regular grammar.
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum NfaState {
     Advance {
         chars: CharacterSet,
@@ -29,31 +53,43 @@ pub enum NfaState {
     },
 }
 
-#[derive(PartialEq, Eq, Default)]
+/// The ids of a set of [`Nfa`] states, in ascending order. Most of the sets that are explored
+/// while analyzing and lexing tokens hold only a few states, so they are stored inline instead
+/// of being allocated separately.
+pub type NfaStateSet = SmallVec<[u32; 4]>;
+
+/// A nondeterministic finite automaton that recognizes the tokens of a lexical grammar.
+/// Each token's states end in an `Accept` state, and the token starts at the
+/// `start_state` of its [`LexicalVariable`](super::LexicalVariable). The states are all
+/// allocated together in `states`, and refer to each other by their index in it.
+#[derive(Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct Nfa {
     pub states: Vec<NfaState>,
 }
 
+/// A set of current states in an [`Nfa`], for stepping through it one character set at a
+/// time. `Split` states are followed when states are added, so the set never contains them.
 #[derive(Debug)]
 pub struct NfaCursor<'a> {
-    pub(crate) state_ids: Vec<u32>,
+    pub(crate) state_ids: NfaStateSet,
     nfa: &'a Nfa,
 }
 
+/// The states that an [`NfaCursor`] moves to on a set of characters.
 #[derive(Debug, PartialEq, Eq)]
 pub struct NfaTransition {
     pub characters: CharacterSet,
     pub is_separator: bool,
     pub precedence: i32,
-    pub states: Vec<u32>,
+    pub states: NfaStateSet,
 }
 
 const END: u32 = char::MAX as u32 + 1;
 
 impl CharacterSet {
     /// Create a character set with a single character.
-    pub const fn empty() -> Self {
-        Self { ranges: Vec::new() }
+    pub fn empty() -> Self {
+        Self::default()
     }
 
     /// Create a character set with a given *inclusive* range of characters.
@@ -63,7 +99,7 @@ impl CharacterSet {
             swap(&mut first, &mut last);
         }
         Self {
-            ranges: vec![(first as u32)..(last as u32 + 1)],
+            ranges: Arc::new(vec![(first as u32)..(last as u32 + 1)]),
         }
     }
 
@@ -71,28 +107,29 @@ impl CharacterSet {
     #[allow(clippy::single_range_in_vec_init)]
     pub fn from_char(c: char) -> Self {
         Self {
-            ranges: vec![(c as u32)..(c as u32 + 1)],
+            ranges: Arc::new(vec![(c as u32)..(c as u32 + 1)]),
         }
     }
 
     /// Create a character set containing all characters *not* present
     /// in this character set.
     pub fn negate(mut self) -> Self {
+        let ranges = Arc::make_mut(&mut self.ranges);
         let mut i = 0;
         let mut previous_end = 0;
-        while i < self.ranges.len() {
-            let range = &mut self.ranges[i];
+        while i < ranges.len() {
+            let range = &mut ranges[i];
             let start = previous_end;
             previous_end = range.end;
             if start < range.start {
-                self.ranges[i] = start..range.start;
+                ranges[i] = start..range.start;
                 i += 1;
             } else {
-                self.ranges.remove(i);
+                ranges.remove(i);
             }
         }
         if previous_end < END {
-            self.ranges.push(previous_end..END);
+            ranges.push(previous_end..END);
         }
         self
     }
@@ -107,24 +144,25 @@ impl CharacterSet {
         self
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn add(mut self, other: &Self) -> Self {
         let mut index = 0;
-        for range in &other.ranges {
+        for range in other.ranges.iter() {
             index = self.add_int_range(index, range.start, range.end);
         }
         self
     }
 
     pub fn assign(&mut self, other: &Self) {
-        self.ranges.clear();
-        self.ranges.extend_from_slice(&other.ranges);
+        self.ranges = other.ranges.clone();
     }
 
     fn add_int_range(&mut self, mut i: usize, start: u32, end: u32) -> usize {
-        while i < self.ranges.len() {
-            let range = &mut self.ranges[i];
+        let ranges = Arc::make_mut(&mut self.ranges);
+        while i < ranges.len() {
+            let range = &mut ranges[i];
             if range.start > end {
-                self.ranges.insert(i, start..end);
+                ranges.insert(i, start..end);
                 return i;
             }
             if range.end >= start {
@@ -132,16 +170,16 @@ impl CharacterSet {
                 range.start = range.start.min(start);
 
                 // Join this range with the next range if needed.
-                while i + 1 < self.ranges.len() && self.ranges[i + 1].start <= self.ranges[i].end {
-                    self.ranges[i].end = self.ranges[i].end.max(self.ranges[i + 1].end);
-                    self.ranges.remove(i + 1);
+                while i + 1 < ranges.len() && ranges[i + 1].start <= ranges[i].end {
+                    ranges[i].end = ranges[i].end.max(ranges[i + 1].end);
+                    ranges.remove(i + 1);
                 }
 
                 return i;
             }
             i += 1;
         }
-        self.ranges.push(start..end);
+        ranges.push(start..end);
         i
     }
 
@@ -166,12 +204,19 @@ impl CharacterSet {
     /// and the other set. Remove those common characters from both
     /// of the operands.
     pub fn remove_intersection(&mut self, other: &mut Self) -> Self {
+        // Leave shared sets untouched when there's nothing to remove from them.
+        if !self.does_intersect(other) {
+            return Self::empty();
+        }
+
+        let left_ranges = Arc::make_mut(&mut self.ranges);
+        let right_ranges = Arc::make_mut(&mut other.ranges);
         let mut intersection = Vec::new();
         let mut left_i = 0;
         let mut right_i = 0;
-        while left_i < self.ranges.len() && right_i < other.ranges.len() {
-            let left = &mut self.ranges[left_i];
-            let right = &mut other.ranges[right_i];
+        while left_i < left_ranges.len() && right_i < right_ranges.len() {
+            let left = &mut left_ranges[left_i];
+            let right = &mut right_ranges[right_i];
 
             match left.start.cmp(&right.start) {
                 Ordering::Less => {
@@ -196,7 +241,7 @@ impl CharacterSet {
                         Ordering::Equal => {
                             intersection.push(right.clone());
                             left.end = right.start;
-                            other.ranges.remove(right_i);
+                            right_ranges.remove(right_i);
                         }
 
                         // [   L   ]
@@ -205,8 +250,8 @@ impl CharacterSet {
                             intersection.push(right.clone());
                             let new_range = left.start..right.start;
                             left.start = right.end;
-                            self.ranges.insert(left_i, new_range);
-                            other.ranges.remove(right_i);
+                            left_ranges.insert(left_i, new_range);
+                            right_ranges.remove(right_i);
                             left_i += 1;
                         }
                     }
@@ -216,21 +261,21 @@ impl CharacterSet {
                 Ordering::Equal if left.end < right.end => {
                     intersection.push(left.start..left.end);
                     right.start = left.end;
-                    self.ranges.remove(left_i);
+                    left_ranges.remove(left_i);
                 }
                 // [ L ]
                 // [ R ]
                 Ordering::Equal if left.end == right.end => {
                     intersection.push(left.clone());
-                    self.ranges.remove(left_i);
-                    other.ranges.remove(right_i);
+                    left_ranges.remove(left_i);
+                    right_ranges.remove(right_i);
                 }
                 // [  L  ]
                 // [ R ]
                 Ordering::Equal if left.end > right.end => {
                     intersection.push(right.clone());
                     left.start = right.end;
-                    other.ranges.remove(right_i);
+                    right_ranges.remove(right_i);
                 }
                 Ordering::Equal => {}
                 Ordering::Greater => {
@@ -248,8 +293,8 @@ impl CharacterSet {
                             intersection.push(left.clone());
                             let new_range = right.start..left.start;
                             right.start = left.end;
-                            other.ranges.insert(right_i, new_range);
-                            self.ranges.remove(left_i);
+                            right_ranges.insert(right_i, new_range);
+                            left_ranges.remove(left_i);
                             right_i += 1;
                         }
 
@@ -258,7 +303,7 @@ impl CharacterSet {
                         Ordering::Equal => {
                             intersection.push(left.clone());
                             right.end = left.start;
-                            self.ranges.remove(left_i);
+                            left_ranges.remove(left_i);
                         }
 
                         //   [   L   ]
@@ -273,7 +318,7 @@ impl CharacterSet {
             }
         }
         Self {
-            ranges: intersection,
+            ranges: Arc::new(intersection),
         }
     }
 
@@ -320,32 +365,33 @@ impl CharacterSet {
     pub fn simplify_ignoring(&self, ruled_out_characters: &Self) -> Self {
         let mut prev_range: Option<Range<u32>> = None;
         Self {
-            ranges: self
-                .ranges
-                .iter()
-                .map(|range| Some(range.clone()))
-                .chain([None])
-                .filter_map(move |range| {
-                    if let Some(range) = &range {
-                        if ruled_out_characters.contains_codepoint_range(range.clone()) {
-                            return None;
-                        }
-
-                        if let Some(prev_range) = &mut prev_range {
-                            if ruled_out_characters
-                                .contains_codepoint_range(prev_range.end..range.start)
-                            {
-                                prev_range.end = range.end;
+            ranges: Arc::new(
+                self.ranges
+                    .iter()
+                    .map(|range| Some(range.clone()))
+                    .chain([None])
+                    .filter_map(move |range| {
+                        if let Some(range) = &range {
+                            if ruled_out_characters.contains_codepoint_range(range.clone()) {
                                 return None;
                             }
+
+                            if let Some(prev_range) = &mut prev_range {
+                                if ruled_out_characters
+                                    .contains_codepoint_range(prev_range.end..range.start)
+                                {
+                                    prev_range.end = range.end;
+                                    return None;
+                                }
+                            }
                         }
-                    }
 
-                    let result = prev_range.clone();
-                    prev_range = range;
-                    result
-                })
-                .collect(),
+                        let result = prev_range.clone();
+                        prev_range = range;
+                        result
+                    })
+                    .collect(),
+            ),
         }
     }
 
@@ -371,6 +417,46 @@ impl CharacterSet {
     }
 }
 
+impl CharacterSetInterner {
+    /// Get the stored copy of the given set, storing it if it hasn't been seen before.
+    pub fn intern(&mut self, set: CharacterSet) -> CharacterSet {
+        if let Some(existing) = self.sets.get(&set) {
+            return existing.clone();
+        }
+        self.sets.insert(set.clone());
+        set
+    }
+}
+
+// Interned sets share their ranges, so they can be compared without looking at the ranges.
+impl PartialEq for CharacterSet {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.ranges, &other.ranges) || self.ranges == other.ranges
+    }
+}
+
+impl Hash for CharacterSet {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.ranges.hash(state);
+    }
+}
+
+impl From<CharacterRanges> for CharacterSet {
+    fn from(set: CharacterRanges) -> Self {
+        Self {
+            ranges: Arc::new(set.ranges),
+        }
+    }
+}
+
+impl From<CharacterSet> for CharacterRanges {
+    fn from(set: CharacterSet) -> Self {
+        Self {
+            ranges: Arc::try_unwrap(set.ranges).unwrap_or_else(|ranges| ranges.as_ref().clone()),
+        }
+    }
+}
+
 impl Ord for CharacterSet {
     fn cmp(&self, other: &Self) -> Ordering {
         let count_cmp = self
@@ -447,24 +533,29 @@ impl fmt::Debug for Nfa {
 }
 
 impl<'a> NfaCursor<'a> {
-    pub fn new(nfa: &'a Nfa, mut states: Vec<u32>) -> Self {
+    pub fn new(nfa: &'a Nfa, mut states: NfaStateSet) -> Self {
         let mut result = Self {
             nfa,
-            state_ids: Vec::new(),
+            state_ids: NfaStateSet::new(),
         };
         result.add_states(&mut states);
         result
     }
 
-    pub fn reset(&mut self, mut states: Vec<u32>) {
+    pub fn reset(&mut self, mut states: NfaStateSet) {
         self.state_ids.clear();
         self.add_states(&mut states);
     }
 
-    pub fn force_reset(&mut self, states: Vec<u32>) {
+    pub fn force_reset(&mut self, states: NfaStateSet) {
         self.state_ids = states;
     }
 
+    /// The ids of the current states, in ascending order.
+    pub fn state_ids(&self) -> &[u32] {
+        &self.state_ids
+    }
+
     pub fn transition_chars(&self) -> impl Iterator<Item = (&CharacterSet, bool)> {
         self.raw_transitions().map(|t| (t.0, t.1))
     }
@@ -522,7 +613,7 @@ impl<'a> NfaCursor<'a> {
                 result.push(NfaTransition {
                     characters: chars,
                     precedence: prec,
-                    states: vec![state],
+                    states: smallvec![state],
                     is_separator: is_sep,
                 });
             }
@@ -563,7 +654,7 @@ impl<'a> NfaCursor<'a> {
         })
     }
 
-    pub fn add_states(&mut self, new_state_ids: &mut Vec<u32>) {
+    pub fn add_states(&mut self, new_state_ids: &mut NfaStateSet) {
         let mut i = 0;
         while i < new_state_ids.len() {
             let state_id = new_state_ids[i];
@@ -670,19 +761,19 @@ mod tests {
                         characters: CharacterSet::empty().add_range('a', 'c'),
                         is_separator: false,
                         precedence: 0,
-                        states: vec![1],
+                        states: smallvec![1],
                     },
                     NfaTransition {
                         characters: CharacterSet::empty().add_range('d', 'f'),
                         is_separator: false,
                         precedence: 1,
-                        states: vec![1, 2],
+                        states: smallvec![1, 2],
                     },
                     NfaTransition {
                         characters: CharacterSet::empty().add_range('g', 'i'),
                         is_separator: false,
                         precedence: 1,
-                        states: vec![2],
+                        states: smallvec![2],
                     },
                 ],
             ),
@@ -699,19 +790,19 @@ mod tests {
                         characters: CharacterSet::empty().add_char('d'),
                         is_separator: false,
                         precedence: 0,
-                        states: vec![1, 2],
+                        states: smallvec![1, 2],
                     },
                     NfaTransition {
                         characters: CharacterSet::empty().add_char('f'),
                         is_separator: false,
                         precedence: 0,
-                        states: vec![1, 4],
+                        states: smallvec![1, 4],
                     },
                     NfaTransition {
                         characters: CharacterSet::empty().add_char('i'),
                         is_separator: false,
                         precedence: 0,
-                        states: vec![1, 3],
+                        states: smallvec![1, 3],
                     },
                     NfaTransition {
                         characters: CharacterSet::empty()
@@ -721,7 +812,7 @@ mod tests {
                             .add_range('j', 'z'),
                         is_separator: false,
                         precedence: 0,
-                        states: vec![1],
+                        states: smallvec![1],
                     },
                 ],
             ),
@@ -742,19 +833,19 @@ mod tests {
                     NfaTransition {
                         characters: CharacterSet::empty().add_char('0'),
                         precedence: 0,
-                        states: vec![1, 3],
+                        states: smallvec![1, 3],
                         is_separator: false,
                     },
                     NfaTransition {
                         characters: CharacterSet::empty().add_char('b'),
                         precedence: 0,
-                        states: vec![2],
+                        states: smallvec![2],
                         is_separator: false,
                     },
                     NfaTransition {
                         characters: CharacterSet::empty().add_char('c'),
                         precedence: 0,
-                        states: vec![4],
+                        states: smallvec![4],
                         is_separator: false,
                     },
                     NfaTransition {
@@ -763,7 +854,7 @@ mod tests {
                             .add_char('0')
                             .negate(),
                         precedence: 0,
-                        states: vec![3],
+                        states: smallvec![3],
                         is_separator: false,
                     },
                 ],
@@ -782,37 +873,37 @@ mod tests {
                     NfaTransition {
                         characters: CharacterSet::from_char('a'),
                         precedence: 0,
-                        states: vec![1, 3, 4],
+                        states: smallvec![1, 3, 4],
                         is_separator: false,
                     },
                     NfaTransition {
                         characters: CharacterSet::from_char('g'),
                         precedence: 0,
-                        states: vec![2, 3, 5, 6],
+                        states: smallvec![2, 3, 5, 6],
                         is_separator: false,
                     },
                     NfaTransition {
                         characters: CharacterSet::from_range('b', 'c'),
                         precedence: 0,
-                        states: vec![3, 4],
+                        states: smallvec![3, 4],
                         is_separator: false,
                     },
                     NfaTransition {
                         characters: CharacterSet::from_range('h', 'i'),
                         precedence: 0,
-                        states: vec![2, 3],
+                        states: smallvec![2, 3],
                         is_separator: false,
                     },
                     NfaTransition {
                         characters: CharacterSet::from_range('d', 'f'),
                         precedence: 0,
-                        states: vec![2, 4],
+                        states: smallvec![2, 4],
                         is_separator: false,
                     },
                     NfaTransition {
                         characters: CharacterSet::from_range('a', 'i').negate(),
                         precedence: 0,
-                        states: vec![2, 3, 4],
+                        states: smallvec![2, 3, 4],
                         is_separator: false,
                     },
                 ],
@@ -830,13 +921,13 @@ mod tests {
                     NfaTransition {
                         characters: CharacterSet::empty().add_char('b').add_char('e'),
                         precedence: 0,
-                        states: vec![2],
+                        states: smallvec![2],
                         is_separator: false,
                     },
                     NfaTransition {
                         characters: CharacterSet::empty().add_char('a').add_range('c', 'd'),
                         precedence: 0,
-                        states: vec![1],
+                        states: smallvec![1],
                         is_separator: false,
                     },
                 ],
@@ -1059,6 +1150,36 @@ mod tests {
         assert!(b.does_intersect(&a));
     }
 
+    #[test]
+    fn test_character_set_interning() {
+        let mut interner = CharacterSetInterner::default();
+        let letters = interner.intern(CharacterSet::from_range('a', 'z'));
+        let same_letters = interner.intern(CharacterSet::empty().add_range('a', 'z'));
+        let digits = interner.intern(CharacterSet::from_range('0', '9'));
+        assert!(Arc::ptr_eq(&letters.ranges, &same_letters.ranges));
+        assert!(!Arc::ptr_eq(&letters.ranges, &digits.ranges));
+
+        // Changing a copy of an interned set leaves the interned set as it was.
+        let letters_and_digits = same_letters.add(&digits);
+        assert!(letters_and_digits.contains('5'));
+        assert!(!letters.contains('5'));
+
+        // Removing the characters of a disjoint set doesn't copy either set.
+        let mut same_digits = digits.clone();
+        assert!(letters
+            .clone()
+            .remove_intersection(&mut same_digits)
+            .is_empty());
+        assert!(Arc::ptr_eq(&digits.ranges, &same_digits.ranges));
+
+        let json = serde_json::to_string(&letters).unwrap();
+        assert_eq!(json, r#"{"ranges":[{"start":97,"end":123}]}"#);
+        assert_eq!(
+            serde_json::from_str::<CharacterSet>(&json).unwrap(),
+            letters
+        );
+    }
+
     #[test]
     #[allow(clippy::single_range_in_vec_init)]
     fn test_character_set_simplify_ignoring() {
@@ -4,7 +4,7 @@ use std::{
 };
 
 use anyhow::{anyhow, Result};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::{
     grammars::{LexicalGrammar, SyntaxGrammar, VariableType},
@@ -31,31 +31,35 @@ pub struct VariableInfo {
     pub has_multi_step_production: bool,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq, Default, PartialOrd, Ord)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Default, PartialOrd, Ord)]
 pub struct NodeInfoJSON {
     #[serde(rename = "type")]
-    kind: String,
-    named: bool,
+    pub(super) kind: String,
+    pub(super) named: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(super) deprecated: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    fields: Option<BTreeMap<String, FieldInfoJSON>>,
+    pub(super) fields: Option<BTreeMap<String, FieldInfoJSON>>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    children: Option<FieldInfoJSON>,
+    pub(super) children: Option<FieldInfoJSON>,
     #[serde(skip_serializing_if = "Option::is_none")]
-    subtypes: Option<Vec<NodeTypeJSON>>,
+    pub(super) subtypes: Option<Vec<NodeTypeJSON>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) supertypes: Option<Vec<NodeTypeJSON>>,
 }
 
-#[derive(Clone, Debug, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct NodeTypeJSON {
     #[serde(rename = "type")]
-    kind: String,
-    named: bool,
+    pub(super) kind: String,
+    pub(super) named: bool,
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
 pub struct FieldInfoJSON {
-    multiple: bool,
-    required: bool,
-    types: Vec<NodeTypeJSON>,
+    pub(super) multiple: bool,
+    pub(super) required: bool,
+    pub(super) types: Vec<NodeTypeJSON>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -475,9 +479,11 @@ pub fn generate_node_types_json(
                     .or_insert_with(|| NodeInfoJSON {
                         kind: variable.name.clone(),
                         named: true,
+                        deprecated: false,
                         fields: None,
                         children: None,
                         subtypes: None,
+                        supertypes: None,
                     });
             let mut subtypes = info
                 .children
@@ -520,9 +526,11 @@ pub fn generate_node_types_json(
                     NodeInfoJSON {
                         kind: kind.clone(),
                         named: is_named,
+                        deprecated: false,
                         fields: Some(BTreeMap::new()),
                         children: None,
                         subtypes: None,
+                        supertypes: None,
                     }
                 });
 
@@ -634,9 +642,11 @@ pub fn generate_node_types_json(
                         .or_insert_with(|| NodeInfoJSON {
                             kind: name.clone(),
                             named: true,
+                            deprecated: false,
                             fields: None,
                             children: None,
                             subtypes: None,
+                            supertypes: None,
                         });
                 if let Some(children) = &mut node_type_json.children {
                     children.required = false;
@@ -650,9 +660,11 @@ pub fn generate_node_types_json(
             VariableType::Anonymous => anonymous_node_types.push(NodeInfoJSON {
                 kind: name.clone(),
                 named: false,
+                deprecated: false,
                 fields: None,
                 children: None,
                 subtypes: None,
+                supertypes: None,
             }),
             _ => {}
         }
@@ -660,6 +672,44 @@ pub fn generate_node_types_json(
 
     let mut result = node_types_json.into_iter().map(|e| e.1).collect::<Vec<_>>();
     result.extend(anonymous_node_types);
+
+    for symbol in &syntax_grammar.deprecated_symbols {
+        let name = match symbol.kind {
+            SymbolType::NonTerminal => &syntax_grammar.variables[symbol.index].name,
+            SymbolType::Terminal => &lexical_grammar.variables[symbol.index].name,
+            SymbolType::External => &syntax_grammar.external_tokens[symbol.index].name,
+            _ => continue,
+        };
+        let kind = variable_type_for_child_type(
+            &ChildType::Normal(*symbol),
+            syntax_grammar,
+            lexical_grammar,
+        );
+        for node_type_json in &mut result {
+            if node_type_json.kind == *name && node_type_json.named == (kind == VariableType::Named)
+            {
+                node_type_json.deprecated = true;
+            }
+        }
+    }
+
+    // List the supertypes that each node type is a direct subtype of.
+    for node_type_json in &mut result {
+        let node_type = NodeTypeJSON {
+            kind: node_type_json.kind.clone(),
+            named: node_type_json.named,
+        };
+        let mut supertypes = subtype_map
+            .iter()
+            .filter(|(_, subtypes)| subtypes.contains(&node_type))
+            .map(|(supertype, _)| supertype.clone())
+            .collect::<Vec<_>>();
+        if !supertypes.is_empty() {
+            supertypes.sort_unstable();
+            node_type_json.supertypes = Some(supertypes);
+        }
+    }
+
     result.sort_unstable_by(|a, b| {
         b.subtypes
             .is_some()
@@ -675,6 +725,96 @@ pub fn generate_node_types_json(
     result
 }
 
+/// Describe the differences between two versions of a grammar's node types that
+/// can break code written against the old version: node types, fields and subtypes
+/// that were removed, and fields or children that became optional or repeated.
+pub fn get_breaking_node_type_changes(old: &[NodeInfoJSON], new: &[NodeInfoJSON]) -> Vec<String> {
+    let mut result = Vec::new();
+    for old_node in old {
+        let description = if old_node.named {
+            format!("node `{}`", old_node.kind)
+        } else {
+            format!("anonymous node {:?}", old_node.kind)
+        };
+
+        let Some(new_node) = new
+            .iter()
+            .find(|n| n.kind == old_node.kind && n.named == old_node.named)
+        else {
+            result.push(format!(
+                "{description} was removed{}",
+                if old_node.deprecated {
+                    " (it was deprecated)"
+                } else {
+                    ""
+                }
+            ));
+            continue;
+        };
+
+        for (name, old_field) in old_node.fields.iter().flatten() {
+            let context = format!("field `{name}` of {description}");
+            match new_node.fields.as_ref().and_then(|fields| fields.get(name)) {
+                Some(new_field) => {
+                    check_quantity_change(&context, old_field, new_field, &mut result);
+                }
+                None => result.push(format!("{context} was removed")),
+            }
+        }
+
+        match (&old_node.children, &new_node.children) {
+            (Some(old_children), Some(new_children)) => check_quantity_change(
+                &format!("children of {description}"),
+                old_children,
+                new_children,
+                &mut result,
+            ),
+            (Some(_), None) => result.push(format!("children of {description} were removed")),
+            _ => {}
+        }
+
+        for subtype in old_node.subtypes.iter().flatten() {
+            if !new_node
+                .subtypes
+                .as_ref()
+                .is_some_and(|subtypes| subtypes.contains(subtype))
+            {
+                result.push(format!(
+                    "{} `{}` is no longer a subtype of {description}",
+                    if subtype.named {
+                        "node"
+                    } else {
+                        "anonymous node"
+                    },
+                    subtype.kind
+                ));
+            }
+        }
+    }
+    result
+}
+
+fn check_quantity_change(
+    context: &str,
+    old: &FieldInfoJSON,
+    new: &FieldInfoJSON,
+    result: &mut Vec<String>,
+) {
+    if (old.required && !new.required) || (!old.multiple && new.multiple) {
+        let describe = |info: &FieldInfoJSON| match (info.multiple, info.required) {
+            (false, true) => "exactly one node",
+            (false, false) => "an optional node",
+            (true, true) => "one or more nodes",
+            (true, false) => "zero or more nodes",
+        };
+        result.push(format!(
+            "{context} changed from {} to {}",
+            describe(old),
+            describe(new)
+        ));
+    }
+}
+
 fn process_supertypes(info: &mut FieldInfoJSON, subtype_map: &[(NodeTypeJSON, Vec<NodeTypeJSON>)]) {
     for (supertype, subtypes) in subtype_map {
         if info.types.contains(supertype) {
@@ -730,6 +870,7 @@ mod tests {
         },
         prepare_grammar::prepare_grammar,
         rules::Rule,
+        timings::Timings,
     };
 
     #[test]
@@ -767,7 +908,9 @@ mod tests {
             NodeInfoJSON {
                 kind: "v1".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: None,
                 fields: Some(
                     vec![
@@ -804,7 +947,9 @@ mod tests {
             NodeInfoJSON {
                 kind: ";".to_string(),
                 named: false,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: None,
                 fields: None
             }
@@ -814,7 +959,9 @@ mod tests {
             NodeInfoJSON {
                 kind: "v2".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: None,
                 fields: None
             }
@@ -858,7 +1005,9 @@ mod tests {
             NodeInfoJSON {
                 kind: "v1".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: None,
                 fields: Some(
                     vec![
@@ -895,7 +1044,9 @@ mod tests {
             NodeInfoJSON {
                 kind: ";".to_string(),
                 named: false,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: None,
                 fields: None
             }
@@ -905,7 +1056,9 @@ mod tests {
             NodeInfoJSON {
                 kind: "v2".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: None,
                 fields: None
             }
@@ -915,13 +1068,99 @@ mod tests {
             NodeInfoJSON {
                 kind: "v3".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: None,
                 fields: None
             }
         );
     }
 
+    #[test]
+    fn test_node_types_with_deprecated_symbols() {
+        let node_types = get_node_types(&InputGrammar {
+            deprecated_symbols: vec!["v2".to_string(), "v3".to_string()],
+            variables: vec![
+                Variable {
+                    name: "v1".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::named("v2"), Rule::named("v3")]),
+                },
+                Variable {
+                    name: "v2".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::seq(vec![Rule::string("("), Rule::string(")")]),
+                },
+                Variable {
+                    name: "v3".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::string("x"),
+                },
+            ],
+            ..Default::default()
+        });
+
+        assert_eq!(
+            node_types
+                .iter()
+                .filter(|node_type| node_type.deprecated)
+                .map(|node_type| node_type.kind.as_str())
+                .collect::<Vec<_>>(),
+            ["v2", "v3"]
+        );
+    }
+
+    #[test]
+    fn test_breaking_node_type_changes() {
+        let old = serde_json::from_str::<Vec<NodeInfoJSON>>(
+            r#"[
+                {
+                    "type": "call",
+                    "named": true,
+                    "fields": {
+                        "function": {"multiple": false, "required": true, "types": [{"type": "identifier", "named": true}]},
+                        "arguments": {"multiple": false, "required": true, "types": [{"type": "arguments", "named": true}]}
+                    },
+                    "children": {"multiple": false, "required": false, "types": [{"type": "comment", "named": true}]}
+                },
+                {"type": "identifier", "named": true},
+                {"type": "legacy", "named": true, "deprecated": true},
+                {"type": "(", "named": false}
+            ]"#,
+        )
+        .unwrap();
+        let new = serde_json::from_str::<Vec<NodeInfoJSON>>(
+            r#"[
+                {
+                    "type": "call",
+                    "named": true,
+                    "fields": {
+                        "function": {"multiple": true, "required": false, "types": [{"type": "identifier", "named": true}]}
+                    },
+                    "children": {"multiple": false, "required": true, "types": [{"type": "comment", "named": true}]}
+                },
+                {"type": "identifier", "named": true},
+                {"type": "new_node", "named": true}
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_breaking_node_type_changes(&old, &old),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            get_breaking_node_type_changes(&old, &new),
+            [
+                "field `arguments` of node `call` was removed",
+                "field `function` of node `call` changed from exactly one node to zero or more nodes",
+                "node `legacy` was removed (it was deprecated)",
+                "anonymous node \"(\" was removed",
+            ]
+        );
+    }
+
     #[test]
     fn test_node_types_with_supertypes() {
         let node_types = get_node_types(&InputGrammar {
@@ -960,6 +1199,7 @@ mod tests {
             NodeInfoJSON {
                 kind: "_v2".to_string(),
                 named: true,
+                deprecated: false,
                 fields: None,
                 children: None,
                 subtypes: Some(vec![
@@ -976,6 +1216,7 @@ mod tests {
                         named: true,
                     },
                 ]),
+                supertypes: None,
             }
         );
         assert_eq!(
@@ -983,7 +1224,9 @@ mod tests {
             NodeInfoJSON {
                 kind: "v1".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: None,
                 fields: Some(
                     vec![(
@@ -1002,6 +1245,29 @@ mod tests {
                 )
             }
         );
+        assert_eq!(
+            node_types[3],
+            NodeInfoJSON {
+                kind: "v3".to_string(),
+                named: true,
+                deprecated: false,
+                subtypes: None,
+                supertypes: Some(vec![NodeTypeJSON {
+                    kind: "_v2".to_string(),
+                    named: true,
+                }]),
+                children: None,
+                fields: None,
+            }
+        );
+        assert_eq!(node_types[2].kind, "*");
+        assert_eq!(
+            node_types[2].supertypes,
+            Some(vec![NodeTypeJSON {
+                kind: "_v2".to_string(),
+                named: true,
+            }])
+        );
     }
 
     #[test]
@@ -1045,7 +1311,9 @@ mod tests {
             NodeInfoJSON {
                 kind: "v1".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: Some(FieldInfoJSON {
                     multiple: true,
                     required: true,
@@ -1082,7 +1350,9 @@ mod tests {
             NodeInfoJSON {
                 kind: "v2".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: Some(FieldInfoJSON {
                     multiple: false,
                     required: false,
@@ -1126,7 +1396,9 @@ mod tests {
             NodeInfoJSON {
                 kind: "v1".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: Some(FieldInfoJSON {
                     multiple: true,
                     required: true,
@@ -1199,7 +1471,9 @@ mod tests {
             Some(&NodeInfoJSON {
                 kind: "identifier".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: None,
                 fields: None,
             })
@@ -1209,7 +1483,9 @@ mod tests {
             Some(&NodeInfoJSON {
                 kind: "type_identifier".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: None,
                 fields: None,
             })
@@ -1250,7 +1526,9 @@ mod tests {
             NodeInfoJSON {
                 kind: "a".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: Some(FieldInfoJSON {
                     multiple: true,
                     required: true,
@@ -1297,9 +1575,11 @@ mod tests {
             [NodeInfoJSON {
                 kind: "script".to_string(),
                 named: true,
+                deprecated: false,
                 fields: Some(BTreeMap::new()),
                 children: None,
-                subtypes: None
+                subtypes: None,
+                supertypes: None,
             }]
         );
     }
@@ -1353,7 +1633,9 @@ mod tests {
                 NodeInfoJSON {
                     kind: "a".to_string(),
                     named: true,
+                    deprecated: false,
                     subtypes: None,
+                    supertypes: None,
                     children: None,
                     fields: Some(
                         vec![
@@ -1408,7 +1690,9 @@ mod tests {
                 NodeInfoJSON {
                     kind: "script".to_string(),
                     named: true,
+                    deprecated: false,
                     subtypes: None,
+                    supertypes: None,
                     // Only one node
                     children: Some(FieldInfoJSON {
                         multiple: false,
@@ -1462,7 +1746,9 @@ mod tests {
             NodeInfoJSON {
                 kind: "b".to_string(),
                 named: true,
+                deprecated: false,
                 subtypes: None,
+                supertypes: None,
                 children: Some(FieldInfoJSON {
                     multiple: true,
                     required: false,
@@ -1778,7 +2064,7 @@ mod tests {
 
     fn get_node_types(grammar: &InputGrammar) -> Vec<NodeInfoJSON> {
         let (syntax_grammar, lexical_grammar, _, default_aliases) =
-            prepare_grammar(grammar).unwrap();
+            prepare_grammar(grammar, None, &HashMap::new(), &mut Timings::default()).unwrap();
         let variable_info =
             get_variable_info(&syntax_grammar, &lexical_grammar, &default_aliases).unwrap();
         generate_node_types_json(
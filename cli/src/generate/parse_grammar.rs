@@ -1,118 +1,252 @@
+use std::{borrow::Cow, collections::HashMap};
+
 use anyhow::{anyhow, Result};
+use indexmap::IndexMap;
+use log::warn;
 use serde::Deserialize;
 use serde_json::{Map, Value};
 
 use super::{
-    grammars::{InputGrammar, PrecedenceEntry, Variable, VariableType},
+    grammars::{
+        Injection, InjectionLanguage, InputGrammar, PrecedenceEntry, Variable, VariableType,
+    },
     rules::{Precedence, Rule},
 };
 
+/// The version of the grammar JSON format that this version of Tree-sitter reads and
+/// writes. Grammars without a `schema_version` property are from before the format was
+/// versioned, and are treated as version 0.
+pub const GRAMMAR_SCHEMA_VERSION: u64 = 1;
+
+type SchemaUpgrade = fn(&mut Map<String, Value>);
+
+/// The upgrades from each version of the grammar format to the next, indexed by the
+/// version that they upgrade from. When the format changes in a way that older grammars
+/// don't follow, the version is bumped and an upgrade is added here, so that grammars
+/// generated by older versions of the CLI keep working. Versions that older grammars
+/// already follow have no upgrade.
+const SCHEMA_UPGRADES: [Option<SchemaUpgrade>; GRAMMAR_SCHEMA_VERSION as usize] = [
+    // Version 1 only introduced the `schema_version` property.
+    None,
+];
+
+/// A string in a grammar document. It borrows from the document, unless it had to be
+/// unescaped.
+#[derive(Deserialize, PartialEq, Eq, Hash)]
+pub(crate) struct StrJSON<'a>(#[serde(borrow)] Cow<'a, str>);
+
+impl From<StrJSON<'_>> for String {
+    fn from(value: StrJSON<'_>) -> Self {
+        value.0.into_owned()
+    }
+}
+
 #[derive(Deserialize)]
 #[serde(tag = "type")]
 #[allow(non_camel_case_types)]
 #[allow(clippy::upper_case_acronyms)]
-enum RuleJSON {
+enum RuleJSON<'a> {
     ALIAS {
-        content: Box<RuleJSON>,
+        #[serde(borrow)]
+        content: Box<RuleJSON<'a>>,
         named: bool,
-        value: String,
+        #[serde(borrow)]
+        value: StrJSON<'a>,
     },
     BLANK,
     STRING {
-        value: String,
+        #[serde(borrow)]
+        value: StrJSON<'a>,
     },
     PATTERN {
-        value: String,
-        flags: Option<String>,
+        #[serde(borrow)]
+        value: StrJSON<'a>,
+        #[serde(borrow)]
+        flags: Option<StrJSON<'a>>,
     },
     SYMBOL {
-        name: String,
+        #[serde(borrow)]
+        name: StrJSON<'a>,
     },
     CHOICE {
-        members: Vec<RuleJSON>,
+        #[serde(borrow)]
+        members: Vec<RuleJSON<'a>>,
     },
     FIELD {
-        name: String,
-        content: Box<RuleJSON>,
+        #[serde(borrow)]
+        name: StrJSON<'a>,
+        #[serde(borrow)]
+        content: Box<RuleJSON<'a>>,
     },
     SEQ {
-        members: Vec<RuleJSON>,
+        #[serde(borrow)]
+        members: Vec<RuleJSON<'a>>,
     },
     REPEAT {
-        content: Box<RuleJSON>,
+        #[serde(borrow)]
+        content: Box<RuleJSON<'a>>,
     },
     REPEAT1 {
-        content: Box<RuleJSON>,
+        #[serde(borrow)]
+        content: Box<RuleJSON<'a>>,
     },
     PREC_DYNAMIC {
         value: i32,
-        content: Box<RuleJSON>,
+        #[serde(borrow)]
+        content: Box<RuleJSON<'a>>,
     },
     PREC_LEFT {
-        value: PrecedenceValueJSON,
-        content: Box<RuleJSON>,
+        #[serde(borrow)]
+        value: PrecedenceValueJSON<'a>,
+        #[serde(borrow)]
+        content: Box<RuleJSON<'a>>,
     },
     PREC_RIGHT {
-        value: PrecedenceValueJSON,
-        content: Box<RuleJSON>,
+        #[serde(borrow)]
+        value: PrecedenceValueJSON<'a>,
+        #[serde(borrow)]
+        content: Box<RuleJSON<'a>>,
     },
     PREC {
-        value: PrecedenceValueJSON,
-        content: Box<RuleJSON>,
+        #[serde(borrow)]
+        value: PrecedenceValueJSON<'a>,
+        #[serde(borrow)]
+        content: Box<RuleJSON<'a>>,
     },
     TOKEN {
-        content: Box<RuleJSON>,
+        #[serde(borrow)]
+        content: Box<RuleJSON<'a>>,
     },
     IMMEDIATE_TOKEN {
-        content: Box<RuleJSON>,
+        #[serde(borrow)]
+        content: Box<RuleJSON<'a>>,
     },
 }
 
 #[derive(Deserialize)]
 #[serde(untagged)]
-enum PrecedenceValueJSON {
+enum PrecedenceValueJSON<'a> {
     Integer(i32),
-    Name(String),
+    #[serde(borrow)]
+    Name(StrJSON<'a>),
 }
 
 #[derive(Deserialize)]
-pub(crate) struct GrammarJSON {
-    pub(crate) name: String,
-    rules: Map<String, Value>,
-    #[serde(default)]
-    precedences: Vec<Vec<RuleJSON>>,
-    #[serde(default)]
-    conflicts: Vec<Vec<String>>,
+pub(crate) struct GrammarJSON<'a> {
+    #[serde(borrow)]
+    pub(crate) name: StrJSON<'a>,
     #[serde(default)]
-    externals: Vec<RuleJSON>,
-    #[serde(default)]
-    extras: Vec<RuleJSON>,
-    #[serde(default)]
-    inline: Vec<String>,
+    schema_version: u64,
+    #[serde(borrow)]
+    rules: IndexMap<StrJSON<'a>, RuleJSON<'a>>,
+    #[serde(default, borrow)]
+    precedences: Vec<Vec<RuleJSON<'a>>>,
+    #[serde(default, borrow)]
+    conflicts: Vec<Vec<StrJSON<'a>>>,
+    #[serde(default, borrow)]
+    externals: Vec<RuleJSON<'a>>,
+    #[serde(default, borrow)]
+    extras: Vec<RuleJSON<'a>>,
+    #[serde(default, borrow)]
+    inline: Vec<StrJSON<'a>>,
+    #[serde(default, borrow)]
+    supertypes: Vec<StrJSON<'a>>,
+    #[serde(default, borrow)]
+    deprecated: Vec<StrJSON<'a>>,
+    #[serde(borrow)]
+    word: Option<StrJSON<'a>>,
+    #[serde(default, borrow)]
+    injections: Vec<InjectionJSON<'a>>,
+    #[serde(default, borrow)]
+    display_names: HashMap<StrJSON<'a>, StrJSON<'a>>,
+}
+
+#[derive(Deserialize)]
+struct InjectionJSON<'a> {
+    #[serde(borrow)]
+    node: StrJSON<'a>,
+    #[serde(default, borrow, deserialize_with = "deserialize_present")]
+    language: Option<StrJSON<'a>>,
+    #[serde(default, borrow, deserialize_with = "deserialize_present")]
+    language_field: Option<StrJSON<'a>>,
+    #[serde(default, borrow, deserialize_with = "deserialize_present")]
+    content_field: Option<StrJSON<'a>>,
     #[serde(default)]
-    supertypes: Vec<String>,
-    word: Option<String>,
+    include_children: bool,
+}
+
+/// Deserialize an optional property that must not be `null` when it is present, the way
+/// `validate_grammar_json` checks it.
+fn deserialize_present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+impl GrammarJSON<'_> {
+    /// Whether the grammar is in the current version of the format, and passes the checks
+    /// of `validate_grammar_json` that deserializing it doesn't already make.
+    fn is_current_and_valid(&self) -> bool {
+        self.schema_version <= GRAMMAR_SCHEMA_VERSION
+            && SCHEMA_UPGRADES[self.schema_version as usize..]
+                .iter()
+                .all(Option::is_none)
+            && !self.rules.is_empty()
+            && self
+                .injections
+                .iter()
+                .all(|injection| injection.language.is_some() != injection.language_field.is_some())
+    }
 }
 
 pub(crate) fn parse_grammar(input: &str) -> Result<InputGrammar> {
-    let grammar_json = serde_json::from_str::<GrammarJSON>(input)?;
+    // Grammar documents can be several megabytes, so they are deserialized straight from the
+    // input, without building a `Value` for the whole document first. Only a grammar that
+    // has to be upgraded, or that turns out to be malformed, is loaded as a `Value`, to be
+    // upgraded or to report the path of the malformed value.
+    match deserialize_current_grammar_json(input) {
+        Some(grammar_json) => convert_grammar_json(grammar_json),
+        None => parse_grammar_value(input),
+    }
+}
+
+/// Deserialize a grammar document directly, if it is in the current version of the format
+/// and is accepted by `validate_grammar_json`. Any other document is left to
+/// `parse_grammar_value`, so that both ways of loading a grammar reject the same input
+/// with the same error.
+fn deserialize_current_grammar_json(input: &str) -> Option<GrammarJSON<'_>> {
+    serde_json::from_str::<GrammarJSON>(input)
+        .ok()
+        .filter(GrammarJSON::is_current_and_valid)
+}
 
-    let mut variables = Vec::with_capacity(grammar_json.rules.len());
-    for (name, value) in grammar_json.rules {
-        variables.push(Variable {
-            name: name.clone(),
+fn parse_grammar_value(input: &str) -> Result<InputGrammar> {
+    let mut grammar_value = serde_json::from_str::<Value>(input)?;
+    upgrade_grammar_json(&mut grammar_value)?;
+    validate_grammar_json(&grammar_value)?;
+    convert_grammar_json(GrammarJSON::deserialize(&grammar_value)?)
+}
+
+fn convert_grammar_json(grammar_json: GrammarJSON) -> Result<InputGrammar> {
+    let variables = grammar_json
+        .rules
+        .into_iter()
+        .map(|(name, rule)| Variable {
+            name: name.into(),
             kind: VariableType::Named,
-            rule: parse_rule(serde_json::from_value(value)?),
-        });
-    }
+            rule: parse_rule(rule),
+        })
+        .collect();
 
     let mut precedence_orderings = Vec::with_capacity(grammar_json.precedences.len());
     for list in grammar_json.precedences {
         let mut ordering = Vec::with_capacity(list.len());
         for entry in list {
             ordering.push(match entry {
-                RuleJSON::STRING { value } => PrecedenceEntry::Name(value),
-                RuleJSON::SYMBOL { name } => PrecedenceEntry::Symbol(name),
+                RuleJSON::STRING { value } => PrecedenceEntry::Name(value.into()),
+                RuleJSON::SYMBOL { name } => PrecedenceEntry::Symbol(name.into()),
                 _ => {
                     return Err(anyhow!(
                         "Invalid rule in precedences array. Only strings and symbols are allowed"
@@ -141,38 +275,346 @@ pub(crate) fn parse_grammar(input: &str) -> Result<InputGrammar> {
 
     let external_tokens = grammar_json.externals.into_iter().map(parse_rule).collect();
 
+    let injections = grammar_json
+        .injections
+        .into_iter()
+        .map(|injection| Injection {
+            node: injection.node.into(),
+            language: match injection.language_field {
+                Some(field) => InjectionLanguage::Field(field.into()),
+                None => {
+                    InjectionLanguage::Name(injection.language.map(Into::into).unwrap_or_default())
+                }
+            },
+            content_field: injection.content_field.map(Into::into),
+            include_children: injection.include_children,
+        })
+        .collect();
+
     Ok(InputGrammar {
-        name: grammar_json.name,
-        word_token: grammar_json.word,
-        expected_conflicts: grammar_json.conflicts,
-        supertype_symbols: grammar_json.supertypes,
-        variables_to_inline: grammar_json.inline,
+        name: grammar_json.name.into(),
+        word_token: grammar_json.word.map(Into::into),
+        expected_conflicts: grammar_json
+            .conflicts
+            .into_iter()
+            .map(|names| names.into_iter().map(Into::into).collect())
+            .collect(),
+        supertype_symbols: grammar_json
+            .supertypes
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        deprecated_symbols: grammar_json
+            .deprecated
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        variables_to_inline: grammar_json.inline.into_iter().map(Into::into).collect(),
         precedence_orderings,
         variables,
         extra_symbols,
         external_tokens,
+        injections,
+        display_names: grammar_json
+            .display_names
+            .into_iter()
+            .map(|(name, display_name)| (name.into(), display_name.into()))
+            .collect(),
     })
 }
 
+/// Upgrade a grammar document written in an older version of the grammar format to the
+/// current one, and set its `schema_version` to the current version.
+pub(crate) fn upgrade_grammar_json(grammar: &mut Value) -> Result<()> {
+    let Value::Object(grammar) = grammar else {
+        return Ok(());
+    };
+    let version = match grammar.get("schema_version") {
+        None => 0,
+        Some(value) => value
+            .as_u64()
+            .ok_or_else(|| type_error("schema_version", "a non-negative integer", value))?,
+    };
+    if version > GRAMMAR_SCHEMA_VERSION {
+        return Err(anyhow!(
+            "The grammar uses version {version} of the grammar format, but this version of Tree-sitter only supports versions up to {GRAMMAR_SCHEMA_VERSION}. Upgrade the Tree-sitter CLI to generate it."
+        ));
+    }
+    for upgrade in SCHEMA_UPGRADES[version as usize..].iter().flatten() {
+        upgrade(grammar);
+    }
+    grammar.insert("schema_version".to_string(), GRAMMAR_SCHEMA_VERSION.into());
+    Ok(())
+}
+
+/// Check the structure of a grammar document before it is deserialized, so that
+/// malformed input is reported with the path of the offending value (e.g.
+/// `rules.expression.members[2].type`) instead of a generic serde error.
+fn validate_grammar_json(grammar: &Value) -> Result<()> {
+    let grammar = expect_object(grammar, "")?;
+
+    match grammar.get("name") {
+        Some(Value::String(_)) => {}
+        Some(value) => return Err(type_error("name", "a string", value)),
+        None => return Err(anyhow!("Invalid grammar: missing required property `name`")),
+    }
+
+    match grammar.get("rules") {
+        Some(Value::Object(rules)) => {
+            if rules.is_empty() {
+                return Err(anyhow!(
+                    "Invalid grammar at `rules`: a grammar must have at least one rule"
+                ));
+            }
+            for (name, rule) in rules {
+                validate_rule_json(rule, &format!("rules.{name}"))?;
+            }
+        }
+        Some(value) => return Err(type_error("rules", "an object", value)),
+        None => {
+            return Err(anyhow!(
+                "Invalid grammar: missing required property `rules`"
+            ))
+        }
+    }
+
+    for key in ["extras", "externals"] {
+        if let Some(value) = grammar.get(key) {
+            for (i, rule) in expect_array(value, key)?.iter().enumerate() {
+                validate_rule_json(rule, &format!("{key}[{i}]"))?;
+            }
+        }
+    }
+
+    if let Some(value) = grammar.get("precedences") {
+        for (i, list) in expect_array(value, "precedences")?.iter().enumerate() {
+            let path = format!("precedences[{i}]");
+            for (j, rule) in expect_array(list, &path)?.iter().enumerate() {
+                validate_rule_json(rule, &format!("{path}[{j}]"))?;
+            }
+        }
+    }
+
+    if let Some(value) = grammar.get("conflicts") {
+        for (i, list) in expect_array(value, "conflicts")?.iter().enumerate() {
+            let path = format!("conflicts[{i}]");
+            for (j, name) in expect_array(list, &path)?.iter().enumerate() {
+                expect_string(name, &format!("{path}[{j}]"))?;
+            }
+        }
+    }
+
+    for key in ["inline", "supertypes", "deprecated"] {
+        if let Some(value) = grammar.get(key) {
+            for (i, name) in expect_array(value, key)?.iter().enumerate() {
+                expect_string(name, &format!("{key}[{i}]"))?;
+            }
+        }
+    }
+
+    if let Some(value) = grammar.get("word") {
+        if !value.is_null() {
+            expect_string(value, "word")?;
+        }
+    }
+
+    if let Some(value) = grammar.get("display_names") {
+        for (name, display_name) in expect_object(value, "display_names")? {
+            expect_string(display_name, &format!("display_names.{name}"))?;
+        }
+    }
+
+    if let Some(value) = grammar.get("injections") {
+        for (i, injection) in expect_array(value, "injections")?.iter().enumerate() {
+            validate_injection_json(injection, &format!("injections[{i}]"))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_rule_json(rule: &Value, path: &str) -> Result<()> {
+    let object = expect_object(rule, path)?;
+    let type_path = format!("{path}.type");
+    let rule_type = match object.get("type") {
+        Some(value) => expect_string(value, &type_path)?,
+        None => {
+            return Err(anyhow!(
+                "Invalid grammar at `{path}`: missing required property `type`"
+            ))
+        }
+    };
+
+    let require = |key: &str| {
+        object.get(key).ok_or_else(|| {
+            anyhow!("Invalid grammar at `{path}`: missing required property `{key}` for a {rule_type} rule")
+        })
+    };
+
+    match rule_type {
+        "BLANK" => {}
+        "STRING" | "PATTERN" => {
+            expect_string(require("value")?, &format!("{path}.value"))?;
+            if rule_type == "PATTERN" {
+                if let Some(flags) = object.get("flags") {
+                    if !flags.is_null() {
+                        expect_string(flags, &format!("{path}.flags"))?;
+                    }
+                }
+            }
+        }
+        "SYMBOL" => {
+            expect_string(require("name")?, &format!("{path}.name"))?;
+        }
+        "SEQ" | "CHOICE" => {
+            let members_path = format!("{path}.members");
+            for (i, member) in expect_array(require("members")?, &members_path)?
+                .iter()
+                .enumerate()
+            {
+                validate_rule_json(member, &format!("{members_path}[{i}]"))?;
+            }
+        }
+        "ALIAS" => {
+            expect_string(require("value")?, &format!("{path}.value"))?;
+            let named = require("named")?;
+            if !named.is_boolean() {
+                return Err(type_error(&format!("{path}.named"), "a boolean", named));
+            }
+            validate_rule_json(require("content")?, &format!("{path}.content"))?;
+        }
+        "FIELD" => {
+            expect_string(require("name")?, &format!("{path}.name"))?;
+            validate_rule_json(require("content")?, &format!("{path}.content"))?;
+        }
+        "PREC_DYNAMIC" => {
+            let value = require("value")?;
+            if !value.as_i64().is_some_and(|v| i32::try_from(v).is_ok()) {
+                return Err(type_error(&format!("{path}.value"), "an integer", value));
+            }
+            validate_rule_json(require("content")?, &format!("{path}.content"))?;
+        }
+        "PREC" | "PREC_LEFT" | "PREC_RIGHT" => {
+            let value = require("value")?;
+            if !(value.is_string() || value.as_i64().is_some_and(|v| i32::try_from(v).is_ok())) {
+                return Err(type_error(
+                    &format!("{path}.value"),
+                    "an integer or a precedence name",
+                    value,
+                ));
+            }
+            validate_rule_json(require("content")?, &format!("{path}.content"))?;
+        }
+        "REPEAT" | "REPEAT1" | "TOKEN" | "IMMEDIATE_TOKEN" => {
+            validate_rule_json(require("content")?, &format!("{path}.content"))?;
+        }
+        _ => {
+            return Err(anyhow!(
+                "Invalid grammar at `{type_path}`: unknown rule type {rule_type:?}"
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_injection_json(injection: &Value, path: &str) -> Result<()> {
+    let object = expect_object(injection, path)?;
+    match object.get("node") {
+        Some(node) => expect_string(node, &format!("{path}.node"))?,
+        None => {
+            return Err(anyhow!(
+                "Invalid grammar at `{path}`: missing required property `node` for an injection"
+            ))
+        }
+    };
+
+    for key in ["language", "language_field", "content_field"] {
+        if let Some(value) = object.get(key) {
+            expect_string(value, &format!("{path}.{key}"))?;
+        }
+    }
+
+    match (object.get("language"), object.get("language_field")) {
+        (Some(_), Some(_)) => {
+            return Err(anyhow!(
+                "Invalid grammar at `{path}`: an injection cannot have both a `language` and a `language_field`"
+            ))
+        }
+        (None, None) => {
+            return Err(anyhow!(
+                "Invalid grammar at `{path}`: an injection must have either a `language` or a `language_field`"
+            ))
+        }
+        _ => {}
+    }
+
+    if let Some(value) = object.get("include_children") {
+        if !value.is_boolean() {
+            return Err(type_error(
+                &format!("{path}.include_children"),
+                "a boolean",
+                value,
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn expect_object<'a>(value: &'a Value, path: &str) -> Result<&'a Map<String, Value>> {
+    value
+        .as_object()
+        .ok_or_else(|| type_error(path, "an object", value))
+}
+
+fn expect_array<'a>(value: &'a Value, path: &str) -> Result<&'a Vec<Value>> {
+    value
+        .as_array()
+        .ok_or_else(|| type_error(path, "an array", value))
+}
+
+fn expect_string<'a>(value: &'a Value, path: &str) -> Result<&'a str> {
+    value
+        .as_str()
+        .ok_or_else(|| type_error(path, "a string", value))
+}
+
+fn type_error(path: &str, expected: &str, value: &Value) -> anyhow::Error {
+    let found = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    };
+    if path.is_empty() {
+        anyhow!("Invalid grammar: expected {expected}, found {found}")
+    } else {
+        anyhow!("Invalid grammar at `{path}`: expected {expected}, found {found}")
+    }
+}
+
 fn parse_rule(json: RuleJSON) -> Rule {
     match json {
         RuleJSON::ALIAS {
             content,
             value,
             named,
-        } => Rule::alias(parse_rule(*content), value, named),
+        } => Rule::alias(parse_rule(*content), value.into(), named),
         RuleJSON::BLANK => Rule::Blank,
-        RuleJSON::STRING { value } => Rule::String(value),
+        RuleJSON::STRING { value } => Rule::String(value.into()),
         RuleJSON::PATTERN { value, flags } => Rule::Pattern(
-            value,
+            value.into(),
             flags.map_or(String::new(), |f| {
-                f.matches(|c| {
+                f.0.matches(|c| {
                     if c == 'i' {
                         true
                     } else {
                         // silently ignore unicode flags
                         if c != 'u' && c != 'v' {
-                            eprintln!("Warning: unsupported flag {c}");
+                            warn!("unsupported flag {c}");
                         }
                         false
                     }
@@ -180,9 +622,9 @@ fn parse_rule(json: RuleJSON) -> Rule {
                 .collect()
             }),
         ),
-        RuleJSON::SYMBOL { name } => Rule::NamedSymbol(name),
+        RuleJSON::SYMBOL { name } => Rule::NamedSymbol(name.into()),
         RuleJSON::CHOICE { members } => Rule::choice(members.into_iter().map(parse_rule).collect()),
-        RuleJSON::FIELD { content, name } => Rule::field(name, parse_rule(*content)),
+        RuleJSON::FIELD { content, name } => Rule::field(name.into(), parse_rule(*content)),
         RuleJSON::SEQ { members } => Rule::seq(members.into_iter().map(parse_rule).collect()),
         RuleJSON::REPEAT1 { content } => Rule::repeat(parse_rule(*content)),
         RuleJSON::REPEAT { content } => {
@@ -203,11 +645,11 @@ fn parse_rule(json: RuleJSON) -> Rule {
     }
 }
 
-impl From<PrecedenceValueJSON> for Precedence {
+impl From<PrecedenceValueJSON<'_>> for Precedence {
     fn from(val: PrecedenceValueJSON) -> Self {
         match val {
             PrecedenceValueJSON::Integer(i) => Self::Integer(i),
-            PrecedenceValueJSON::Name(i) => Self::Name(i),
+            PrecedenceValueJSON::Name(i) => Self::Name(i.into()),
         }
     }
 }
@@ -255,4 +697,148 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn test_parse_grammar_borrows_strings() {
+        let input = r#"{
+            "name": "my_lang",
+            "rules": {
+                "string": {"type": "PATTERN", "value": "\"[^\"]*\"", "flags": "i"},
+                "word": {"type": "ALIAS", "named": true, "value": "name", "content": {"type": "STRING", "value": "w"}}
+            },
+            "precedences": [[{"type": "STRING", "value": "high"}, {"type": "SYMBOL", "name": "word"}]],
+            "injections": [{"node": "string", "language": "regex"}]
+        }"#;
+
+        let grammar_json = serde_json::from_str::<GrammarJSON>(input).unwrap();
+        let (names, rules): (Vec<_>, Vec<_>) = grammar_json.rules.iter().unzip();
+        assert!(matches!(names[0].0, Cow::Borrowed("string")));
+        assert!(matches!(
+            rules[0],
+            RuleJSON::PATTERN {
+                value: StrJSON(Cow::Owned(_)),
+                flags: Some(StrJSON(Cow::Borrowed("i"))),
+            }
+        ));
+        assert!(matches!(
+            rules[1],
+            RuleJSON::ALIAS {
+                value: StrJSON(Cow::Borrowed("name")),
+                ..
+            }
+        ));
+
+        // Grammars that are loaded as a `Value` first are parsed the same way.
+        let grammar_value = serde_json::from_str::<Value>(input).unwrap();
+        assert_eq!(
+            parse_grammar(input).unwrap(),
+            convert_grammar_json(GrammarJSON::deserialize(&grammar_value).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_grammar_with_invalid_rule_reports_path() {
+        let result = parse_grammar(
+            r#"{
+            "name": "my_lang",
+            "rules": {
+                "expression": {
+                    "type": "CHOICE",
+                    "members": [
+                        {"type": "STRING", "value": "a"},
+                        {"type": "SYMBOL", "name": "b"},
+                        {"type": "STRNG", "value": "c"}
+                    ]
+                },
+                "b": {"type": "STRING", "value": "b"}
+            }
+        }"#,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid grammar at `rules.expression.members[2].type`: unknown rule type \"STRNG\""
+        );
+
+        let result = parse_grammar(
+            r#"{
+            "name": "my_lang",
+            "rules": {
+                "a": {"type": "FIELD", "name": "f", "content": {"type": "SYMBOL", "name": 5}}
+            }
+        }"#,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid grammar at `rules.a.content.name`: expected a string, found a number"
+        );
+
+        let result = parse_grammar(r#"{"name": "my_lang", "rules": {}, "extras": {}}"#);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid grammar at `rules`: a grammar must have at least one rule"
+        );
+
+        let result = parse_grammar(
+            r#"{
+            "name": "my_lang",
+            "rules": {"a": {"type": "BLANK"}},
+            "injections": [{"node": "a", "content_field": "body"}]
+        }"#,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Invalid grammar at `injections[0]`: an injection must have either a `language` or a `language_field`"
+        );
+    }
+
+    #[test]
+    fn test_parse_grammar_rejects_the_same_input_on_both_paths() {
+        for (input, message) in [
+            (
+                r#"{"name": "my_lang", "rules": {}}"#,
+                "Invalid grammar at `rules`: a grammar must have at least one rule",
+            ),
+            (
+                r#"{"name": "my_lang", "rules": {"a": {"type": "BLANK"}}, "injections": [{"node": "a", "language": null}]}"#,
+                "Invalid grammar at `injections[0].language`: expected a string, found null",
+            ),
+            (
+                r#"{"name": "my_lang", "rules": {"a": {"type": "BLANK"}}, "injections": [{"node": "a", "language": null, "language_field": "lang"}]}"#,
+                "Invalid grammar at `injections[0].language`: expected a string, found null",
+            ),
+            (
+                r#"{"name": "my_lang", "rules": {"a": {"type": "BLANK"}}, "injections": [{"node": "a", "language": "js", "content_field": null}]}"#,
+                "Invalid grammar at `injections[0].content_field`: expected a string, found null",
+            ),
+            (
+                r#"{"name": "my_lang", "rules": {"a": {"type": "BLANK"}}, "injections": [{"node": "a", "language": "js", "language_field": "lang"}]}"#,
+                "Invalid grammar at `injections[0]`: an injection cannot have both a `language` and a `language_field`",
+            ),
+        ] {
+            assert!(deserialize_current_grammar_json(input).is_none(), "{input}");
+            assert_eq!(parse_grammar(input).unwrap_err().to_string(), message);
+            assert_eq!(parse_grammar_value(input).unwrap_err().to_string(), message);
+        }
+    }
+
+    #[test]
+    fn test_upgrade_grammar_json() {
+        let mut grammar = serde_json::json!({"name": "my_lang", "rules": {"a": {"type": "BLANK"}}});
+        upgrade_grammar_json(&mut grammar).unwrap();
+        assert_eq!(grammar["schema_version"], GRAMMAR_SCHEMA_VERSION);
+
+        let mut grammar = serde_json::json!({"name": "my_lang", "schema_version": "1"});
+        assert_eq!(
+            upgrade_grammar_json(&mut grammar).unwrap_err().to_string(),
+            "Invalid grammar at `schema_version`: expected a non-negative integer, found a string"
+        );
+
+        let result = parse_grammar(
+            r#"{"name": "my_lang", "schema_version": 1000, "rules": {"a": {"type": "BLANK"}}}"#,
+        );
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!("The grammar uses version 1000 of the grammar format, but this version of Tree-sitter only supports versions up to {GRAMMAR_SCHEMA_VERSION}. Upgrade the Tree-sitter CLI to generate it.")
+        );
+    }
 }
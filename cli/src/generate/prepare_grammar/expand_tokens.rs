@@ -1,6 +1,9 @@
-use std::collections::HashMap;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    thread,
+};
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{Context, Error, Result};
 use lazy_static::lazy_static;
 use regex_syntax::ast::{
     parse, Ast, ClassPerlKind, ClassSet, ClassSetBinaryOpKind, ClassSetItem, ClassUnicodeKind,
@@ -9,8 +12,9 @@ use regex_syntax::ast::{
 
 use super::ExtractedLexicalGrammar;
 use crate::generate::{
-    grammars::{LexicalGrammar, LexicalVariable},
-    nfa::{CharacterSet, Nfa, NfaState},
+    error::{GrammarError, GrammarErrorKind},
+    grammars::{LexicalGrammar, LexicalVariable, Variable},
+    nfa::{CharacterSet, CharacterSetInterner, Nfa, NfaState},
     rules::{Precedence, Rule},
 };
 
@@ -30,6 +34,9 @@ const UNICODE_PROPERTIES_JSON: &str = include_str!("./unicode-properties.json");
 const UNICODE_CATEGORY_ALIASES_JSON: &str = include_str!("./unicode-category-aliases.json");
 const UNICODE_PROPERTY_ALIASES_JSON: &str = include_str!("./unicode-property-aliases.json");
 
+/// The smallest number of tokens that is worth expanding on a separate thread.
+const MIN_TOKENS_PER_THREAD: usize = 32;
+
 struct NfaBuilder {
     nfa: Nfa,
     is_sep: bool,
@@ -59,13 +66,27 @@ const fn get_completion_precedence(rule: &Rule) -> i32 {
     0
 }
 
-pub fn expand_tokens(mut grammar: ExtractedLexicalGrammar) -> Result<LexicalGrammar> {
-    let mut builder = NfaBuilder {
-        nfa: Nfa::new(),
-        is_sep: true,
-        precedence_stack: vec![0],
-    };
+fn unsupported_token_error(message: impl Into<String>) -> Error {
+    GrammarError::new(GrammarErrorKind::UnsupportedToken, message).into()
+}
+
+pub fn expand_tokens(grammar: ExtractedLexicalGrammar) -> Result<LexicalGrammar> {
+    let thread_count = thread::available_parallelism().map_or(1, usize::from);
+    let tokens_per_thread = grammar
+        .variables
+        .len()
+        .div_ceil(thread_count)
+        .max(MIN_TOKENS_PER_THREAD);
+    expand_tokens_in_chunks(grammar, tokens_per_thread)
+}
 
+/// Expand each token into an NFA of its own, expanding each chunk of tokens on a separate
+/// thread, and then append the tokens' states to a single NFA in the order of the tokens, so
+/// that the result doesn't depend on how the tokens were divided.
+fn expand_tokens_in_chunks(
+    mut grammar: ExtractedLexicalGrammar,
+    tokens_per_chunk: usize,
+) -> Result<LexicalGrammar> {
     let separator_rule = if grammar.separators.is_empty() {
         Rule::Blank
     } else {
@@ -73,41 +94,197 @@ pub fn expand_tokens(mut grammar: ExtractedLexicalGrammar) -> Result<LexicalGram
         Rule::repeat(Rule::choice(grammar.separators))
     };
 
-    let mut variables = Vec::new();
-    for (i, variable) in grammar.variables.into_iter().enumerate() {
-        let is_immediate_token = match &variable.rule {
-            Rule::Metadata { params, .. } => params.is_main_token,
-            _ => false,
-        };
-
-        builder.is_sep = false;
-        builder.nfa.states.push(NfaState::Accept {
-            variable_index: i,
-            precedence: get_completion_precedence(&variable.rule),
-        });
-        let last_state_id = builder.nfa.last_state_id();
-        builder
-            .expand_rule(&variable.rule, last_state_id)
-            .with_context(|| format!("Error processing rule {}", variable.name))?;
-
-        if !is_immediate_token {
-            builder.is_sep = true;
-            let last_state_id = builder.nfa.last_state_id();
-            builder.expand_rule(&separator_rule, last_state_id)?;
-        }
+    let expand_chunk = |chunk_index: usize, chunk: &[Variable]| {
+        chunk
+            .iter()
+            .enumerate()
+            .map(|(i, variable)| {
+                expand_token(
+                    chunk_index * tokens_per_chunk + i,
+                    variable,
+                    &separator_rule,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let token_nfas = if grammar.variables.len() > tokens_per_chunk {
+        thread::scope(|scope| {
+            let threads = grammar
+                .variables
+                .chunks(tokens_per_chunk)
+                .enumerate()
+                .map(|(chunk_index, chunk)| scope.spawn(move || expand_chunk(chunk_index, chunk)))
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .flat_map(|thread| thread.join().unwrap())
+                .collect::<Vec<_>>()
+        })
+    } else {
+        expand_chunk(0, &grammar.variables)
+    };
 
+    // Tokens that use the same character classes share them.
+    let mut character_sets = CharacterSetInterner::default();
+    let mut nfa = Nfa::new();
+    let mut variables = Vec::with_capacity(grammar.variables.len());
+    for (variable, token_nfa) in grammar.variables.into_iter().zip(token_nfas) {
+        let offset = nfa.states.len() as u32;
+        nfa.states
+            .extend(token_nfa?.states.into_iter().map(|state| match state {
+                NfaState::Advance {
+                    chars,
+                    state_id,
+                    is_sep,
+                    precedence,
+                } => NfaState::Advance {
+                    chars: character_sets.intern(chars),
+                    state_id: state_id + offset,
+                    is_sep,
+                    precedence,
+                },
+                NfaState::Split(left, right) => NfaState::Split(left + offset, right + offset),
+                NfaState::Accept { .. } => state,
+            }));
         variables.push(LexicalVariable {
+            implicit_precedence: get_implicit_precedence(&variable.rule),
             name: variable.name,
             kind: variable.kind,
-            implicit_precedence: get_implicit_precedence(&variable.rule),
-            start_state: builder.nfa.last_state_id(),
+            start_state: nfa.last_state_id(),
         });
     }
 
-    Ok(LexicalGrammar {
-        nfa: builder.nfa,
-        variables,
-    })
+    Ok(LexicalGrammar { nfa, variables })
+}
+
+/// Build the NFA for a single token, whose states are numbered from zero and which ends with
+/// the token's start state.
+fn expand_token(index: usize, variable: &Variable, separator_rule: &Rule) -> Result<Nfa> {
+    let mut builder = NfaBuilder {
+        nfa: Nfa::new(),
+        is_sep: false,
+        precedence_stack: vec![0],
+    };
+    let is_immediate_token = match &variable.rule {
+        Rule::Metadata { params, .. } => params.is_main_token,
+        _ => false,
+    };
+
+    builder.nfa.states.push(NfaState::Accept {
+        variable_index: index,
+        precedence: get_completion_precedence(&variable.rule),
+    });
+    let last_state_id = builder.nfa.last_state_id();
+    builder
+        .expand_rule(&variable.rule, last_state_id)
+        .map_err(|error| match error.downcast::<GrammarError>() {
+            Ok(error) => Error::new(error.with_symbols([&variable.name])),
+            Err(error) => error,
+        })
+        .with_context(|| format!("Error processing rule {}", variable.name))?;
+
+    if !is_immediate_token {
+        builder.is_sep = true;
+        let last_state_id = builder.nfa.last_state_id();
+        builder.expand_rule(separator_rule, last_state_id)?;
+    }
+
+    share_identical_states(&mut builder.nfa, 0);
+    Ok(builder.nfa)
+}
+
+/// Merge structurally identical states among the states of the most recently
+/// expanded token (those with ids at or after `first_state_id`), and remove
+/// states that are unreachable from its start state.
+///
+/// Because the NFA is built backwards, two states are identical only if they
+/// also lead to identical continuations, so this shares common suffixes of
+/// sub-patterns, like the `bc` in `abc|xbc`. The relative order of the
+/// remaining states is preserved, so the start state is still the last one.
+///
+/// Running this over the states of several tokens wouldn't share anything more:
+/// every state's continuations end in its own token's `Accept` state, so no
+/// state of one token is identical to a state of another. Each token's states
+/// must also occupy a contiguous range of ids, which is how the lexer and the
+/// conflict analysis tell which token a state belongs to. What identical
+/// sub-patterns of different tokens do share are their character sets, which
+/// are interned when the tokens' states are appended to the grammar's NFA.
+fn share_identical_states(nfa: &mut Nfa, first_state_id: u32) {
+    let start_state_id = nfa.last_state_id();
+    let mut states = nfa.states.split_off(first_state_id as usize);
+    let local = |id: u32| (id - first_state_id) as usize;
+
+    // Repeatedly merge identical states until no more merges are possible,
+    // since merging two states can make their predecessors identical as well.
+    let mut replacements = (first_state_id..=start_state_id).collect::<Vec<_>>();
+    loop {
+        let mut representatives = HashMap::new();
+        let mut changed = false;
+        for id in (first_state_id..=start_state_id).rev() {
+            if replacements[local(id)] != id {
+                continue;
+            }
+            match representatives.entry(states[local(id)].clone()) {
+                Entry::Occupied(entry) => {
+                    replacements[local(id)] = *entry.get();
+                    changed = true;
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(id);
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+        for state in &mut states {
+            match state {
+                NfaState::Advance { state_id, .. } => *state_id = replacements[local(*state_id)],
+                NfaState::Split(left, right) => {
+                    *left = replacements[local(*left)];
+                    *right = replacements[local(*right)];
+                }
+                NfaState::Accept { .. } => {}
+            }
+        }
+    }
+
+    let mut is_reachable = vec![false; states.len()];
+    let mut stack = vec![start_state_id];
+    while let Some(id) = stack.pop() {
+        if is_reachable[local(id)] {
+            continue;
+        }
+        is_reachable[local(id)] = true;
+        match &states[local(id)] {
+            NfaState::Advance { state_id, .. } => stack.push(*state_id),
+            NfaState::Split(left, right) => stack.extend([*left, *right]),
+            NfaState::Accept { .. } => {}
+        }
+    }
+
+    let mut new_ids = vec![0; states.len()];
+    let mut next_id = first_state_id;
+    for (i, reachable) in is_reachable.iter().enumerate() {
+        if *reachable {
+            new_ids[i] = next_id;
+            next_id += 1;
+        }
+    }
+    for (i, mut state) in states.into_iter().enumerate() {
+        if !is_reachable[i] {
+            continue;
+        }
+        match &mut state {
+            NfaState::Advance { state_id, .. } => *state_id = new_ids[local(*state_id)],
+            NfaState::Split(left, right) => {
+                *left = new_ids[local(*left)];
+                *right = new_ids[local(*right)];
+            }
+            NfaState::Accept { .. } => {}
+        }
+        nfa.states.push(state);
+    }
 }
 
 impl NfaBuilder {
@@ -179,7 +356,9 @@ impl NfaBuilder {
                 result
             }
             Rule::Blank => Ok(false),
-            _ => Err(anyhow!("Grammar error: Unexpected rule {rule:?}")),
+            _ => Err(unsupported_token_error(format!(
+                "Grammar error: Unexpected rule {rule:?}"
+            ))),
         }
     }
 
@@ -209,7 +388,9 @@ impl NfaBuilder {
 
         match ast {
             Ast::Empty(_) => Ok(false),
-            Ast::Flags(_) => Err(anyhow!("Regex error: Flags are not supported")),
+            Ast::Flags(_) => Err(unsupported_token_error(
+                "Regex error: Flags are not supported",
+            )),
             Ast::Literal(literal) => {
                 let mut char_set = CharacterSet::from_char(literal.c);
                 if case_insensitive {
@@ -225,7 +406,9 @@ impl NfaBuilder {
                 self.push_advance(CharacterSet::from_char('\n').negate(), next_state_id);
                 Ok(true)
             }
-            Ast::Assertion(_) => Err(anyhow!("Regex error: Assertions are not supported")),
+            Ast::Assertion(_) => Err(unsupported_token_error(
+                "Regex error: Assertions are not supported",
+            )),
             Ast::ClassUnicode(class) => {
                 let mut chars = self.expand_unicode_character_class(&class.kind)?;
                 if class.negated {
@@ -443,9 +626,9 @@ impl NfaBuilder {
                 }
                 Ok(set)
             }
-            ClassSetItem::Ascii(_) => Err(anyhow!(
+            ClassSetItem::Ascii(_) => Err(unsupported_token_error(format!(
                 "Regex error: Unsupported character class syntax {item:?}",
-            )),
+            ))),
         }
     }
 
@@ -465,15 +648,14 @@ impl NfaBuilder {
                 if actual_class_name.len() == 1 {
                     category_letter = actual_class_name.clone();
                 } else {
-                    let code_points =
-                        UNICODE_CATEGORIES
-                            .get(actual_class_name.as_str())
-                            .or_else(|| UNICODE_PROPERTIES.get(actual_class_name.as_str()))
-                            .ok_or_else(|| {
-                                anyhow!(
-                                    "Regex error: Unsupported unicode character class {class_name}",
-                                )
-                            })?;
+                    let code_points = UNICODE_CATEGORIES
+                        .get(actual_class_name.as_str())
+                        .or_else(|| UNICODE_PROPERTIES.get(actual_class_name.as_str()))
+                        .ok_or_else(|| {
+                            unsupported_token_error(format!(
+                                "Regex error: Unsupported unicode character class {class_name}",
+                            ))
+                        })?;
                     for c in code_points {
                         if let Some(c) = char::from_u32(*c) {
                             chars = chars.add_char(c);
@@ -484,8 +666,8 @@ impl NfaBuilder {
                 }
             }
             ClassUnicodeKind::NamedValue { .. } => {
-                return Err(anyhow!(
-                    "Regex error: Key-value unicode properties are not supported"
+                return Err(unsupported_token_error(
+                    "Regex error: Key-value unicode properties are not supported",
                 ))
             }
         }
@@ -937,4 +1119,54 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_expanding_tokens_in_parallel() {
+        let grammar = || ExtractedLexicalGrammar {
+            separators: vec![Rule::pattern("\\s", "")],
+            variables: (0..10)
+                .map(|i| {
+                    Variable::named(
+                        &format!("token_{i}"),
+                        Rule::Pattern(format!("[a-z]{{{i}}}\\w*[0-9]"), String::new()),
+                    )
+                })
+                .chain([Variable::named("keyword", Rule::string("if"))])
+                .collect(),
+        };
+
+        let sequential = expand_tokens_in_chunks(grammar(), 100).unwrap();
+        let parallel = expand_tokens_in_chunks(grammar(), 3).unwrap();
+        assert_eq!(parallel, sequential);
+        assert_eq!(simulate_nfa(&parallel, " if "), Some((10, "if")));
+
+        // Errors are reported for the first invalid token.
+        let mut invalid_grammar = grammar();
+        invalid_grammar.variables[4].rule = Rule::pattern("[", "");
+        invalid_grammar.variables[8].rule = Rule::pattern("(", "");
+        let error = expand_tokens_in_chunks(invalid_grammar, 3).unwrap_err();
+        assert_eq!(error.to_string(), "Error processing rule token_4");
+    }
+
+    #[test]
+    fn test_sharing_identical_states_within_tokens() {
+        let grammar = expand_tokens(ExtractedLexicalGrammar {
+            separators: vec![],
+            variables: vec![
+                Variable::named("", Rule::pattern("abc|xbc", "")),
+                Variable::named("", Rule::pattern("a(bc|bc)", "")),
+            ],
+        })
+        .unwrap();
+
+        // The `bc` suffix is shared between the two alternatives, but not between
+        // the two tokens, whose `bc` states lead to different `Accept` states.
+        assert_eq!(grammar.variables[0].start_state, 5);
+        assert_eq!(grammar.variables[1].start_state, 10);
+        assert_eq!(grammar.nfa.states.len(), 11);
+
+        assert_eq!(simulate_nfa(&grammar, "xbc."), Some((0, "xbc")));
+        assert_eq!(simulate_nfa(&grammar, "abc."), Some((1, "abc")));
+        assert_eq!(simulate_nfa(&grammar, "xb."), None);
+    }
 }
@@ -1,9 +1,10 @@
 use std::{collections::HashMap, mem};
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 
 use super::{ExtractedLexicalGrammar, ExtractedSyntaxGrammar, InternedGrammar};
 use crate::generate::{
+    error::{GrammarError, GrammarErrorKind},
     grammars::{ExternalToken, Variable, VariableType},
     rules::{MetadataParams, Rule, Symbol, SymbolType},
 };
@@ -86,6 +87,12 @@ pub(super) fn extract_tokens(
         .map(|symbol| symbol_replacer.replace_symbol(symbol))
         .collect();
 
+    let deprecated_symbols = grammar
+        .deprecated_symbols
+        .into_iter()
+        .map(|symbol| symbol_replacer.replace_symbol(symbol))
+        .collect();
+
     let variables_to_inline = grammar
         .variables_to_inline
         .into_iter()
@@ -109,10 +116,15 @@ pub(super) fn extract_tokens(
         let rule = symbol_replacer.replace_symbols_in_rule(&external_token.rule);
         if let Rule::Symbol(symbol) = rule {
             if symbol.is_non_terminal() {
-                return Err(anyhow!(
-                    "Rule '{}' cannot be used as both an external token and a non-terminal rule",
-                    &variables[symbol.index].name,
-                ));
+                let name = &variables[symbol.index].name;
+                return Err(GrammarError::new(
+                    GrammarErrorKind::InvalidExternalToken,
+                    format!(
+                        "Rule '{name}' cannot be used as both an external token and a non-terminal rule"
+                    ),
+                )
+                .with_symbols([name])
+                .into());
             }
 
             if symbol.is_external() {
@@ -129,9 +141,12 @@ pub(super) fn extract_tokens(
                 });
             }
         } else {
-            return Err(anyhow!(
-                "Non-symbol rules cannot be used as external tokens"
-            ));
+            return Err(GrammarError::new(
+                GrammarErrorKind::InvalidExternalToken,
+                "Non-symbol rules cannot be used as external tokens",
+            )
+            .with_suggestion("Define the token as a rule, and reference it by name in `externals`")
+            .into());
         }
     }
 
@@ -139,10 +154,14 @@ pub(super) fn extract_tokens(
     if let Some(token) = grammar.word_token {
         let token = symbol_replacer.replace_symbol(token);
         if token.is_non_terminal() {
-            return Err(anyhow!(
-                "Non-terminal symbol '{}' cannot be used as the word token",
-                &variables[token.index].name
-            ));
+            let name = &variables[token.index].name;
+            return Err(GrammarError::new(
+                GrammarErrorKind::InvalidWordToken,
+                format!("Non-terminal symbol '{name}' cannot be used as the word token"),
+            )
+            .with_symbols([name])
+            .with_suggestion(format!("Wrap the definition of `{name}` in `token(...)`"))
+            .into());
         }
         word_token = Some(token);
     }
@@ -154,6 +173,7 @@ pub(super) fn extract_tokens(
             extra_symbols,
             variables_to_inline,
             supertype_symbols,
+            deprecated_symbols,
             external_tokens,
             word_token,
             precedence_orderings: grammar.precedence_orderings,
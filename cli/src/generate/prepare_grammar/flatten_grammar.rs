@@ -1,8 +1,13 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
+use log::info;
 
 use super::ExtractedSyntaxGrammar;
 use crate::generate::{
-    grammars::{Production, ProductionStep, SyntaxGrammar, SyntaxVariable, Variable},
+    error::{GrammarError, GrammarErrorKind},
+    grammars::{
+        PrecedenceEntry, Production, ProductionStep, SyntaxGrammar, SyntaxVariable, Variable,
+        VariableType,
+    },
     rules::{Alias, Associativity, Precedence, Rule, Symbol},
 };
 
@@ -196,23 +201,38 @@ pub(super) fn flatten_grammar(grammar: ExtractedSyntaxGrammar) -> Result<SyntaxG
 
         for production in &variable.productions {
             if production.steps.is_empty() && symbol_is_used(&variables, symbol) {
-                return Err(anyhow!(
-                    "The rule `{}` matches the empty string.
+                return Err(GrammarError::new(
+                    GrammarErrorKind::EmptyRule,
+                    format!(
+                        "The rule `{}` matches the empty string.
 
 Tree-sitter does not support syntactic rules that match the empty string
 unless they are used only as the grammar's start rule.
 ",
+                        variable.name
+                    ),
+                )
+                .with_symbols([&variable.name])
+                .with_suggestion(format!(
+                    "Make `{}` match at least one token, and wrap its uses in `optional(...)`",
                     variable.name
-                ));
+                ))
+                .into());
             }
 
             if grammar.variables_to_inline.contains(&symbol)
                 && production.steps.iter().any(|step| step.symbol == symbol)
             {
-                return Err(anyhow!(
-                    "Rule `{}` cannot be inlined because it contains a reference to itself.",
-                    variable.name,
-                ));
+                return Err(GrammarError::new(
+                    GrammarErrorKind::InvalidInline,
+                    format!(
+                        "Rule `{}` cannot be inlined because it contains a reference to itself.",
+                        variable.name,
+                    ),
+                )
+                .with_symbols([&variable.name])
+                .with_suggestion(format!("Remove `{}` from `inline`", variable.name))
+                .into());
             }
         }
     }
@@ -223,15 +243,121 @@ unless they are used only as the grammar's start rule.
         precedence_orderings: grammar.precedence_orderings,
         external_tokens: grammar.external_tokens,
         supertype_symbols: grammar.supertype_symbols,
+        deprecated_symbols: grammar.deprecated_symbols,
         word_token: grammar.word_token,
         variables,
     })
 }
 
+/// Mark small hidden rules for inlining, as if they had been listed in the
+/// grammar's `inline` array.
+///
+/// A rule is only inlined automatically if doing so cannot change the shape of
+/// the resulting syntax trees or the meaning of other grammar declarations: it
+/// must be hidden, must not refer to itself, directly or through other inlined
+/// rules, and must not be a supertype, an extra, or be mentioned in any conflict
+/// or precedence list. Rules that are referenced with
+/// an alias or a field name are also left alone. The size of a rule is the
+/// total number of steps across all of its productions.
+pub(super) fn auto_inline_variables(grammar: &mut SyntaxGrammar, max_size: usize) {
+    let mut candidates = Vec::new();
+    for (i, variable) in grammar.variables.iter().enumerate().skip(1) {
+        let symbol = Symbol::non_terminal(i);
+        if variable.kind != VariableType::Hidden
+            || grammar.variables_to_inline.contains(&symbol)
+            || grammar.supertype_symbols.contains(&symbol)
+            || grammar.extra_symbols.contains(&symbol)
+            || grammar
+                .expected_conflicts
+                .iter()
+                .any(|conflict| conflict.contains(&symbol))
+            || grammar.precedence_orderings.iter().any(|ordering| {
+                ordering
+                    .iter()
+                    .any(|entry| matches!(entry, PrecedenceEntry::Symbol(name) if *name == variable.name))
+            })
+        {
+            continue;
+        }
+
+        let size = variable
+            .productions
+            .iter()
+            .map(|p| p.steps.len())
+            .sum::<usize>();
+        if size > max_size || variable.productions.iter().any(|p| p.steps.is_empty()) {
+            continue;
+        }
+
+        let is_referenced_with_metadata = grammar.variables.iter().any(|v| {
+            v.productions.iter().any(|p| {
+                p.steps.iter().any(|step| {
+                    step.symbol == symbol && (step.alias.is_some() || step.field_name.is_some())
+                })
+            })
+        });
+        if is_referenced_with_metadata {
+            continue;
+        }
+
+        candidates.push(symbol);
+    }
+
+    // Inlining replaces each reference to a rule with the rule's productions, so a rule
+    // that can reach itself through inlined rules would be expanded forever.
+    let is_inlined = |symbol: Symbol| {
+        candidates.contains(&symbol) || grammar.variables_to_inline.contains(&symbol)
+    };
+    let recursive_candidates = candidates
+        .iter()
+        .copied()
+        .filter(|&symbol| reaches_itself(grammar, symbol, is_inlined))
+        .collect::<Vec<_>>();
+
+    for symbol in candidates {
+        if !recursive_candidates.contains(&symbol) {
+            info!(
+                "auto-inlining rule {}",
+                grammar.variables[symbol.index].name
+            );
+            grammar.variables_to_inline.push(symbol);
+        }
+    }
+}
+
+/// Whether a rule refers to itself, either directly or through the rules for which
+/// `is_inlined` returns true.
+fn reaches_itself(
+    grammar: &SyntaxGrammar,
+    symbol: Symbol,
+    is_inlined: impl Fn(Symbol) -> bool,
+) -> bool {
+    let mut visited = vec![symbol];
+    let mut stack = vec![symbol];
+    while let Some(current) = stack.pop() {
+        for step in grammar.variables[current.index]
+            .productions
+            .iter()
+            .flat_map(|p| &p.steps)
+        {
+            if step.symbol == symbol {
+                return true;
+            }
+            if step.symbol.is_non_terminal()
+                && is_inlined(step.symbol)
+                && !visited.contains(&step.symbol)
+            {
+                visited.push(step.symbol);
+                stack.push(step.symbol);
+            }
+        }
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::generate::grammars::VariableType;
 
     #[test]
     fn test_flatten_grammar() {
@@ -433,6 +559,7 @@ mod tests {
             precedence_orderings: Vec::new(),
             external_tokens: Vec::new(),
             supertype_symbols: Vec::new(),
+            deprecated_symbols: Vec::new(),
             word_token: None,
             variables: vec![Variable {
                 name: "test".to_string(),
@@ -450,4 +577,74 @@ mod tests {
             "Rule `test` cannot be inlined because it contains a reference to itself.",
         );
     }
+
+    #[test]
+    fn test_auto_inline_variables() {
+        let mut grammar = flatten_grammar(ExtractedSyntaxGrammar {
+            variables: vec![
+                Variable::named(
+                    "program",
+                    Rule::seq(vec![
+                        Rule::non_terminal(1),
+                        Rule::non_terminal(2),
+                        Rule::alias(Rule::non_terminal(3), "renamed".to_string(), true),
+                        Rule::non_terminal(4),
+                    ]),
+                ),
+                Variable::hidden(
+                    "_small",
+                    Rule::choice(vec![Rule::terminal(0), Rule::terminal(1)]),
+                ),
+                Variable::hidden(
+                    "_large",
+                    Rule::seq(vec![
+                        Rule::terminal(0),
+                        Rule::terminal(1),
+                        Rule::terminal(2),
+                    ]),
+                ),
+                Variable::hidden("_aliased", Rule::terminal(0)),
+                Variable::named("visible", Rule::terminal(0)),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        auto_inline_variables(&mut grammar, 2);
+        assert_eq!(grammar.variables_to_inline, vec![Symbol::non_terminal(1)]);
+    }
+
+    #[test]
+    fn test_auto_inline_variables_with_mutual_recursion() {
+        // source_file: repeat(_a), _a: seq("(", _b, ")"), _b: choice(_a, "x")
+        let mut grammar = flatten_grammar(ExtractedSyntaxGrammar {
+            variables: vec![
+                Variable::named(
+                    "source_file",
+                    Rule::choice(vec![
+                        Rule::non_terminal(1),
+                        Rule::seq(vec![Rule::non_terminal(0), Rule::non_terminal(1)]),
+                    ]),
+                ),
+                Variable::hidden(
+                    "_a",
+                    Rule::seq(vec![
+                        Rule::terminal(0),
+                        Rule::non_terminal(2),
+                        Rule::terminal(1),
+                    ]),
+                ),
+                Variable::hidden(
+                    "_b",
+                    Rule::choice(vec![Rule::non_terminal(1), Rule::terminal(2)]),
+                ),
+                Variable::hidden("_c", Rule::terminal(2)),
+            ],
+            ..Default::default()
+        })
+        .unwrap();
+
+        auto_inline_variables(&mut grammar, 3);
+        assert_eq!(grammar.variables_to_inline, vec![Symbol::non_terminal(3)]);
+    }
 }
@@ -1,16 +1,29 @@
-use anyhow::{anyhow, Result};
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use log::warn;
 
 use super::InternedGrammar;
 use crate::generate::{
-    grammars::{InputGrammar, Variable, VariableType},
+    error::{GrammarError, GrammarErrorKind},
+    grammars::{InputGrammar, PrecedenceEntry, Variable, VariableType},
     rules::{Rule, Symbol},
 };
 
 pub(super) fn intern_symbols(grammar: &InputGrammar) -> Result<InternedGrammar> {
-    let interner = Interner { grammar };
+    let interner = Interner::new(grammar);
 
     if variable_type_for_name(&grammar.variables[0].name) == VariableType::Hidden {
-        return Err(anyhow!("A grammar's start rule must be visible."));
+        return Err(GrammarError::new(
+            GrammarErrorKind::HiddenStartRule,
+            "A grammar's start rule must be visible.",
+        )
+        .with_symbols([&grammar.variables[0].name])
+        .with_suggestion(format!(
+            "Rename `{}` so that its name doesn't start with an underscore",
+            grammar.variables[0].name
+        ))
+        .into());
     }
 
     let mut variables = Vec::with_capacity(grammar.variables.len());
@@ -35,7 +48,41 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> Result<InternedGrammar>
 
     let mut extra_symbols = Vec::with_capacity(grammar.extra_symbols.len());
     for extra_token in &grammar.extra_symbols {
-        extra_symbols.push(interner.intern_rule(extra_token, None)?);
+        let rule = match extra_token {
+            Rule::NamedSymbol(name) => Rule::Symbol(interner.intern_name(name).ok_or_else(|| {
+                GrammarError::new(
+                    GrammarErrorKind::UndefinedSymbol,
+                    format!("Undefined symbol `{name}` in `extras`. Extras must refer to rules defined in the grammar's `rules` or `externals`."),
+                )
+                .with_symbols([name])
+            })?),
+            _ if references_symbol(extra_token) => {
+                return Err(GrammarError::new(
+                    GrammarErrorKind::InvalidExtra,
+                    format!(
+                        "Invalid rule in `extras`: {}. An extra must be either a reference to a rule (e.g. `$.comment`) or a token (a string, a regex, or a `token(...)` rule). To use a rule that contains other rules as an extra, define it in `rules` and reference it by name.",
+                        describe_extra(extra_token),
+                    ),
+                )
+                .with_suggestion(
+                    "Define the extra as a rule in `rules`, and reference it by name in `extras`",
+                )
+                .into());
+            }
+            _ => interner.intern_rule(extra_token, None)?,
+        };
+        if extra_symbols.contains(&rule) {
+            return Err(GrammarError::new(
+                GrammarErrorKind::InvalidExtra,
+                format!(
+                    "Duplicate entry in `extras`: {}. Each extra may only be listed once.",
+                    describe_extra(extra_token),
+                ),
+            )
+            .with_suggestion("Remove the duplicate entry from `extras`")
+            .into());
+        }
+        extra_symbols.push(rule);
     }
 
     let mut supertype_symbols = Vec::with_capacity(grammar.supertype_symbols.len());
@@ -43,7 +90,16 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> Result<InternedGrammar>
         supertype_symbols.push(
             interner
                 .intern_name(supertype_symbol_name)
-                .ok_or_else(|| anyhow!("Undefined symbol `{supertype_symbol_name}`"))?,
+                .ok_or_else(|| undefined_symbol_error(supertype_symbol_name, ""))?,
+        );
+    }
+
+    let mut deprecated_symbols = Vec::with_capacity(grammar.deprecated_symbols.len());
+    for name in &grammar.deprecated_symbols {
+        deprecated_symbols.push(
+            interner
+                .intern_name(name)
+                .ok_or_else(|| undefined_symbol_error(name, " in `deprecated`"))?,
         );
     }
 
@@ -54,7 +110,7 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> Result<InternedGrammar>
             interned_conflict.push(
                 interner
                     .intern_name(name)
-                    .ok_or_else(|| anyhow!("Undefined symbol `{name}`"))?,
+                    .ok_or_else(|| undefined_symbol_error(name, ""))?,
             );
         }
         expected_conflicts.push(interned_conflict);
@@ -72,7 +128,7 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> Result<InternedGrammar>
         word_token = Some(
             interner
                 .intern_name(name)
-                .ok_or_else(|| anyhow!("Undefined symbol `{name}`"))?,
+                .ok_or_else(|| undefined_symbol_error(name, ""))?,
         );
     }
 
@@ -82,6 +138,29 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> Result<InternedGrammar>
         }
     }
 
+    // Remind the grammar's author of the rules that still refer to deprecated
+    // rules, so that those references can be migrated before the rules are removed.
+    for (i, variable) in variables.iter().enumerate() {
+        if deprecated_symbols.contains(&Symbol::non_terminal(i)) {
+            continue;
+        }
+        let mut symbols = Vec::new();
+        referenced_symbols(&variable.rule, &mut symbols);
+        for symbol in symbols {
+            if deprecated_symbols.contains(&symbol) {
+                let name = if symbol.is_external() {
+                    &external_tokens[symbol.index].name
+                } else {
+                    &variables[symbol.index].name
+                };
+                warn!(
+                    "rule `{}` refers to the deprecated rule `{name}`",
+                    variable.name
+                );
+            }
+        }
+    }
+
     Ok(InternedGrammar {
         variables,
         external_tokens,
@@ -89,16 +168,120 @@ pub(super) fn intern_symbols(grammar: &InputGrammar) -> Result<InternedGrammar>
         expected_conflicts,
         variables_to_inline,
         supertype_symbols,
+        deprecated_symbols,
         word_token,
         precedence_orderings: grammar.precedence_orderings.clone(),
     })
 }
 
+/// Give rules and external tokens the names in `renames`, so that the nodes they
+/// produce are published under a different name than the one used in the grammar.
+/// References between rules have already been resolved by this point, so only the
+/// names of the variables themselves change.
+pub(super) fn rename_symbols(
+    grammar: &mut InternedGrammar,
+    renames: &HashMap<String, String>,
+) -> Result<()> {
+    if renames.is_empty() {
+        return Ok(());
+    }
+
+    let mut renames = renames.iter().collect::<Vec<_>>();
+    renames.sort_unstable();
+    for (from, to) in &renames {
+        if to.is_empty() {
+            return Err(GrammarError::new(
+                GrammarErrorKind::InvalidRename,
+                format!("Cannot rename `{from}` to an empty name"),
+            )
+            .with_symbols([from])
+            .into());
+        }
+        if variable_type_for_name(from) != variable_type_for_name(to) {
+            return Err(GrammarError::new(
+                GrammarErrorKind::InvalidRename,
+                format!("Cannot rename `{from}` to `{to}`: renaming must not change whether a rule is hidden"),
+            )
+            .with_symbols([from])
+            .into());
+        }
+        if !grammar.variables.iter().any(|v| v.name == **from)
+            && !grammar.external_tokens.iter().any(|t| t.name == **from)
+        {
+            return Err(GrammarError::new(
+                GrammarErrorKind::InvalidRename,
+                format!(
+                    "Cannot rename `{from}`: there is no rule or external token with that name"
+                ),
+            )
+            .with_symbols([from])
+            .into());
+        }
+    }
+
+    let renames = renames.into_iter().collect::<HashMap<_, _>>();
+    for variables in [&mut grammar.variables, &mut grammar.external_tokens] {
+        let mut names = HashSet::new();
+        for variable in variables.iter_mut() {
+            if let Some(to) = renames.get(&variable.name) {
+                variable.name.clone_from(to);
+            }
+            if !variable.name.is_empty() && !names.insert(variable.name.as_str()) {
+                return Err(GrammarError::new(
+                    GrammarErrorKind::InvalidRename,
+                    format!(
+                        "Cannot rename a rule to `{}`: another rule already has that name",
+                        variable.name
+                    ),
+                )
+                .with_symbols([&variable.name])
+                .into());
+            }
+        }
+    }
+
+    for entry in grammar.precedence_orderings.iter_mut().flatten() {
+        if let PrecedenceEntry::Symbol(name) = entry {
+            if let Some(to) = renames.get(name) {
+                name.clone_from(to);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Resolves the names that rules refer to each other by. The names are looked up in a table
+/// that is built once, because large grammars contain many thousands of references.
+///
+/// This is the only pass that handles rule names: from here on, rules refer to each other by
+/// [`Symbol`] ids, and each name is kept once, on its [`Variable`], for error messages and
+/// the generated code. Field names, alias values and named precedences are still strings.
 struct Interner<'a> {
-    grammar: &'a InputGrammar,
+    symbols_by_name: HashMap<&'a str, Symbol>,
 }
 
 impl<'a> Interner<'a> {
+    fn new(grammar: &'a InputGrammar) -> Self {
+        let mut symbols_by_name = HashMap::new();
+
+        // Rules take precedence over external tokens with the same name.
+        for (i, variable) in grammar.variables.iter().enumerate() {
+            symbols_by_name
+                .entry(variable.name.as_str())
+                .or_insert(Symbol::non_terminal(i));
+        }
+        for (i, external_token) in grammar.external_tokens.iter().enumerate() {
+            if let Rule::NamedSymbol(name) = external_token {
+                symbols_by_name
+                    .entry(name.as_str())
+                    .or_insert(Symbol::external(i));
+            }
+        }
+
+        Self { symbols_by_name }
+    }
+
     fn intern_rule(&self, rule: &Rule, name: Option<&str>) -> Result<Rule> {
         match rule {
             Rule::Choice(elements) => {
@@ -123,7 +306,7 @@ impl<'a> Interner<'a> {
                 params: params.clone(),
             }),
             Rule::NamedSymbol(name) => self.intern_name(name).map_or_else(
-                || Err(anyhow!("Undefined symbol `{name}`")),
+                || Err(undefined_symbol_error(name, "").into()),
                 |symbol| Ok(Rule::Symbol(symbol)),
             ),
             _ => Ok(rule.clone()),
@@ -131,35 +314,65 @@ impl<'a> Interner<'a> {
     }
 
     fn intern_name(&self, symbol: &str) -> Option<Symbol> {
-        for (i, variable) in self.grammar.variables.iter().enumerate() {
-            if variable.name == symbol {
-                return Some(Symbol::non_terminal(i));
-            }
-        }
-
-        for (i, external_token) in self.grammar.external_tokens.iter().enumerate() {
-            if let Rule::NamedSymbol(name) = external_token {
-                if name == symbol {
-                    return Some(Symbol::external(i));
-                }
-            }
-        }
-
-        None
+        self.symbols_by_name.get(symbol).copied()
     }
 
     // In the case of a seq or choice rule of 1 element in a hidden rule, weird
     // inconsistent behavior with queries can occur. So we should warn the user about it.
     fn check_single(&self, elements: &[Rule], name: Option<&str>) {
         if elements.len() == 1 && matches!(elements[0], Rule::String(_) | Rule::Pattern(_, _)) {
-            eprintln!(
-                "Warning: rule {} is just a `seq` or `choice` rule with a single element. This is unnecessary.",
+            warn!(
+                "rule {} is just a `seq` or `choice` rule with a single element. This is unnecessary.",
                 name.unwrap_or_default()
             );
         }
     }
 }
 
+fn references_symbol(rule: &Rule) -> bool {
+    match rule {
+        Rule::NamedSymbol(_) | Rule::Symbol(_) => true,
+        Rule::Choice(elements) | Rule::Seq(elements) => elements.iter().any(references_symbol),
+        Rule::Repeat(content) | Rule::Metadata { rule: content, .. } => references_symbol(content),
+        _ => false,
+    }
+}
+
+fn referenced_symbols(rule: &Rule, result: &mut Vec<Symbol>) {
+    match rule {
+        Rule::Symbol(symbol) if !result.contains(symbol) => result.push(*symbol),
+        Rule::Choice(elements) | Rule::Seq(elements) => {
+            for element in elements {
+                referenced_symbols(element, result);
+            }
+        }
+        Rule::Repeat(content) | Rule::Metadata { rule: content, .. } => {
+            referenced_symbols(content, result);
+        }
+        _ => {}
+    }
+}
+
+fn describe_extra(rule: &Rule) -> String {
+    match rule {
+        Rule::NamedSymbol(name) => format!("`{name}`"),
+        Rule::String(value) => format!("the string {value:?}"),
+        Rule::Pattern(value, _) => format!("the regex /{value}/"),
+        Rule::Seq(_) => "a `seq` rule".to_string(),
+        Rule::Choice(_) => "a `choice` rule".to_string(),
+        Rule::Repeat(_) => "a `repeat` rule".to_string(),
+        _ => "a rule".to_string(),
+    }
+}
+
+fn undefined_symbol_error(name: &str, location: &str) -> GrammarError {
+    GrammarError::new(
+        GrammarErrorKind::UndefinedSymbol,
+        format!("Undefined symbol `{name}`{location}"),
+    )
+    .with_symbols([name])
+}
+
 fn variable_type_for_name(name: &str) -> VariableType {
     if name.starts_with('_') {
         VariableType::Hidden
@@ -250,6 +463,105 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_grammar_with_invalid_extras() {
+        let mut grammar = build_grammar(vec![
+            Variable::named("x", Rule::named("y")),
+            Variable::named("y", Rule::pattern("[a-z]+", "")),
+        ]);
+        let error = |grammar: &InputGrammar| intern_symbols(grammar).err().unwrap().to_string();
+
+        grammar.extra_symbols = vec![Rule::named("comment")];
+        assert_eq!(
+            error(&grammar),
+            "Undefined symbol `comment` in `extras`. Extras must refer to rules defined in the grammar's `rules` or `externals`."
+        );
+
+        grammar.extra_symbols = vec![Rule::seq(vec![Rule::string("#"), Rule::named("y")])];
+        assert!(error(&grammar)
+            .starts_with("Invalid rule in `extras`: a `seq` rule. An extra must be either"));
+
+        grammar.extra_symbols = vec![
+            Rule::pattern("\\s", ""),
+            Rule::named("y"),
+            Rule::pattern("\\s", ""),
+        ];
+        assert_eq!(
+            error(&grammar),
+            "Duplicate entry in `extras`: the regex /\\s/. Each extra may only be listed once."
+        );
+
+        grammar.extra_symbols = vec![
+            Rule::pattern("\\s", ""),
+            Rule::named("y"),
+            Rule::token(Rule::seq(vec![Rule::string("#"), Rule::pattern(".*", "")])),
+        ];
+        assert!(intern_symbols(&grammar).is_ok());
+    }
+
+    #[test]
+    fn test_renaming_symbols() {
+        let mut input_grammar = build_grammar(vec![
+            Variable::named("program", Rule::named("generated_statement_list_1")),
+            Variable::named("generated_statement_list_1", Rule::named("identifier")),
+            Variable::named("identifier", Rule::pattern("[a-z]+", "")),
+        ]);
+        input_grammar.external_tokens.push(Rule::named("heredoc"));
+
+        let mut renames = HashMap::new();
+        renames.insert(
+            "generated_statement_list_1".to_string(),
+            "statements".to_string(),
+        );
+        renames.insert("heredoc".to_string(), "heredoc_body".to_string());
+        let mut grammar = intern_symbols(&input_grammar).unwrap();
+        rename_symbols(&mut grammar, &renames).unwrap();
+
+        assert_eq!(
+            grammar.variables,
+            vec![
+                Variable::named("program", Rule::non_terminal(1)),
+                Variable::named("statements", Rule::non_terminal(2)),
+                Variable::named("identifier", Rule::pattern("[a-z]+", "")),
+            ]
+        );
+        assert_eq!(grammar.external_tokens[0].name, "heredoc_body");
+
+        input_grammar.precedence_orderings = vec![vec![
+            PrecedenceEntry::Symbol("generated_statement_list_1".to_string()),
+            PrecedenceEntry::Name("and".to_string()),
+        ]];
+        let mut grammar = intern_symbols(&input_grammar).unwrap();
+        rename_symbols(&mut grammar, &renames).unwrap();
+        assert_eq!(
+            grammar.precedence_orderings,
+            vec![vec![
+                PrecedenceEntry::Symbol("statements".to_string()),
+                PrecedenceEntry::Name("and".to_string()),
+            ]]
+        );
+
+        let mut renames = HashMap::new();
+        renames.insert("identifier".to_string(), "program".to_string());
+        let mut grammar = intern_symbols(&input_grammar).unwrap();
+        assert_eq!(
+            rename_symbols(&mut grammar, &renames)
+                .unwrap_err()
+                .to_string(),
+            "Cannot rename a rule to `program`: another rule already has that name"
+        );
+
+        let mut renames = HashMap::new();
+        renames.insert("identifier".to_string(), "_identifier".to_string());
+        let mut grammar = intern_symbols(&input_grammar).unwrap();
+        assert_eq!(
+            rename_symbols(&mut grammar, &renames)
+                .unwrap_err()
+                .to_string(),
+            "Cannot rename `identifier` to `_identifier`: renaming must not change whether a rule is hidden"
+        );
+    }
+
     fn build_grammar(variables: Vec<Variable>) -> InputGrammar {
         InputGrammar {
             variables,
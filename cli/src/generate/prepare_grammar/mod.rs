@@ -12,21 +12,27 @@ use std::{
     mem,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 pub(super) use flatten_grammar::symbol_is_used;
+use log::info;
+pub(super) use process_inlines::process_inlines;
 
 pub use self::expand_tokens::expand_tokens;
 use self::{
-    expand_repeats::expand_repeats, extract_default_aliases::extract_default_aliases,
-    extract_tokens::extract_tokens, flatten_grammar::flatten_grammar,
-    intern_symbols::intern_symbols, process_inlines::process_inlines,
+    expand_repeats::expand_repeats,
+    extract_default_aliases::extract_default_aliases,
+    extract_tokens::extract_tokens,
+    flatten_grammar::{auto_inline_variables, flatten_grammar},
+    intern_symbols::{intern_symbols, rename_symbols},
 };
 use super::{
+    error::{GrammarError, GrammarErrorKind},
     grammars::{
         ExternalToken, InlinedProductionMap, InputGrammar, LexicalGrammar, PrecedenceEntry,
         SyntaxGrammar, Variable,
     },
     rules::{AliasMap, Precedence, Rule, Symbol},
+    timings::Timings,
 };
 
 pub struct IntermediateGrammar<T, U> {
@@ -37,6 +43,7 @@ pub struct IntermediateGrammar<T, U> {
     external_tokens: Vec<U>,
     variables_to_inline: Vec<Symbol>,
     supertype_symbols: Vec<Symbol>,
+    deprecated_symbols: Vec<Symbol>,
     word_token: Option<Symbol>,
 }
 
@@ -60,6 +67,7 @@ impl<T, U> Default for IntermediateGrammar<T, U> {
             external_tokens: Vec::default(),
             variables_to_inline: Vec::default(),
             supertype_symbols: Vec::default(),
+            deprecated_symbols: Vec::default(),
             word_token: Option::default(),
         }
     }
@@ -67,8 +75,16 @@ impl<T, U> Default for IntermediateGrammar<T, U> {
 
 /// Transform an input grammar into separate components that are ready
 /// for parse table construction.
+///
+/// If `auto_inline_threshold` is given, hidden rules whose productions contain
+/// at most that many steps in total are inlined automatically. Rules named in
+/// `renames` are published under their new names. The time taken to expand the
+/// tokens is added to `timings`.
 pub fn prepare_grammar(
     input_grammar: &InputGrammar,
+    auto_inline_threshold: Option<usize>,
+    renames: &HashMap<String, String>,
+    timings: &mut Timings,
 ) -> Result<(
     SyntaxGrammar,
     LexicalGrammar,
@@ -77,13 +93,24 @@ pub fn prepare_grammar(
 )> {
     validate_precedences(input_grammar)?;
 
-    let interned_grammar = intern_symbols(input_grammar)?;
+    let mut interned_grammar = intern_symbols(input_grammar)?;
+    rename_symbols(&mut interned_grammar, renames)?;
     let (syntax_grammar, lexical_grammar) = extract_tokens(interned_grammar)?;
     let syntax_grammar = expand_repeats(syntax_grammar);
     let mut syntax_grammar = flatten_grammar(syntax_grammar)?;
-    let lexical_grammar = expand_tokens(lexical_grammar)?;
+    if let Some(threshold) = auto_inline_threshold {
+        auto_inline_variables(&mut syntax_grammar, threshold);
+    }
+    let lexical_grammar = timings.time("token expansion", || expand_tokens(lexical_grammar))?;
     let default_aliases = extract_default_aliases(&mut syntax_grammar, &lexical_grammar);
     let inlines = process_inlines(&syntax_grammar, &lexical_grammar)?;
+    // Unused tokens are common while a grammar is being written, and are harmless, so they
+    // are only mentioned when `--log` is passed.
+    for name in unused_token_names(&syntax_grammar, &lexical_grammar) {
+        info!(
+            "token `{name}` is defined but never used by any rule or extra. It still participates in token conflict analysis."
+        );
+    }
     Ok((syntax_grammar, lexical_grammar, inlines, default_aliases))
 }
 
@@ -102,7 +129,13 @@ fn validate_precedences(grammar: &InputGrammar) -> Result<()> {
             Rule::Metadata { rule, params } => {
                 if let Precedence::Name(n) = &params.precedence {
                     if !names.contains(n) {
-                        return Err(anyhow!("Undeclared precedence '{n}' in rule '{rule_name}'"));
+                        return Err(GrammarError::new(
+                            GrammarErrorKind::UndeclaredPrecedence,
+                            format!("Undeclared precedence '{n}' in rule '{rule_name}'"),
+                        )
+                        .with_symbols([rule_name, n])
+                        .with_suggestion(format!("Add '{n}' to one of the lists in `precedences`"))
+                        .into());
                     }
                 }
                 validate(rule_name, rule, names)?;
@@ -114,6 +147,9 @@ fn validate_precedences(grammar: &InputGrammar) -> Result<()> {
 
     // For any two precedence names `a` and `b`, if `a` comes before `b`
     // in some list, then it cannot come *after* `b` in any list.
+    let entry_name = |entry: &PrecedenceEntry| match entry {
+        PrecedenceEntry::Name(name) | PrecedenceEntry::Symbol(name) => name.clone(),
+    };
     let mut pairs = HashMap::new();
     for list in &grammar.precedence_orderings {
         for (i, mut entry1) in list.iter().enumerate() {
@@ -132,9 +168,14 @@ fn validate_precedences(grammar: &InputGrammar) -> Result<()> {
                     }
                     hash_map::Entry::Occupied(e) => {
                         if e.get() != &ordering {
-                            return Err(anyhow!(
-                                "Conflicting orderings for precedences {entry1} and {entry2}",
-                            ));
+                            return Err(GrammarError::new(
+                                GrammarErrorKind::ConflictingPrecedences,
+                                format!(
+                                    "Conflicting orderings for precedences {entry1} and {entry2}",
+                                ),
+                            )
+                            .with_symbols([entry_name(entry1), entry_name(entry2)])
+                            .into());
                         }
                     }
                 }
@@ -161,6 +202,30 @@ fn validate_precedences(grammar: &InputGrammar) -> Result<()> {
     Ok(())
 }
 
+/// Find the tokens in the lexical grammar that are not referenced from any
+/// production, extra, external token or the word token.
+fn unused_token_names<'a>(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+) -> Vec<&'a str> {
+    lexical_grammar
+        .variables
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| {
+            let symbol = Symbol::terminal(*i);
+            !symbol_is_used(&syntax_grammar.variables, symbol)
+                && !syntax_grammar.extra_symbols.contains(&symbol)
+                && syntax_grammar.word_token != Some(symbol)
+                && !syntax_grammar
+                    .external_tokens
+                    .iter()
+                    .any(|t| t.corresponding_internal_token == Some(symbol))
+        })
+        .map(|(_, variable)| variable.name.as_str())
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -249,4 +314,41 @@ mod tests {
             "Conflicting orderings for precedences 'a' and 'b'",
         );
     }
+
+    #[test]
+    fn test_unused_token_names() {
+        let grammar = InputGrammar {
+            variables: vec![
+                Variable {
+                    name: "program".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::repeat(Rule::named("identifier")),
+                },
+                Variable {
+                    name: "identifier".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[a-z]+", ""),
+                },
+                Variable {
+                    name: "number".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("[0-9]+", ""),
+                },
+                Variable {
+                    name: "comment".to_string(),
+                    kind: VariableType::Named,
+                    rule: Rule::pattern("#.*", ""),
+                },
+            ],
+            extra_symbols: vec![Rule::named("comment")],
+            ..Default::default()
+        };
+
+        let (syntax_grammar, lexical_grammar, _, _) =
+            prepare_grammar(&grammar, None, &HashMap::new(), &mut Timings::default()).unwrap();
+        assert_eq!(
+            unused_token_names(&syntax_grammar, &lexical_grammar),
+            vec!["number"]
+        );
+    }
 }
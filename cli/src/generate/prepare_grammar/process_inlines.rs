@@ -1,8 +1,9 @@
 use std::collections::HashMap;
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 
 use crate::generate::{
+    error::{GrammarError, GrammarErrorKind},
     grammars::{InlinedProductionMap, LexicalGrammar, Production, ProductionStep, SyntaxGrammar},
     rules::SymbolType,
 };
@@ -74,8 +75,11 @@ impl InlinedProductionMapBuilder {
                     |variable_index| {
                         &grammar.variables[variable_index].productions[step_id.production_index]
                     },
-                ) as *const Production;
-                ((production, step_id.step_index as u32), production_indices)
+                );
+                (
+                    InlinedProductionMap::key(production, step_id.step_index as u32),
+                    production_indices,
+                )
             })
             .collect();
 
@@ -187,29 +191,32 @@ impl InlinedProductionMapBuilder {
     }
 }
 
-pub(super) fn process_inlines(
+pub fn process_inlines(
     grammar: &SyntaxGrammar,
     lexical_grammar: &LexicalGrammar,
 ) -> Result<InlinedProductionMap> {
     for symbol in &grammar.variables_to_inline {
         match symbol.kind {
             SymbolType::External => {
-                return Err(anyhow!(
-                    "External token `{}` cannot be inlined",
-                    grammar.external_tokens[symbol.index].name
-                ))
+                let name = &grammar.external_tokens[symbol.index].name;
+                return Err(invalid_inline_error(
+                    name,
+                    format!("External token `{name}` cannot be inlined"),
+                ));
             }
             SymbolType::Terminal => {
-                return Err(anyhow!(
-                    "Token `{}` cannot be inlined",
-                    lexical_grammar.variables[symbol.index].name,
-                ))
+                let name = &lexical_grammar.variables[symbol.index].name;
+                return Err(invalid_inline_error(
+                    name,
+                    format!("Token `{name}` cannot be inlined"),
+                ));
             }
             SymbolType::NonTerminal if symbol.index == 0 => {
-                return Err(anyhow!(
-                    "Rule `{}` cannot be inlined because it is the first rule",
-                    grammar.variables[symbol.index].name,
-                ))
+                let name = &grammar.variables[symbol.index].name;
+                return Err(invalid_inline_error(
+                    name,
+                    format!("Rule `{name}` cannot be inlined because it is the first rule"),
+                ));
             }
             _ => {}
         }
@@ -222,6 +229,13 @@ pub(super) fn process_inlines(
     .build(grammar))
 }
 
+fn invalid_inline_error(name: &str, message: String) -> anyhow::Error {
+    GrammarError::new(GrammarErrorKind::InvalidInline, message)
+        .with_symbols([name])
+        .with_suggestion(format!("Remove `{name}` from `inline`"))
+        .into()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,316 @@
+//! Loading `grammar.js` with the QuickJS engine that is built into the CLI, which is the
+//! `native` JavaScript runtime.
+//!
+//! `dsl.js` runs the same way as in Node.js, with the parts of the Node.js module system
+//! that grammars use: `require` and `module.exports` in CommonJS files, `import` in ES
+//! modules, and packages in `node_modules` directories. As in Node.js, a `.js` file is an
+//! ES module when the nearest `package.json` has `"type": "module"`. CommonJS files are run
+//! as strict mode code.
+
+use std::{
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use rquickjs::{
+    loader::{Loader, Resolver},
+    module::Declared,
+    CatchResultExt, Context, Ctx, Exception, Function, Module, Object, Runtime,
+};
+use semver::Version;
+
+/// The prefix of the module names of CommonJS files that are imported from ES modules.
+/// Such a module only re-exports the file's `module.exports`, and the file itself is run
+/// by `require`.
+const COMMONJS_PREFIX: &str = "commonjs:";
+
+/// Evaluate a `grammar.js` file, and return the grammar as JSON.
+pub fn load_grammar_file(grammar_path: &Path, cli_version: &Version) -> Result<String> {
+    let runtime = Runtime::new()?;
+    runtime.set_loader(ModuleResolver, ModuleLoader);
+    let context = Context::full(&runtime)?;
+    context.with(|ctx| {
+        evaluate_grammar(&ctx, grammar_path, cli_version)
+            .catch(&ctx)
+            .map_err(|error| anyhow!("{}", error.to_string().trim_end()))
+    })
+}
+
+fn evaluate_grammar(
+    ctx: &Ctx<'_>,
+    grammar_path: &Path,
+    cli_version: &Version,
+) -> rquickjs::Result<String> {
+    let globals = ctx.globals();
+    globals.set("TREE_SITTER_CLI_VERSION_MAJOR", cli_version.major)?;
+    globals.set("TREE_SITTER_CLI_VERSION_MINOR", cli_version.minor)?;
+    globals.set("TREE_SITTER_CLI_VERSION_PATCH", cli_version.patch)?;
+
+    let host = Object::new(ctx.clone())?;
+    host.set("grammarPath", grammar_path.to_string_lossy().as_ref())?;
+    host.set("print", Function::new(ctx.clone(), print)?)?;
+    host.set("readFile", Function::new(ctx.clone(), read_file)?)?;
+    host.set("resolve", Function::new(ctx.clone(), resolve_specifier)?)?;
+    host.set("compile", Function::new(ctx.clone(), compile_commonjs)?)?;
+    globals.set("__treeSitter", host.clone())?;
+
+    for (name, source) in [
+        ("prelude.js", include_str!("./quickjs_prelude.js")),
+        ("dsl.js", include_str!("./dsl.js")),
+    ] {
+        let (_, promise) = Module::declare(ctx.clone(), name, source)?.eval()?;
+        promise.finish::<()>()?;
+    }
+    host.get("output")
+}
+
+fn print(text: String, is_error: bool) {
+    if is_error {
+        eprintln!("{text}");
+    } else {
+        println!("{text}");
+    }
+}
+
+fn read_file(ctx: Ctx<'_>, path: String) -> rquickjs::Result<String> {
+    fs::read_to_string(&path)
+        .map_err(|error| Exception::throw_message(&ctx, &format!("Failed to read {path}: {error}")))
+}
+
+fn resolve_specifier(ctx: Ctx<'_>, base: String, specifier: String) -> rquickjs::Result<String> {
+    resolve(Path::new(&base), &specifier)
+        .map(|path| path.to_string_lossy().into_owned())
+        .map_err(|error| Exception::throw_message(&ctx, &error.to_string()))
+}
+
+/// Compile a CommonJS file into a function that takes the `module`, `exports` and `require`
+/// of the file, and runs it.
+fn compile_commonjs(ctx: Ctx<'_>, path: String) -> rquickjs::Result<Function<'_>> {
+    if is_es_module(Path::new(&path)) {
+        return Err(Exception::throw_message(
+            &ctx,
+            &format!(
+                "{path} is an ES module, so it must be loaded with `import` instead of `require`"
+            ),
+        ));
+    }
+    let source = read_file(ctx.clone(), path.clone())?;
+    let dirname = Path::new(&path).parent().unwrap_or(Path::new(""));
+    // The wrapper is kept on the first line, so that line numbers in errors match the file.
+    let wrapper = format!(
+        "export default function (module, exports, require) {{ const __filename = {}, __dirname = {}; {source}\n}}",
+        json_string(&path),
+        json_string(&dirname.to_string_lossy()),
+    );
+    let (module, promise) = Module::declare(ctx, path, wrapper)?.eval()?;
+    promise.finish::<()>()?;
+    module.get("default")
+}
+
+struct ModuleResolver;
+
+impl Resolver for ModuleResolver {
+    fn resolve<'js>(&mut self, ctx: &Ctx<'js>, base: &str, name: &str) -> rquickjs::Result<String> {
+        let path = resolve(Path::new(base), name)
+            .map_err(|error| Exception::throw_message(ctx, &error.to_string()))?;
+        let path = path.to_string_lossy();
+        Ok(if is_es_module(Path::new(path.as_ref())) {
+            path.into_owned()
+        } else {
+            format!("{COMMONJS_PREFIX}{path}")
+        })
+    }
+}
+
+struct ModuleLoader;
+
+impl Loader for ModuleLoader {
+    fn load<'js>(&mut self, ctx: &Ctx<'js>, name: &str) -> rquickjs::Result<Module<'js, Declared>> {
+        let source = match name.strip_prefix(COMMONJS_PREFIX) {
+            Some(path) => format!(
+                "export default __treeSitter.require({});",
+                json_string(path)
+            ),
+            None => read_file(ctx.clone(), name.to_string())?,
+        };
+        Module::declare(ctx.clone(), name, source)
+    }
+}
+
+/// Find the file that a module specifier refers to, the way Node.js does: relative to the
+/// importing file, or as a package in a `node_modules` directory above it.
+fn resolve(base: &Path, specifier: &str) -> Result<PathBuf> {
+    let directory = base.parent().unwrap_or(Path::new(""));
+    let path = if Path::new(specifier).is_absolute()
+        || specifier.starts_with("./")
+        || specifier.starts_with("../")
+    {
+        resolve_path(&directory.join(specifier))
+    } else {
+        directory
+            .ancestors()
+            .find_map(|directory| resolve_path(&directory.join("node_modules").join(specifier)))
+    };
+    let path = path.ok_or_else(|| anyhow!("Cannot find module '{specifier}' from {base:?}"))?;
+    Ok(fs::canonicalize(path)?)
+}
+
+/// Find the file that a path refers to: the file itself, the file with a `.js` or `.json`
+/// extension added, or the `main` file or `index.js` of a directory.
+fn resolve_path(path: &Path) -> Option<PathBuf> {
+    if let Some(file) = resolve_file(path) {
+        return Some(file);
+    }
+    if !path.is_dir() {
+        return None;
+    }
+    read_package_json(path)
+        .as_ref()
+        .and_then(|package| package.get("main")?.as_str())
+        .and_then(|main| {
+            let main = path.join(main);
+            resolve_file(&main).or_else(|| resolve_file(&main.join("index")))
+        })
+        .or_else(|| resolve_file(&path.join("index")))
+}
+
+fn resolve_file(path: &Path) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.to_path_buf());
+    }
+    [".js", ".json"].into_iter().find_map(|extension| {
+        let mut file = OsString::from(path);
+        file.push(extension);
+        let file = PathBuf::from(file);
+        file.is_file().then_some(file)
+    })
+}
+
+/// Whether a file is an ES module rather than a CommonJS file, going by its extension and
+/// the `type` in the nearest `package.json`.
+fn is_es_module(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("mjs") => true,
+        Some("js") => path
+            .ancestors()
+            .skip(1)
+            .find_map(read_package_json)
+            .is_some_and(|package| package.get("type").and_then(|t| t.as_str()) == Some("module")),
+        _ => false,
+    }
+}
+
+fn read_package_json(directory: &Path) -> Option<serde_json::Value> {
+    let package_json = fs::read_to_string(directory.join("package.json")).ok()?;
+    serde_json::from_str(&package_json).ok()
+}
+
+fn json_string(value: &str) -> String {
+    serde_json::Value::from(value).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_files(files: &[(&str, &str)]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (path, contents) in files {
+            let path = dir.path().join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        dir
+    }
+
+    fn load(path: &Path) -> Result<serde_json::Value> {
+        let version = Version::parse(env!("CARGO_PKG_VERSION")).unwrap();
+        let grammar_json = load_grammar_file(&fs::canonicalize(path)?, &version)?;
+        Ok(serde_json::from_str(&grammar_json)?)
+    }
+
+    #[test]
+    fn test_load_commonjs_grammar() {
+        let dir = write_files(&[
+            (
+                "grammar.js",
+                "const base = require('base');
+                 const { keywords } = require('./keywords.json');
+                 const { list } = require('./helpers');
+                 module.exports = grammar(base, {
+                   name: 'lang',
+                   rules: { program: $ => choice(...keywords, list($.word)) },
+                 });",
+            ),
+            ("keywords.json", r#"{"keywords": ["if", "else"]}"#),
+            ("helpers.js", "exports.list = (rule) => seq(rule, repeat(seq(',', rule)));"),
+            ("node_modules/base/package.json", r#"{"main": "lib/index"}"#),
+            (
+                "node_modules/base/lib/index.js",
+                "module.exports = grammar({ name: 'base', rules: { program: $ => $.word, word: _ => /\\w+/ } });",
+            ),
+        ]);
+
+        let grammar = load(&dir.path().join("grammar.js")).unwrap();
+        assert_eq!(grammar["name"], "lang");
+        assert_eq!(grammar["inherits"], "base");
+        assert_eq!(grammar["rules"]["program"]["members"][0]["value"], "if");
+        assert_eq!(grammar["rules"]["program"]["members"][2]["type"], "SEQ");
+        assert_eq!(grammar["rules"]["word"]["value"], "\\w+");
+    }
+
+    #[test]
+    fn test_load_es_module_grammar() {
+        let dir = write_files(&[
+            ("package.json", r#"{"type": "module"}"#),
+            (
+                "grammar.js",
+                "import { list } from './helpers.js';
+                 import word from './word.cjs';
+                 export default grammar({
+                   name: 'lang',
+                   rules: { program: $ => list($.word), word: _ => word.pattern },
+                 });",
+            ),
+            (
+                "helpers.js",
+                "export const list = (rule) => seq(rule, repeat(seq(',', rule)));",
+            ),
+            ("word.cjs", "module.exports = { pattern: /[a-z]+/ };"),
+        ]);
+
+        let grammar = load(&dir.path().join("grammar.js")).unwrap();
+        assert_eq!(grammar["name"], "lang");
+        assert_eq!(grammar["rules"]["program"]["type"], "SEQ");
+        assert_eq!(grammar["rules"]["word"]["value"], "[a-z]+");
+    }
+
+    #[test]
+    fn test_load_grammar_with_errors() {
+        let dir = write_files(&[
+            ("missing.js", "require('./nope');"),
+            (
+                "undefined.js",
+                "module.exports = grammar({ name: 'lang', rules: { program: $ => $.word } });",
+            ),
+        ]);
+
+        let error = load(&dir.path().join("missing.js"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("Error: Cannot find module './nope'"),
+            "{error}"
+        );
+
+        let error = load(&dir.path().join("undefined.js"))
+            .unwrap_err()
+            .to_string();
+        assert!(
+            error.starts_with("Error: Undefined symbol 'word'"),
+            "{error}"
+        );
+    }
+}
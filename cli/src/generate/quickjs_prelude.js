@@ -0,0 +1,42 @@
+// The parts of the Node.js API that `dsl.js` and grammars use, for the QuickJS engine that
+// is built into the CLI. `__treeSitter` holds the functions that the CLI provides for them.
+
+const format = (args) => args
+  .map((arg) => (typeof arg === 'string' ? arg : JSON.stringify(arg) ?? String(arg)))
+  .join(' ');
+
+globalThis.console = {
+  log: (...args) => __treeSitter.print(format(args), false),
+  info: (...args) => __treeSitter.print(format(args), false),
+  warn: (...args) => __treeSitter.print(format(args), true),
+  error: (...args) => __treeSitter.print(format(args), true),
+};
+
+Error.captureStackTrace ??= () => {};
+
+globalThis.process = {
+  env: { TREE_SITTER_GRAMMAR_PATH: __treeSitter.grammarPath },
+  stdout: {
+    write: (output) => {
+      __treeSitter.output = output;
+    },
+  },
+};
+
+// CommonJS modules, by their canonical path, so that each file is only run once.
+const modules = new Map();
+
+__treeSitter.require = (path) => {
+  let module = modules.get(path);
+  if (!module) {
+    module = { exports: {} };
+    modules.set(path, module);
+    if (path.endsWith('.json')) {
+      module.exports = JSON.parse(__treeSitter.readFile(path));
+    } else {
+      const require = (specifier) => __treeSitter.require(__treeSitter.resolve(path, specifier));
+      __treeSitter.compile(path)(module, module.exports, require);
+    }
+  }
+  return module.exports;
+};
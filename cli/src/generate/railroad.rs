@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+
+use super::{grammars::InputGrammar, rules::Rule};
+
+/// The structure of each of a grammar's rules, in a form that maps directly onto the
+/// elements of a railroad diagram.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RailroadGrammar {
+    pub name: String,
+    pub rules: Vec<RailroadRule>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RailroadRule {
+    pub name: String,
+    pub diagram: RailroadNode,
+}
+
+/// An element of a railroad diagram. Precedence and associativity don't change the shape
+/// of a rule, so they are left out.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RailroadNode {
+    Sequence {
+        items: Vec<RailroadNode>,
+    },
+    Choice {
+        items: Vec<RailroadNode>,
+    },
+    Optional {
+        item: Box<RailroadNode>,
+    },
+    OneOrMore {
+        item: Box<RailroadNode>,
+    },
+    ZeroOrMore {
+        item: Box<RailroadNode>,
+    },
+    /// A string token.
+    Terminal {
+        text: String,
+    },
+    /// A regular expression token.
+    Pattern {
+        text: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        flags: String,
+    },
+    /// A reference to another rule.
+    NonTerminal {
+        name: String,
+    },
+    Field {
+        name: String,
+        item: Box<RailroadNode>,
+    },
+    /// Content that appears in the syntax tree under a different name.
+    Alias {
+        value: String,
+        named: bool,
+        item: Box<RailroadNode>,
+    },
+    /// Content that is lexed as a single token.
+    Token {
+        immediate: bool,
+        item: Box<RailroadNode>,
+    },
+    Skip,
+}
+
+/// Describe each rule of a grammar as a railroad diagram, in the order that they are
+/// defined, for generating reference documentation for a language.
+pub fn railroad_diagrams(grammar: &InputGrammar) -> RailroadGrammar {
+    RailroadGrammar {
+        name: grammar.name.clone(),
+        rules: grammar
+            .variables
+            .iter()
+            .map(|variable| RailroadRule {
+                name: variable.name.clone(),
+                diagram: railroad_node(&variable.rule),
+            })
+            .collect(),
+    }
+}
+
+pub fn render_railroad_json(grammar: &InputGrammar) -> String {
+    let mut json = serde_json::to_string_pretty(&railroad_diagrams(grammar)).unwrap();
+    json.push('\n');
+    json
+}
+
+fn railroad_node(rule: &Rule) -> RailroadNode {
+    match rule {
+        Rule::Blank => RailroadNode::Skip,
+        Rule::String(value) => RailroadNode::Terminal {
+            text: value.clone(),
+        },
+        Rule::Pattern(value, flags) => RailroadNode::Pattern {
+            text: value.clone(),
+            flags: flags.clone(),
+        },
+        Rule::NamedSymbol(name) => RailroadNode::NonTerminal { name: name.clone() },
+        // Interned symbols only appear after the grammar has been prepared.
+        Rule::Symbol(symbol) => RailroadNode::NonTerminal {
+            name: format!("{:?}", symbol.kind).to_lowercase() + &symbol.index.to_string(),
+        },
+        Rule::Seq(elements) => match elements.as_slice() {
+            [element] => railroad_node(element),
+            _ => RailroadNode::Sequence {
+                items: elements.iter().map(railroad_node).collect(),
+            },
+        },
+        Rule::Repeat(rule) => RailroadNode::OneOrMore {
+            item: Box::new(railroad_node(rule)),
+        },
+        Rule::Choice(elements) => {
+            // `optional` and `repeat` are both parsed as a choice with a blank.
+            let is_optional = elements.contains(&Rule::Blank);
+            let mut items = elements
+                .iter()
+                .filter(|element| **element != Rule::Blank)
+                .map(railroad_node)
+                .collect::<Vec<_>>();
+            let node = if items.len() == 1 {
+                items.pop().unwrap()
+            } else {
+                RailroadNode::Choice { items }
+            };
+            match node {
+                RailroadNode::OneOrMore { item } if is_optional => {
+                    RailroadNode::ZeroOrMore { item }
+                }
+                node if is_optional => RailroadNode::Optional {
+                    item: Box::new(node),
+                },
+                node => node,
+            }
+        }
+        Rule::Metadata { params, rule } => {
+            let mut node = railroad_node(rule);
+            if params.is_token {
+                node = RailroadNode::Token {
+                    immediate: params.is_main_token,
+                    item: Box::new(node),
+                };
+            }
+            if let Some(alias) = &params.alias {
+                node = RailroadNode::Alias {
+                    value: alias.value.clone(),
+                    named: alias.is_named,
+                    item: Box::new(node),
+                };
+            }
+            if let Some(field_name) = &params.field_name {
+                node = RailroadNode::Field {
+                    name: field_name.clone(),
+                    item: Box::new(node),
+                };
+            }
+            node
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::generate::parse_grammar::parse_grammar;
+
+    #[test]
+    fn test_railroad_diagrams() {
+        let grammar = parse_grammar(
+            r#"{
+                "name": "test",
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "statement"}},
+                    "statement": {
+                        "type": "SEQ",
+                        "members": [
+                            {"type": "FIELD", "name": "name", "content": {"type": "SYMBOL", "name": "identifier"}},
+                            {
+                                "type": "CHOICE",
+                                "members": [
+                                    {"type": "STRING", "value": "="},
+                                    {"type": "ALIAS", "value": "op", "named": false, "content": {"type": "STRING", "value": ":="}},
+                                    {"type": "BLANK"}
+                                ]
+                            },
+                            {"type": "PREC", "value": 1, "content": {"type": "REPEAT1", "content": {"type": "STRING", "value": ";"}}}
+                        ]
+                    },
+                    "identifier": {"type": "IMMEDIATE_TOKEN", "content": {"type": "PATTERN", "value": "[a-z]+", "flags": "i"}}
+                }
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(railroad_diagrams(&grammar)).unwrap(),
+            json!({
+                "name": "test",
+                "rules": [
+                    {
+                        "name": "program",
+                        "diagram": {
+                            "type": "zero_or_more",
+                            "item": {"type": "non_terminal", "name": "statement"}
+                        }
+                    },
+                    {
+                        "name": "statement",
+                        "diagram": {
+                            "type": "sequence",
+                            "items": [
+                                {
+                                    "type": "field",
+                                    "name": "name",
+                                    "item": {"type": "non_terminal", "name": "identifier"}
+                                },
+                                {
+                                    "type": "optional",
+                                    "item": {
+                                        "type": "choice",
+                                        "items": [
+                                            {"type": "terminal", "text": "="},
+                                            {
+                                                "type": "alias",
+                                                "value": "op",
+                                                "named": false,
+                                                "item": {"type": "terminal", "text": ":="}
+                                            }
+                                        ]
+                                    }
+                                },
+                                {
+                                    "type": "one_or_more",
+                                    "item": {"type": "terminal", "text": ";"}
+                                }
+                            ]
+                        }
+                    },
+                    {
+                        "name": "identifier",
+                        "diagram": {
+                            "type": "token",
+                            "immediate": true,
+                            "item": {"type": "pattern", "text": "[a-z]+", "flags": "i"}
+                        }
+                    }
+                ]
+            })
+        );
+    }
+}
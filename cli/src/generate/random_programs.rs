@@ -0,0 +1,216 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use super::{
+    grammars::{LexicalGrammar, SyntaxGrammar, VariableType},
+    nfa::{CharacterSet, NfaState},
+    rules::{AliasMap, Symbol},
+    skeleton_corpus::{longest_match, CorpusGenerator, UNREACHABLE},
+};
+
+/// Beyond this depth, or once a program has this many tokens, non-terminals are
+/// derived as small as possible, so that the programs stay reasonably short.
+const MAX_DEPTH: usize = 16;
+const MAX_TOKEN_COUNT: usize = 256;
+
+/// Tokens whose random text would be longer than this use their shortest text instead.
+const MAX_TOKEN_LENGTH: usize = 16;
+
+struct ProgramGenerator<'a> {
+    generator: CorpusGenerator<'a>,
+    string_start_states: Vec<u32>,
+    rng: StdRng,
+}
+
+/// Generate random programs by deriving the grammar's start rule, choosing between
+/// the productions of each rule and between the texts that each token matches at
+/// random.
+///
+/// The programs follow the grammar's rules and token definitions, but the parser can
+/// still reject some of them where the grammar relies on precedences or conflicts to
+/// rule out ambiguous programs. Productions that need tokens from the external scanner
+/// are never chosen.
+pub fn render_random_programs(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    default_aliases: &AliasMap,
+    count: usize,
+    seed: u64,
+) -> Vec<String> {
+    let generator = CorpusGenerator::new(syntax_grammar, lexical_grammar, default_aliases);
+    if generator.derivation_sizes[0] == UNREACHABLE {
+        return Vec::new();
+    }
+
+    let string_start_states = lexical_grammar
+        .variables
+        .iter()
+        .filter(|variable| variable.kind == VariableType::Anonymous)
+        .map(|variable| variable.start_state)
+        .collect();
+    let mut program_generator = ProgramGenerator {
+        generator,
+        string_start_states,
+        rng: StdRng::seed_from_u64(seed),
+    };
+    (0..count)
+        .map(|_| {
+            let mut tokens = Vec::new();
+            program_generator.expand(Symbol::non_terminal(0), 0, &mut tokens);
+            program_generator.generator.join_tokens(&tokens)
+        })
+        .collect()
+}
+
+impl<'a> ProgramGenerator<'a> {
+    fn expand(&mut self, symbol: Symbol, depth: usize, tokens: &mut Vec<(usize, String)>) {
+        if symbol.is_terminal() {
+            let text = self.random_token(symbol.index);
+            tokens.push((symbol.index, text));
+            return;
+        }
+
+        let steps = if depth >= MAX_DEPTH || tokens.len() >= MAX_TOKEN_COUNT {
+            self.generator.choose_production(symbol, None).0
+        } else {
+            let productions = self.generator.syntax_grammar.variables[symbol.index]
+                .productions
+                .iter()
+                .filter(|production| {
+                    self.generator
+                        .production_size(&production.steps, &self.generator.derivation_sizes)
+                        != UNREACHABLE
+                })
+                .collect::<Vec<_>>();
+            &productions[self.rng.gen_range(0..productions.len())].steps
+        };
+        for step in steps {
+            self.expand(step.symbol, depth + 1, tokens);
+        }
+    }
+
+    /// Choose a random text for the given token by walking its NFA. Strings, and tokens
+    /// whose random text would be lexed as one of the grammar's strings instead, use
+    /// their shortest text.
+    fn random_token(&mut self, index: usize) -> String {
+        let lexical_grammar = self.generator.lexical_grammar;
+        let variable = &lexical_grammar.variables[index];
+        let sample = self.generator.token_samples[index].clone().unwrap();
+        if variable.kind == VariableType::Anonymous {
+            return sample;
+        }
+
+        let nfa = &lexical_grammar.nfa;
+        let mut text = String::new();
+        let mut state_id = variable.start_state;
+        loop {
+            match &nfa.states[state_id as usize] {
+                NfaState::Accept { .. } => break,
+                NfaState::Split(a, b) => state_id = if self.rng.gen() { *a } else { *b },
+                NfaState::Advance {
+                    chars,
+                    state_id: next_state_id,
+                    is_sep,
+                    ..
+                } => {
+                    if *is_sep || text.len() >= MAX_TOKEN_LENGTH {
+                        return sample;
+                    }
+                    let Some(c) = self.random_char(chars) else {
+                        return sample;
+                    };
+                    text.push(c);
+                    state_id = *next_state_id;
+                }
+            }
+        }
+
+        if text.is_empty()
+            || longest_match(nfa, self.string_start_states.clone(), &text) == text.len()
+        {
+            return sample;
+        }
+        text
+    }
+
+    /// Choose a random printable ASCII character from the set, if there is one.
+    fn random_char(&mut self, chars: &CharacterSet) -> Option<char> {
+        let candidates = ('!'..='~')
+            .filter(|c| chars.contains(*c))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return chars.chars().find(|c| !c.is_control());
+        }
+        Some(candidates[self.rng.gen_range(0..candidates.len())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::generate::{
+        parse_grammar::parse_grammar, prepare_grammar::prepare_grammar, timings::Timings,
+    };
+
+    #[test]
+    fn test_render_random_programs() {
+        let input_grammar = parse_grammar(
+            r#"{
+                "name": "test",
+                "extras": [{"type": "PATTERN", "value": "\\s"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "statement"}},
+                    "statement": {"type": "SEQ", "members": [
+                        {"type": "STRING", "value": "let"},
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": "="},
+                        {"type": "SYMBOL", "name": "number"},
+                        {"type": "STRING", "value": ";"}
+                    ]},
+                    "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                    "number": {"type": "PATTERN", "value": "\\d+"}
+                }
+            }"#,
+        )
+        .unwrap();
+        let (syntax_grammar, lexical_grammar, _, simple_aliases) = prepare_grammar(
+            &input_grammar,
+            None,
+            &HashMap::new(),
+            &mut Timings::default(),
+        )
+        .unwrap();
+
+        let programs =
+            render_random_programs(&syntax_grammar, &lexical_grammar, &simple_aliases, 20, 0);
+        assert_eq!(programs.len(), 20);
+        assert!(programs
+            .iter()
+            .any(|program| program.len() > "let a=0;".len()));
+        for program in &programs {
+            let mut text = program.as_str();
+            while !text.is_empty() {
+                let statement_end = text.find(';').unwrap() + 1;
+                let statement = &text[..statement_end];
+                text = &text[statement_end..];
+
+                let (name, value) = statement
+                    .strip_prefix("let ")
+                    .unwrap()
+                    .strip_suffix(';')
+                    .unwrap()
+                    .split_once('=')
+                    .unwrap();
+                assert!(!name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase()));
+                assert!(name != "let");
+                assert!(!value.is_empty() && value.chars().all(|c| c.is_ascii_digit()));
+            }
+        }
+
+        assert_eq!(
+            programs,
+            render_random_programs(&syntax_grammar, &lexical_grammar, &simple_aliases, 20, 0)
+        );
+    }
+}
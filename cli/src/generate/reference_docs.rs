@@ -0,0 +1,515 @@
+use std::fmt::Write;
+
+use anyhow::{Context, Result};
+
+use super::{
+    node_types::{FieldInfoJSON, NodeInfoJSON, NodeTypeJSON},
+    parse_grammar::parse_grammar,
+    rules::Rule,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocsFormat {
+    Markdown,
+    Html,
+}
+
+/// An example of the language's syntax, with the syntax tree that it is expected to parse
+/// to as an S-expression, like the tests in a grammar's corpus.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DocsExample {
+    pub name: String,
+    pub source: String,
+    pub tree: String,
+}
+
+enum Block {
+    Heading(usize, Vec<Inline>),
+    Paragraph(Vec<Inline>),
+    Code(String),
+    Table(Vec<&'static str>, Vec<Vec<Vec<Inline>>>),
+}
+
+enum Inline {
+    Text(String),
+    Code(String),
+}
+
+/// Render a reference of a grammar's syntax from its `grammar.json` and `node-types.json`:
+/// each named node type, in alphabetical order, with its fields, children, supertypes and
+/// the definition of its token, followed by the keywords and punctuation. Each node type is illustrated by the
+/// first of the examples whose tree contains it.
+pub fn render_reference_docs(
+    grammar_json: &str,
+    node_types_json: &str,
+    examples: &[DocsExample],
+    format: DocsFormat,
+) -> Result<String> {
+    let grammar = parse_grammar(grammar_json)?;
+    let node_types = serde_json::from_str::<Vec<NodeInfoJSON>>(node_types_json)
+        .with_context(|| "Failed to parse the node types")?;
+
+    let mut blocks = vec![Block::Heading(
+        1,
+        vec![Inline::Text(format!("{} syntax reference", grammar.name))],
+    )];
+
+    blocks.push(Block::Heading(
+        2,
+        vec![Inline::Text("Node types".to_string())],
+    ));
+    let mut named_types = node_types
+        .iter()
+        .filter(|node_type| node_type.named)
+        .collect::<Vec<_>>();
+    named_types.sort_unstable_by(|a, b| a.kind.cmp(&b.kind));
+    for node_type in named_types {
+        blocks.push(Block::Heading(
+            3,
+            vec![Inline::Code(node_type.kind.clone())],
+        ));
+        if node_type.deprecated {
+            blocks.push(Block::Paragraph(vec![Inline::Text(
+                "Deprecated.".to_string(),
+            )]));
+        }
+        if let Some(subtypes) = &node_type.subtypes {
+            let mut paragraph = vec![Inline::Text("One of ".to_string())];
+            paragraph.extend(type_list(subtypes));
+            paragraph.push(Inline::Text(".".to_string()));
+            blocks.push(Block::Paragraph(paragraph));
+        }
+        if let Some(supertypes) = &node_type.supertypes {
+            let mut paragraph = vec![Inline::Text("A kind of ".to_string())];
+            paragraph.extend(type_list(supertypes));
+            paragraph.push(Inline::Text(".".to_string()));
+            blocks.push(Block::Paragraph(paragraph));
+        }
+        if let Some(fields) = node_type.fields.as_ref().filter(|f| !f.is_empty()) {
+            blocks.push(Block::Table(
+                vec!["Field", "Types", "Quantity"],
+                fields
+                    .iter()
+                    .map(|(name, field)| {
+                        vec![
+                            vec![Inline::Code(name.clone())],
+                            type_list(&field.types),
+                            vec![Inline::Text(quantity(field).to_string())],
+                        ]
+                    })
+                    .collect(),
+            ));
+        }
+        if let Some(children) = &node_type.children {
+            let mut paragraph = vec![Inline::Text("Children: ".to_string())];
+            paragraph.extend(type_list(&children.types));
+            paragraph.push(Inline::Text(format!(" ({})", quantity(children))));
+            blocks.push(Block::Paragraph(paragraph));
+        }
+
+        let is_leaf = node_type.fields.as_ref().map_or(true, |f| f.is_empty())
+            && node_type.children.is_none()
+            && node_type.subtypes.is_none();
+        if let Some(variable) = grammar
+            .variables
+            .iter()
+            .find(|variable| is_leaf && variable.name == node_type.kind)
+        {
+            blocks.push(Block::Paragraph(vec![Inline::Text("Matches:".to_string())]));
+            blocks.push(Block::Code(describe_rule(&variable.rule, false)));
+        }
+
+        if let Some(example) = examples
+            .iter()
+            .find(|example| tree_contains(&example.tree, &node_type.kind))
+        {
+            blocks.push(Block::Paragraph(vec![
+                Inline::Text("Example, from ".to_string()),
+                Inline::Code(example.name.clone()),
+                Inline::Text(":".to_string()),
+            ]));
+            blocks.push(Block::Code(
+                example
+                    .source
+                    .trim_start_matches(['\r', '\n'])
+                    .trim_end()
+                    .to_string(),
+            ));
+        }
+    }
+
+    let anonymous_types = node_types
+        .iter()
+        .filter(|node_type| !node_type.named)
+        .map(|node_type| NodeTypeJSON {
+            kind: node_type.kind.clone(),
+            named: false,
+        })
+        .collect::<Vec<_>>();
+    if !anonymous_types.is_empty() {
+        blocks.push(Block::Heading(
+            2,
+            vec![Inline::Text("Keywords and punctuation".to_string())],
+        ));
+        blocks.push(Block::Paragraph(type_list(&anonymous_types)));
+    }
+
+    Ok(match format {
+        DocsFormat::Markdown => render_markdown(&blocks),
+        DocsFormat::Html => render_html(&grammar.name, &blocks),
+    })
+}
+
+fn type_list(types: &[NodeTypeJSON]) -> Vec<Inline> {
+    let mut result = Vec::new();
+    for (i, node_type) in types.iter().enumerate() {
+        if i > 0 {
+            result.push(Inline::Text(", ".to_string()));
+        }
+        result.push(Inline::Code(if node_type.named {
+            node_type.kind.clone()
+        } else {
+            format!("\"{}\"", node_type.kind)
+        }));
+    }
+    result
+}
+
+const fn quantity(field: &FieldInfoJSON) -> &'static str {
+    match (field.required, field.multiple) {
+        (true, false) => "one",
+        (false, false) => "optional",
+        (true, true) => "one or more",
+        (false, true) => "zero or more",
+    }
+}
+
+/// Whether an S-expression contains a node of the given type.
+fn tree_contains(tree: &str, kind: &str) -> bool {
+    let start = format!("({kind}");
+    tree.match_indices(&start).any(|(i, _)| {
+        tree[i + start.len()..]
+            .chars()
+            .next()
+            .map_or(true, |c| c == ' ' || c == ')')
+    })
+}
+
+/// Describe a rule in a compact, regex-like notation. Nested choices and sequences are
+/// parenthesized where they would otherwise be ambiguous.
+fn describe_rule(rule: &Rule, is_nested: bool) -> String {
+    let parenthesize = |description: String| {
+        if is_nested {
+            format!("({description})")
+        } else {
+            description
+        }
+    };
+    match rule {
+        Rule::Blank => "blank".to_string(),
+        Rule::String(value) => format!("{value:?}"),
+        Rule::Pattern(value, flags) => format!("/{value}/{flags}"),
+        Rule::NamedSymbol(name) => name.clone(),
+        Rule::Symbol(symbol) => format!("{symbol:?}"),
+        Rule::Metadata { rule, .. } => describe_rule(rule, is_nested),
+        Rule::Repeat(rule) => format!("{}+", describe_rule(rule, true)),
+        Rule::Seq(elements) => parenthesize(
+            elements
+                .iter()
+                .map(|element| describe_rule(element, true))
+                .collect::<Vec<_>>()
+                .join(" "),
+        ),
+        Rule::Choice(elements) => {
+            let is_optional = elements.contains(&Rule::Blank);
+            let elements = elements
+                .iter()
+                .filter(|element| **element != Rule::Blank)
+                .collect::<Vec<_>>();
+            let description = match elements.as_slice() {
+                [Rule::Repeat(rule)] if is_optional => {
+                    return format!("{}*", describe_rule(rule, true));
+                }
+                [element] => describe_rule(element, is_nested || is_optional),
+                _ => {
+                    let alternatives = elements
+                        .iter()
+                        .map(|element| describe_rule(element, true))
+                        .collect::<Vec<_>>()
+                        .join(" | ");
+                    if is_nested || is_optional {
+                        format!("({alternatives})")
+                    } else {
+                        alternatives
+                    }
+                }
+            };
+            if is_optional {
+                format!("{description}?")
+            } else {
+                description
+            }
+        }
+    }
+}
+
+fn render_markdown(blocks: &[Block]) -> String {
+    let mut result = String::new();
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => {
+                writeln!(result, "{} {}", "#".repeat(*level), markdown_inline(text)).unwrap();
+            }
+            Block::Paragraph(text) => writeln!(result, "{}", markdown_inline(text)).unwrap(),
+            Block::Code(code) => {
+                let fence = "`".repeat(longest_backtick_run(code).max(2) + 1);
+                writeln!(result, "{fence}\n{code}\n{fence}").unwrap();
+            }
+            Block::Table(headers, rows) => {
+                writeln!(result, "| {} |", headers.join(" | ")).unwrap();
+                writeln!(result, "|{}", " --- |".repeat(headers.len())).unwrap();
+                for row in rows {
+                    let cells = row
+                        .iter()
+                        .map(|cell| markdown_inline(cell).replace('|', "\\|"))
+                        .collect::<Vec<_>>();
+                    writeln!(result, "| {} |", cells.join(" | ")).unwrap();
+                }
+            }
+        }
+        result.push('\n');
+    }
+    result.pop();
+    result
+}
+
+fn markdown_inline(text: &[Inline]) -> String {
+    text.iter()
+        .map(|inline| match inline {
+            Inline::Text(text) => text.clone(),
+            Inline::Code(code) => {
+                let ticks = "`".repeat(longest_backtick_run(code) + 1);
+                if code.starts_with('`') || code.ends_with('`') {
+                    format!("{ticks} {code} {ticks}")
+                } else {
+                    format!("{ticks}{code}{ticks}")
+                }
+            }
+        })
+        .collect()
+}
+
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+fn render_html(name: &str, blocks: &[Block]) -> String {
+    let mut result = String::new();
+    writeln!(result, "<!DOCTYPE html>").unwrap();
+    writeln!(result, "<html>").unwrap();
+    writeln!(result, "<head>").unwrap();
+    writeln!(result, "<meta charset=\"utf-8\">").unwrap();
+    writeln!(
+        result,
+        "<title>{} syntax reference</title>",
+        escape_html(name)
+    )
+    .unwrap();
+    writeln!(result, "</head>").unwrap();
+    writeln!(result, "<body>").unwrap();
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => {
+                let id = text
+                    .iter()
+                    .map(|inline| match inline {
+                        Inline::Text(text) | Inline::Code(text) => text.as_str(),
+                    })
+                    .collect::<String>();
+                writeln!(
+                    result,
+                    "<h{level} id=\"{}\">{}</h{level}>",
+                    escape_html(&id),
+                    html_inline(text)
+                )
+                .unwrap();
+            }
+            Block::Paragraph(text) => writeln!(result, "<p>{}</p>", html_inline(text)).unwrap(),
+            Block::Code(code) => {
+                writeln!(result, "<pre><code>{}</code></pre>", escape_html(code)).unwrap();
+            }
+            Block::Table(headers, rows) => {
+                writeln!(result, "<table>").unwrap();
+                let headers = headers
+                    .iter()
+                    .map(|header| format!("<th>{header}</th>"))
+                    .collect::<String>();
+                writeln!(result, "<tr>{headers}</tr>").unwrap();
+                for row in rows {
+                    let cells = row
+                        .iter()
+                        .map(|cell| format!("<td>{}</td>", html_inline(cell)))
+                        .collect::<String>();
+                    writeln!(result, "<tr>{cells}</tr>").unwrap();
+                }
+                writeln!(result, "</table>").unwrap();
+            }
+        }
+    }
+    writeln!(result, "</body>").unwrap();
+    writeln!(result, "</html>").unwrap();
+    result
+}
+
+fn html_inline(text: &[Inline]) -> String {
+    text.iter()
+        .map(|inline| match inline {
+            Inline::Text(text) => escape_html(text),
+            Inline::Code(code) => format!("<code>{}</code>", escape_html(code)),
+        })
+        .collect()
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate_parser, GenerateOptions};
+
+    const GRAMMAR_JSON: &str = r#"{
+        "name": "docs",
+        "extras": [{"type": "PATTERN", "value": "\\s"}],
+        "supertypes": ["_expression"],
+        "rules": {
+            "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "_expression"}},
+            "_expression": {"type": "CHOICE", "members": [
+                {"type": "SYMBOL", "name": "sum"},
+                {"type": "SYMBOL", "name": "number"}
+            ]},
+            "sum": {"type": "PREC_LEFT", "value": 1, "content": {"type": "SEQ", "members": [
+                {"type": "FIELD", "name": "left", "content": {"type": "SYMBOL", "name": "_expression"}},
+                {"type": "STRING", "value": "+"},
+                {"type": "FIELD", "name": "right", "content": {"type": "SYMBOL", "name": "_expression"}}
+            ]}},
+            "number": {"type": "TOKEN", "content": {"type": "SEQ", "members": [
+                {"type": "PATTERN", "value": "\\d+"},
+                {"type": "CHOICE", "members": [
+                    {"type": "SEQ", "members": [
+                        {"type": "STRING", "value": "."},
+                        {"type": "PATTERN", "value": "\\d+"}
+                    ]},
+                    {"type": "BLANK"}
+                ]}
+            ]}}
+        }
+    }"#;
+
+    #[test]
+    fn test_render_reference_docs_as_markdown() {
+        let output = generate_parser(GRAMMAR_JSON, &GenerateOptions::default()).unwrap();
+        let examples = [DocsExample {
+            name: "Sums".to_string(),
+            source: "\n1 + 2.5\n".to_string(),
+            tree: "(program (sum left: (number) right: (number)))".to_string(),
+        }];
+        assert_eq!(
+            render_reference_docs(
+                GRAMMAR_JSON,
+                &output.node_types_json,
+                &examples,
+                DocsFormat::Markdown
+            )
+            .unwrap(),
+            r#"# docs syntax reference
+
+## Node types
+
+### `_expression`
+
+One of `number`, `sum`.
+
+### `number`
+
+A kind of `_expression`.
+
+Matches:
+
+```
+/\d+/ ("." /\d+/)?
+```
+
+Example, from `Sums`:
+
+```
+1 + 2.5
+```
+
+### `program`
+
+Children: `_expression` (zero or more)
+
+Example, from `Sums`:
+
+```
+1 + 2.5
+```
+
+### `sum`
+
+A kind of `_expression`.
+
+| Field | Types | Quantity |
+| --- | --- | --- |
+| `left` | `_expression` | one |
+| `right` | `_expression` | one |
+
+Example, from `Sums`:
+
+```
+1 + 2.5
+```
+
+## Keywords and punctuation
+
+`"+"`
+"#
+        );
+    }
+
+    #[test]
+    fn test_render_reference_docs_as_html() {
+        let output = generate_parser(GRAMMAR_JSON, &GenerateOptions::default()).unwrap();
+        let examples = [DocsExample {
+            name: "<sum>".to_string(),
+            source: "1 + 2".to_string(),
+            tree: "(program (sum left: (number) right: (number)))".to_string(),
+        }];
+        let html = render_reference_docs(
+            GRAMMAR_JSON,
+            &output.node_types_json,
+            &examples,
+            DocsFormat::Html,
+        )
+        .unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>\n"));
+        assert!(html.contains("<h3 id=\"sum\"><code>sum</code></h3>\n"));
+        assert!(html.contains(
+            "<tr><td><code>left</code></td><td><code>_expression</code></td><td>one</td></tr>\n"
+        ));
+        assert!(html.contains("<p>Example, from <code>&lt;sum&gt;</code>:</p>\n"));
+        assert!(html.contains("<p><code>&quot;+&quot;</code></p>\n"));
+    }
+
+    #[test]
+    fn test_tree_contains() {
+        assert!(tree_contains("(program (sum (number)))", "sum"));
+        assert!(tree_contains("(program (sum (number)))", "number"));
+        assert!(!tree_contains("(program (sum_list (number)))", "sum"));
+    }
+}
@@ -1,15 +1,17 @@
 use std::{
     cmp,
-    collections::{HashMap, HashSet},
+    collections::{hash_map, HashMap, HashSet},
     fmt::Write,
-    mem::swap,
+    mem::{replace, swap, take},
 };
 
 use super::{
-    build_tables::Tables,
+    build_tables::{ParseStateSource, Tables},
+    fnv1a_hash,
     grammars::{ExternalToken, LexicalGrammar, SyntaxGrammar, VariableType},
     nfa::CharacterSet,
     rules::{Alias, AliasMap, Symbol, SymbolType},
+    table_dump::symbol_name,
     tables::{
         AdvanceAction, FieldLocation, GotoAction, LexState, LexTable, ParseAction, ParseTable,
         ParseTableEntry,
@@ -21,6 +23,75 @@ const ABI_VERSION_MIN: usize = 13;
 const ABI_VERSION_MAX: usize = tree_sitter::LANGUAGE_VERSION;
 const ABI_VERSION_WITH_PRIMARY_STATES: usize = 14;
 
+pub const DEFAULT_EXPORT_PREFIX: &str = "tree_sitter_";
+
+/// Character sets with at least this many ranges are checked with a lookup in a
+/// range table when using [`LexerStrategy::RangeTable`]. Sets with fewer ranges are
+/// checked just as quickly with inline comparisons.
+const RANGE_TABLE_MIN_RANGE_COUNT: usize = 3;
+
+/// Character sets whose ASCII characters form at least this many ranges have those
+/// characters checked with a lookup in a bitmap when using [`LexerStrategy::AsciiBitmap`].
+const ASCII_BITMAP_MIN_RANGE_COUNT: usize = 3;
+
+/// How the lex functions are emitted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LexerStrategy {
+    /// A `switch` statement over the lex states, with inline character comparisons.
+    #[default]
+    Switch,
+    /// A table of label addresses, used to jump directly to the code for each lex state.
+    /// Compilers without support for computed gotos fall back to a `switch` statement.
+    JumpTable,
+    /// Like `Switch`, but every character set with several ranges is checked by a binary
+    /// search in a table of ranges, instead of with inline comparisons.
+    RangeTable,
+    /// Like `Switch`, but the ASCII characters of each character set with several ranges,
+    /// like the ones that continue identifiers or that are whitespace, are checked with a
+    /// single lookup in a 128-bit bitmap. Other characters are checked as usual.
+    AsciiBitmap,
+    /// Like `JumpTable`, but states with many single-character transitions dispatch on the
+    /// lookahead character with a `switch` statement, instead of searching a table of the
+    /// characters. The lexer is larger, but doesn't search any tables.
+    DirectCoded,
+}
+
+/// The version of the C standard that the generated code is written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CStandard {
+    /// C89, except that the tables still use designated initializers, which the runtime's
+    /// unions can't be initialized without, and `stdbool.h` and `stdint.h`. C89 compilers
+    /// that are still in use support these as extensions.
+    C89,
+    #[default]
+    C99,
+}
+
+/// The start of the comments that `--annotate` adds to the generated code. Each of them is
+/// either on its own line or at the end of a line, so that they can be stripped again.
+const ANNOTATION_START: &str = "/*| ";
+
+pub const LEXER_FILE_HEADER: &str =
+    "// This file is generated by `tree-sitter generate --split-lexer`.";
+
+/// The start of the header that holds the character sets shared between parsers. Each
+/// parser defines the `TS_USE_CHARACTER_SET_*` macros of the sets it uses before
+/// including the header, and the linker merges the copies of a set that is used by
+/// several parsers. Its comments are block comments, so that parsers that are compiled
+/// as C89 can include it.
+pub const SHARED_CHARACTER_SETS_HEADER: &str = "\
+/* This file is generated by `tree-sitter generate --shared-character-sets`.
+   It is shared between parsers, and must not be edited. */
+
+#ifndef TS_SHARED_CHARACTER_SET
+#ifdef _WIN32
+#define TS_SHARED_CHARACTER_SET __declspec(selectany) const TSCharacterRange
+#else
+#define TS_SHARED_CHARACTER_SET __attribute__((weak, visibility(\"hidden\"))) const TSCharacterRange
+#endif
+#endif
+";
+
 macro_rules! add {
     ($this: tt, $($arg: tt)*) => {{
         $this.buffer.write_fmt(format_args!($($arg)*)).unwrap();
@@ -65,35 +136,63 @@ struct Generator {
     keyword_lex_table: LexTable,
     large_character_sets: Vec<(Option<Symbol>, CharacterSet)>,
     large_character_set_info: Vec<LargeCharacterSetInfo>,
+    ascii_bitmaps: Vec<[u32; 4]>,
     large_state_count: usize,
     keyword_capture_token: Option<Symbol>,
     syntax_grammar: SyntaxGrammar,
     lexical_grammar: LexicalGrammar,
     default_aliases: AliasMap,
+    token_display_names: HashMap<Symbol, String>,
     symbol_order: HashMap<Symbol, usize>,
+    unused_symbol_ids: Vec<usize>,
+    unused_token_id_count: usize,
     symbol_ids: HashMap<Symbol, String>,
     alias_ids: HashMap<Alias, String>,
     unique_aliases: Vec<Alias>,
     symbol_map: HashMap<Symbol, Symbol>,
     field_names: Vec<String>,
+    previous_symbol_ids: Vec<String>,
+    split_lexer: bool,
+    freestanding: bool,
+    export_prefix: String,
+    shared_character_sets_include: Option<String>,
+    shared_character_sets: Vec<(String, String)>,
+    lexer_strategy: LexerStrategy,
+    c_standard: CStandard,
+    inline_keyword: Option<String>,
+    annotate: bool,
+    parse_state_sources: Vec<ParseStateSource>,
 
     #[allow(unused)]
     abi_version: usize,
 }
 
+/// The C code generated for a parser.
+pub struct RenderedParser {
+    pub c_code: String,
+    pub lexer_c_code: Option<String>,
+    pub symbols_header: String,
+    pub symbol_ids: Vec<String>,
+    pub shared_character_sets: Vec<(String, String)>,
+}
+
 struct LargeCharacterSetInfo {
     constant_name: String,
     is_used: bool,
 }
 
 impl Generator {
-    fn generate(mut self) -> String {
+    fn generate(mut self) -> RenderedParser {
         self.init();
         self.add_includes();
         self.add_pragmas();
+        let buffer_offset_after_pragmas = self.buffer.len();
         self.add_stats();
+        let buffer_offset_before_symbol_enum = self.buffer.len();
         self.add_symbol_enum();
+        let buffer_offset_after_symbol_enum = self.buffer.len();
         self.add_symbol_names_list();
+        self.add_token_display_names_list();
         self.add_unique_symbol_map();
         self.add_symbol_metadata_list();
 
@@ -113,27 +212,46 @@ impl Generator {
             self.add_primary_state_id_list();
         }
 
-        let buffer_offset_before_lex_functions = self.buffer.len();
-
+        // The lex functions determine which large character sets are used, and those
+        // character sets are declared before the lex functions. So render the lex functions
+        // into a buffer of their own, and append them after the character sets.
+        let buffer = take(&mut self.buffer);
         let mut main_lex_table = LexTable::default();
         swap(&mut main_lex_table, &mut self.main_lex_table);
-        self.add_lex_function("ts_lex", main_lex_table);
+        self.add_lex_function(&self.lex_function_name(), main_lex_table);
 
         if self.keyword_capture_token.is_some() {
             let mut keyword_lex_table = LexTable::default();
             swap(&mut keyword_lex_table, &mut self.keyword_lex_table);
-            self.add_lex_function("ts_lex_keywords", keyword_lex_table);
+            self.add_lex_function(&self.keyword_lex_function_name(), keyword_lex_table);
         }
+        let lex_functions = replace(&mut self.buffer, buffer);
 
-        // Once the lex functions are generated, and we've determined which large
-        // character sets are actually used, we can generate the large character set
-        // constants. Insert them into the output buffer before the lex functions.
-        let lex_functions = self.buffer[buffer_offset_before_lex_functions..].to_string();
-        self.buffer.truncate(buffer_offset_before_lex_functions);
+        let buffer_offset_before_lex_functions = self.buffer.len();
         for ix in 0..self.large_character_sets.len() {
             self.add_character_set(ix);
         }
-        self.buffer.push_str(&lex_functions);
+        self.add_ascii_bitmaps();
+        self.add_shared_character_sets_include();
+        self.buffer += &lex_functions;
+        drop(lex_functions);
+
+        // When splitting the lexer into its own compilation unit, move the character
+        // sets and lex functions into a separate buffer, preceded by the includes,
+        // pragmas and symbol enum that they depend on. The parser only needs to
+        // declare the lex functions.
+        let lexer_code = if self.split_lexer {
+            let mut lexer_code = format!("{LEXER_FILE_HEADER}\n\n");
+            lexer_code += &self.buffer[..buffer_offset_after_pragmas];
+            lexer_code +=
+                &self.buffer[buffer_offset_before_symbol_enum..buffer_offset_after_symbol_enum];
+            lexer_code += &self.buffer[buffer_offset_before_lex_functions..];
+            self.buffer.truncate(buffer_offset_before_lex_functions);
+            self.add_lex_function_declarations();
+            Some(lexer_code)
+        } else {
+            None
+        };
 
         self.add_lex_modes_list();
         self.add_parse_table();
@@ -146,7 +264,90 @@ impl Generator {
 
         self.add_parser_export();
 
-        self.buffer
+        let mut symbol_ids = vec![String::new(); self.symbol_count() - 1];
+        for symbol in &self.parse_table.symbols {
+            if *symbol != Symbol::end() {
+                symbol_ids[self.symbol_order[symbol] - 1].clone_from(&self.symbol_ids[symbol]);
+            }
+        }
+        let (c_code, lexer_code) = if self.c_standard == CStandard::C89 {
+            (
+                to_block_comments(&self.buffer),
+                lexer_code.as_deref().map(to_block_comments),
+            )
+        } else {
+            (take(&mut self.buffer), lexer_code)
+        };
+        RenderedParser {
+            symbols_header: self.render_symbols_header(),
+            c_code,
+            lexer_c_code: lexer_code,
+            symbol_ids,
+            shared_character_sets: self.shared_character_sets,
+        }
+    }
+
+    /// Render a header with the public ids of the visible symbols and of the fields, so
+    /// that code outside of the parser can refer to them by name.
+    fn render_symbols_header(&self) -> String {
+        let name = &self.language_name;
+        let guard = format!("TREE_SITTER_{}_SYMBOLS_H_", name.to_ascii_uppercase());
+        let mut result = String::new();
+        writeln!(&mut result, "#ifndef {guard}\n#define {guard}\n").unwrap();
+
+        writeln!(
+            &mut result,
+            "// The ids of the node types, as returned by `ts_node_symbol`."
+        )
+        .unwrap();
+        writeln!(&mut result, "enum ts_{name}_symbol_identifiers {{").unwrap();
+        for symbol in &self.parse_table.symbols {
+            let is_visible = self.default_aliases.contains_key(symbol)
+                || matches!(
+                    self.metadata_for_symbol(*symbol).1,
+                    VariableType::Named | VariableType::Anonymous
+                );
+            if is_visible {
+                writeln!(
+                    &mut result,
+                    "  {name}_{} = {},",
+                    self.symbol_ids[symbol], self.symbol_order[&self.symbol_map[symbol]]
+                )
+                .unwrap();
+            }
+        }
+        for (i, alias) in self.unique_aliases.iter().enumerate() {
+            writeln!(
+                &mut result,
+                "  {name}_{} = {},",
+                self.alias_ids[alias],
+                self.symbol_count() + i
+            )
+            .unwrap();
+        }
+        writeln!(&mut result, "}};\n").unwrap();
+
+        if !self.field_names.is_empty() {
+            writeln!(
+                &mut result,
+                "// The ids of the fields, as returned by `ts_language_field_id_for_name`."
+            )
+            .unwrap();
+            writeln!(&mut result, "enum ts_{name}_field_identifiers {{").unwrap();
+            for (i, field_name) in self.field_names.iter().enumerate() {
+                writeln!(
+                    &mut result,
+                    "  {name}_{} = {},",
+                    self.field_id(field_name),
+                    i + 1
+                )
+                .unwrap();
+            }
+            writeln!(&mut result, "}};\n").unwrap();
+        }
+
+        writeln!(&mut result, "#endif // {guard}").unwrap();
+        result
     }
 
     fn init(&mut self) {
@@ -159,6 +360,10 @@ impl Generator {
             self.symbol_ids[&Symbol::end()].clone(),
         );
 
+        if !self.previous_symbol_ids.is_empty() {
+            self.reorder_symbols_by_previous_ids();
+        }
+
         self.symbol_map = HashMap::new();
 
         for symbol in &self.parse_table.symbols {
@@ -238,9 +443,9 @@ impl Generator {
                         }
 
                         if alias.is_named {
-                            format!("alias_sym_{}", self.sanitize_identifier(&alias.value))
+                            format!("alias_sym_{}", sanitize_identifier(&alias.value))
                         } else {
-                            format!("anon_alias_sym_{}", self.sanitize_identifier(&alias.value))
+                            format!("anon_alias_sym_{}", sanitize_identifier(&alias.value))
                         }
                     };
 
@@ -255,7 +460,9 @@ impl Generator {
                 .filter(|(sym, _)| sym == symbol)
                 .count()
                 + 1;
-            let constant_name = if let Some(symbol) = symbol {
+            let constant_name = if self.shared_character_sets_include.is_some() {
+                self.shared_character_set_name(&self.large_character_sets[ix].1)
+            } else if let Some(symbol) = symbol {
                 format!("{}_character_set_{}", self.symbol_ids[symbol], count)
             } else {
                 format!("extras_character_set_{count}")
@@ -280,7 +487,78 @@ impl Generator {
             .count();
     }
 
+    /// Order the symbols so that the ones that existed in a previous generation of
+    /// the parser keep their relative order, and new symbols come after them. Symbols
+    /// only move within their own category (tokens, external tokens and non-terminals),
+    /// and the end-of-input symbol and the word token keep their fixed positions.
+    ///
+    /// The symbols also keep their previous ids where they can: the ids of removed symbols
+    /// are left unused rather than being given to the symbols that follow them. Only new
+    /// symbols in an earlier category push the ids of a later category forward.
+    fn reorder_symbols_by_previous_ids(&mut self) {
+        let previous_positions = self
+            .previous_symbol_ids
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| !id.is_empty())
+            .map(|(i, id)| (id.as_str(), i))
+            .collect::<HashMap<_, _>>();
+        let fixed_count =
+            if self.parse_table.symbols.get(1).copied() == self.syntax_grammar.word_token {
+                2
+            } else {
+                1
+            };
+        self.parse_table.symbols[fixed_count..].sort_by_key(|symbol| {
+            let category = match symbol.kind {
+                SymbolType::Terminal => 0,
+                SymbolType::External => 1,
+                _ => 2,
+            };
+            let position = previous_positions
+                .get(self.symbol_ids[symbol].as_str())
+                .copied()
+                .unwrap_or(usize::MAX);
+            (category, position)
+        });
+
+        let mut next_id = 1;
+        let mut first_non_terminal_id = usize::MAX;
+        for symbol in &self.parse_table.symbols[1..] {
+            let id = previous_positions
+                .get(self.symbol_ids[symbol].as_str())
+                .map_or(next_id, |position| cmp::max(position + 1, next_id));
+            self.unused_symbol_ids.extend(next_id..id);
+            if symbol.is_non_terminal() {
+                first_non_terminal_id = cmp::min(first_non_terminal_id, id);
+            }
+            next_id = id + 1;
+        }
+        self.unused_token_id_count = self
+            .unused_symbol_ids
+            .iter()
+            .filter(|id| **id < first_non_terminal_id)
+            .count();
+    }
+
+    /// The number of symbol ids, including the ids that are left unused by removed symbols.
+    fn symbol_count(&self) -> usize {
+        self.parse_table.symbols.len() + self.unused_symbol_ids.len()
+    }
+
     fn add_includes(&mut self) {
+        if self.freestanding {
+            add_line!(self, "#ifndef TREE_SITTER_FREESTANDING");
+            add_line!(self, "#define TREE_SITTER_FREESTANDING");
+            add_line!(self, "#endif");
+            add_line!(self, "");
+        }
+        if let Some(inline_keyword) = &self.inline_keyword {
+            add_line!(self, "#ifndef TS_INLINE");
+            add_line!(self, "#define TS_INLINE {inline_keyword}");
+            add_line!(self, "#endif");
+            add_line!(self, "");
+        }
         add_line!(self, "#include \"tree_sitter/parser.h\"");
         add_line!(self, "");
     }
@@ -335,13 +613,13 @@ impl Generator {
         );
         add_line!(self, "#define LARGE_STATE_COUNT {}", self.large_state_count);
 
+        add_line!(self, "#define SYMBOL_COUNT {}", self.symbol_count());
+        add_line!(self, "#define ALIAS_COUNT {}", self.unique_aliases.len());
         add_line!(
             self,
-            "#define SYMBOL_COUNT {}",
-            self.parse_table.symbols.len()
+            "#define TOKEN_COUNT {}",
+            token_count + self.unused_token_id_count
         );
-        add_line!(self, "#define ALIAS_COUNT {}", self.unique_aliases.len());
-        add_line!(self, "#define TOKEN_COUNT {}", token_count);
         add_line!(
             self,
             "#define EXTERNAL_TOKEN_COUNT {}",
@@ -368,6 +646,9 @@ impl Generator {
         let mut i = 1;
         for symbol in &self.parse_table.symbols {
             if *symbol != Symbol::end() {
+                while self.unused_symbol_ids.contains(&i) {
+                    i += 1;
+                }
                 self.symbol_order.insert(*symbol, i);
                 add_line!(self, "{} = {i},", self.symbol_ids[symbol]);
                 i += 1;
@@ -377,11 +658,20 @@ impl Generator {
             add_line!(self, "{} = {i},", self.alias_ids[alias]);
             i += 1;
         }
+        self.remove_trailing_enum_comma();
         dedent!(self);
         add_line!(self, "}};");
         add_line!(self, "");
     }
 
+    /// C89 doesn't allow a comma after the last value of an enum.
+    fn remove_trailing_enum_comma(&mut self) {
+        if self.c_standard == CStandard::C89 && self.buffer.ends_with(",\n") {
+            self.buffer.truncate(self.buffer.len() - 2);
+            self.buffer.push('\n');
+        }
+    }
+
     fn add_symbol_names_list(&mut self) {
         add_line!(self, "static const char * const ts_symbol_names[] = {{");
         indent!(self);
@@ -408,6 +698,49 @@ impl Generator {
         add_line!(self, "");
     }
 
+    /// Describe each token the way it should appear in error messages: its grammar-given
+    /// display name if it has one, otherwise its text for anonymous tokens, or its name.
+    fn add_token_display_names_list(&mut self) {
+        add_line!(
+            self,
+            "static const char * const ts_token_display_names[SYMBOL_COUNT] = {{"
+        );
+        indent!(self);
+        for symbol in &self.parse_table.symbols {
+            if !matches!(
+                symbol.kind,
+                SymbolType::End | SymbolType::Terminal | SymbolType::External
+            ) {
+                continue;
+            }
+            let display_name = if let Some(display_name) = self.token_display_names.get(symbol) {
+                display_name.clone()
+            } else if symbol.is_eof() {
+                "end of file".to_string()
+            } else if let Some(alias) = self.default_aliases.get(symbol) {
+                if alias.is_named {
+                    alias.value.clone()
+                } else {
+                    format!("{:?}", alias.value)
+                }
+            } else {
+                match self.metadata_for_symbol(*symbol) {
+                    (name, VariableType::Anonymous) => format!("{name:?}"),
+                    (name, _) => name.to_string(),
+                }
+            };
+            add_line!(
+                self,
+                "[{}] = \"{}\",",
+                self.symbol_ids[symbol],
+                self.sanitize_string(&display_name)
+            );
+        }
+        dedent!(self);
+        add_line!(self, "}};");
+        add_line!(self, "");
+    }
+
     fn add_unique_symbol_map(&mut self) {
         add_line!(self, "static const TSSymbol ts_symbol_map[] = {{");
         indent!(self);
@@ -440,6 +773,7 @@ impl Generator {
         for (i, field_name) in self.field_names.iter().enumerate() {
             add_line!(self, "{} = {},", self.field_id(field_name), i + 1);
         }
+        self.remove_trailing_enum_comma();
         dedent!(self);
         add_line!(self, "}};");
         add_line!(self, "");
@@ -682,21 +1016,120 @@ impl Generator {
         add_line!(self, "");
     }
 
+    /// The name of the function that returns the language, which prefixes the names of
+    /// the parser's other exported functions.
+    fn exported_name(&self) -> String {
+        format!("{}{}", self.export_prefix, self.language_name)
+    }
+
+    fn lex_function_name(&self) -> String {
+        if self.split_lexer {
+            format!("{}_lex", self.exported_name())
+        } else {
+            "ts_lex".to_string()
+        }
+    }
+
+    fn keyword_lex_function_name(&self) -> String {
+        if self.split_lexer {
+            format!("{}_lex_keywords", self.exported_name())
+        } else {
+            "ts_lex_keywords".to_string()
+        }
+    }
+
+    fn add_lex_function_declarations(&mut self) {
+        add_line!(
+            self,
+            "bool {}(TSLexer *lexer, TSStateId state);",
+            self.lex_function_name()
+        );
+        if self.keyword_capture_token.is_some() {
+            add_line!(
+                self,
+                "bool {}(TSLexer *lexer, TSStateId state);",
+                self.keyword_lex_function_name()
+            );
+        }
+        add_line!(self, "");
+    }
+
+    /// Shared character sets are named after their contents, so that parsers that use
+    /// the same set refer to the same constant.
+    fn shared_character_set_name(&self, characters: &CharacterSet) -> String {
+        let mut ranges = String::new();
+        for range in characters.ranges() {
+            write!(
+                &mut ranges,
+                "{:x}-{:x},",
+                *range.start() as u32,
+                *range.end() as u32
+            )
+            .unwrap();
+        }
+        format!("ts_character_set_{:016x}", fnv1a_hash(ranges.as_bytes()))
+    }
+
+    /// Find the large character set that is equal to the given set, adding it to the
+    /// list of large character sets if there is none.
+    fn get_large_character_set_id(&mut self, characters: &CharacterSet) -> usize {
+        if let Some(ix) = self
+            .large_character_sets
+            .iter()
+            .position(|(_, set)| set == characters)
+        {
+            return ix;
+        }
+        let constant_name = if self.shared_character_sets_include.is_some() {
+            self.shared_character_set_name(characters)
+        } else {
+            let count = self
+                .large_character_set_info
+                .iter()
+                .filter(|info| info.constant_name.starts_with("lex_character_set_"))
+                .count();
+            format!("lex_character_set_{}", count + 1)
+        };
+        self.large_character_sets.push((None, characters.clone()));
+        self.large_character_set_info.push(LargeCharacterSetInfo {
+            constant_name,
+            is_used: false,
+        });
+        self.large_character_sets.len() - 1
+    }
+
     fn add_lex_function(&mut self, name: &str, lex_table: LexTable) {
+        let linkage = if self.split_lexer { "" } else { "static " };
         add_line!(
             self,
-            "static bool {name}(TSLexer *lexer, TSStateId state) {{",
+            "{linkage}bool {name}(TSLexer *lexer, TSStateId state) {{",
         );
         indent!(self);
 
         add_line!(self, "START_LEXER();");
         add_line!(self, "eof = lexer->eof(lexer);");
+
+        if matches!(
+            self.lexer_strategy,
+            LexerStrategy::JumpTable | LexerStrategy::DirectCoded
+        ) {
+            self.add_lex_jump_table(lex_table);
+            dedent!(self);
+            add_line!(self, "}}");
+            add_line!(self, "");
+            return;
+        }
+
         add_line!(self, "switch (state) {{");
 
         indent!(self);
+        let annotations = self.lex_state_annotations(&lex_table);
         for (i, state) in lex_table.states.into_iter().enumerate() {
             add_line!(self, "case {i}:");
             indent!(self);
+            if let Some(annotation) = annotations.get(i) {
+                add_line!(self, "{annotation}");
+            }
             self.add_lex_state(i, state);
             dedent!(self);
         }
@@ -714,6 +1147,71 @@ impl Generator {
         add_line!(self, "");
     }
 
+    /// Dispatch on the lex state with a computed goto, through a table of the addresses
+    /// of each state's label. Compilers without the labels-as-values extension use a
+    /// `switch` statement that jumps to the same labels instead.
+    fn add_lex_jump_table(&mut self, lex_table: LexTable) {
+        let state_count = lex_table.states.len();
+        add_line!(self, "#ifdef __GNUC__");
+        add_line!(self, "{{");
+        indent!(self);
+        add_line!(self, "static const void *const lex_states[] = {{");
+        indent!(self);
+        for i in 0..state_count {
+            add_line!(self, "&&lex_state_{i},");
+        }
+        dedent!(self);
+        add_line!(self, "}};");
+        add_line!(self, "if (state >= {state_count}) return false;");
+        add_line!(self, "goto *lex_states[state];");
+        dedent!(self);
+        add_line!(self, "}}");
+        add_line!(self, "#else");
+        add_line!(self, "switch (state) {{");
+        indent!(self);
+        for i in 0..state_count {
+            add_line!(self, "case {i}: goto lex_state_{i};");
+        }
+        add_line!(self, "default: return false;");
+        dedent!(self);
+        add_line!(self, "}}");
+        add_line!(self, "#endif");
+
+        let annotations = self.lex_state_annotations(&lex_table);
+        for (i, state) in lex_table.states.into_iter().enumerate() {
+            add_line!(self, "lex_state_{i}:");
+            indent!(self);
+            if let Some(annotation) = annotations.get(i) {
+                add_line!(self, "{annotation}");
+            }
+            self.add_lex_state(i, state);
+            dedent!(self);
+        }
+    }
+
+    /// Describe each lex state by the tokens that can be accepted from it.
+    fn lex_state_annotations(&self, lex_table: &LexTable) -> Vec<String> {
+        if !self.annotate {
+            return Vec::new();
+        }
+        (0..lex_table.states.len())
+            .map(|state_id| {
+                let tokens = lex_table
+                    .reachable_tokens(state_id)
+                    .into_iter()
+                    .map(|index| {
+                        symbol_name(
+                            &self.syntax_grammar,
+                            &self.lexical_grammar,
+                            &Symbol::terminal(index),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                annotation(&format!("tokens: {}", tokens.join(" ")))
+            })
+            .collect()
+    }
+
     fn add_lex_state(&mut self, _state_ix: usize, state: LexState) {
         if let Some(accept_action) = state.accept_action {
             add_line!(self, "ACCEPT_TOKEN({});", self.symbol_ids[&accept_action]);
@@ -749,7 +1247,29 @@ impl Generator {
             }
         }
 
-        if leading_simple_transition_range_count >= 8 {
+        if leading_simple_transition_range_count >= 8
+            && self.lexer_strategy == LexerStrategy::DirectCoded
+        {
+            add_line!(self, "switch (lookahead) {{");
+            indent!(self);
+            for (chars, action) in &state.advance_actions[0..leading_simple_transition_count] {
+                for c in chars.chars() {
+                    add_whitespace!(self);
+                    add!(self, "case ");
+                    self.add_character(c);
+                    add!(self, ":\n");
+                }
+                indent!(self);
+                add_line!(self, "ADVANCE({});", action.state);
+                dedent!(self);
+                ruled_out_chars = ruled_out_chars.add(chars);
+            }
+            dedent!(self);
+            add_line!(self, "}}");
+        } else if leading_simple_transition_range_count >= 8
+            // The `ADVANCE_MAP` macro is variadic, which C89 doesn't support.
+            && self.c_standard != CStandard::C89
+        {
             add_line!(self, "ADVANCE_MAP(");
             indent!(self);
             for (chars, action) in &state.advance_actions[0..leading_simple_transition_count] {
@@ -784,13 +1304,29 @@ impl Generator {
             // be checked), not that this transition is impossible.
             let simplified_chars = chars.simplify_ignoring(&ruled_out_chars);
 
+            if self.lexer_strategy == LexerStrategy::AsciiBitmap
+                && self.add_ascii_bitmap_condition(&simplified_chars)
+            {
+                ruled_out_chars = ruled_out_chars.add(chars);
+                self.add_advance_action(action);
+                add!(self, "\n");
+                continue;
+            }
+
             // For large character sets, find the best matching character set from
             // a pre-selected list of large character sets, which are based on the
             // state transitions for invidual tokens. This transition may not exactly
             // match one of the pre-selected character sets. In that case, determine
             // the additional checks that need to be performed to match this transition.
             let mut best_large_char_set: Option<(usize, CharacterSet, CharacterSet)> = None;
-            if simplified_chars.range_count() >= super::build_tables::LARGE_CHARACTER_RANGE_COUNT {
+            if self.lexer_strategy == LexerStrategy::RangeTable
+                && simplified_chars.range_count() >= RANGE_TABLE_MIN_RANGE_COUNT
+            {
+                let ix = self.get_large_character_set_id(&simplified_chars);
+                best_large_char_set = Some((ix, CharacterSet::empty(), CharacterSet::empty()));
+            } else if simplified_chars.range_count()
+                >= super::build_tables::LARGE_CHARACTER_RANGE_COUNT
+            {
                 for (ix, (_, set)) in self.large_character_sets.iter().enumerate() {
                     chars_copy.assign(&simplified_chars);
                     large_set.assign(set);
@@ -899,6 +1435,74 @@ impl Generator {
         add_line!(self, "END_STATE();");
     }
 
+    /// Check the ASCII characters of a transition with a lookup in a bitmap, and any other
+    /// characters with comparisons, or with a binary search if there are many of them.
+    /// Nothing is added if the ASCII characters have too few ranges for the lookup to be
+    /// faster than comparing the lookahead character to each of them.
+    fn add_ascii_bitmap_condition(&mut self, characters: &CharacterSet) -> bool {
+        let mut other_chars = characters.clone();
+        let ascii_chars =
+            other_chars.remove_intersection(&mut CharacterSet::empty().add_range('\0', '\x7f'));
+        if ascii_chars.range_count() < ASCII_BITMAP_MIN_RANGE_COUNT {
+            return false;
+        }
+
+        let mut bitmap = [0; 4];
+        for c in ascii_chars.chars() {
+            bitmap[c as usize / 32] |= 1 << (c as u32 % 32);
+        }
+        let bitmap_ix = self
+            .ascii_bitmaps
+            .iter()
+            .position(|b| *b == bitmap)
+            .unwrap_or_else(|| {
+                self.ascii_bitmaps.push(bitmap);
+                self.ascii_bitmaps.len() - 1
+            });
+
+        let mut line_break = "\n".to_string();
+        for _ in 0..self.indent_level + 2 {
+            line_break.push_str("  ");
+        }
+
+        add!(self, "if (");
+        // At the end of the file, the lookahead character is zero.
+        let check_eof = ascii_chars.contains('\0');
+        let needs_parentheses = check_eof || !other_chars.is_empty();
+        if needs_parentheses {
+            add!(self, "(");
+        }
+        if check_eof {
+            add!(self, "!eof && ");
+        }
+        // The cast also rules out the -1 that the lexer reads for invalid bytes.
+        add!(
+            self,
+            "(uint32_t)lookahead < 128 && (lex_ascii_bitmap_{}[lookahead >> 5] >> (lookahead & 31)) & 1",
+            bitmap_ix + 1
+        );
+        if needs_parentheses {
+            add!(self, ")");
+        }
+
+        if other_chars.range_count() >= super::build_tables::LARGE_CHARACTER_RANGE_COUNT {
+            let ix = self.get_large_character_set_id(&other_chars);
+            let char_set_info = &mut self.large_character_set_info[ix];
+            char_set_info.is_used = true;
+            add!(
+                self,
+                " ||{line_break}set_contains({}, {}, lookahead)",
+                &char_set_info.constant_name,
+                other_chars.range_count(),
+            );
+        } else if !other_chars.is_empty() {
+            add!(self, " ||{line_break}");
+            self.add_character_range_conditions(&other_chars, true, &line_break);
+        }
+        add!(self, ") ");
+        true
+    }
+
     fn add_character_range_conditions(
         &mut self,
         characters: &CharacterSet,
@@ -971,11 +1575,18 @@ impl Generator {
             return;
         }
 
-        add_line!(
-            self,
-            "static TSCharacterRange {}[] = {{",
-            info.constant_name
-        );
+        let start = self.buffer.len();
+        let constant_name = info.constant_name.clone();
+        if self.shared_character_sets_include.is_some() {
+            add_line!(
+                self,
+                "#ifdef TS_USE_{}",
+                constant_name.trim_start_matches("ts_").to_ascii_uppercase()
+            );
+            add_line!(self, "TS_SHARED_CHARACTER_SET {constant_name}[] = {{");
+        } else {
+            add_line!(self, "static TSCharacterRange {constant_name}[] = {{");
+        }
 
         indent!(self);
         for (ix, range) in characters.ranges().enumerate() {
@@ -997,6 +1608,58 @@ impl Generator {
         add!(self, "\n");
         dedent!(self);
         add_line!(self, "}};");
+        if self.shared_character_sets_include.is_some() {
+            add_line!(self, "#endif");
+            let definition = self.buffer.split_off(start);
+            self.shared_character_sets.push((constant_name, definition));
+        } else {
+            add_line!(self, "");
+        }
+    }
+
+    fn add_ascii_bitmaps(&mut self) {
+        if self.ascii_bitmaps.is_empty() {
+            return;
+        }
+        for (ix, bitmap) in self.ascii_bitmaps.iter().enumerate() {
+            let words = bitmap
+                .iter()
+                .map(|word| format!("0x{word:08x}"))
+                .collect::<Vec<_>>();
+            add_line!(
+                self,
+                "static const uint32_t lex_ascii_bitmap_{}[] = {{{}}};",
+                ix + 1,
+                words.join(", ")
+            );
+        }
+        add_line!(self, "");
+    }
+
+    /// Include the shared header that defines the character sets used by the lex
+    /// functions, selecting the sets to define.
+    fn add_shared_character_sets_include(&mut self) {
+        let Some(include_path) = self.shared_character_sets_include.clone() else {
+            return;
+        };
+        if self.shared_character_sets.is_empty() {
+            return;
+        }
+        let mut constant_names = self
+            .shared_character_sets
+            .iter()
+            .map(|(constant_name, _)| constant_name.clone())
+            .collect::<Vec<_>>();
+        constant_names.sort_unstable();
+        constant_names.dedup();
+        for constant_name in constant_names {
+            add_line!(
+                self,
+                "#define TS_USE_{}",
+                constant_name.trim_start_matches("ts_").to_ascii_uppercase()
+            );
+        }
+        add_line!(self, "#include \"{include_path}\"");
         add_line!(self, "");
     }
 
@@ -1043,6 +1706,7 @@ impl Generator {
                 self.external_token_id(&self.syntax_grammar.external_tokens[i]),
             );
         }
+        self.remove_trailing_enum_comma();
         dedent!(self);
         add_line!(self, "}};");
         add_line!(self, "");
@@ -1127,6 +1791,9 @@ impl Generator {
             .enumerate()
             .take(self.large_state_count)
         {
+            for annotation in self.parse_state_annotations(i) {
+                add_line!(self, "{annotation}");
+            }
             add_line!(self, "[{i}] = {{");
             indent!(self);
 
@@ -1172,9 +1839,15 @@ impl Generator {
 
             let mut index = 0;
             let mut small_state_indices = Vec::new();
+            let mut small_state_indices_by_row = HashMap::new();
             let mut symbols_by_value = HashMap::<(usize, SymbolType), Vec<Symbol>>::new();
-            for state in self.parse_table.states.iter().skip(self.large_state_count) {
-                small_state_indices.push(index);
+            for (state_id, state) in self
+                .parse_table
+                .states
+                .iter()
+                .enumerate()
+                .skip(self.large_state_count)
+            {
                 symbols_by_value.clear();
 
                 terminal_entries.clear();
@@ -1198,9 +1871,7 @@ impl Generator {
                 for (symbol, action) in &state.nonterminal_entries {
                     let state_id = match action {
                         GotoAction::Goto(i) => *i,
-                        GotoAction::ShiftExtra => {
-                            self.large_state_count + small_state_indices.len() - 1
-                        }
+                        GotoAction::ShiftExtra => state_id,
                     };
                     symbols_by_value
                         .entry((state_id, SymbolType::NonTerminal))
@@ -1212,31 +1883,55 @@ impl Generator {
                 values_with_symbols.sort_unstable_by_key(|((value, kind), symbols)| {
                     (symbols.len(), *kind, *value, symbols[0])
                 });
+                for (_, symbols) in &mut values_with_symbols {
+                    symbols.sort_unstable();
+                }
 
-                add_line!(self, "[{index}] = {},", values_with_symbols.len());
-                indent!(self);
-
-                for ((value, kind), symbols) in &mut values_with_symbols {
-                    if *kind == SymbolType::NonTerminal {
-                        add_line!(self, "STATE({value}), {},", symbols.len());
-                    } else {
-                        add_line!(self, "ACTIONS({value}), {},", symbols.len());
+                // Distinct states often have identical rows, e.g. because they only
+                // differ in their lex modes. Those states can share a single row.
+                match small_state_indices_by_row.entry(values_with_symbols) {
+                    hash_map::Entry::Occupied(entry) => {
+                        small_state_indices.push(*entry.get());
+                        continue;
                     }
+                    hash_map::Entry::Vacant(entry) => {
+                        let row_index = index;
+                        small_state_indices.push(row_index);
+                        let values_with_symbols = entry.key();
+                        // Small states are indexed by their offset in the table, rather
+                        // than by their id.
+                        if self.annotate {
+                            add_line!(self, "{}", annotation(&format!("state {state_id}")));
+                        }
+                        for annotation in self.parse_state_annotations(state_id) {
+                            add_line!(self, "{annotation}");
+                        }
+                        add_line!(self, "[{index}] = {},", values_with_symbols.len());
+                        indent!(self);
+
+                        for ((value, kind), symbols) in values_with_symbols {
+                            if *kind == SymbolType::NonTerminal {
+                                add_line!(self, "STATE({value}), {},", symbols.len());
+                            } else {
+                                add_line!(self, "ACTIONS({value}), {},", symbols.len());
+                            }
 
-                    symbols.sort_unstable();
-                    indent!(self);
-                    for symbol in symbols {
-                        add_line!(self, "{},", self.symbol_ids[symbol]);
-                    }
-                    dedent!(self);
-                }
+                            indent!(self);
+                            for symbol in symbols {
+                                add_line!(self, "{},", self.symbol_ids[symbol]);
+                            }
+                            dedent!(self);
+                        }
 
-                dedent!(self);
+                        dedent!(self);
 
-                index += 1 + values_with_symbols
-                    .iter()
-                    .map(|(_, symbols)| 2 + symbols.len())
-                    .sum::<usize>();
+                        index += 1 + values_with_symbols
+                            .iter()
+                            .map(|(_, symbols)| 2 + symbols.len())
+                            .sum::<usize>();
+                        entry.insert(row_index);
+                    }
+                }
             }
 
             dedent!(self);
@@ -1268,6 +1963,45 @@ impl Generator {
         self.add_parse_action_list(parse_table_entries);
     }
 
+    /// Describe a parse state by the items that it was reached with.
+    fn parse_state_annotations(&self, state_id: usize) -> Vec<String> {
+        if !self.annotate {
+            return Vec::new();
+        }
+        let source = &self.parse_state_sources[state_id];
+        if source.items.is_empty() {
+            return vec![annotation("error recovery")];
+        }
+        source.items[..source.kernel_item_count]
+            .iter()
+            .map(|item| annotation(item))
+            .collect()
+    }
+
+    /// Describe a shift by the first item of the state that it shifts to, and a reduce
+    /// by the rule that it reduces to.
+    fn describe_parse_action(&self, action: &ParseAction) -> Option<String> {
+        match action {
+            ParseAction::Shift { state, .. } => {
+                let source = &self.parse_state_sources[*state];
+                let item = source.items.first()?;
+                if source.kernel_item_count > 1 {
+                    Some(format!(
+                        "shift {item} (+{} more)",
+                        source.kernel_item_count - 1
+                    ))
+                } else {
+                    Some(format!("shift {item}"))
+                }
+            }
+            ParseAction::Reduce { symbol, .. } => Some(format!(
+                "reduce {}",
+                symbol_name(&self.syntax_grammar, &self.lexical_grammar, symbol)
+            )),
+            _ => None,
+        }
+    }
+
     fn add_parse_action_list(&mut self, parse_table_entries: Vec<(usize, ParseTableEntry)>) {
         add_line!(
             self,
@@ -1275,6 +2009,15 @@ impl Generator {
         );
         indent!(self);
         for (i, entry) in parse_table_entries {
+            let descriptions = if self.annotate {
+                entry
+                    .actions
+                    .iter()
+                    .filter_map(|action| self.describe_parse_action(action))
+                    .collect::<Vec<_>>()
+            } else {
+                Vec::new()
+            };
             add!(
                 self,
                 "  [{i}] = {{.entry = {{.count = {}, .reusable = {}}}}},",
@@ -1313,6 +2056,9 @@ impl Generator {
                 }
                 add!(self, ",");
             }
+            if !descriptions.is_empty() {
+                add!(self, " {}", annotation(&descriptions.join(", ")));
+            }
             add!(self, "\n");
         }
         dedent!(self);
@@ -1321,7 +2067,7 @@ impl Generator {
     }
 
     fn add_parser_export(&mut self) {
-        let language_function_name = format!("tree_sitter_{}", self.language_name);
+        let language_function_name = self.exported_name();
         let external_scanner_name = format!("{language_function_name}_external_scanner");
 
         add_line!(self, "#ifdef __cplusplus");
@@ -1405,9 +2151,13 @@ impl Generator {
 
         // Lexing
         add_line!(self, ".lex_modes = ts_lex_modes,");
-        add_line!(self, ".lex_fn = ts_lex,");
+        add_line!(self, ".lex_fn = {},", self.lex_function_name());
         if let Some(keyword_capture_token) = self.keyword_capture_token {
-            add_line!(self, ".keyword_lex_fn = ts_lex_keywords,");
+            add_line!(
+                self,
+                ".keyword_lex_fn = {},",
+                self.keyword_lex_function_name()
+            );
             add_line!(
                 self,
                 ".keyword_capture_token = {},",
@@ -1438,6 +2188,19 @@ impl Generator {
         add_line!(self, "return &language;");
         dedent!(self);
         add_line!(self, "}}");
+        add_line!(self, "");
+
+        add_line!(
+            self,
+            "TS_PUBLIC const char *{language_function_name}_token_display_name(TSSymbol symbol) {{",
+        );
+        indent!(self);
+        add_line!(
+            self,
+            "return symbol < SYMBOL_COUNT ? ts_token_display_names[symbol] : NULL;"
+        );
+        dedent!(self);
+        add_line!(self, "}}");
         add_line!(self, "#ifdef __cplusplus");
         add_line!(self, "}}");
         add_line!(self, "#endif");
@@ -1476,10 +2239,7 @@ impl Generator {
     }
 
     fn external_token_id(&self, token: &ExternalToken) -> String {
-        format!(
-            "ts_external_token_{}",
-            self.sanitize_identifier(&token.name)
-        )
+        format!("ts_external_token_{}", sanitize_identifier(&token.name))
     }
 
     fn assign_symbol_id(&mut self, symbol: Symbol, used_identifiers: &mut HashSet<String>) {
@@ -1489,10 +2249,10 @@ impl Generator {
         } else {
             let (name, kind) = self.metadata_for_symbol(symbol);
             id = match kind {
-                VariableType::Auxiliary => format!("aux_sym_{}", self.sanitize_identifier(name)),
-                VariableType::Anonymous => format!("anon_sym_{}", self.sanitize_identifier(name)),
+                VariableType::Auxiliary => format!("aux_sym_{}", sanitize_identifier(name)),
+                VariableType::Anonymous => format!("anon_sym_{}", sanitize_identifier(name)),
                 VariableType::Hidden | VariableType::Named => {
-                    format!("sym_{}", self.sanitize_identifier(name))
+                    format!("sym_{}", sanitize_identifier(name))
                 }
             };
 
@@ -1532,101 +2292,6 @@ impl Generator {
         }
     }
 
-    fn sanitize_identifier(&self, name: &str) -> String {
-        let mut result = String::with_capacity(name.len());
-        for c in name.chars() {
-            if c.is_ascii_alphanumeric() || c == '_' {
-                result.push(c);
-            } else {
-                'special_chars: {
-                    let replacement = match c {
-                        ' ' if name.len() == 1 => "SPACE",
-                        '~' => "TILDE",
-                        '`' => "BQUOTE",
-                        '!' => "BANG",
-                        '@' => "AT",
-                        '#' => "POUND",
-                        '$' => "DOLLAR",
-                        '%' => "PERCENT",
-                        '^' => "CARET",
-                        '&' => "AMP",
-                        '*' => "STAR",
-                        '(' => "LPAREN",
-                        ')' => "RPAREN",
-                        '-' => "DASH",
-                        '+' => "PLUS",
-                        '=' => "EQ",
-                        '{' => "LBRACE",
-                        '}' => "RBRACE",
-                        '[' => "LBRACK",
-                        ']' => "RBRACK",
-                        '\\' => "BSLASH",
-                        '|' => "PIPE",
-                        ':' => "COLON",
-                        ';' => "SEMI",
-                        '"' => "DQUOTE",
-                        '\'' => "SQUOTE",
-                        '<' => "LT",
-                        '>' => "GT",
-                        ',' => "COMMA",
-                        '.' => "DOT",
-                        '?' => "QMARK",
-                        '/' => "SLASH",
-                        '\n' => "LF",
-                        '\r' => "CR",
-                        '\t' => "TAB",
-                        '\0' => "NULL",
-                        '\u{0001}' => "SOH",
-                        '\u{0002}' => "STX",
-                        '\u{0003}' => "ETX",
-                        '\u{0004}' => "EOT",
-                        '\u{0005}' => "ENQ",
-                        '\u{0006}' => "ACK",
-                        '\u{0007}' => "BEL",
-                        '\u{0008}' => "BS",
-                        '\u{000b}' => "VTAB",
-                        '\u{000c}' => "FF",
-                        '\u{000e}' => "SO",
-                        '\u{000f}' => "SI",
-                        '\u{0010}' => "DLE",
-                        '\u{0011}' => "DC1",
-                        '\u{0012}' => "DC2",
-                        '\u{0013}' => "DC3",
-                        '\u{0014}' => "DC4",
-                        '\u{0015}' => "NAK",
-                        '\u{0016}' => "SYN",
-                        '\u{0017}' => "ETB",
-                        '\u{0018}' => "CAN",
-                        '\u{0019}' => "EM",
-                        '\u{001a}' => "SUB",
-                        '\u{001b}' => "ESC",
-                        '\u{001c}' => "FS",
-                        '\u{001d}' => "GS",
-                        '\u{001e}' => "RS",
-                        '\u{001f}' => "US",
-                        '\u{007F}' => "DEL",
-                        '\u{FEFF}' => "BOM",
-                        '\u{0080}'..='\u{FFFF}' => {
-                            result.push_str(&format!("u{:04x}", c as u32));
-                            break 'special_chars;
-                        }
-                        '\u{10000}'..='\u{10FFFF}' => {
-                            result.push_str(&format!("U{:08x}", c as u32));
-                            break 'special_chars;
-                        }
-                        '0'..='9' | 'a'..='z' | 'A'..='Z' | '_' => unreachable!(),
-                        ' ' => break 'special_chars,
-                    };
-                    if !result.is_empty() && !result.ends_with('_') {
-                        result.push('_');
-                    }
-                    result += replacement;
-                }
-            }
-        }
-        result
-    }
-
     fn sanitize_string(&self, name: &str) -> String {
         let mut result = String::with_capacity(name.len());
         for c in name.chars() {
@@ -1674,7 +2339,103 @@ impl Generator {
     }
 }
 
-/// Returns a String of C code for the given components of a parser.
+/// Convert a symbol name into a string that can be used in a C identifier.
+pub fn sanitize_identifier(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            result.push(c);
+        } else {
+            'special_chars: {
+                let replacement = match c {
+                    ' ' if name.len() == 1 => "SPACE",
+                    '~' => "TILDE",
+                    '`' => "BQUOTE",
+                    '!' => "BANG",
+                    '@' => "AT",
+                    '#' => "POUND",
+                    '$' => "DOLLAR",
+                    '%' => "PERCENT",
+                    '^' => "CARET",
+                    '&' => "AMP",
+                    '*' => "STAR",
+                    '(' => "LPAREN",
+                    ')' => "RPAREN",
+                    '-' => "DASH",
+                    '+' => "PLUS",
+                    '=' => "EQ",
+                    '{' => "LBRACE",
+                    '}' => "RBRACE",
+                    '[' => "LBRACK",
+                    ']' => "RBRACK",
+                    '\\' => "BSLASH",
+                    '|' => "PIPE",
+                    ':' => "COLON",
+                    ';' => "SEMI",
+                    '"' => "DQUOTE",
+                    '\'' => "SQUOTE",
+                    '<' => "LT",
+                    '>' => "GT",
+                    ',' => "COMMA",
+                    '.' => "DOT",
+                    '?' => "QMARK",
+                    '/' => "SLASH",
+                    '\n' => "LF",
+                    '\r' => "CR",
+                    '\t' => "TAB",
+                    '\0' => "NULL",
+                    '\u{0001}' => "SOH",
+                    '\u{0002}' => "STX",
+                    '\u{0003}' => "ETX",
+                    '\u{0004}' => "EOT",
+                    '\u{0005}' => "ENQ",
+                    '\u{0006}' => "ACK",
+                    '\u{0007}' => "BEL",
+                    '\u{0008}' => "BS",
+                    '\u{000b}' => "VTAB",
+                    '\u{000c}' => "FF",
+                    '\u{000e}' => "SO",
+                    '\u{000f}' => "SI",
+                    '\u{0010}' => "DLE",
+                    '\u{0011}' => "DC1",
+                    '\u{0012}' => "DC2",
+                    '\u{0013}' => "DC3",
+                    '\u{0014}' => "DC4",
+                    '\u{0015}' => "NAK",
+                    '\u{0016}' => "SYN",
+                    '\u{0017}' => "ETB",
+                    '\u{0018}' => "CAN",
+                    '\u{0019}' => "EM",
+                    '\u{001a}' => "SUB",
+                    '\u{001b}' => "ESC",
+                    '\u{001c}' => "FS",
+                    '\u{001d}' => "GS",
+                    '\u{001e}' => "RS",
+                    '\u{001f}' => "US",
+                    '\u{007F}' => "DEL",
+                    '\u{FEFF}' => "BOM",
+                    '\u{0080}'..='\u{FFFF}' => {
+                        result.push_str(&format!("u{:04x}", c as u32));
+                        break 'special_chars;
+                    }
+                    '\u{10000}'..='\u{10FFFF}' => {
+                        result.push_str(&format!("U{:08x}", c as u32));
+                        break 'special_chars;
+                    }
+                    '0'..='9' | 'a'..='z' | 'A'..='Z' | '_' => unreachable!(),
+                    ' ' => break 'special_chars,
+                };
+                if !result.is_empty() && !result.ends_with('_') {
+                    result.push('_');
+                }
+                result += replacement;
+            }
+        }
+    }
+    result
+}
+
+/// Returns the C code for the given components of a parser.
 ///
 /// # Arguments
 ///
@@ -1691,6 +2452,20 @@ impl Generator {
 /// * `abi_version` - The language ABI version that should be generated. Usually you want
 ///   Tree-sitter's current version, but right after making an ABI change, it may be useful to
 ///   generate code with the previous ABI.
+/// * `previous_symbol_ids` - The symbol ids of a previous generation, whose order should be kept.
+/// * `split_lexer` - Whether to return the lex functions as a separate C file, alongside the
+///   parser.
+/// * `freestanding` - Whether the generated code should avoid depending on libc.
+/// * `export_prefix` - The prefix of the names of the functions that the parser exports and
+///   expects its external scanner to export.
+/// * `shared_character_sets_include` - The path that the parser includes the shared character
+///   sets from, if they are shared between parsers.
+/// * `lexer_strategy` - How the lex functions are emitted.
+/// * `c_standard` - The version of the C standard that the generated code is written in.
+/// * `inline_keyword` - The keyword that the parser header should use for inline functions,
+///   if not the one that it picks for the compiler.
+/// * `annotate` - Whether to add comments that describe the parse and lex states in terms of
+///   the grammar. The tables must have been built with their parse state sources.
 #[allow(clippy::too_many_arguments)]
 pub fn render_c_code(
     name: &str,
@@ -1698,8 +2473,18 @@ pub fn render_c_code(
     syntax_grammar: SyntaxGrammar,
     lexical_grammar: LexicalGrammar,
     default_aliases: AliasMap,
+    token_display_names: HashMap<Symbol, String>,
     abi_version: usize,
-) -> String {
+    previous_symbol_ids: Vec<String>,
+    split_lexer: bool,
+    freestanding: bool,
+    export_prefix: &str,
+    shared_character_sets_include: Option<&str>,
+    lexer_strategy: LexerStrategy,
+    c_standard: CStandard,
+    inline_keyword: Option<&str>,
+    annotate: bool,
+) -> RenderedParser {
     assert!(
         (ABI_VERSION_MIN..=ABI_VERSION_MAX).contains(&abi_version),
         "This version of Tree-sitter can only generate parsers with ABI version {ABI_VERSION_MIN} - {ABI_VERSION_MAX}, not {abi_version}",
@@ -1716,16 +2501,90 @@ pub fn render_c_code(
         keyword_capture_token: tables.word_token,
         large_character_sets: tables.large_character_sets,
         large_character_set_info: Vec::new(),
+        ascii_bitmaps: Vec::new(),
+        parse_state_sources: tables.parse_state_sources,
         syntax_grammar,
         lexical_grammar,
         default_aliases,
+        token_display_names,
         symbol_ids: HashMap::new(),
         symbol_order: HashMap::new(),
+        unused_symbol_ids: Vec::new(),
+        unused_token_id_count: 0,
         alias_ids: HashMap::new(),
         symbol_map: HashMap::new(),
         unique_aliases: Vec::new(),
         field_names: Vec::new(),
+        previous_symbol_ids,
+        split_lexer,
+        freestanding,
+        export_prefix: export_prefix.to_string(),
+        shared_character_sets_include: shared_character_sets_include.map(str::to_string),
+        shared_character_sets: Vec::new(),
+        lexer_strategy,
+        c_standard,
+        inline_keyword: inline_keyword.map(str::to_string),
+        annotate,
         abi_version,
     }
     .generate()
 }
+
+/// A comment that describes the generated code, which can't end the comment early or
+/// start a nested one.
+fn annotation(text: &str) -> String {
+    let text = text
+        .chars()
+        .map(|c| {
+            if c.is_control() {
+                c.escape_default().to_string()
+            } else {
+                c.to_string()
+            }
+        })
+        .collect::<String>()
+        .replace("*/", "* /")
+        .replace("/*", "/ *");
+    format!("{ANNOTATION_START}{text} */")
+}
+
+/// Remove the comments that `--annotate` adds to the generated code, which gives the
+/// code that is generated without it.
+pub fn strip_annotations(code: &str) -> String {
+    let mut result = String::with_capacity(code.len());
+    for line in code.lines() {
+        if line.trim_start().starts_with(ANNOTATION_START) {
+            continue;
+        }
+        let line = match line.find(&format!(" {ANNOTATION_START}")) {
+            Some(index) if line.ends_with("*/") => &line[..index],
+            _ => line,
+        };
+        result += line;
+        result.push('\n');
+    }
+    result
+}
+
+/// Rewrite the lines that consist of a `//` comment as `/* */` comments, because C89
+/// doesn't have line comments. This is only safe for code that doesn't have line
+/// comments inside of block comments, like the generated code.
+pub fn to_block_comments(code: &str) -> String {
+    let mut result = String::with_capacity(code.len());
+    for line in code.lines() {
+        let trimmed_line = line.trim_start();
+        if let Some(comment) = trimmed_line.strip_prefix("//") {
+            let indent = &line[..line.len() - trimmed_line.len()];
+            let comment = comment.trim_end().replace("*/", "* /");
+            if comment.trim().is_empty() {
+                result.push('\n');
+            } else {
+                writeln!(&mut result, "{indent}/*{comment} */").unwrap();
+            }
+        } else {
+            result += line;
+            result.push('\n');
+        }
+    }
+    result
+}
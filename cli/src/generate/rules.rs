@@ -1,10 +1,10 @@
-use std::{collections::HashMap, fmt};
+use std::{collections::HashMap, fmt, iter};
 
-use smallbitvec::SmallBitVec;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use super::grammars::VariableType;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum SymbolType {
     External,
     End,
@@ -13,19 +13,19 @@ pub enum SymbolType {
     NonTerminal,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum Associativity {
     Left,
     Right,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Alias {
     pub value: String,
     pub is_named: bool,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize)]
 pub enum Precedence {
     #[default]
     None,
@@ -35,7 +35,7 @@ pub enum Precedence {
 
 pub type AliasMap = HashMap<Symbol, Alias>;
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct MetadataParams {
     pub precedence: Precedence,
     pub dynamic_precedence: i32,
@@ -48,13 +48,13 @@ pub struct MetadataParams {
     pub field_name: Option<String>,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Symbol {
     pub kind: SymbolType,
     pub index: usize,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Rule {
     Blank,
     String(String),
@@ -76,8 +76,8 @@ pub enum Rule {
 // the token is present in the set.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TokenSet {
-    terminal_bits: SmallBitVec,
-    external_bits: SmallBitVec,
+    terminal_bits: BitSet,
+    external_bits: BitSet,
     eof: bool,
     end_of_nonterminal_extra: bool,
 }
@@ -274,36 +274,16 @@ impl From<Symbol> for Rule {
 impl TokenSet {
     pub const fn new() -> Self {
         Self {
-            terminal_bits: SmallBitVec::new(),
-            external_bits: SmallBitVec::new(),
+            terminal_bits: BitSet::new(),
+            external_bits: BitSet::new(),
             eof: false,
             end_of_nonterminal_extra: false,
         }
     }
 
     pub fn iter(&self) -> impl Iterator<Item = Symbol> + '_ {
-        self.terminal_bits
-            .iter()
-            .enumerate()
-            .filter_map(|(i, value)| {
-                if value {
-                    Some(Symbol::terminal(i))
-                } else {
-                    None
-                }
-            })
-            .chain(
-                self.external_bits
-                    .iter()
-                    .enumerate()
-                    .filter_map(|(i, value)| {
-                        if value {
-                            Some(Symbol::external(i))
-                        } else {
-                            None
-                        }
-                    }),
-            )
+        self.terminals()
+            .chain(self.external_bits.iter().map(Symbol::external))
             .chain(if self.eof { Some(Symbol::end()) } else { None })
             .chain(if self.end_of_nonterminal_extra {
                 Some(Symbol::end_of_nonterminal_extra())
@@ -313,30 +293,21 @@ impl TokenSet {
     }
 
     pub fn terminals(&self) -> impl Iterator<Item = Symbol> + '_ {
-        self.terminal_bits
-            .iter()
-            .enumerate()
-            .filter_map(|(i, value)| {
-                if value {
-                    Some(Symbol::terminal(i))
-                } else {
-                    None
-                }
-            })
+        self.terminal_bits.iter().map(Symbol::terminal)
     }
 
     pub fn contains(&self, symbol: &Symbol) -> bool {
         match symbol.kind {
             SymbolType::NonTerminal => panic!("Cannot store non-terminals in a TokenSet"),
-            SymbolType::Terminal => self.terminal_bits.get(symbol.index).unwrap_or(false),
-            SymbolType::External => self.external_bits.get(symbol.index).unwrap_or(false),
+            SymbolType::Terminal => self.terminal_bits.contains(symbol.index),
+            SymbolType::External => self.external_bits.contains(symbol.index),
             SymbolType::End => self.eof,
             SymbolType::EndOfNonTerminalExtra => self.end_of_nonterminal_extra,
         }
     }
 
     pub fn contains_terminal(&self, index: usize) -> bool {
-        self.terminal_bits.get(index).unwrap_or(false)
+        self.terminal_bits.contains(index)
     }
 
     pub fn insert(&mut self, other: Symbol) {
@@ -353,10 +324,7 @@ impl TokenSet {
                 return;
             }
         };
-        if other.index >= vec.len() {
-            vec.resize(other.index + 1, false);
-        }
-        vec.set(other.index, true);
+        vec.insert(other.index);
     }
 
     pub fn remove(&mut self, other: &Symbol) -> bool {
@@ -381,46 +349,22 @@ impl TokenSet {
                 };
             }
         };
-        if other.index < vec.len() && vec[other.index] {
-            vec.set(other.index, false);
-            return true;
-        }
-        false
+        vec.remove(other.index)
     }
 
     pub fn is_empty(&self) -> bool {
         !self.eof
             && !self.end_of_nonterminal_extra
-            && !self.terminal_bits.iter().any(|a| a)
-            && !self.external_bits.iter().any(|a| a)
+            && self.terminal_bits.is_empty()
+            && self.external_bits.is_empty()
     }
 
     pub fn insert_all_terminals(&mut self, other: &Self) -> bool {
-        let mut result = false;
-        if other.terminal_bits.len() > self.terminal_bits.len() {
-            self.terminal_bits.resize(other.terminal_bits.len(), false);
-        }
-        for (i, element) in other.terminal_bits.iter().enumerate() {
-            if element {
-                result |= !self.terminal_bits[i];
-                self.terminal_bits.set(i, element);
-            }
-        }
-        result
+        self.terminal_bits.insert_all(&other.terminal_bits)
     }
 
     fn insert_all_externals(&mut self, other: &Self) -> bool {
-        let mut result = false;
-        if other.external_bits.len() > self.external_bits.len() {
-            self.external_bits.resize(other.external_bits.len(), false);
-        }
-        for (i, element) in other.external_bits.iter().enumerate() {
-            if element {
-                result |= !self.external_bits[i];
-                self.external_bits.set(i, element);
-            }
-        }
-        result
+        self.external_bits.insert_all(&other.external_bits)
     }
 
     pub fn insert_all(&mut self, other: &Self) -> bool {
@@ -439,6 +383,83 @@ impl TokenSet {
     }
 }
 
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A set of small integers, stored a word at a time so that sets can be merged and
+/// compared without visiting each of their elements. The last word is never zero, so
+/// equal sets are stored identically.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    const fn new() -> Self {
+        Self { words: Vec::new() }
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.words
+            .get(index / WORD_BITS)
+            .is_some_and(|word| word & (1 << (index % WORD_BITS)) != 0)
+    }
+
+    fn insert(&mut self, index: usize) {
+        let word_index = index / WORD_BITS;
+        if word_index >= self.words.len() {
+            self.words.resize(word_index + 1, 0);
+        }
+        self.words[word_index] |= 1 << (index % WORD_BITS);
+    }
+
+    fn remove(&mut self, index: usize) -> bool {
+        let Some(word) = self.words.get_mut(index / WORD_BITS) else {
+            return false;
+        };
+        let bit = 1 << (index % WORD_BITS);
+        let was_present = *word & bit != 0;
+        *word &= !bit;
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+        was_present
+    }
+
+    /// Add all of the elements of `other`, returning whether any of them were new.
+    fn insert_all(&mut self, other: &Self) -> bool {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        let mut changed = false;
+        for (word, other_word) in self.words.iter_mut().zip(&other.words) {
+            changed |= other_word & !*word != 0;
+            *word |= other_word;
+        }
+        changed
+    }
+
+    fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words
+            .iter()
+            .enumerate()
+            .flat_map(|(word_index, word)| {
+                let mut word = *word;
+                iter::from_fn(move || {
+                    if word == 0 {
+                        return None;
+                    }
+                    let bit = word.trailing_zeros() as usize;
+                    word &= word - 1;
+                    Some(word_index * WORD_BITS + bit)
+                })
+            })
+    }
+}
+
 impl FromIterator<Symbol> for TokenSet {
     fn from_iter<T: IntoIterator<Item = Symbol>>(iter: T) -> Self {
         let mut result = Self::new();
@@ -449,6 +470,26 @@ impl FromIterator<Symbol> for TokenSet {
     }
 }
 
+// A token set is stored as the list of its symbols, since its bit vectors can't be
+// serialized directly.
+impl Serialize for TokenSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for TokenSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let symbols = Vec::<Symbol>::deserialize(deserializer)?;
+        if symbols.iter().any(Symbol::is_non_terminal) {
+            return Err(de::Error::custom(
+                "a token set cannot contain non-terminals",
+            ));
+        }
+        Ok(symbols.into_iter().collect())
+    }
+}
+
 fn add_metadata<T: FnOnce(&mut MetadataParams)>(input: Rule, f: T) -> Rule {
     match input {
         Rule::Metadata { rule, mut params } if !params.is_token => {
@@ -490,3 +531,48 @@ impl fmt::Display for Precedence {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_set() {
+        let mut set = TokenSet::new();
+        assert!(set.is_empty());
+        set.insert(Symbol::terminal(3));
+        set.insert(Symbol::terminal(200));
+        set.insert(Symbol::external(64));
+        set.insert(Symbol::end());
+        assert!(set.contains(&Symbol::terminal(200)));
+        assert!(!set.contains(&Symbol::terminal(136)));
+        assert!(!set.contains(&Symbol::terminal(1000)));
+        assert_eq!(
+            set.iter().collect::<Vec<_>>(),
+            vec![
+                Symbol::terminal(3),
+                Symbol::terminal(200),
+                Symbol::external(64),
+                Symbol::end(),
+            ]
+        );
+
+        let mut other = [Symbol::terminal(3), Symbol::terminal(64)]
+            .into_iter()
+            .collect::<TokenSet>();
+        assert!(other.insert_all(&set));
+        assert!(!other.insert_all(&set));
+        assert_eq!(other.terminals().count(), 3);
+
+        // Sets are equal when they have the same elements, regardless of which elements
+        // they held before.
+        assert!(set.remove(&Symbol::terminal(200)));
+        assert!(!set.remove(&Symbol::terminal(200)));
+        assert_eq!(
+            set,
+            [Symbol::terminal(3), Symbol::external(64), Symbol::end()]
+                .into_iter()
+                .collect()
+        );
+    }
+}
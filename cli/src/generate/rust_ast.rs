@@ -0,0 +1,339 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+};
+
+use heck::{ToSnakeCase, ToUpperCamelCase};
+
+use super::node_types::{FieldInfoJSON, NodeInfoJSON, NodeTypeJSON};
+
+pub const RUST_AST_HEADER: &str = "// This file is generated by `tree-sitter generate --rust-ast`.";
+
+/// Names that the generated types must not shadow, because the generated code uses them.
+const RESERVED_TYPE_NAMES: [&str; 11] = [
+    "AstNode",
+    "Clone",
+    "Copy",
+    "Debug",
+    "Eq",
+    "Node",
+    "None",
+    "Option",
+    "PartialEq",
+    "Some",
+    "Vec",
+];
+
+const RUST_KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use",
+    "where", "while", "yield",
+];
+
+/// The Rust type used for the nodes of a field, or for the children of a node.
+enum ChildType {
+    Typed(String),
+    Untyped,
+}
+
+struct RustAstGenerator<'a> {
+    buffer: String,
+    node_types: &'a [NodeInfoJSON],
+    type_names: HashMap<&'a str, String>,
+    used_type_names: HashSet<String>,
+    choice_enums: Vec<(String, Vec<String>)>,
+}
+
+/// Render a Rust module with a typed wrapper for each named node type: a struct with an
+/// accessor for each field for regular nodes, and an enum of the possible subtypes for
+/// supertypes.
+pub fn render_rust_ast(language_name: &str, node_types: &[NodeInfoJSON]) -> String {
+    let mut generator = RustAstGenerator {
+        buffer: String::new(),
+        node_types,
+        type_names: HashMap::new(),
+        used_type_names: HashSet::new(),
+        choice_enums: Vec::new(),
+    };
+    generator.generate(language_name);
+    generator.buffer
+}
+
+impl<'a> RustAstGenerator<'a> {
+    fn generate(&mut self, language_name: &str) {
+        for node_type in self.node_types {
+            if node_type.named && !self.is_empty_supertype(node_type) {
+                let name = self.unique_type_name(node_type.kind.to_upper_camel_case());
+                self.type_names.insert(&node_type.kind, name);
+            }
+        }
+
+        writeln!(&mut self.buffer, "{RUST_AST_HEADER}").unwrap();
+        writeln!(
+            &mut self.buffer,
+            "//! Typed wrappers for the syntax nodes of the `{language_name}` grammar.
+
+use tree_sitter::Node;
+
+/// A typed wrapper around a syntax node.
+pub trait AstNode<'tree>: Sized {{
+    /// Wrap the given node, if it has this type.
+    fn cast(node: Node<'tree>) -> Option<Self>;
+
+    /// The wrapped node.
+    fn node(&self) -> Node<'tree>;
+}}
+
+/// The named children of a node that are not the values of any field.
+fn children_without_field(node: Node<'_>) -> Vec<Node<'_>> {{
+    let mut cursor = node.walk();
+    let mut result = Vec::new();
+    if cursor.goto_first_child() {{
+        loop {{
+            if cursor.field_name().is_none() && cursor.node().is_named() {{
+                result.push(cursor.node());
+            }}
+            if !cursor.goto_next_sibling() {{
+                break;
+            }}
+        }}
+    }}
+    result
+}}"
+        )
+        .unwrap();
+
+        for node_type in self.node_types {
+            if !node_type.named {
+                continue;
+            }
+            let Some(name) = self.type_names.get(node_type.kind.as_str()).cloned() else {
+                continue;
+            };
+            if let Some(subtypes) = &node_type.subtypes {
+                let variants = subtypes
+                    .iter()
+                    .filter_map(|subtype| self.named_type_name(subtype))
+                    .collect::<Vec<_>>();
+                self.add_enum(&format!("A `{}` node.", node_type.kind), &name, &variants);
+            } else {
+                self.add_struct(node_type, &name);
+            }
+        }
+
+        for (name, variants) in std::mem::take(&mut self.choice_enums) {
+            self.add_enum("One of the types of nodes in a field.", &name, &variants);
+        }
+    }
+
+    fn is_empty_supertype(&self, node_type: &NodeInfoJSON) -> bool {
+        node_type
+            .subtypes
+            .as_ref()
+            .is_some_and(|subtypes| !subtypes.iter().any(|subtype| subtype.named))
+    }
+
+    fn unique_type_name(&mut self, name: String) -> String {
+        let mut name = name
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>();
+        if name.is_empty()
+            || name.starts_with(|c: char| c.is_ascii_digit())
+            || RESERVED_TYPE_NAMES.contains(&name.as_str())
+            || name == "Self"
+        {
+            name.insert_str(0, "Node");
+        }
+        let mut result = name.clone();
+        let mut suffix = 1;
+        while !self.used_type_names.insert(result.clone()) {
+            suffix += 1;
+            result = format!("{name}{suffix}");
+        }
+        result
+    }
+
+    fn named_type_name(&self, node_type: &NodeTypeJSON) -> Option<String> {
+        if node_type.named {
+            self.type_names.get(node_type.kind.as_str()).cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Determine the Rust type of the nodes of a field, adding an enum of the node types
+    /// if there are several. Fields that can contain anonymous nodes are left untyped.
+    fn child_type(&mut self, enum_name: String, field_info: &FieldInfoJSON) -> ChildType {
+        let names = field_info
+            .types
+            .iter()
+            .map(|node_type| self.named_type_name(node_type))
+            .collect::<Option<Vec<_>>>();
+        match names {
+            Some(names) if names.len() == 1 => ChildType::Typed(names[0].clone()),
+            Some(names) if !names.is_empty() => {
+                let enum_name = self.unique_type_name(enum_name);
+                self.choice_enums.push((enum_name.clone(), names));
+                ChildType::Typed(enum_name)
+            }
+            _ => ChildType::Untyped,
+        }
+    }
+
+    fn add_struct(&mut self, node_type: &NodeInfoJSON, name: &str) {
+        let kind = &node_type.kind;
+        write!(
+            &mut self.buffer,
+            "
+/// A `{kind}` node.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct {name}<'tree>(Node<'tree>);
+
+impl<'tree> AstNode<'tree> for {name}<'tree> {{
+    fn cast(node: Node<'tree>) -> Option<Self> {{
+        (node.is_named() && node.kind() == {kind:?}).then_some(Self(node))
+    }}
+
+    fn node(&self) -> Node<'tree> {{
+        self.0
+    }}
+}}
+"
+        )
+        .unwrap();
+
+        let empty_fields = BTreeMap::new();
+        let fields = node_type.fields.as_ref().unwrap_or(&empty_fields);
+        let mut accessors = Vec::new();
+        for (field_name, field_info) in fields {
+            let child_type = self.child_type(
+                format!("{name}{}", field_name.to_upper_camel_case()),
+                field_info,
+            );
+            let method_name = method_name(field_name);
+            let nodes = if field_info.multiple {
+                format!("{{\n        let mut cursor = self.0.walk();\n        self.0.children_by_field_name({field_name:?}, &mut cursor)")
+            } else {
+                format!("self.0.child_by_field_name({field_name:?})")
+            };
+            accessors.push(render_accessor(
+                &format!("The `{field_name}` field."),
+                &method_name,
+                &child_type,
+                field_info.multiple,
+                &nodes,
+            ));
+        }
+        if let Some(children) = &node_type.children {
+            let child_type = self.child_type(format!("{name}Child"), children);
+            accessors.push(render_accessor(
+                "The named children that are not in a field.",
+                "children",
+                &child_type,
+                true,
+                "{\n        children_without_field(self.0).into_iter()",
+            ));
+        }
+
+        if !accessors.is_empty() {
+            writeln!(&mut self.buffer, "\nimpl<'tree> {name}<'tree> {{").unwrap();
+            self.buffer += &accessors.join("\n");
+            writeln!(&mut self.buffer, "}}").unwrap();
+        }
+    }
+
+    fn add_enum(&mut self, doc: &str, name: &str, variants: &[String]) {
+        writeln!(
+            &mut self.buffer,
+            "\n/// {doc}\n#[derive(Clone, Copy, Debug, PartialEq, Eq)]\npub enum {name}<'tree> {{"
+        )
+        .unwrap();
+        for variant in variants {
+            writeln!(&mut self.buffer, "    {variant}({variant}<'tree>),").unwrap();
+        }
+        writeln!(
+            &mut self.buffer,
+            "}}\n\nimpl<'tree> AstNode<'tree> for {name}<'tree> {{\n    fn cast(node: Node<'tree>) -> Option<Self> {{"
+        )
+        .unwrap();
+        for variant in variants {
+            writeln!(
+                &mut self.buffer,
+                "        if let Some(node) = {variant}::cast(node) {{\n            return Some(Self::{variant}(node));\n        }}"
+            )
+            .unwrap();
+        }
+        writeln!(
+            &mut self.buffer,
+            "        None\n    }}\n\n    fn node(&self) -> Node<'tree> {{\n        match self {{"
+        )
+        .unwrap();
+        for variant in variants {
+            writeln!(
+                &mut self.buffer,
+                "            Self::{variant}(node) => node.node(),"
+            )
+            .unwrap();
+        }
+        writeln!(&mut self.buffer, "        }}\n    }}\n}}").unwrap();
+    }
+}
+
+fn render_accessor(
+    doc: &str,
+    method_name: &str,
+    child_type: &ChildType,
+    multiple: bool,
+    nodes: &str,
+) -> String {
+    let mut result = format!("    /// {doc}\n");
+    match (child_type, multiple) {
+        (ChildType::Typed(name), false) => {
+            write!(
+                &mut result,
+                "    pub fn {method_name}(&self) -> Option<{name}<'tree>> {{\n        {nodes}.and_then({name}::cast)\n    }}\n"
+            )
+            .unwrap();
+        }
+        (ChildType::Untyped, false) => {
+            write!(
+                &mut result,
+                "    pub fn {method_name}(&self) -> Option<Node<'tree>> {{\n        {nodes}\n    }}\n"
+            )
+            .unwrap();
+        }
+        (ChildType::Typed(name), true) => {
+            write!(
+                &mut result,
+                "    pub fn {method_name}(&self) -> Vec<{name}<'tree>> {nodes}\n            .filter_map({name}::cast)\n            .collect()\n    }}\n"
+            )
+            .unwrap();
+        }
+        (ChildType::Untyped, true) => {
+            write!(
+                &mut result,
+                "    pub fn {method_name}(&self) -> Vec<Node<'tree>> {nodes}\n            .collect()\n    }}\n"
+            )
+            .unwrap();
+        }
+    }
+    result
+}
+
+/// The name of the accessor method for a field, avoiding keywords and the methods of
+/// `AstNode`.
+fn method_name(field_name: &str) -> String {
+    let name = field_name.to_snake_case();
+    if matches!(
+        name.as_str(),
+        "self" | "super" | "crate" | "cast" | "node" | "children"
+    ) {
+        format!("{name}_field")
+    } else if RUST_KEYWORDS.contains(&name.as_str()) {
+        format!("r#{name}")
+    } else {
+        name
+    }
+}
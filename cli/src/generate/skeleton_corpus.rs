@@ -0,0 +1,512 @@
+use std::{collections::VecDeque, fmt::Write};
+
+use smallvec::smallvec;
+
+use super::{
+    grammars::{LexicalGrammar, ProductionStep, SyntaxGrammar, VariableType},
+    nfa::{CharacterSet, Nfa, NfaCursor, NfaState, NfaStateSet},
+    rules::{Alias, AliasMap, Symbol, SymbolType},
+};
+
+pub(super) const UNREACHABLE: usize = usize::MAX;
+
+/// A node that appears in the expected syntax tree of a test, with the field
+/// that it belongs to in its parent.
+struct SexpNode {
+    field_name: Option<String>,
+    sexp: String,
+}
+
+pub(super) struct CorpusGenerator<'a> {
+    pub(super) syntax_grammar: &'a SyntaxGrammar,
+    pub(super) lexical_grammar: &'a LexicalGrammar,
+    default_aliases: &'a AliasMap,
+    pub(super) token_samples: Vec<Option<String>>,
+    pub(super) derivation_sizes: Vec<usize>,
+}
+
+/// Render a starter corpus with one test for each visible rule in the grammar. Each
+/// test's input is the shortest program, in number of tokens, that contains the rule,
+/// and its expected output is the tree that the program was derived from.
+///
+/// Programs that would need tokens from the external scanner are not generated, so
+/// rules that can't be reached without them have no test.
+pub fn render_skeleton_corpus(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    default_aliases: &AliasMap,
+) -> Option<String> {
+    let generator = CorpusGenerator::new(syntax_grammar, lexical_grammar, default_aliases);
+    let mut result = String::new();
+    for (i, variable) in syntax_grammar.variables.iter().enumerate() {
+        let symbol = Symbol::non_terminal(i);
+        if variable.kind != VariableType::Named
+            || syntax_grammar.variables_to_inline.contains(&symbol)
+        {
+            continue;
+        }
+
+        let sizes = generator.compute_derivation_sizes(Some(symbol));
+        if sizes[0] == UNREACHABLE {
+            continue;
+        }
+        let mut tokens = Vec::new();
+        let nodes = generator.expand(
+            Symbol::non_terminal(0),
+            None,
+            Some((symbol, &sizes)),
+            &mut tokens,
+        );
+        let [node] = nodes.as_slice() else {
+            continue;
+        };
+
+        if !result.is_empty() {
+            result.push('\n');
+        }
+        writeln!(
+            &mut result,
+            "{}\n{}\n{}\n\n{}\n\n{}\n\n{}",
+            "=".repeat(80),
+            variable.name,
+            "=".repeat(80),
+            generator.join_tokens(&tokens),
+            "-".repeat(80),
+            tree_sitter::format_sexp(&node.sexp, 0),
+        )
+        .unwrap();
+    }
+
+    if result.is_empty() {
+        None
+    } else {
+        Some(result)
+    }
+}
+
+impl<'a> CorpusGenerator<'a> {
+    /// Prepare a sample of each token, and the size of each non-terminal's smallest
+    /// derivation.
+    pub(super) fn new(
+        syntax_grammar: &'a SyntaxGrammar,
+        lexical_grammar: &'a LexicalGrammar,
+        default_aliases: &'a AliasMap,
+    ) -> Self {
+        let token_samples = (0..lexical_grammar.variables.len())
+            .map(|i| token_sample(lexical_grammar, i))
+            .collect();
+        let mut generator = CorpusGenerator {
+            syntax_grammar,
+            lexical_grammar,
+            default_aliases,
+            token_samples,
+            derivation_sizes: Vec::new(),
+        };
+        generator.derivation_sizes = generator.compute_derivation_sizes(None);
+        generator
+    }
+
+    /// Compute the size, in tokens, of the smallest derivation of each non-terminal.
+    /// If a target symbol is given, only derivations that contain the target are
+    /// considered.
+    fn compute_derivation_sizes(&self, target: Option<Symbol>) -> Vec<usize> {
+        let mut sizes = vec![UNREACHABLE; self.syntax_grammar.variables.len()];
+        loop {
+            let mut changed = false;
+            for (i, variable) in self.syntax_grammar.variables.iter().enumerate() {
+                let size = if target == Some(Symbol::non_terminal(i)) {
+                    self.derivation_sizes[i]
+                } else {
+                    variable
+                        .productions
+                        .iter()
+                        .map(|production| {
+                            if target.is_none() {
+                                return self.production_size(&production.steps, &sizes);
+                            }
+                            let step_sizes = production
+                                .steps
+                                .iter()
+                                .map(|step| self.symbol_size(step.symbol, &self.derivation_sizes))
+                                .collect::<Vec<_>>();
+                            let total = step_sizes
+                                .iter()
+                                .fold(0, |total: usize, size| total.saturating_add(*size));
+
+                            // The target must be derived from one of the steps, and the
+                            // other steps are derived as small as possible.
+                            production
+                                .steps
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, step)| step.symbol.is_non_terminal())
+                                .map(|(j, step)| {
+                                    let size = sizes[step.symbol.index];
+                                    if size == UNREACHABLE || step_sizes[j] == UNREACHABLE {
+                                        UNREACHABLE
+                                    } else {
+                                        total.saturating_sub(step_sizes[j]).saturating_add(size)
+                                    }
+                                })
+                                .min()
+                                .unwrap_or(UNREACHABLE)
+                        })
+                        .min()
+                        .unwrap_or(UNREACHABLE)
+                };
+                if size < sizes[i] {
+                    sizes[i] = size;
+                    changed = true;
+                }
+            }
+            if !changed {
+                return sizes;
+            }
+        }
+    }
+
+    pub(super) fn production_size(&self, steps: &[ProductionStep], sizes: &[usize]) -> usize {
+        steps.iter().fold(0, |total: usize, step| {
+            total.saturating_add(self.symbol_size(step.symbol, sizes))
+        })
+    }
+
+    fn symbol_size(&self, symbol: Symbol, sizes: &[usize]) -> usize {
+        match symbol.kind {
+            SymbolType::NonTerminal => sizes[symbol.index],
+            SymbolType::Terminal if self.token_samples[symbol.index].is_some() => 1,
+            _ => UNREACHABLE,
+        }
+    }
+
+    /// Derive the smallest program for the given symbol, which contains the target
+    /// symbol if one is given. The tokens of the program are added to `tokens`, and
+    /// the visible nodes of the derivation are returned.
+    fn expand(
+        &self,
+        symbol: Symbol,
+        alias: Option<&Alias>,
+        target: Option<(Symbol, &[usize])>,
+        tokens: &mut Vec<(usize, String)>,
+    ) -> Vec<SexpNode> {
+        let alias = alias.or_else(|| self.default_aliases.get(&symbol));
+        let (name, kind) = match symbol.kind {
+            SymbolType::Terminal => {
+                let variable = &self.lexical_grammar.variables[symbol.index];
+                (&variable.name, variable.kind)
+            }
+            _ => {
+                let variable = &self.syntax_grammar.variables[symbol.index];
+                if self.syntax_grammar.variables_to_inline.contains(&symbol) {
+                    (&variable.name, VariableType::Hidden)
+                } else {
+                    (&variable.name, variable.kind)
+                }
+            }
+        };
+        let name = alias.map_or(name, |alias| &alias.value);
+        let is_named = alias.map_or(kind == VariableType::Named, |alias| alias.is_named);
+
+        let mut children = Vec::new();
+        if symbol.is_terminal() {
+            tokens.push((
+                symbol.index,
+                self.token_samples[symbol.index].clone().unwrap(),
+            ));
+        } else {
+            let target = target.filter(|(target, _)| *target != symbol);
+            let (production, target_step) = self.choose_production(symbol, target);
+            for (i, step) in production.iter().enumerate() {
+                let step_target = target.filter(|_| Some(i) == target_step);
+                for mut node in self.expand(step.symbol, step.alias.as_ref(), step_target, tokens) {
+                    if node.field_name.is_none() {
+                        node.field_name.clone_from(&step.field_name);
+                    }
+                    children.push(node);
+                }
+            }
+        }
+
+        if !is_named {
+            return children;
+        }
+        let mut sexp = format!("({name}");
+        for child in children {
+            sexp.push(' ');
+            if let Some(field_name) = child.field_name {
+                write!(&mut sexp, "{field_name}: ").unwrap();
+            }
+            sexp += &child.sexp;
+        }
+        sexp.push(')');
+        vec![SexpNode {
+            field_name: None,
+            sexp,
+        }]
+    }
+
+    /// Join the tokens of a program, separating them with a space wherever the text of
+    /// one token would otherwise run into the next, unless the grammar doesn't allow
+    /// whitespace there.
+    ///
+    /// Words are always separated. Otherwise, only the two tokens themselves and the
+    /// grammar's string tokens are checked for running into the next token, because
+    /// tokens with broad patterns, like the arguments of C preprocessor directives,
+    /// would run into almost anything.
+    pub(super) fn join_tokens(&self, tokens: &[(usize, String)]) -> String {
+        let nfa = &self.lexical_grammar.nfa;
+        let string_start_states = self
+            .lexical_grammar
+            .variables
+            .iter()
+            .filter(|variable| variable.kind == VariableType::Anonymous)
+            .map(|variable| variable.start_state)
+            .collect::<Vec<_>>();
+        let mut result = String::new();
+        let mut previous = None;
+        for (index, token) in tokens {
+            let Some((previous_index, previous_token)) = previous.replace((*index, token)) else {
+                result += token;
+                continue;
+            };
+
+            let allows_space = NfaCursor::new(
+                nfa,
+                smallvec![self.lexical_grammar.variables[*index].start_state],
+            )
+            .transition_chars()
+            .any(|(chars, is_sep)| is_sep && chars.contains(' '));
+            let mut start_states = string_start_states.clone();
+            start_states.push(self.lexical_grammar.variables[previous_index].start_state);
+            start_states.push(self.lexical_grammar.variables[*index].start_state);
+            let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+            let joined = format!("{previous_token}{token}");
+            if allows_space
+                && (previous_token.ends_with(is_word_char) && token.starts_with(is_word_char)
+                    || longest_match(nfa, start_states, &joined) > previous_token.len())
+            {
+                result.push(' ');
+            }
+            result += token;
+        }
+        result
+    }
+
+    /// Choose the production of a non-terminal with the smallest derivation, and the
+    /// step that the target should be derived from, if there is a target.
+    pub(super) fn choose_production(
+        &self,
+        symbol: Symbol,
+        target: Option<(Symbol, &[usize])>,
+    ) -> (&'a [ProductionStep], Option<usize>) {
+        let variable = &self.syntax_grammar.variables[symbol.index];
+        let mut best = (UNREACHABLE, 0, None);
+        for (i, production) in variable.productions.iter().enumerate() {
+            let size = self.production_size(&production.steps, &self.derivation_sizes);
+            if let Some((_, target_sizes)) = target {
+                for (j, step) in production.steps.iter().enumerate() {
+                    if !step.symbol.is_non_terminal()
+                        || target_sizes[step.symbol.index] == UNREACHABLE
+                    {
+                        continue;
+                    }
+                    let step_size = self.symbol_size(step.symbol, &self.derivation_sizes);
+                    let size = size
+                        .saturating_sub(step_size)
+                        .saturating_add(target_sizes[step.symbol.index]);
+                    if size < best.0 {
+                        best = (size, i, Some(j));
+                    }
+                }
+            } else if size < best.0 {
+                best = (size, i, None);
+            }
+        }
+        (&variable.productions[best.1].steps, best.2)
+    }
+}
+
+/// Find the shortest text that matches the given token, preferring ASCII letters
+/// and digits.
+fn token_sample(lexical_grammar: &LexicalGrammar, index: usize) -> Option<String> {
+    let nfa = &lexical_grammar.nfa;
+    let start = lexical_grammar.variables[index].start_state;
+
+    // Explore the NFA breadth-first, recording how each state was reached.
+    let mut previous = vec![None; nfa.states.len()];
+    let mut visited = vec![false; nfa.states.len()];
+    let mut queue = VecDeque::from([start]);
+    visited[start as usize] = true;
+    while let Some(state_id) = queue.pop_front() {
+        match &nfa.states[state_id as usize] {
+            NfaState::Accept { variable_index, .. } => {
+                if *variable_index == index {
+                    let mut result = Vec::new();
+                    let mut state_id = state_id;
+                    while let Some((previous_state_id, c)) = previous[state_id as usize] {
+                        if let Some(c) = c {
+                            result.push(c);
+                        }
+                        state_id = previous_state_id;
+                    }
+                    return Some(result.into_iter().rev().collect());
+                }
+            }
+            NfaState::Split(a, b) => {
+                for next in [*a, *b] {
+                    if !visited[next as usize] {
+                        visited[next as usize] = true;
+                        previous[next as usize] = Some((state_id, None));
+                        queue.push_front(next);
+                    }
+                }
+            }
+            NfaState::Advance {
+                chars,
+                state_id: next,
+                is_sep,
+                ..
+            } => {
+                if !*is_sep && !visited[*next as usize] {
+                    if let Some(c) = sample_char(chars) {
+                        visited[*next as usize] = true;
+                        previous[*next as usize] = Some((state_id, Some(c)));
+                        queue.push_back(*next);
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The length of the longest prefix of the text that matches any of the tokens whose
+/// NFAs start at the given states.
+pub(super) fn longest_match(nfa: &Nfa, start_states: Vec<u32>, text: &str) -> usize {
+    let mut cursor = NfaCursor::new(nfa, start_states.into());
+    let mut result = 0;
+    for (i, c) in text.char_indices() {
+        let states = cursor
+            .transitions()
+            .into_iter()
+            .filter(|transition| !transition.is_separator && transition.characters.contains(c))
+            .flat_map(|transition| transition.states)
+            .collect::<NfaStateSet>();
+        if states.is_empty() {
+            break;
+        }
+        cursor.reset(states);
+        if cursor.completions().next().is_some() {
+            result = i + c.len_utf8();
+        }
+    }
+    result
+}
+
+fn sample_char(chars: &CharacterSet) -> Option<char> {
+    ('a'..='z')
+        .chain('0'..='9')
+        .chain('A'..='Z')
+        .chain('!'..='~')
+        .find(|c| chars.contains(*c))
+        .or_else(|| chars.chars().find(|c| !c.is_control()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::generate::{
+        parse_grammar::parse_grammar, prepare_grammar::prepare_grammar, timings::Timings,
+    };
+
+    #[test]
+    fn test_render_skeleton_corpus() {
+        let input_grammar = parse_grammar(
+            r#"{
+                "name": "test",
+                "extras": [{"type": "PATTERN", "value": "\\s"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "_statement"}},
+                    "_statement": {"type": "CHOICE", "members": [
+                        {"type": "SYMBOL", "name": "assignment"},
+                        {"type": "SYMBOL", "name": "call"}
+                    ]},
+                    "assignment": {"type": "SEQ", "members": [
+                        {"type": "STRING", "value": "let"},
+                        {"type": "FIELD", "name": "left", "content": {"type": "SYMBOL", "name": "identifier"}},
+                        {"type": "STRING", "value": "="},
+                        {"type": "FIELD", "name": "right", "content": {"type": "SYMBOL", "name": "_expression"}},
+                        {"type": "STRING", "value": ";"}
+                    ]},
+                    "call": {"type": "SEQ", "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": "("},
+                        {"type": "SYMBOL", "name": "_expression"},
+                        {"type": "STRING", "value": ")"},
+                        {"type": "STRING", "value": ";"}
+                    ]},
+                    "_expression": {"type": "CHOICE", "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "SYMBOL", "name": "number"}
+                    ]},
+                    "identifier": {"type": "PATTERN", "value": "[a-z_]+"},
+                    "number": {"type": "PATTERN", "value": "[1-9]\\d*"}
+                }
+            }"#,
+        )
+        .unwrap();
+        let (syntax_grammar, lexical_grammar, _, simple_aliases) = prepare_grammar(
+            &input_grammar,
+            None,
+            &HashMap::new(),
+            &mut Timings::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            render_skeleton_corpus(&syntax_grammar, &lexical_grammar, &simple_aliases).unwrap(),
+            format!(
+                "{header}
+program
+{header}
+
+
+
+{divider}
+
+(program)
+
+{header}
+assignment
+{header}
+
+let a=a;
+
+{divider}
+
+(program
+  (assignment
+    left: (identifier)
+    right: (identifier)))
+
+{header}
+call
+{header}
+
+a(a);
+
+{divider}
+
+(program
+  (call
+    (identifier)
+    (identifier)))
+",
+                header = "=".repeat(80),
+                divider = "-".repeat(80),
+            )
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use serde::Serialize;
+
+use super::{
+    build_tables::Tables,
+    grammars::{LexicalGrammar, SyntaxGrammar},
+    rules::Symbol,
+    table_dump::symbol_name,
+    tables::LexTable,
+};
+
+#[derive(Serialize)]
+struct SourceMap {
+    parse_states: Vec<ParseStateSourceMap>,
+    lex_states: Vec<LexStateSourceMap>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    keyword_lex_states: Vec<LexStateSourceMap>,
+}
+
+#[derive(Serialize)]
+struct ParseStateSourceMap {
+    id: usize,
+    lex_state: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    external_tokens: Vec<String>,
+    symbol_sequence: Vec<String>,
+    rules: Vec<String>,
+    items: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct LexStateSourceMap {
+    id: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    accepts: Option<String>,
+    tokens: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    parse_states: Vec<usize>,
+}
+
+/// Describe which rules and items of the grammar each parse state was built from, and
+/// which tokens each lex state can lead to, so that the states that appear in the
+/// generated C code, or in a debug graph, can be traced back to the grammar.
+///
+/// The tables must have been built with their parse state sources recorded.
+pub fn render_source_map(
+    tables: &Tables,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> String {
+    let parse_table = &tables.parse_table;
+    let parse_states = parse_table
+        .states
+        .iter()
+        .zip(&tables.parse_state_sources)
+        .enumerate()
+        .map(|(id, (state, source))| ParseStateSourceMap {
+            id,
+            lex_state: state.lex_state_id,
+            external_tokens: parse_table.external_lex_states[state.external_lex_state_id]
+                .iter()
+                .map(|symbol| symbol_name(syntax_grammar, lexical_grammar, &symbol))
+                .collect(),
+            symbol_sequence: source
+                .symbol_sequence
+                .iter()
+                .map(|symbol| symbol_name(syntax_grammar, lexical_grammar, symbol))
+                .collect(),
+            rules: source
+                .variable_indices
+                .iter()
+                .map(|index| syntax_grammar.variables[*index].name.clone())
+                .collect(),
+            items: source.items.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    let mut parse_states_by_lex_state = vec![Vec::new(); tables.main_lex_table.states.len()];
+    for (id, state) in parse_table.states.iter().enumerate() {
+        parse_states_by_lex_state[state.lex_state_id].push(id);
+    }
+
+    let source_map = SourceMap {
+        parse_states,
+        lex_states: lex_state_source_maps(
+            &tables.main_lex_table,
+            parse_states_by_lex_state,
+            syntax_grammar,
+            lexical_grammar,
+        ),
+        keyword_lex_states: if tables.word_token.is_some() {
+            lex_state_source_maps(
+                &tables.keyword_lex_table,
+                Vec::new(),
+                syntax_grammar,
+                lexical_grammar,
+            )
+        } else {
+            Vec::new()
+        },
+    };
+    serde_json::to_string_pretty(&source_map).unwrap()
+}
+
+fn lex_state_source_maps(
+    lex_table: &LexTable,
+    mut parse_states_by_lex_state: Vec<Vec<usize>>,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> Vec<LexStateSourceMap> {
+    parse_states_by_lex_state.resize(lex_table.states.len(), Vec::new());
+    lex_table
+        .states
+        .iter()
+        .zip(parse_states_by_lex_state)
+        .enumerate()
+        .map(|(id, (state, parse_states))| LexStateSourceMap {
+            id,
+            accepts: state
+                .accept_action
+                .map(|symbol| symbol_name(syntax_grammar, lexical_grammar, &symbol)),
+            tokens: lex_table
+                .reachable_tokens(id)
+                .iter()
+                .map(|index| {
+                    symbol_name(syntax_grammar, lexical_grammar, &Symbol::terminal(*index))
+                })
+                .collect(),
+            parse_states,
+        })
+        .collect()
+}
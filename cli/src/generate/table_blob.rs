@@ -0,0 +1,877 @@
+//! A binary encoding of the parse table and the lex tables, which applications can ship
+//! as a data file and decode at runtime with [`read_table_blob`], to drive their own
+//! parser or to inspect the tables without compiling the generated C code.
+//!
+//! All integers are little-endian, and are 32 bits wide unless noted otherwise.
+//! Strings are encoded as their length in bytes followed by their UTF-8 contents, and
+//! optional values use `u32::MAX` when they are absent. The blob consists of:
+//!
+//! * A header: the bytes `TSTB`, the version of this format, the language ABI version
+//!   and the name of the language.
+//! * The symbols, whose indices are used by the rest of the blob. Each symbol has an
+//!   8-bit kind (0 = end, 1 = token, 2 = external token, 3 = non-terminal), 8 bits of
+//!   metadata (1 = visible, 2 = named, 4 = supertype) and a name.
+//! * The production infos: each has a sequence of optional aliases (an 8-bit presence
+//!   flag, then the name and an 8-bit named flag) and the field map (for each field,
+//!   its name and the child indices that it refers to, each with an 8-bit inherited
+//!   flag).
+//! * The parse states: each has its lex state, its external lex state, the actions for
+//!   each terminal (an 8-bit reusable flag, then the actions, each starting with an
+//!   8-bit type: 0 = accept, 1 = shift, 2 = shift extra, 3 = recover, 4 = reduce) and
+//!   the goto for each non-terminal (an 8-bit type: 0 = goto, 1 = shift extra, then the
+//!   target state).
+//! * The external tokens, as the symbols that they produce, and the external lex states,
+//!   as lists of the indices of the external tokens that are valid in them.
+//! * The main lex table, the keyword lex table and the word token. Each lex state has
+//!   an optional accepted symbol, an optional EOF transition and a list of transitions
+//!   on character ranges. Each transition has a target state and an 8-bit skip flag.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+
+use super::{
+    build_tables::Tables,
+    grammars::{LexicalGrammar, SyntaxGrammar, VariableType},
+    nfa::CharacterSet,
+    rules::{Alias, AliasMap, Symbol, SymbolType},
+    tables::{AdvanceAction, FieldLocation, GotoAction, LexTable, ParseAction, ProductionInfo},
+};
+
+const MAGIC: &[u8; 4] = b"TSTB";
+pub const TABLE_BLOB_FORMAT_VERSION: u32 = 1;
+
+const NONE: u32 = u32::MAX;
+
+/// The tables of a blob, as decoded by [`read_table_blob`]. Symbols are referred to by
+/// their indices in `symbols`, and states by their indices in the lists of states.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TableBlob {
+    pub abi_version: u32,
+    pub name: String,
+    pub symbols: Vec<BlobSymbol>,
+    pub production_infos: Vec<ProductionInfo>,
+    pub parse_states: Vec<BlobParseState>,
+    /// The symbol that each external token produces.
+    pub external_tokens: Vec<usize>,
+    /// The indices of the external tokens that are valid in each external lex state.
+    pub external_lex_states: Vec<Vec<usize>>,
+    pub main_lex_states: Vec<BlobLexState>,
+    pub keyword_lex_states: Vec<BlobLexState>,
+    pub word_token: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlobSymbol {
+    pub kind: SymbolType,
+    pub name: String,
+    pub visible: bool,
+    pub named: bool,
+    pub supertype: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlobParseState {
+    pub lex_state_id: usize,
+    pub external_lex_state_id: usize,
+    /// The entries for the terminals, ordered by symbol.
+    pub terminal_entries: Vec<(usize, BlobParseTableEntry)>,
+    /// The gotos for the non-terminals, ordered by symbol.
+    pub nonterminal_entries: Vec<(usize, GotoAction)>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlobParseTableEntry {
+    pub actions: Vec<BlobParseAction>,
+    pub reusable: bool,
+}
+
+/// A [`ParseAction`], with the symbol of a reduction given by its index.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlobParseAction {
+    Accept,
+    Shift {
+        state: usize,
+        is_repetition: bool,
+    },
+    ShiftExtra,
+    Recover,
+    Reduce {
+        symbol: usize,
+        child_count: usize,
+        dynamic_precedence: i32,
+        production_id: usize,
+    },
+}
+
+/// A lex state, with its accepted token given by its symbol index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlobLexState {
+    pub accept_symbol: Option<usize>,
+    pub eof_action: Option<AdvanceAction>,
+    pub advance_actions: Vec<(CharacterSet, AdvanceAction)>,
+}
+
+struct BlobReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    symbol_count: usize,
+}
+
+struct BlobWriter<'a> {
+    buffer: Vec<u8>,
+    tables: &'a Tables,
+    syntax_grammar: &'a SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+    default_aliases: &'a AliasMap,
+}
+
+/// Encode the parse table and the lex tables as a versioned binary blob.
+pub fn render_table_blob(
+    language_name: &str,
+    tables: &Tables,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    default_aliases: &AliasMap,
+    abi_version: usize,
+) -> Vec<u8> {
+    let mut writer = BlobWriter {
+        buffer: Vec::new(),
+        tables,
+        syntax_grammar,
+        lexical_grammar,
+        default_aliases,
+    };
+    writer.buffer.extend_from_slice(MAGIC);
+    writer.add_u32(TABLE_BLOB_FORMAT_VERSION);
+    writer.add_usize(abi_version);
+    writer.add_str(language_name);
+    writer.add_symbols();
+    writer.add_production_infos();
+    writer.add_parse_states();
+    writer.add_external_lex_states();
+    writer.add_lex_table(&tables.main_lex_table);
+    if tables.word_token.is_some() {
+        writer.add_lex_table(&tables.keyword_lex_table);
+    } else {
+        writer.add_u32(0);
+    }
+    writer.add_optional_symbol(tables.word_token);
+    writer.buffer
+}
+
+/// Decode a blob that was written by `generate --table-blob`.
+pub fn read_table_blob(data: &[u8]) -> Result<TableBlob> {
+    let mut reader = BlobReader {
+        data,
+        position: 0,
+        symbol_count: 0,
+    };
+    if reader.read_bytes(MAGIC.len())? != MAGIC {
+        return Err(anyhow!("Invalid table blob: it doesn't start with `TSTB`"));
+    }
+    let version = reader.read_u32()?;
+    if version != TABLE_BLOB_FORMAT_VERSION {
+        return Err(anyhow!(
+            "Unsupported table blob format version {version}. Only version {TABLE_BLOB_FORMAT_VERSION} is supported."
+        ));
+    }
+    let abi_version = reader.read_u32()?;
+    let name = reader.read_str()?;
+    let symbols = reader.read_symbols()?;
+    let production_infos = reader.read_list(BlobReader::read_production_info)?;
+    let parse_states = reader.read_list(BlobReader::read_parse_state)?;
+    let external_tokens = reader.read_list(BlobReader::read_symbol)?;
+    let external_lex_states =
+        reader.read_list(|reader| reader.read_list(BlobReader::read_usize))?;
+    let main_lex_states = reader.read_list(BlobReader::read_lex_state)?;
+    let keyword_lex_states = reader.read_list(BlobReader::read_lex_state)?;
+    let word_token = reader.read_optional_symbol()?;
+    if reader.position != data.len() {
+        return Err(anyhow!(
+            "Invalid table blob: unexpected data at byte {}",
+            reader.position
+        ));
+    }
+    Ok(TableBlob {
+        abi_version,
+        name,
+        symbols,
+        production_infos,
+        parse_states,
+        external_tokens,
+        external_lex_states,
+        main_lex_states,
+        keyword_lex_states,
+        word_token,
+    })
+}
+
+impl<'a> BlobWriter<'a> {
+    fn add_u8(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    fn add_u32(&mut self, value: u32) {
+        self.buffer.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn add_usize(&mut self, value: usize) {
+        self.add_u32(u32::try_from(value).expect("table value does not fit in 32 bits"));
+    }
+
+    fn add_bool(&mut self, value: bool) {
+        self.add_u8(u8::from(value));
+    }
+
+    fn add_str(&mut self, value: &str) {
+        self.add_usize(value.len());
+        self.buffer.extend_from_slice(value.as_bytes());
+    }
+
+    fn add_symbols(&mut self) {
+        self.add_usize(self.tables.parse_table.symbols.len());
+        for symbol in &self.tables.parse_table.symbols {
+            let (kind, name, variable_type) = match symbol.kind {
+                SymbolType::End | SymbolType::EndOfNonTerminalExtra => {
+                    (0, "end", VariableType::Hidden)
+                }
+                SymbolType::Terminal => {
+                    let variable = &self.lexical_grammar.variables[symbol.index];
+                    (1, variable.name.as_str(), variable.kind)
+                }
+                SymbolType::External => {
+                    let token = &self.syntax_grammar.external_tokens[symbol.index];
+                    (2, token.name.as_str(), token.kind)
+                }
+                SymbolType::NonTerminal => {
+                    let variable = &self.syntax_grammar.variables[symbol.index];
+                    (3, variable.name.as_str(), variable.kind)
+                }
+            };
+            let (name, visible, named) = if let Some(alias) = self.default_aliases.get(symbol) {
+                (alias.value.as_str(), true, alias.is_named)
+            } else {
+                match variable_type {
+                    VariableType::Named => (name, true, true),
+                    VariableType::Anonymous => (name, true, false),
+                    VariableType::Hidden => (name, false, true),
+                    VariableType::Auxiliary => (name, false, false),
+                }
+            };
+            let supertype = self.syntax_grammar.supertype_symbols.contains(symbol);
+            self.add_u8(kind);
+            self.add_u8(u8::from(visible) | u8::from(named) << 1 | u8::from(supertype) << 2);
+            self.add_str(name);
+        }
+    }
+
+    fn add_production_infos(&mut self) {
+        let production_infos = &self.tables.parse_table.production_infos;
+        self.add_usize(production_infos.len());
+        for info in production_infos {
+            self.add_usize(info.alias_sequence.len());
+            for alias in &info.alias_sequence {
+                self.add_bool(alias.is_some());
+                if let Some(alias) = alias {
+                    self.add_str(&alias.value);
+                    self.add_bool(alias.is_named);
+                }
+            }
+            self.add_usize(info.field_map.len());
+            for (field_name, locations) in &info.field_map {
+                self.add_str(field_name);
+                self.add_usize(locations.len());
+                for location in locations {
+                    self.add_usize(location.index);
+                    self.add_bool(location.inherited);
+                }
+            }
+        }
+    }
+
+    fn add_parse_states(&mut self) {
+        let states = &self.tables.parse_table.states;
+        self.add_usize(states.len());
+        for state in states {
+            self.add_usize(state.lex_state_id);
+            self.add_usize(state.external_lex_state_id);
+
+            // Ensure the entries are in a deterministic order, since they are
+            // internally represented as a hash map.
+            let mut terminal_entries = state.terminal_entries.iter().collect::<Vec<_>>();
+            terminal_entries.sort_unstable_by_key(|(symbol, _)| self.symbol_index(**symbol));
+            self.add_usize(terminal_entries.len());
+            for (symbol, entry) in terminal_entries {
+                self.add_symbol(*symbol);
+                self.add_bool(entry.reusable);
+                self.add_usize(entry.actions.len());
+                for action in &entry.actions {
+                    self.add_parse_action(action);
+                }
+            }
+
+            let mut nonterminal_entries = state.nonterminal_entries.iter().collect::<Vec<_>>();
+            nonterminal_entries.sort_unstable_by_key(|(symbol, _)| **symbol);
+            self.add_usize(nonterminal_entries.len());
+            for (symbol, action) in nonterminal_entries {
+                self.add_symbol(*symbol);
+                match action {
+                    GotoAction::Goto(state) => {
+                        self.add_u8(0);
+                        self.add_usize(*state);
+                    }
+                    GotoAction::ShiftExtra => {
+                        self.add_u8(1);
+                        self.add_usize(state.id);
+                    }
+                }
+            }
+        }
+    }
+
+    fn add_parse_action(&mut self, action: &ParseAction) {
+        match action {
+            ParseAction::Accept => self.add_u8(0),
+            ParseAction::Shift {
+                state,
+                is_repetition,
+            } => {
+                self.add_u8(1);
+                self.add_usize(*state);
+                self.add_bool(*is_repetition);
+            }
+            ParseAction::ShiftExtra => self.add_u8(2),
+            ParseAction::Recover => self.add_u8(3),
+            ParseAction::Reduce {
+                symbol,
+                child_count,
+                dynamic_precedence,
+                production_id,
+            } => {
+                self.add_u8(4);
+                self.add_symbol(*symbol);
+                self.add_usize(*child_count);
+                self.buffer
+                    .extend_from_slice(&dynamic_precedence.to_le_bytes());
+                self.add_usize(*production_id);
+            }
+        }
+    }
+
+    fn add_external_lex_states(&mut self) {
+        let external_tokens = &self.syntax_grammar.external_tokens;
+        self.add_usize(external_tokens.len());
+        for (i, token) in external_tokens.iter().enumerate() {
+            self.add_symbol(
+                token
+                    .corresponding_internal_token
+                    .unwrap_or_else(|| Symbol::external(i)),
+            );
+        }
+
+        let external_lex_states = &self.tables.parse_table.external_lex_states;
+        self.add_usize(external_lex_states.len());
+        for token_set in external_lex_states {
+            let tokens = token_set.iter().collect::<Vec<_>>();
+            self.add_usize(tokens.len());
+            for token in tokens {
+                self.add_usize(token.index);
+            }
+        }
+    }
+
+    fn add_lex_table(&mut self, lex_table: &LexTable) {
+        self.add_usize(lex_table.states.len());
+        for state in &lex_table.states {
+            self.add_optional_symbol(state.accept_action);
+            if let Some(action) = &state.eof_action {
+                self.add_advance_action(action);
+            } else {
+                self.add_u32(NONE);
+                self.add_bool(false);
+            }
+            self.add_usize(state.advance_actions.len());
+            for (characters, action) in &state.advance_actions {
+                self.add_usize(characters.range_count());
+                for range in characters.ranges() {
+                    self.add_u32(*range.start() as u32);
+                    self.add_u32(*range.end() as u32);
+                }
+                self.add_advance_action(action);
+            }
+        }
+    }
+
+    fn add_advance_action(&mut self, action: &AdvanceAction) {
+        self.add_usize(action.state);
+        self.add_bool(!action.in_main_token);
+    }
+
+    fn add_symbol(&mut self, symbol: Symbol) {
+        self.add_usize(self.symbol_index(symbol));
+    }
+
+    fn add_optional_symbol(&mut self, symbol: Option<Symbol>) {
+        match symbol {
+            Some(symbol) => self.add_symbol(symbol),
+            None => self.add_u32(NONE),
+        }
+    }
+
+    fn symbol_index(&self, symbol: Symbol) -> usize {
+        let symbol = if symbol.kind == SymbolType::EndOfNonTerminalExtra {
+            Symbol::end()
+        } else {
+            symbol
+        };
+        self.tables
+            .parse_table
+            .symbols
+            .iter()
+            .position(|s| *s == symbol)
+            .expect("symbol is not in the parse table")
+    }
+}
+
+impl<'a> BlobReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or_else(|| anyhow!("Invalid table blob: it ends unexpectedly"))?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn read_usize(&mut self) -> Result<usize> {
+        Ok(self.read_u32()? as usize)
+    }
+
+    fn read_bool(&mut self) -> Result<bool> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    fn read_str(&mut self) -> Result<String> {
+        let len = self.read_usize()?;
+        let bytes = self.read_bytes(len)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| anyhow!("Invalid table blob: a string is not valid UTF-8"))
+    }
+
+    fn read_list<T>(
+        &mut self,
+        mut read_item: impl FnMut(&mut Self) -> Result<T>,
+    ) -> Result<Vec<T>> {
+        let len = self.read_usize()?;
+        // Every item takes at least a byte, so a corrupt length can't allocate more than
+        // the blob's size.
+        let mut items = Vec::with_capacity(len.min(self.data.len() - self.position));
+        for _ in 0..len {
+            items.push(read_item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn read_symbols(&mut self) -> Result<Vec<BlobSymbol>> {
+        let symbols = self.read_list(|reader| {
+            let kind = match reader.read_u8()? {
+                0 => SymbolType::End,
+                1 => SymbolType::Terminal,
+                2 => SymbolType::External,
+                3 => SymbolType::NonTerminal,
+                kind => return Err(anyhow!("Invalid table blob: unknown symbol kind {kind}")),
+            };
+            let metadata = reader.read_u8()?;
+            Ok(BlobSymbol {
+                kind,
+                name: reader.read_str()?,
+                visible: metadata & 1 != 0,
+                named: metadata & 2 != 0,
+                supertype: metadata & 4 != 0,
+            })
+        })?;
+        self.symbol_count = symbols.len();
+        Ok(symbols)
+    }
+
+    fn read_symbol(&mut self) -> Result<usize> {
+        let index = self.read_usize()?;
+        if index >= self.symbol_count {
+            return Err(anyhow!("Invalid table blob: unknown symbol {index}"));
+        }
+        Ok(index)
+    }
+
+    fn read_optional_symbol(&mut self) -> Result<Option<usize>> {
+        let position = self.position;
+        if self.read_u32()? == NONE {
+            return Ok(None);
+        }
+        self.position = position;
+        self.read_symbol().map(Some)
+    }
+
+    fn read_production_info(&mut self) -> Result<ProductionInfo> {
+        let alias_sequence = self.read_list(|reader| {
+            if !reader.read_bool()? {
+                return Ok(None);
+            }
+            Ok(Some(Alias {
+                value: reader.read_str()?,
+                is_named: reader.read_bool()?,
+            }))
+        })?;
+        let field_map = self
+            .read_list(|reader| {
+                let field_name = reader.read_str()?;
+                let locations = reader.read_list(|reader| {
+                    Ok(FieldLocation {
+                        index: reader.read_usize()?,
+                        inherited: reader.read_bool()?,
+                    })
+                })?;
+                Ok((field_name, locations))
+            })?
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        Ok(ProductionInfo {
+            alias_sequence,
+            field_map,
+        })
+    }
+
+    fn read_parse_state(&mut self) -> Result<BlobParseState> {
+        let lex_state_id = self.read_usize()?;
+        let external_lex_state_id = self.read_usize()?;
+        let terminal_entries = self.read_list(|reader| {
+            let symbol = reader.read_symbol()?;
+            let reusable = reader.read_bool()?;
+            let actions = reader.read_list(BlobReader::read_parse_action)?;
+            Ok((symbol, BlobParseTableEntry { actions, reusable }))
+        })?;
+        let nonterminal_entries = self.read_list(|reader| {
+            let symbol = reader.read_symbol()?;
+            let action = match reader.read_u8()? {
+                0 => GotoAction::Goto(reader.read_usize()?),
+                1 => {
+                    reader.read_usize()?;
+                    GotoAction::ShiftExtra
+                }
+                kind => return Err(anyhow!("Invalid table blob: unknown goto type {kind}")),
+            };
+            Ok((symbol, action))
+        })?;
+        Ok(BlobParseState {
+            lex_state_id,
+            external_lex_state_id,
+            terminal_entries,
+            nonterminal_entries,
+        })
+    }
+
+    fn read_parse_action(&mut self) -> Result<BlobParseAction> {
+        Ok(match self.read_u8()? {
+            0 => BlobParseAction::Accept,
+            1 => BlobParseAction::Shift {
+                state: self.read_usize()?,
+                is_repetition: self.read_bool()?,
+            },
+            2 => BlobParseAction::ShiftExtra,
+            3 => BlobParseAction::Recover,
+            4 => BlobParseAction::Reduce {
+                symbol: self.read_symbol()?,
+                child_count: self.read_usize()?,
+                dynamic_precedence: i32::from_le_bytes(self.read_bytes(4)?.try_into().unwrap()),
+                production_id: self.read_usize()?,
+            },
+            kind => return Err(anyhow!("Invalid table blob: unknown action type {kind}")),
+        })
+    }
+
+    fn read_lex_state(&mut self) -> Result<BlobLexState> {
+        let accept_symbol = self.read_optional_symbol()?;
+        let eof_state = self.read_u32()?;
+        let eof_skip = self.read_bool()?;
+        let eof_action = (eof_state != NONE).then_some(AdvanceAction {
+            state: eof_state as usize,
+            in_main_token: !eof_skip,
+        });
+        let advance_actions = self.read_list(|reader| {
+            let characters = reader.read_list(|reader| {
+                let start = reader.read_char()?;
+                let end = reader.read_char()?;
+                Ok((start, end))
+            })?;
+            let characters = characters
+                .into_iter()
+                .fold(CharacterSet::empty(), |set, (start, end)| {
+                    set.add_range(start, end)
+                });
+            let action = AdvanceAction {
+                state: reader.read_usize()?,
+                in_main_token: !reader.read_bool()?,
+            };
+            Ok((characters, action))
+        })?;
+        Ok(BlobLexState {
+            accept_symbol,
+            eof_action,
+            advance_actions,
+        })
+    }
+
+    fn read_char(&mut self) -> Result<char> {
+        let code = self.read_u32()?;
+        char::from_u32(code)
+            .ok_or_else(|| anyhow!("Invalid table blob: {code:#x} is not a character"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::generate::{
+        build_tables::build_tables, node_types::get_variable_info, parse_grammar::parse_grammar,
+        prepare_grammar::prepare_grammar, timings::Timings,
+    };
+
+    fn build(grammar_json: &str) -> (Tables, SyntaxGrammar, LexicalGrammar, AliasMap) {
+        let input_grammar = parse_grammar(grammar_json).unwrap();
+        let (syntax_grammar, lexical_grammar, inlines, simple_aliases) = prepare_grammar(
+            &input_grammar,
+            None,
+            &HashMap::new(),
+            &mut Timings::default(),
+        )
+        .unwrap();
+        let variable_info =
+            get_variable_info(&syntax_grammar, &lexical_grammar, &simple_aliases).unwrap();
+        let tables = build_tables(
+            &syntax_grammar,
+            &lexical_grammar,
+            &simple_aliases,
+            &variable_info,
+            &inlines,
+            None,
+            false,
+            None,
+            None,
+            &mut Timings::default(),
+        )
+        .unwrap();
+        (tables, syntax_grammar, lexical_grammar, simple_aliases)
+    }
+
+    #[test]
+    fn test_render_table_blob() {
+        let (tables, syntax_grammar, lexical_grammar, simple_aliases) = build(
+            r#"{
+                "name": "test",
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "word"}},
+                    "word": {"type": "PATTERN", "value": "[a-z]+"}
+                }
+            }"#,
+        );
+
+        let blob = render_table_blob(
+            "test",
+            &tables,
+            &syntax_grammar,
+            &lexical_grammar,
+            &simple_aliases,
+            14,
+        );
+        let mut expected_start = b"TSTB".to_vec();
+        expected_start.extend_from_slice(&TABLE_BLOB_FORMAT_VERSION.to_le_bytes());
+        expected_start.extend_from_slice(&14_u32.to_le_bytes());
+        expected_start.extend_from_slice(&4_u32.to_le_bytes());
+        expected_start.extend_from_slice(b"test");
+        expected_start.extend_from_slice(&(tables.parse_table.symbols.len() as u32).to_le_bytes());
+        expected_start.extend_from_slice(&[0, 2, 3, 0, 0, 0, b'e', b'n', b'd']);
+        expected_start.extend_from_slice(&[1, 3, 4, 0, 0, 0, b'w', b'o', b'r', b'd']);
+        assert!(blob.starts_with(&expected_start));
+
+        // The blob ends with the lex table's transition on the word's characters, an
+        // empty keyword lex table and the absent word token.
+        let mut expected_end = Vec::new();
+        expected_end.extend_from_slice(&1_u32.to_le_bytes());
+        expected_end.extend_from_slice(&('a' as u32).to_le_bytes());
+        expected_end.extend_from_slice(&('z' as u32).to_le_bytes());
+        assert!(blob
+            .windows(expected_end.len())
+            .any(|window| window == expected_end));
+        assert!(blob.ends_with(&[0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff]));
+    }
+
+    #[test]
+    fn test_read_table_blob() {
+        let (tables, syntax_grammar, lexical_grammar, simple_aliases) = build(
+            r#"{
+                "name": "test",
+                "word": "identifier",
+                "extras": [{"type": "PATTERN", "value": "\\s"}],
+                "externals": [{"type": "SYMBOL", "name": "heredoc"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "statement"}},
+                    "statement": {"type": "CHOICE", "members": [
+                        {"type": "SEQ", "members": [
+                            {"type": "STRING", "value": "let"},
+                            {"type": "FIELD", "name": "name", "content": {"type": "SYMBOL", "name": "identifier"}},
+                            {"type": "PREC_DYNAMIC", "value": 2, "content": {"type": "ALIAS", "value": "value", "named": true, "content": {"type": "SYMBOL", "name": "identifier"}}}
+                        ]},
+                        {"type": "SYMBOL", "name": "heredoc"}
+                    ]},
+                    "identifier": {"type": "PATTERN", "value": "[a-zé]+"}
+                }
+            }"#,
+        );
+        let data = render_table_blob(
+            "test",
+            &tables,
+            &syntax_grammar,
+            &lexical_grammar,
+            &simple_aliases,
+            14,
+        );
+        let blob = read_table_blob(&data).unwrap();
+
+        let symbols = &tables.parse_table.symbols;
+        let index = |symbol: &Symbol| {
+            let symbol = if symbol.kind == SymbolType::EndOfNonTerminalExtra {
+                Symbol::end()
+            } else {
+                *symbol
+            };
+            symbols.iter().position(|s| *s == symbol).unwrap()
+        };
+        assert_eq!(blob.name, "test");
+        assert_eq!(blob.abi_version, 14);
+        assert_eq!(
+            blob.symbols.iter().map(|s| s.kind).collect::<Vec<_>>(),
+            symbols.iter().map(|s| s.kind).collect::<Vec<_>>()
+        );
+        assert!(blob
+            .symbols
+            .iter()
+            .any(|s| s.name == "identifier" && s.visible && s.named));
+        assert_eq!(blob.production_infos, tables.parse_table.production_infos);
+        assert!(blob
+            .production_infos
+            .iter()
+            .any(|info| info.field_map.contains_key("name")));
+
+        assert_eq!(blob.parse_states.len(), tables.parse_table.states.len());
+        for (blob_state, state) in blob.parse_states.iter().zip(&tables.parse_table.states) {
+            assert_eq!(blob_state.lex_state_id, state.lex_state_id);
+            assert_eq!(
+                blob_state.external_lex_state_id,
+                state.external_lex_state_id
+            );
+            let mut terminal_entries = state
+                .terminal_entries
+                .iter()
+                .map(|(symbol, entry)| {
+                    let actions = entry
+                        .actions
+                        .iter()
+                        .map(|action| match *action {
+                            ParseAction::Accept => BlobParseAction::Accept,
+                            ParseAction::Shift {
+                                state,
+                                is_repetition,
+                            } => BlobParseAction::Shift {
+                                state,
+                                is_repetition,
+                            },
+                            ParseAction::ShiftExtra => BlobParseAction::ShiftExtra,
+                            ParseAction::Recover => BlobParseAction::Recover,
+                            ParseAction::Reduce {
+                                symbol,
+                                child_count,
+                                dynamic_precedence,
+                                production_id,
+                            } => BlobParseAction::Reduce {
+                                symbol: index(&symbol),
+                                child_count,
+                                dynamic_precedence,
+                                production_id,
+                            },
+                        })
+                        .collect();
+                    let entry = BlobParseTableEntry {
+                        actions,
+                        reusable: entry.reusable,
+                    };
+                    (index(symbol), entry)
+                })
+                .collect::<Vec<_>>();
+            terminal_entries.sort_unstable_by_key(|(symbol, _)| *symbol);
+            assert_eq!(blob_state.terminal_entries, terminal_entries);
+            let mut nonterminal_entries = state
+                .nonterminal_entries
+                .iter()
+                .map(|(symbol, action)| (index(symbol), *action))
+                .collect::<Vec<_>>();
+            nonterminal_entries.sort_unstable_by_key(|(symbol, _)| *symbol);
+            assert_eq!(blob_state.nonterminal_entries, nonterminal_entries);
+        }
+        assert!(blob.parse_states.iter().any(|state| state
+            .terminal_entries
+            .iter()
+            .flat_map(|(_, entry)| &entry.actions)
+            .any(|action| matches!(
+                action,
+                BlobParseAction::Reduce {
+                    dynamic_precedence: 2,
+                    ..
+                }
+            ))));
+
+        assert_eq!(blob.external_tokens, [index(&Symbol::external(0))]);
+        assert_eq!(
+            blob.external_lex_states.len(),
+            tables.parse_table.external_lex_states.len()
+        );
+
+        for (blob_states, lex_table) in [
+            (&blob.main_lex_states, &tables.main_lex_table),
+            (&blob.keyword_lex_states, &tables.keyword_lex_table),
+        ] {
+            assert!(!blob_states.is_empty());
+            assert_eq!(blob_states.len(), lex_table.states.len());
+            for (blob_state, state) in blob_states.iter().zip(&lex_table.states) {
+                assert_eq!(
+                    blob_state.accept_symbol,
+                    state.accept_action.as_ref().map(index)
+                );
+                assert_eq!(blob_state.eof_action, state.eof_action);
+                assert_eq!(blob_state.advance_actions, state.advance_actions);
+            }
+        }
+        assert_eq!(blob.word_token, tables.word_token.as_ref().map(index));
+        assert!(blob.word_token.is_some());
+
+        assert_eq!(
+            read_table_blob(&data[..data.len() - 1])
+                .unwrap_err()
+                .to_string(),
+            "Invalid table blob: it ends unexpectedly"
+        );
+        assert_eq!(
+            read_table_blob(b"TSTP").unwrap_err().to_string(),
+            "Invalid table blob: it doesn't start with `TSTB`"
+        );
+    }
+}
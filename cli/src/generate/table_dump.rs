@@ -0,0 +1,213 @@
+use std::fmt::Write;
+
+use super::{
+    build_tables::Tables,
+    grammars::{LexicalGrammar, SyntaxGrammar, VariableType},
+    nfa::CharacterSet,
+    rules::{Symbol, SymbolType},
+    tables::{AdvanceAction, GotoAction, LexTable, ParseAction},
+};
+
+struct TableDumper<'a> {
+    buffer: String,
+    syntax_grammar: &'a SyntaxGrammar,
+    lexical_grammar: &'a LexicalGrammar,
+}
+
+/// Describe the parse table and the lex tables in terms of the grammar's rule and
+/// token names, so that changes to the tables can be reviewed without reading the
+/// generated C code.
+pub fn render_table_dump(
+    tables: &Tables,
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+) -> String {
+    let mut dumper = TableDumper {
+        buffer: String::new(),
+        syntax_grammar,
+        lexical_grammar,
+    };
+    dumper.add_parse_table(tables);
+    dumper.add_lex_table("Lex table", &tables.main_lex_table);
+    if tables.word_token.is_some() {
+        dumper.add_lex_table("Keyword lex table", &tables.keyword_lex_table);
+    }
+    dumper.buffer
+}
+
+impl<'a> TableDumper<'a> {
+    fn add_heading(&mut self, heading: &str) {
+        if !self.buffer.is_empty() {
+            self.buffer.push('\n');
+        }
+        writeln!(&mut self.buffer, "{heading}\n{}", "=".repeat(heading.len())).unwrap();
+    }
+
+    fn add_parse_table(&mut self, tables: &Tables) {
+        self.add_heading("Parse table");
+        let parse_table = &tables.parse_table;
+        for (i, state) in parse_table.states.iter().enumerate() {
+            write!(
+                &mut self.buffer,
+                "\nstate {i} (lex state {}",
+                state.lex_state_id
+            )
+            .unwrap();
+            if state.external_lex_state_id > 0 {
+                let external_tokens = parse_table.external_lex_states[state.external_lex_state_id]
+                    .iter()
+                    .map(|symbol| self.symbol_name(&symbol))
+                    .collect::<Vec<_>>();
+                write!(
+                    &mut self.buffer,
+                    ", external tokens {}",
+                    external_tokens.join(" ")
+                )
+                .unwrap();
+            }
+            writeln!(&mut self.buffer, ")").unwrap();
+
+            for (symbol, entry) in &state.terminal_entries {
+                let actions = entry
+                    .actions
+                    .iter()
+                    .map(|action| self.describe_parse_action(action))
+                    .collect::<Vec<_>>();
+                let name = self.symbol_name(symbol);
+                writeln!(&mut self.buffer, "  {name}: {}", actions.join(", ")).unwrap();
+            }
+            for (symbol, action) in &state.nonterminal_entries {
+                let action = match action {
+                    GotoAction::Goto(state) => format!("goto {state}"),
+                    GotoAction::ShiftExtra => "shift extra".to_string(),
+                };
+                let name = self.symbol_name(symbol);
+                writeln!(&mut self.buffer, "  {name}: {action}").unwrap();
+            }
+        }
+    }
+
+    fn add_lex_table(&mut self, heading: &str, lex_table: &LexTable) {
+        self.add_heading(heading);
+        for (i, state) in lex_table.states.iter().enumerate() {
+            writeln!(&mut self.buffer, "\nlex state {i}").unwrap();
+            if let Some(symbol) = state.accept_action {
+                let name = self.symbol_name(&symbol);
+                writeln!(&mut self.buffer, "  accept {name}").unwrap();
+            }
+            if let Some(action) = &state.eof_action {
+                writeln!(
+                    &mut self.buffer,
+                    "  EOF: {}",
+                    describe_advance_action(action)
+                )
+                .unwrap();
+            }
+            for (characters, action) in &state.advance_actions {
+                writeln!(
+                    &mut self.buffer,
+                    "  {}: {}",
+                    describe_characters(characters),
+                    describe_advance_action(action)
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    fn describe_parse_action(&self, action: &ParseAction) -> String {
+        describe_parse_action(self.syntax_grammar, self.lexical_grammar, action)
+    }
+
+    fn symbol_name(&self, symbol: &Symbol) -> String {
+        symbol_name(self.syntax_grammar, self.lexical_grammar, symbol)
+    }
+}
+
+/// The name of a symbol as it's written in the grammar, with anonymous tokens quoted.
+pub(super) fn symbol_name(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    symbol: &Symbol,
+) -> String {
+    match symbol.kind {
+        SymbolType::End | SymbolType::EndOfNonTerminalExtra => "EOF".to_string(),
+        SymbolType::External => syntax_grammar.external_tokens[symbol.index].name.clone(),
+        SymbolType::NonTerminal => syntax_grammar.variables[symbol.index].name.clone(),
+        SymbolType::Terminal => {
+            let variable = &lexical_grammar.variables[symbol.index];
+            if variable.kind == VariableType::Named {
+                variable.name.clone()
+            } else {
+                format!("{:?}", variable.name)
+            }
+        }
+    }
+}
+
+/// Describe a parse action, naming the symbol that a reduction produces.
+pub(super) fn describe_parse_action(
+    syntax_grammar: &SyntaxGrammar,
+    lexical_grammar: &LexicalGrammar,
+    action: &ParseAction,
+) -> String {
+    match action {
+        ParseAction::Accept => "accept".to_string(),
+        ParseAction::Recover => "recover".to_string(),
+        ParseAction::ShiftExtra => "shift extra".to_string(),
+        ParseAction::Shift {
+            state,
+            is_repetition,
+        } => {
+            if *is_repetition {
+                format!("shift {state} (repetition)")
+            } else {
+                format!("shift {state}")
+            }
+        }
+        ParseAction::Reduce {
+            symbol,
+            child_count,
+            dynamic_precedence,
+            ..
+        } => {
+            let mut result = format!(
+                "reduce {} ({child_count})",
+                symbol_name(syntax_grammar, lexical_grammar, symbol)
+            );
+            if *dynamic_precedence != 0 {
+                write!(&mut result, " (dynamic precedence {dynamic_precedence})").unwrap();
+            }
+            result
+        }
+    }
+}
+
+fn describe_advance_action(action: &AdvanceAction) -> String {
+    if action.in_main_token {
+        format!("advance {}", action.state)
+    } else {
+        format!("skip {}", action.state)
+    }
+}
+
+fn describe_characters(characters: &CharacterSet) -> String {
+    let mut result = String::from("[");
+    let mut characters = characters.clone();
+    if characters.contains(char::MAX) {
+        result.push('^');
+        characters = characters.negate();
+    }
+    for (i, range) in characters.ranges().enumerate() {
+        if i > 0 {
+            result.push(' ');
+        }
+        if range.start() == range.end() {
+            write!(&mut result, "{:?}", range.start()).unwrap();
+        } else {
+            write!(&mut result, "{:?}-{:?}", range.start(), range.end()).unwrap();
+        }
+    }
+    result.push(']');
+    result
+}
@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
 use super::{
     nfa::CharacterSet,
@@ -12,16 +12,21 @@ use std::hash::BuildHasherDefault;
 
 use indexmap::IndexMap;
 use rustc_hash::FxHasher;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// What the parser does when it sees a given token in a given state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum ParseAction {
     Accept,
     Shift {
         state: ParseStateId,
+        /// Whether the shift continues a repetition, rather than starting a new node.
         is_repetition: bool,
     },
+    /// Shift an extra, like a comment, without changing state.
     ShiftExtra,
     Recover,
+    /// Replace the last `child_count` subtrees on the stack with a node of type `symbol`.
     Reduce {
         symbol: Symbol,
         child_count: usize,
@@ -30,68 +35,88 @@ pub enum ParseAction {
     },
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The state that the parser moves to after reducing to a given non-terminal.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum GotoAction {
     Goto(ParseStateId),
     ShiftExtra,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+/// The actions for a token in a parse state. There is more than one action when the
+/// grammar has a conflict that is resolved at runtime.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct ParseTableEntry {
     pub actions: Vec<ParseAction>,
+    /// Whether a node ending in this token can be reused in an incremental parse.
     pub reusable: bool,
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ParseState {
     pub id: ParseStateId,
+    // Symbols aren't strings, so these maps are stored as lists of pairs.
+    #[serde(with = "indexmap::map::serde_seq")]
     pub terminal_entries: IndexMap<Symbol, ParseTableEntry, BuildHasherDefault<FxHasher>>,
+    #[serde(with = "indexmap::map::serde_seq")]
     pub nonterminal_entries: IndexMap<Symbol, GotoAction, BuildHasherDefault<FxHasher>>,
+    /// The state of the main lex table that tokens are lexed from in this state.
     pub lex_state_id: usize,
+    /// The index in [`ParseTable::external_lex_states`] of the external tokens that are
+    /// valid in this state, where 0 means that none are.
     pub external_lex_state_id: usize,
     pub core_id: usize,
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// Which child of a node a field refers to. An inherited field belongs to a hidden child,
+/// and is found among that child's children.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FieldLocation {
     pub index: usize,
     pub inherited: bool,
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+/// The aliases and fields of the children of the nodes that a reduction produces,
+/// shared between the reductions that have the same ones.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ProductionInfo {
     pub alias_sequence: Vec<Option<Alias>>,
     pub field_map: BTreeMap<String, Vec<FieldLocation>>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// The LR(1) parse table, with one state per entry in `states`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ParseTable {
     pub states: Vec<ParseState>,
+    /// The symbols that appear in the table, in the order of their ids in the generated parser.
     pub symbols: Vec<Symbol>,
     pub production_infos: Vec<ProductionInfo>,
     pub max_aliased_production_length: usize,
     pub external_lex_states: Vec<TokenSet>,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// A transition to another lex state after consuming a character.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct AdvanceAction {
     pub state: LexStateId,
     pub in_main_token: bool,
 }
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct LexState {
+    /// The token that is recognized if the lexer stops in this state.
     pub accept_action: Option<Symbol>,
     pub eof_action: Option<AdvanceAction>,
     pub advance_actions: Vec<(CharacterSet, AdvanceAction)>,
 }
 
-#[derive(Debug, PartialEq, Eq, Default)]
+/// A deterministic state machine that recognizes the grammar's tokens.
+#[derive(Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
 pub struct LexTable {
     pub states: Vec<LexState>,
 }
 
 impl ParseTableEntry {
+    #[allow(clippy::new_without_default)]
     pub const fn new() -> Self {
         Self {
             reusable: true,
@@ -163,3 +188,31 @@ impl ParseState {
         }
     }
 }
+
+impl LexTable {
+    /// The indices of the tokens that can be accepted by lexing from the given state onward.
+    pub fn reachable_tokens(&self, start_state_id: LexStateId) -> BTreeSet<usize> {
+        let mut tokens = BTreeSet::new();
+        let mut visited = vec![false; self.states.len()];
+        let mut queue = VecDeque::from([start_state_id]);
+        visited[start_state_id] = true;
+        while let Some(state_id) = queue.pop_front() {
+            let state = &self.states[state_id];
+            if let Some(symbol) = state.accept_action {
+                tokens.insert(symbol.index);
+            }
+            let next_state_ids = state
+                .advance_actions
+                .iter()
+                .map(|(_, action)| action.state)
+                .chain(state.eof_action.as_ref().map(|action| action.state));
+            for next_state_id in next_state_ids {
+                if !visited[next_state_id] {
+                    visited[next_state_id] = true;
+                    queue.push_back(next_state_id);
+                }
+            }
+        }
+        tokens
+    }
+}
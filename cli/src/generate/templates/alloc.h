@@ -6,11 +6,17 @@ extern "C" {
 #endif
 
 #include <stdbool.h>
+
+#ifdef TREE_SITTER_FREESTANDING
+#include <stddef.h>
+#else
 #include <stdio.h>
 #include <stdlib.h>
+#endif
 
-// Allow clients to override allocation functions
-#ifdef TREE_SITTER_REUSE_ALLOCATOR
+// Allow clients to override allocation functions. Without libc, the allocation
+// functions must always be provided by the client.
+#if defined(TREE_SITTER_REUSE_ALLOCATOR) || defined(TREE_SITTER_FREESTANDING)
 
 extern void *(*ts_current_malloc)(size_t);
 extern void *(*ts_current_calloc)(size_t, size_t);
@@ -3,6 +3,9 @@ package tree_sitter_LOWER_PARSER_NAME
 // #cgo CFLAGS: -std=c11 -fPIC
 // #include "../../src/parser.c"
 // // NOTE: if your language has an external scanner, add it here.
+// // NOTE: if the parser is generated with `--split-lexer`, add a `lexer.c` file to this
+// // directory that includes "../../src/lexer.c". It can't be included here, because it
+// // repeats the symbol definitions of `parser.c`.
 import "C"
 
 import "unsafe"
@@ -5,6 +5,9 @@
       "dependencies": [
         "<!(node -p \"require('node-addon-api').targets\"):node_addon_api_except",
       ],
+      "variables": {
+        "has_lexer": "<!(node -p \"require('fs').existsSync('src/lexer.c')\")",
+      },
       "include_dirs": [
         "src",
       ],
@@ -14,6 +17,10 @@
         # NOTE: if your language has an external scanner, add it here.
       ],
       "conditions": [
+        # The lex functions are in their own file when the parser is generated with `--split-lexer`.
+        ["has_lexer=='true'", {
+          "sources+": ["src/lexer.c"],
+        }],
         ["OS!='win'", {
           "cflags_c": [
             "-std=c11",
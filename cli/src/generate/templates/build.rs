@@ -11,6 +11,13 @@ fn main() {
     c_config.file(&parser_path);
     println!("cargo:rerun-if-changed={}", parser_path.to_str().unwrap());
 
+    // The lex functions are in their own file when the parser is generated with `--split-lexer`.
+    let lexer_path = src_dir.join("lexer.c");
+    if lexer_path.exists() {
+        c_config.file(&lexer_path);
+        println!("cargo:rerun-if-changed={}", lexer_path.to_str().unwrap());
+    }
+
     // NOTE: if your language uses an external scanner, uncomment this block:
     /*
     let scanner_path = src_dir.join("scanner.c");
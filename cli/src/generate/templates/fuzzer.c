@@ -0,0 +1,163 @@
+// This file is generated by `tree-sitter generate --fuzz-harness`.
+//
+// A libFuzzer entry point for the PARSER_NAME parser. It parses each input, checks
+// the structure of the resulting tree, then applies an edit that is chosen by the
+// input's first bytes and checks that reparsing the edited text incrementally gives
+// the same tree as parsing it from scratch. Define `TS_FUZZ_NO_EDITS` to only check
+// the initial parse.
+//
+// Build it from the grammar's directory, with `TREE_SITTER_DIR` pointing at a
+// checkout of the Tree-sitter repository:
+//
+//   clang -g -O1 -fsanitize=fuzzer,address,undefined \
+//     -I "$TREE_SITTER_DIR/lib/include" -I "$TREE_SITTER_DIR/lib/src" \
+//     fuzz/fuzzer.c src/parser.cSCANNER_FILES "$TREE_SITTER_DIR/lib/src/lib.c" \
+//     -o fuzz/fuzzer
+//
+// Then run it with `fuzz/fuzzer -dict=fuzz/tokens.dict fuzz/corpus`. To fuzz with
+// AFL++, build the same sources with `afl-clang-fast` instead of `clang`.
+
+#include <assert.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "tree_sitter/api.h"
+
+const TSLanguage *tree_sitter_PARSER_NAME(void);
+
+typedef struct {
+  uint32_t end_byte;
+  uint32_t previous_end_byte;
+} Frame;
+
+// Check that each node lies within its parent, and after its previous sibling.
+static void check_tree(TSTree *tree, uint32_t length) {
+  TSNode root_node = ts_tree_root_node(tree);
+  assert(ts_node_end_byte(root_node) <= length);
+
+  TSTreeCursor cursor = ts_tree_cursor_new(root_node);
+  Frame *frames = NULL;
+  uint32_t depth = 0;
+  uint32_t capacity = 0;
+  for (;;) {
+    TSNode node = ts_tree_cursor_current_node(&cursor);
+    uint32_t start_byte = ts_node_start_byte(node);
+    uint32_t end_byte = ts_node_end_byte(node);
+    assert(start_byte <= end_byte);
+    if (depth > 0) {
+      Frame *parent = &frames[depth - 1];
+      assert(start_byte >= parent->previous_end_byte);
+      assert(end_byte <= parent->end_byte);
+      parent->previous_end_byte = end_byte;
+    }
+
+    if (ts_tree_cursor_goto_first_child(&cursor)) {
+      if (depth == capacity) {
+        capacity = capacity ? capacity * 2 : 64;
+        frames = realloc(frames, capacity * sizeof(Frame));
+        assert(frames);
+      }
+      frames[depth++] = (Frame){end_byte, start_byte};
+      continue;
+    }
+
+    while (!ts_tree_cursor_goto_next_sibling(&cursor)) {
+      if (!ts_tree_cursor_goto_parent(&cursor)) {
+        free(frames);
+        ts_tree_cursor_delete(&cursor);
+        return;
+      }
+      depth--;
+    }
+  }
+}
+
+static TSPoint point_for_byte(const char *text, uint32_t byte) {
+  TSPoint point = {0, 0};
+  for (uint32_t i = 0; i < byte; i++) {
+    if (text[i] == '\n') {
+      point.row++;
+      point.column = 0;
+    } else {
+      point.column++;
+    }
+  }
+  return point;
+}
+
+#ifndef TS_FUZZ_NO_EDITS
+
+// Replace a range of the text with a copy of its start, and check that reparsing the
+// edited text incrementally gives the same tree as parsing it from scratch.
+static void check_edit(TSParser *parser, TSTree *tree, const char *text, uint32_t length,
+                       const uint8_t *edit_bytes) {
+  uint32_t start_byte = ((uint32_t)edit_bytes[0] << 8 | edit_bytes[1]) % (length + 1);
+  uint32_t old_end_byte = start_byte + edit_bytes[2] % (length - start_byte + 1);
+  uint32_t inserted_length = edit_bytes[3] % (length + 1);
+  uint32_t new_length = length - (old_end_byte - start_byte) + inserted_length;
+
+  char *new_text = malloc(new_length + 1);
+  assert(new_text);
+  memcpy(new_text, text, start_byte);
+  memcpy(new_text + start_byte, text, inserted_length);
+  memcpy(new_text + start_byte + inserted_length, text + old_end_byte, length - old_end_byte);
+
+  TSInputEdit edit = {
+    .start_byte = start_byte,
+    .old_end_byte = old_end_byte,
+    .new_end_byte = start_byte + inserted_length,
+    .start_point = point_for_byte(text, start_byte),
+    .old_end_point = point_for_byte(text, old_end_byte),
+    .new_end_point = point_for_byte(new_text, start_byte + inserted_length),
+  };
+  TSTree *edited_tree = ts_tree_copy(tree);
+  ts_tree_edit(edited_tree, &edit);
+  TSTree *new_tree = ts_parser_parse_string(parser, edited_tree, new_text, new_length);
+  check_tree(new_tree, new_length);
+
+  TSTree *fresh_tree = ts_parser_parse_string(parser, NULL, new_text, new_length);
+  char *new_sexp = ts_node_string(ts_tree_root_node(new_tree));
+  char *fresh_sexp = ts_node_string(ts_tree_root_node(fresh_tree));
+  assert(strcmp(new_sexp, fresh_sexp) == 0);
+
+  free(new_sexp);
+  free(fresh_sexp);
+  ts_tree_delete(fresh_tree);
+  ts_tree_delete(new_tree);
+  ts_tree_delete(edited_tree);
+  free(new_text);
+}
+
+#endif
+
+int LLVMFuzzerTestOneInput(const uint8_t *data, size_t size) {
+  TSParser *parser = ts_parser_new();
+  bool language_ok = ts_parser_set_language(parser, tree_sitter_PARSER_NAME());
+  assert(language_ok);
+
+  // The first bytes of the input choose the edit, and the rest is the text.
+#ifndef TS_FUZZ_NO_EDITS
+  const size_t edit_size = 4;
+  if (size < edit_size) {
+    ts_parser_delete(parser);
+    return 0;
+  }
+  const uint8_t *edit_bytes = data;
+  data += edit_size;
+  size -= edit_size;
+#endif
+
+  const char *text = (const char *)data;
+  uint32_t length = (uint32_t)size;
+  TSTree *tree = ts_parser_parse_string(parser, NULL, text, length);
+  check_tree(tree, length);
+
+#ifndef TS_FUZZ_NO_EDITS
+  check_edit(parser, tree, text, length, edit_bytes);
+#endif
+
+  ts_tree_delete(tree);
+  ts_parser_delete(parser);
+  return 0;
+}
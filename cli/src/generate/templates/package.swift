@@ -1,6 +1,16 @@
 // swift-tools-version:5.3
+import Foundation
 import PackageDescription
 
+var sources = [
+    "src/parser.c",
+    // NOTE: if your language has an external scanner, add it here.
+]
+// The lex functions are in their own file when the parser is generated with `--split-lexer`.
+if FileManager.default.fileExists(atPath: "src/lexer.c") {
+    sources.append("src/lexer.c")
+}
+
 let package = Package(
     name: "TreeSitterCAMEL_PARSER_NAME",
     products: [
@@ -14,10 +24,7 @@ let package = Package(
             name: "TreeSitterCAMEL_PARSER_NAME",
             dependencies: [],
             path: ".",
-            sources: [
-                "src/parser.c",
-                // NOTE: if your language has an external scanner, add it here.
-            ],
+            sources: sources,
             resources: [
                 .copy("queries")
             ],
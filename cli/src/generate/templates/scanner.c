@@ -0,0 +1,35 @@
+#include "tree_sitter/parser.h"
+
+// The external tokens, in the same order as the grammar's `externals`.
+enum TokenType {
+EXTERNAL_TOKENS
+};
+
+// Create the scanner's state. This is called once for each parser, and the
+// returned pointer is passed to all of the other functions below.
+void *tree_sitter_PARSER_NAME_external_scanner_create(void) {
+  return NULL;
+}
+
+// Free the scanner's state.
+void tree_sitter_PARSER_NAME_external_scanner_destroy(void *payload) {}
+
+// Copy the scanner's state into `buffer`, and return the number of bytes that
+// were written. At most `TREE_SITTER_SERIALIZATION_BUFFER_SIZE` bytes may be
+// written. This is called after each external token, so it should be fast.
+unsigned tree_sitter_PARSER_NAME_external_scanner_serialize(void *payload, char *buffer) {
+  return 0;
+}
+
+// Restore the scanner's state from the `length` bytes in `buffer`, which were
+// written by `serialize`. If `length` is zero, reset the state to its initial
+// value.
+void tree_sitter_PARSER_NAME_external_scanner_deserialize(void *payload, const char *buffer, unsigned length) {}
+
+// Try to recognize one of the tokens whose entry in `valid_symbols` is true.
+// To produce a token, set `lexer->result_symbol` and return true. Returning
+// false tells the parser to fall back to its internal lexer. During error
+// recovery, all of the entries in `valid_symbols` are true.
+bool tree_sitter_PARSER_NAME_external_scanner_scan(void *payload, TSLexer *lexer, const bool *valid_symbols) {
+  return false;
+}
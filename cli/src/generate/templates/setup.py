@@ -1,4 +1,4 @@
-from os.path import isdir, join
+from os.path import exists, isdir, join
 from platform import system
 
 from setuptools import Extension, find_packages, setup
@@ -37,7 +37,11 @@ setup(
                 "bindings/python/tree_sitter_LOWER_PARSER_NAME/binding.c",
                 "src/parser.c",
                 # NOTE: if your language uses an external scanner, add it here.
-            ],
+            ] + (
+                # The lex functions are in their own file when the parser is generated
+                # with `--split-lexer`.
+                ["src/lexer.c"] if exists("src/lexer.c") else []
+            ),
             extra_compile_args=[
                 "-std=c11",
                 "-fvisibility=hidden",
@@ -0,0 +1,124 @@
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+use log::debug;
+
+use super::memory::{self, BYTES_PER_MB};
+
+/// How long each phase of parser generation took, and the most memory that was allocated
+/// while it ran, reported by `generate --timing`.
+#[derive(Debug, Default)]
+pub struct Timings {
+    phases: Vec<(&'static str, Duration, usize)>,
+}
+
+impl Timings {
+    /// Run one phase of the generation, adding the time it took to the phase's total. The
+    /// start and end of the phase are also logged, for embedders that route the `log`
+    /// records into their own logging.
+    pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        debug!("{phase}: started");
+        let previous_phase = memory::set_current_phase(Some(phase));
+        memory::reset_peak();
+        let start = Instant::now();
+        let result = f();
+        let duration = start.elapsed();
+        let peak_memory = memory::peak_bytes();
+        memory::set_current_phase(previous_phase);
+        debug!(
+            "{phase}: finished in {:.2} ms",
+            duration.as_micros() as f64 / 1e3
+        );
+        self.add(phase, duration, peak_memory);
+        result
+    }
+
+    fn add(&mut self, phase: &'static str, duration: Duration, peak_memory: usize) {
+        if let Some((_, total, peak)) = self.phases.iter_mut().find(|(name, ..)| *name == phase) {
+            *total += duration;
+            *peak = (*peak).max(peak_memory);
+        } else {
+            self.phases.push((phase, duration, peak_memory));
+        }
+    }
+}
+
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .phases
+            .iter()
+            .map(|(phase, ..)| phase.len())
+            .max()
+            .unwrap_or(0);
+        // Memory is only counted when the CLI's counting allocator is installed and counting.
+        let show_memory = self.phases.iter().any(|(.., peak)| *peak > 0);
+        for (phase, duration, peak_memory) in &self.phases {
+            write!(
+                f,
+                "  {phase:width$}  {:>9.2} ms",
+                duration.as_micros() as f64 / 1e3
+            )?;
+            if show_memory {
+                write!(f, "  {:>9.1} MB", *peak_memory as f64 / BYTES_PER_MB as f64)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timings_add_up_repeated_phases() {
+        let mut timings = Timings::default();
+        timings.add("token expansion", Duration::from_millis(2), 0);
+        timings.add("code emission", Duration::from_millis(5), 0);
+        timings.add("token expansion", Duration::from_millis(3), 0);
+        assert_eq!(timings.time("parse state construction", || 42), 42);
+
+        assert_eq!(
+            timings
+                .phases
+                .iter()
+                .map(|(phase, ..)| *phase)
+                .collect::<Vec<_>>(),
+            [
+                "token expansion",
+                "code emission",
+                "parse state construction"
+            ]
+        );
+        assert_eq!(timings.phases[0].1, Duration::from_millis(5));
+        assert!(timings
+            .to_string()
+            .starts_with("  token expansion                5.00 ms\n"));
+    }
+
+    #[test]
+    fn test_timings_report_peak_memory() {
+        let mut timings = Timings::default();
+        timings.add(
+            "token expansion",
+            Duration::from_millis(2),
+            3 * BYTES_PER_MB,
+        );
+        timings.add("code emission", Duration::from_millis(5), BYTES_PER_MB / 2);
+        timings.add(
+            "token expansion",
+            Duration::from_millis(3),
+            2 * BYTES_PER_MB,
+        );
+
+        assert_eq!(timings.phases[0].2, 3 * BYTES_PER_MB);
+        assert_eq!(
+            timings.to_string(),
+            "  token expansion       5.00 ms        3.0 MB\n  code emission         5.00 ms        0.5 MB\n"
+        );
+    }
+}
@@ -1,23 +1,44 @@
 #![doc = include_str!("../README.md")]
 
+#[cfg(feature = "cli")]
+pub mod benchmark;
+#[cfg(feature = "cli")]
 pub mod fuzz;
 pub mod generate;
+#[cfg(feature = "highlight")]
 pub mod highlight;
+#[cfg(feature = "cli")]
 pub mod logger;
+#[cfg(feature = "cli")]
+pub mod lsp;
+#[cfg(feature = "cli")]
 pub mod parse;
+#[cfg(feature = "playground")]
 pub mod playground;
+#[cfg(feature = "cli")]
 pub mod query;
+#[cfg(feature = "cli")]
 pub mod query_testing;
+#[cfg(feature = "cli")]
+pub mod runtime;
+#[cfg(feature = "cli")]
 pub mod tags;
+#[cfg(feature = "cli")]
 pub mod test;
+#[cfg(feature = "highlight")]
 pub mod test_highlight;
+#[cfg(feature = "cli")]
 pub mod test_tags;
+#[cfg(feature = "cli")]
+pub mod tree_diff;
+#[cfg(feature = "cli")]
 pub mod util;
+#[cfg(feature = "playground")]
 pub mod wasm;
 
-#[cfg(test)]
+#[cfg(all(test, feature = "cli"))]
 mod tests;
 
 // To run compile fail tests
-#[cfg(doctest)]
+#[cfg(all(doctest, feature = "cli"))]
 mod tests;
@@ -1,22 +1,37 @@
-use log::{LevelFilter, Log, Metadata, Record};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// The target of the notes that `generate_parser_in_directory` logs about the files that
+/// it writes, which are shown along with warnings.
+const NOTES_TARGET: &str = "tree_sitter_cli::generate";
 
-#[allow(dead_code)]
 struct Logger {
-    pub filter: Option<String>,
+    level: LevelFilter,
 }
 
 impl Log for Logger {
-    fn enabled(&self, _: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+            || (metadata.level() == Level::Info && metadata.target() == NOTES_TARGET)
     }
 
     fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if record.level() == Level::Warn {
+            eprintln!("Warning: {}", record.args());
+            return;
+        }
+        if record.level() == Level::Info && record.target() == NOTES_TARGET {
+            eprintln!("Note: {}", record.args());
+            return;
+        }
         eprintln!(
             "[{}] {}",
             record
                 .module_path()
                 .unwrap_or_default()
-                .trim_start_matches("rust_tree_sitter_cli::"),
+                .trim_start_matches("tree_sitter_cli::"),
             record.args()
         );
     }
@@ -24,7 +39,9 @@ impl Log for Logger {
     fn flush(&self) {}
 }
 
-pub fn init() {
-    log::set_boxed_logger(Box::new(Logger { filter: None })).unwrap();
-    log::set_max_level(LevelFilter::Info);
+/// Install the logger, which prints warnings and notes, such as those from parser
+/// generation, to stderr. Pass a higher level to show more of the log.
+pub fn init(level: LevelFilter) {
+    log::set_boxed_logger(Box::new(Logger { level })).unwrap();
+    log::set_max_level(level.max(LevelFilter::Info));
 }
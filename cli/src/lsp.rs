@@ -0,0 +1,305 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+    sync::mpsc,
+    thread,
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+
+use crate::generate::{
+    check_grammar_file, watched_grammar_files, GrammarDiagnostic, RuleLocation, WATCH_INTERVAL,
+};
+
+enum Event {
+    Message(Value),
+    FilesChanged,
+    Closed,
+}
+
+/// Run a language server for a grammar over stdin and stdout. The grammar is checked when
+/// the editor connects, and again whenever one of its files changes, and the problems are
+/// published as diagnostics on the grammar file.
+pub fn run(grammar_path: &Path, js_runtime: Option<&str>) -> Result<()> {
+    serve(
+        BufReader::new(io::stdin()),
+        &mut io::stdout(),
+        grammar_path,
+        js_runtime,
+    )
+}
+
+fn serve(
+    input: impl BufRead + Send + 'static,
+    output: &mut impl Write,
+    grammar_path: &Path,
+    js_runtime: Option<&str>,
+) -> Result<()> {
+    let grammar_path = fs::canonicalize(grammar_path)
+        .with_context(|| format!("Failed to find the grammar at {grammar_path:?}"))?;
+    let uri = path_to_uri(&grammar_path);
+
+    let (sender, receiver) = mpsc::channel();
+    let message_sender = sender.clone();
+    thread::spawn(move || {
+        let mut input = input;
+        loop {
+            let event = match read_message(&mut input) {
+                Ok(Some(message)) => Event::Message(message),
+                Ok(None) => Event::Closed,
+                Err(error) => {
+                    eprintln!("{error:?}");
+                    Event::Closed
+                }
+            };
+            let closed = matches!(event, Event::Closed);
+            if message_sender.send(event).is_err() || closed {
+                break;
+            }
+        }
+    });
+    let watched_path = grammar_path.clone();
+    thread::spawn(move || {
+        let mut files = watched_grammar_files(&watched_path);
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let new_files = watched_grammar_files(&watched_path);
+            if new_files != files {
+                files = new_files;
+                if sender.send(Event::FilesChanged).is_err() {
+                    break;
+                }
+            }
+        }
+    });
+
+    let mut initialized = false;
+    for event in receiver {
+        let message = match event {
+            Event::Message(message) => message,
+            Event::FilesChanged => {
+                if initialized {
+                    publish_diagnostics(output, &grammar_path, &uri, js_runtime)?;
+                }
+                continue;
+            }
+            Event::Closed => break,
+        };
+
+        let method = message["method"].as_str().unwrap_or_default();
+        match (method, message.get("id")) {
+            ("initialize", Some(id)) => write_message(
+                output,
+                &json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "result": {
+                        "capabilities": {},
+                        "serverInfo": {
+                            "name": "tree-sitter",
+                            "version": env!("CARGO_PKG_VERSION"),
+                        },
+                    },
+                }),
+            )?,
+            ("initialized", None) => {
+                initialized = true;
+                publish_diagnostics(output, &grammar_path, &uri, js_runtime)?;
+            }
+            ("shutdown", Some(id)) => {
+                write_message(output, &json!({"jsonrpc": "2.0", "id": id, "result": null}))?;
+            }
+            ("exit", None) => break,
+            (_, Some(id)) => write_message(
+                output,
+                &json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": format!("Unsupported method `{method}`")},
+                }),
+            )?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn publish_diagnostics(
+    output: &mut impl Write,
+    grammar_path: &Path,
+    uri: &str,
+    js_runtime: Option<&str>,
+) -> Result<()> {
+    let diagnostics = check_grammar_file(grammar_path, js_runtime)
+        .iter()
+        .map(|diagnostic| lsp_diagnostic(diagnostic, uri))
+        .collect::<Vec<_>>();
+    write_message(
+        output,
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {"uri": uri, "diagnostics": diagnostics},
+        }),
+    )
+}
+
+fn lsp_diagnostic(diagnostic: &GrammarDiagnostic, uri: &str) -> Value {
+    let range = |location: Option<&RuleLocation>| {
+        let (row, column, len) = location.map_or((0, 0, 0), |l| (l.row, l.column, l.len));
+        json!({
+            "start": {"line": row, "character": column},
+            "end": {"line": row, "character": column + len},
+        })
+    };
+    let related_information = diagnostic
+        .rules
+        .iter()
+        .skip(1)
+        .map(|(name, location)| {
+            json!({
+                "location": {"uri": uri, "range": range(Some(location))},
+                "message": format!("`{name}` is defined here"),
+            })
+        })
+        .collect::<Vec<_>>();
+    json!({
+        "range": range(diagnostic.rules.first().map(|(_, location)| location)),
+        "severity": if diagnostic.is_error { 1 } else { 2 },
+        "source": "tree-sitter",
+        "message": diagnostic.message,
+        "relatedInformation": related_information,
+    })
+}
+
+/// Read a message in the base protocol: a `Content-Length` header, a blank line, and then
+/// the JSON content. Returns `None` when the input has ended.
+fn read_message(input: &mut impl BufRead) -> Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = Some(
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .with_context(|| format!("Invalid Content-Length header {line:?}"))?,
+                );
+            }
+        }
+    }
+    let content_length = content_length.ok_or_else(|| anyhow!("Missing Content-Length header"))?;
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+    Ok(Some(
+        serde_json::from_slice(&content).with_context(|| "Failed to parse a message")?,
+    ))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> Result<()> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    output.flush()?;
+    Ok(())
+}
+
+fn path_to_uri(path: &Path) -> String {
+    // Canonical paths on Windows have a verbatim prefix, which doesn't belong in a URI.
+    let path = path.to_string_lossy();
+    let path = path.trim_start_matches(r"\\?\").replace('\\', "/");
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            write!(uri, "%{byte:02X}").unwrap();
+        }
+    }
+    uri
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_serve_publishes_grammar_diagnostics() {
+        let grammar_dir = tempfile::tempdir().unwrap();
+        let grammar_path = grammar_dir.path().join("grammar.json");
+        fs::write(
+            &grammar_path,
+            r#"{
+  "name": "lsp_test",
+  "rules": {
+    "program": {"type": "SYMBOL", "name": "word"},
+    "word": {"type": "PATTERN", "value": "[a-z]+"},
+    "number": {"type": "PATTERN", "value": "[0-9]+"}
+  }
+}"#,
+        )
+        .unwrap();
+
+        let mut input = Vec::new();
+        for message in [
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}),
+            json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+            json!({"jsonrpc": "2.0", "id": 2, "method": "textDocument/hover", "params": {}}),
+            json!({"jsonrpc": "2.0", "id": 3, "method": "shutdown"}),
+            json!({"jsonrpc": "2.0", "method": "exit"}),
+        ] {
+            write_message(&mut input, &message).unwrap();
+        }
+        let mut output = Vec::new();
+        serve(Cursor::new(input), &mut output, &grammar_path, None).unwrap();
+
+        let mut output = Cursor::new(output);
+        let mut messages = Vec::new();
+        while let Some(message) = read_message(&mut output).unwrap() {
+            messages.push(message);
+        }
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0]["id"], 1);
+        assert_eq!(
+            messages[1]["params"]["diagnostics"],
+            json!([{
+                "range": {
+                    "start": {"line": 5, "character": 4},
+                    "end": {"line": 5, "character": 12},
+                },
+                "severity": 2,
+                "source": "tree-sitter",
+                "message": "The rule `number` is never used",
+                "relatedInformation": [],
+            }])
+        );
+        assert_eq!(messages[2]["error"]["code"], -32601);
+        assert_eq!(
+            messages[3],
+            json!({"jsonrpc": "2.0", "id": 3, "result": null})
+        );
+    }
+
+    #[test]
+    fn test_path_to_uri() {
+        assert_eq!(
+            path_to_uri(Path::new("/home/me/tree-sitter-c/grammar file.js")),
+            "file:///home/me/tree-sitter-c/grammar%20file.js"
+        );
+    }
+}
@@ -1,28 +1,40 @@
+#[cfg(feature = "highlight")]
+use std::collections::HashSet;
 use std::{
-    collections::HashSet,
-    env, fs,
+    collections::HashMap,
+    env,
+    ffi::OsString,
+    fs, io,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use anstyle::{AnsiColor, Color, Style};
 use anyhow::{anyhow, Context, Result};
 use clap::{crate_authors, Args, Command, FromArgMatches as _, Subcommand};
 use glob::glob;
+use log::LevelFilter;
 use regex::Regex;
-use tree_sitter::{ffi, Parser, Point};
+use serde::Deserialize;
+use tree_sitter::{ffi, Language, Parser, Point};
 use tree_sitter_cli::{
+    benchmark,
     fuzz::{
         fuzz_language_corpus, FuzzOptions, EDIT_COUNT, ITERATION_COUNT, LOG_ENABLED,
         LOG_GRAPH_ENABLED, START_SEED,
     },
-    generate::{self, lookup_package_json_for_path},
-    highlight, logger,
+    generate, logger, lsp,
     parse::{self, ParseFileOptions, ParseOutput},
-    playground, query, tags,
+    query, tags,
     test::{self, TestOptions},
-    test_highlight, test_tags, util, wasm,
+    test_tags, tree_diff, util,
 };
-use tree_sitter_config::Config;
+#[cfg(feature = "highlight")]
+use tree_sitter_cli::{highlight, test_highlight};
+#[cfg(feature = "playground")]
+use tree_sitter_cli::{playground, wasm};
+use tree_sitter_config::{Config, ProjectConfig};
+#[cfg(feature = "highlight")]
 use tree_sitter_highlight::Highlighter;
 use tree_sitter_loader as loader;
 use tree_sitter_tags::TagsContext;
@@ -31,19 +43,31 @@ const BUILD_VERSION: &str = env!("CARGO_PKG_VERSION");
 const BUILD_SHA: Option<&'static str> = option_env!("BUILD_SHA");
 const DEFAULT_GENERATE_ABI_VERSION: usize = 14;
 
+// Only counts allocations once `generate --timing` or `generate --max-memory` turns it on.
+#[global_allocator]
+static ALLOCATOR: generate::CountingAllocator = generate::CountingAllocator;
+
 #[derive(Subcommand)]
 #[command(about="Generates and tests parsers", author=crate_authors!("\n"), styles=get_styles())]
 enum Commands {
     InitConfig(InitConfig),
+    Init(Init),
     Generate(Generate),
+    CheckNodeTypes(CheckNodeTypes),
+    Docs(Docs),
     Build(Build),
     Parse(Parse),
+    Diff(Diff),
     Test(Test),
     Fuzz(Fuzz),
+    Benchmark(Benchmark),
     Query(Query),
+    #[cfg(feature = "highlight")]
     Highlight(Highlight),
     Tags(Tags),
+    #[cfg(feature = "playground")]
     Playground(Playground),
+    Lsp(Lsp),
     DumpLanguages(DumpLanguages),
 }
 
@@ -51,6 +75,21 @@ enum Commands {
 #[command(about = "Generate a default config file")]
 struct InitConfig;
 
+#[derive(Args)]
+#[command(about = "Scaffold a new grammar project")]
+struct Init {
+    #[arg(
+        index = 1,
+        help = "The name of the language (defaults to the directory's name, without a `tree-sitter-` prefix)"
+    )]
+    pub language_name: Option<String>,
+    #[arg(
+        long,
+        help = "The directory to create the project in (defaults to the current directory)"
+    )]
+    pub path: Option<PathBuf>,
+}
+
 #[derive(Args)]
 #[command(about = "Generate a parser", alias = "gen", alias = "g")]
 struct Generate {
@@ -88,20 +127,238 @@ struct Generate {
     pub libdir: Option<String>,
     #[arg(
         long,
-        help = "Produce a report of the states for the given rule, use `-` to report every rule"
+        help = "Produce a report of the states for the given rule, with their items and conflicts. Use `-` to report every rule"
     )]
     pub report_states_for_rule: Option<String>,
+    #[arg(
+        long,
+        value_name = "STEPS",
+        help = "Automatically inline hidden rules whose productions have at most this many steps in total"
+    )]
+    pub inline_threshold: Option<usize>,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "A JSON file mapping rule names to the node names to use in the generated parser"
+    )]
+    pub rename_map: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Keep symbol ids stable across regenerations by recording them in src/symbol-ids.json. Removed symbols leave their ids unused, but new tokens still shift the ids of the rules"
+    )]
+    pub stable_symbol_ids: bool,
+    #[arg(
+        long,
+        help = "Write the lex functions to src/lexer.c, so that large parsers can be compiled in smaller pieces"
+    )]
+    pub split_lexer: bool,
+    #[arg(
+        long,
+        help = "Generate a parser that does not depend on libc, for embedded and sandboxed environments"
+    )]
+    pub freestanding: bool,
+    #[arg(
+        long,
+        help = "Describe the parse and lex tables in src/parser.txt, in terms of the grammar's rules and tokens"
+    )]
+    pub dump_tables: bool,
+    #[arg(
+        long,
+        help = "Write the ids of the node types and fields to src/symbols.h, for use by scanners and applications"
+    )]
+    pub symbols_header: bool,
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        help = "The prefix of the parser's exported function names, instead of `tree_sitter_`"
+    )]
+    pub export_prefix: Option<String>,
+    #[arg(
+        long,
+        short,
+        value_name = "DIRECTORY",
+        conflicts_with = "build",
+        help = "The directory to write the generated parser to (default src)"
+    )]
+    pub output: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Write manifest.json, listing the hashes of the generated files and of the grammar"
+    )]
+    pub manifest: bool,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "A file whose contents are inserted as a comment at the top of the generated sources"
+    )]
+    pub banner: Option<PathBuf>,
+    #[arg(
+        long,
+        help = "Write typed Rust wrappers for the grammar's node types to bindings/rust/ast.rs"
+    )]
+    pub rust_ast: bool,
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Define the lexer's large character sets in a C header that is shared between parsers"
+    )]
+    pub shared_character_sets: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "STRATEGY",
+        help = "How to emit the lex functions: `switch` (default), `jump-table`, `range-table`, `ascii-bitmap` or `direct-coded`"
+    )]
+    pub lexer_strategy: Option<String>,
+    #[arg(
+        long,
+        conflicts_with = "split_lexer",
+        help = "Also write src/amalgamation.c, a single file with the parser, the external scanner and the headers they include"
+    )]
+    pub amalgamate: bool,
+    #[arg(
+        long,
+        help = "Also write the parse and lex tables to src/parser-tables.bin, in a versioned binary format that can be decoded at runtime with `tree_sitter_cli::generate::read_table_blob`"
+    )]
+    pub table_blob: bool,
+    #[arg(
+        long,
+        help = "Write a libFuzzer entry point for the parser to fuzz/fuzzer.c, with a dictionary of the grammar's tokens"
+    )]
+    pub fuzz_harness: bool,
+    #[arg(
+        long,
+        help = "Write a test corpus with an example of each rule to test/corpus/skeleton.txt, if the grammar doesn't have any tests yet"
+    )]
+    pub skeleton_corpus: bool,
+    #[arg(
+        long,
+        help = "Write a starting point for highlighting the grammar's tokens to queries/highlights.scm, if the grammar doesn't have one yet"
+    )]
+    pub highlights_query: bool,
+    #[arg(
+        long,
+        value_name = "COUNT",
+        help = "Write this many random programs derived from the grammar to test/random"
+    )]
+    pub random_programs: Option<usize>,
+    #[arg(
+        long,
+        value_name = "SEED",
+        requires = "random_programs",
+        help = "The seed of the random programs (default 0)"
+    )]
+    pub random_seed: Option<u64>,
+    #[arg(
+        long,
+        help = "Write a map from each parse and lex state to the grammar rules and items that it was built from to src/source-map.json"
+    )]
+    pub source_map: bool,
+    #[arg(
+        long,
+        help = "Write the structure of each rule to src/railroad.json, for drawing railroad diagrams in the language's documentation"
+    )]
+    pub railroad: bool,
+    #[arg(
+        long,
+        value_name = "STANDARD",
+        help = "The version of the C standard to write the parser in: `c99` (default) or `c89`"
+    )]
+    pub c_standard: Option<String>,
+    #[arg(
+        long,
+        value_name = "KEYWORD",
+        help = "The keyword that the parser's inline functions are declared with, for compilers that don't support `inline`"
+    )]
+    pub inline_keyword: Option<String>,
+    #[arg(
+        long,
+        help = "Annotate the parse and lex states in the generated parser with comments that name the grammar rules and items that they come from"
+    )]
+    pub annotate: bool,
+    #[arg(
+        long,
+        conflicts_with = "annotate",
+        help = "Remove the annotations from a previously generated parser, without generating it again"
+    )]
+    pub strip_annotations: bool,
+    #[arg(
+        long,
+        conflicts_with = "strip_annotations",
+        help = "Keep running, and generate the parser again whenever the grammar's files change, reusing the results of the phases that the change doesn't affect"
+    )]
+    pub watch: bool,
+    #[arg(
+        long,
+        conflicts_with = "strip_annotations",
+        help = "Report how long each phase of the generation took, and how much memory it used"
+    )]
+    pub timing: bool,
+    #[arg(
+        long,
+        value_name = "MB",
+        conflicts_with = "strip_annotations",
+        help = "Stop the generation, naming the phase that was running, if it uses more than this many megabytes of memory"
+    )]
+    pub max_memory: Option<usize>,
+    #[arg(
+        long,
+        value_name = "MS",
+        conflicts_with = "strip_annotations",
+        help = "Treat a pair of tokens as conflicting if analyzing whether they conflict takes longer than this many milliseconds"
+    )]
+    pub conflict_analysis_budget: Option<u64>,
+    #[arg(
+        long,
+        value_name = "DIRECTORY",
+        conflicts_with = "strip_annotations",
+        help = "Save the results of the slowest phases in this directory, and resume from them if the generation was interrupted"
+    )]
+    pub checkpoint_dir: Option<PathBuf>,
 
     #[arg(
         long,
         value_name = "EXECUTABLE",
         env = "TREE_SITTER_JS_RUNTIME",
         default_value = "node",
-        help = "The name or path of the JavaScript runtime to use for generating parsers"
+        help = "The name or path of the JavaScript runtime to use for generating parsers, or `native` for the one built into the CLI"
     )]
     pub js_runtime: Option<String>,
 }
 
+#[derive(Args)]
+#[command(about = "Write a reference of a grammar's syntax, with examples from its test corpus")]
+struct Docs {
+    #[arg(
+        index = 1,
+        help = "The directory of the grammar, whose parser has been generated (defaults to the current directory)"
+    )]
+    pub path: Option<PathBuf>,
+    #[arg(long, help = "Write HTML instead of Markdown")]
+    pub html: bool,
+    #[arg(
+        long,
+        short,
+        value_name = "PATH",
+        help = "The file to write the reference to, instead of the standard output"
+    )]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+#[command(about = "Report breaking changes in a grammar's node types")]
+struct CheckNodeTypes {
+    #[arg(
+        index = 1,
+        help = "The path to the previous version of node-types.json"
+    )]
+    pub old_path: PathBuf,
+    #[arg(
+        index = 2,
+        help = "The path to the new version of node-types.json (default src/node-types.json)"
+    )]
+    pub new_path: Option<PathBuf>,
+}
+
 #[derive(Args)]
 #[command(about = "Compile a parser", alias = "b")]
 struct Build {
@@ -138,6 +395,13 @@ struct Parse {
         help = "Select a language by the scope instead of a file extension"
     )]
     pub scope: Option<String>,
+    #[arg(
+        long = "language",
+        value_name = "NAME",
+        conflicts_with = "scope",
+        help = "Select an installed language by its name, from the parser or library directories"
+    )]
+    pub language_name: Option<String>,
     #[arg(long, short = 'd', help = "Show parsing debug log")]
     pub debug: bool,
     #[arg(long, short = '0', help = "Compile a parser in debug mode")]
@@ -148,6 +412,12 @@ struct Parse {
         help = "Produce the log.html file with debug graphs"
     )]
     pub debug_graph: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["debug", "debug_graph"],
+        help = "Log where the parser pursues several alternatives because of declared conflicts, and which one wins"
+    )]
+    pub log_ambiguities: bool,
     #[arg(
         long,
         help = "Compile parsers to wasm instead of native dynamic libraries"
@@ -155,12 +425,21 @@ struct Parse {
     pub wasm: bool,
     #[arg(long = "dot", help = "Output the parse data with graphviz dot")]
     pub output_dot: bool,
+    #[arg(
+        long = "output",
+        value_name = "FORMAT",
+        conflicts_with_all = ["output_dot", "output_xml"],
+        help = "The format of the parse data: `sexp` (the default), `json`, `xml` or `dot`"
+    )]
+    pub output_format: Option<String>,
     #[arg(
         long = "xml",
         short = 'x',
         help = "Output the parse data in XML format"
     )]
     pub output_xml: bool,
+    #[arg(long, help = "Show the byte range of each node after its position")]
+    pub byte_ranges: bool,
     #[arg(long, short, help = "Show parsing statistic")]
     pub stat: bool,
     #[arg(long, help = "Interrupt the parsing process by timeout (µs)")]
@@ -169,12 +448,30 @@ struct Parse {
     pub time: bool,
     #[arg(long, short, help = "Suppress main output")]
     pub quiet: bool,
+    #[arg(
+        long,
+        conflicts_with_all = ["output_format", "output_dot", "output_xml", "stat", "time"],
+        help = "Print a JSON summary of the results for each file instead of the parse trees"
+    )]
+    pub json: bool,
     #[arg(
         long,
         num_args = 1..,
         help = "Apply edits in the format: \"row, col delcount insert_text\""
     )]
     pub edits: Option<Vec<String>>,
+    #[arg(
+        long = "range",
+        num_args = 1..,
+        help = "Only parse the given ranges of the file, in the format \"start..end\", where each end is a byte offset or \"row,col\""
+    )]
+    pub ranges: Option<Vec<String>>,
+    #[arg(
+        long,
+        requires = "edits",
+        help = "Check that parsing the edited text incrementally gives the same tree as parsing it from scratch"
+    )]
+    pub check_edits: bool,
     #[arg(long, help = "The encoding of the input files")]
     pub encoding: Option<String>,
     #[arg(
@@ -189,6 +486,24 @@ struct Parse {
     pub test_number: Option<u32>,
 }
 
+#[derive(Args)]
+#[command(about = "Compare the syntax trees of two versions of a file")]
+struct Diff {
+    #[arg(index = 1, help = "The old version of the file")]
+    pub old_path: PathBuf,
+    #[arg(index = 2, help = "The new version of the file")]
+    pub new_path: PathBuf,
+    #[arg(
+        long,
+        help = "Select a language by the scope instead of a file extension"
+    )]
+    pub scope: Option<String>,
+    #[arg(long, help = "Print the changes as JSON")]
+    pub json: bool,
+    #[arg(long, help = "The path to an alternative config.json file")]
+    pub config_path: Option<PathBuf>,
+}
+
 #[derive(Args)]
 #[command(about = "Run a parser's tests", alias = "t")]
 struct Test {
@@ -230,10 +545,32 @@ struct Test {
         help = "Open `log.html` in the default browser, if `--debug-graph` is supplied"
     )]
     pub open_log: bool,
+    #[arg(
+        long = "language",
+        value_name = "NAME",
+        help = "Test an installed language, selected by its name, instead of the one in the current directory"
+    )]
+    pub language_name: Option<String>,
     #[arg(long, help = "The path to an alternative config.json file")]
     pub config_path: Option<PathBuf>,
     #[arg(long, help = "Force showing fields in test diffs")]
     pub show_fields: bool,
+    #[arg(
+        long,
+        short,
+        value_name = "COUNT",
+        conflicts_with_all = ["debug", "debug_graph", "wasm"],
+        help = "Run the corpus files in this many threads (default 1)"
+    )]
+    pub jobs: Option<usize>,
+    #[arg(long, short, help = "Only print the failing tests")]
+    pub quiet: bool,
+    #[arg(
+        long,
+        conflicts_with = "update",
+        help = "Print a JSON summary of the results instead of the test output"
+    )]
+    pub json: bool,
 }
 
 #[derive(Args)]
@@ -263,6 +600,52 @@ struct Fuzz {
     pub log_graphs: bool,
     #[arg(long, short, help = "Enable parser logging")]
     pub log: bool,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Save the edited inputs that fail fuzzing to the given directory, so that they can be reproduced"
+    )]
+    pub save_failures: Option<PathBuf>,
+}
+
+#[derive(Args)]
+#[command(about = "Measure how fast files are parsed", alias = "bench")]
+struct Benchmark {
+    #[arg(
+        long = "paths",
+        help = "The path to a file with paths to source file(s)"
+    )]
+    pub paths_file: Option<String>,
+    #[arg(num_args=1.., help = "The source file(s) or directories to benchmark")]
+    pub paths: Option<Vec<String>>,
+    #[arg(
+        long,
+        help = "Select a language by the scope instead of a file extension"
+    )]
+    pub scope: Option<String>,
+    #[arg(long, help = "Number of times to parse each file from scratch")]
+    pub iterations: Option<usize>,
+    #[arg(
+        long,
+        help = "Number of random edits after which to re-parse each file incrementally"
+    )]
+    pub edits: Option<usize>,
+    #[arg(
+        long,
+        help = "Compare the results with a baseline saved by --save-baseline"
+    )]
+    pub baseline: Option<PathBuf>,
+    #[arg(long, help = "Save the results as a baseline for later comparisons")]
+    pub save_baseline: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "PERCENT",
+        requires = "baseline",
+        help = "Fail if a file takes more than this percentage longer to parse or re-parse than in the baseline"
+    )]
+    pub max_regression: Option<f64>,
+    #[arg(long, help = "The path to an alternative config.json file")]
+    pub config_path: Option<PathBuf>,
 }
 
 #[derive(Args)]
@@ -301,6 +684,7 @@ struct Query {
     pub config_path: Option<PathBuf>,
 }
 
+#[cfg(feature = "highlight")]
 #[derive(Args)]
 #[command(about = "Highlight a file", alias = "hi")]
 struct Highlight {
@@ -320,6 +704,13 @@ struct Highlight {
         help = "Select a language by the scope instead of a file extension"
     )]
     pub scope: Option<String>,
+    #[arg(
+        long = "language",
+        value_name = "NAME",
+        conflicts_with = "scope",
+        help = "Select an installed language by its name, from the parser or library directories"
+    )]
+    pub language_name: Option<String>,
     #[arg(long, short, help = "Measure execution time")]
     pub time: bool,
     #[arg(long, short, help = "Suppress main output")]
@@ -343,6 +734,12 @@ struct Tags {
         help = "Select a language by the scope instead of a file extension"
     )]
     pub scope: Option<String>,
+    #[arg(
+        long = "output",
+        value_name = "FORMAT",
+        help = "The format of the tags: `text` (the default), `json`, or `ctags` for a tags file of the definitions"
+    )]
+    pub output_format: Option<String>,
     #[arg(long, short, help = "Measure execution time")]
     pub time: bool,
     #[arg(long, short, help = "Suppress main output")]
@@ -358,6 +755,7 @@ struct Tags {
     pub config_path: Option<PathBuf>,
 }
 
+#[cfg(feature = "playground")]
 #[derive(Args)]
 #[command(
     about = "Start local playground for a parser in the browser",
@@ -375,6 +773,24 @@ struct Playground {
     pub grammar_path: Option<String>,
 }
 
+#[derive(Args)]
+#[command(about = "Run a language server that reports problems in a grammar to an editor")]
+struct Lsp {
+    #[arg(
+        index = 1,
+        help = "The path to the grammar file (defaults to grammar.js in the current directory)"
+    )]
+    pub grammar_path: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "EXECUTABLE",
+        env = "TREE_SITTER_JS_RUNTIME",
+        default_value = "node",
+        help = "The name or path of the JavaScript runtime to use for loading the grammar, or `native` for the one built into the CLI"
+    )]
+    pub js_runtime: Option<String>,
+}
+
 #[derive(Args)]
 #[command(about = "Print info about all known language parsers", alias = "langs")]
 struct DumpLanguages {
@@ -419,12 +835,15 @@ fn run() -> Result<()> {
         .subcommand_required(true)
         .arg_required_else_help(true)
         .disable_help_subcommand(true)
-        .disable_colored_help(false);
+        .disable_colored_help(false)
+        .args_override_self(true);
     let cli = Commands::augment_subcommands(cli);
 
-    let command = Commands::from_arg_matches(&cli.get_matches())?;
-
     let current_dir = env::current_dir().unwrap();
+    let project_config = ProjectConfig::find(&current_dir)?;
+    let args = args_with_project_defaults(&cli, project_config.as_ref())?;
+    let command = Commands::from_arg_matches(&cli.get_matches_from(args))?;
+
     let mut loader = loader::Loader::new()?;
 
     let color = env::var("NO_COLOR").map_or(true, |v| v != "1");
@@ -439,7 +858,8 @@ fn run() -> Result<()> {
             }
             let mut config = Config::initial()?;
             config.add(tree_sitter_loader::Config::initial())?;
-            config.add(tree_sitter_cli::highlight::ThemeConfig::default())?;
+            #[cfg(feature = "highlight")]
+            config.add(highlight::ThemeConfig::default())?;
             config.save()?;
             println!(
                 "Saved initial configuration to {}",
@@ -447,10 +867,32 @@ fn run() -> Result<()> {
             );
         }
 
+        Commands::Init(init_options) => {
+            let repo_path = init_options.path.unwrap_or_else(|| current_dir.clone());
+            let language_name = match init_options.language_name {
+                Some(language_name) => language_name,
+                None => {
+                    let dir_name = fs::canonicalize(&repo_path)
+                        .unwrap_or_else(|_| repo_path.clone())
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_ascii_lowercase())
+                        .ok_or_else(|| anyhow!("Must provide a language name"))?;
+                    dir_name
+                        .strip_prefix("tree-sitter-")
+                        .unwrap_or(&dir_name)
+                        .replace('-', "_")
+                }
+            };
+            generate::init_grammar_project(&repo_path, &language_name)?;
+            eprintln!("Note: created the `{language_name}` grammar in {repo_path:?}. Write its rules in grammar.js, and run `tree-sitter generate --skeleton-corpus --highlights-query` to generate the parser, along with a test corpus and highlights query to start from");
+        }
+
         Commands::Generate(generate_options) => {
-            if generate_options.log {
-                logger::init();
-            }
+            logger::init(if generate_options.log {
+                LevelFilter::Info
+            } else {
+                LevelFilter::Warn
+            });
             let abi_version = generate_options.abi_version.as_ref().map_or(
                 DEFAULT_GENERATE_ABI_VERSION,
                 |version| {
@@ -461,20 +903,147 @@ fn run() -> Result<()> {
                     }
                 },
             );
-            generate::generate_parser_in_directory(
-                &current_dir,
-                generate_options.grammar_path.as_deref(),
+            if generate_options.timing || generate_options.max_memory.is_some() {
+                generate::start_counting_memory();
+            }
+            generate::set_memory_limit(
+                generate_options
+                    .max_memory
+                    .map(|megabytes| megabytes.saturating_mul(1 << 20)),
+            );
+            // When watching, the results of the slowest phases are kept in memory between
+            // generations, unless they're saved in a checkpoint directory.
+            let checkpoints = match &generate_options.checkpoint_dir {
+                Some(dir) => Some(generate::Checkpoints::in_directory(dir)?),
+                None => generate_options
+                    .watch
+                    .then(generate::Checkpoints::in_memory),
+            };
+            let options = generate::GenerateOptions {
                 abi_version,
-                !generate_options.no_bindings,
-                generate_options.report_states_for_rule.as_deref(),
-                generate_options.js_runtime.as_deref(),
-            )?;
-            if generate_options.build {
-                if let Some(path) = generate_options.libdir {
-                    loader = loader::Loader::with_parser_lib_path(PathBuf::from(path));
+                report_symbol_name: generate_options.report_states_for_rule.clone(),
+                auto_inline_threshold: generate_options.inline_threshold,
+                split_lexer: generate_options.split_lexer,
+                freestanding: generate_options.freestanding,
+                dump_tables: generate_options.dump_tables,
+                export_prefix: generate_options.export_prefix.clone(),
+                rust_ast: generate_options.rust_ast,
+                lexer_strategy: generate_options.lexer_strategy.clone(),
+                table_blob: generate_options.table_blob,
+                source_map: generate_options.source_map,
+                railroad: generate_options.railroad,
+                c_standard: generate_options.c_standard.clone(),
+                inline_keyword: generate_options.inline_keyword.clone(),
+                annotate: generate_options.annotate,
+                skeleton_corpus: generate_options.skeleton_corpus,
+                random_programs: generate_options
+                    .random_programs
+                    .map(|count| (count, generate_options.random_seed.unwrap_or(0))),
+                conflict_analysis_budget: generate_options
+                    .conflict_analysis_budget
+                    .map(Duration::from_millis),
+                ..Default::default()
+            };
+            let directory_options = generate::DirectoryOptions {
+                generate_bindings: !generate_options.no_bindings,
+                js_runtime: generate_options.js_runtime.clone(),
+                rename_map_path: generate_options.rename_map.clone(),
+                stable_symbol_ids: generate_options.stable_symbol_ids,
+                symbols_header: generate_options.symbols_header,
+                output_path: generate_options.output.clone(),
+                manifest: generate_options.manifest,
+                banner_path: generate_options.banner.clone(),
+                shared_character_sets_path: generate_options.shared_character_sets.clone(),
+                amalgamate: generate_options.amalgamate,
+                fuzz_harness: generate_options.fuzz_harness,
+                highlights_query: generate_options.highlights_query,
+                timing: generate_options.timing,
+            };
+            let mut generate_parser = || -> Result<()> {
+                if generate_options.strip_annotations {
+                    generate::strip_annotations_in_directory(
+                        &current_dir,
+                        generate_options.output.as_deref(),
+                    )?;
+                } else {
+                    generate::generate_parser_in_directory(
+                        &current_dir,
+                        generate_options.grammar_path.as_deref(),
+                        &options,
+                        &directory_options,
+                        checkpoints.as_ref(),
+                    )?;
+                }
+                if generate_options.build {
+                    if let Some(path) = &generate_options.libdir {
+                        loader = loader::Loader::with_parser_lib_path(PathBuf::from(path));
+                    }
+                    loader.debug_build(generate_options.debug_build);
+                    loader.languages_at_path(&current_dir)?;
+                }
+                Ok(())
+            };
+            if generate_options.watch {
+                generate::watch_grammar(
+                    &current_dir,
+                    generate_options.grammar_path.as_deref(),
+                    generate_parser,
+                )?;
+            } else {
+                generate_parser()?;
+            }
+        }
+
+        Commands::CheckNodeTypes(check_options) => {
+            let new_path = check_options
+                .new_path
+                .unwrap_or_else(|| current_dir.join("src").join("node-types.json"));
+            let old_json = fs::read_to_string(&check_options.old_path)
+                .with_context(|| format!("Failed to read {:?}", check_options.old_path))?;
+            let new_json = fs::read_to_string(&new_path)
+                .with_context(|| format!("Failed to read {new_path:?}"))?;
+            let changes = generate::get_breaking_node_type_changes(&old_json, &new_json)?;
+            if changes.is_empty() {
+                println!("No breaking changes in node types");
+            } else {
+                println!("Breaking changes in node types:");
+                for change in changes {
+                    println!("  {change}");
                 }
-                loader.debug_build(generate_options.debug_build);
-                loader.languages_at_path(&current_dir)?;
+                return Err(anyhow!(""));
+            }
+        }
+
+        Commands::Docs(docs_options) => {
+            let repo_path = docs_options.path.unwrap_or_else(|| current_dir.clone());
+            let src_path = repo_path.join("src");
+            let grammar_json = fs::read_to_string(src_path.join("grammar.json"))
+                .with_context(|| format!("Failed to read {:?}", src_path.join("grammar.json")))?;
+            let node_types_json = fs::read_to_string(src_path.join("node-types.json"))
+                .with_context(|| {
+                    format!("Failed to read {:?}", src_path.join("node-types.json"))
+                })?;
+            let corpus_path = repo_path.join("test").join("corpus");
+            let mut examples = Vec::new();
+            if corpus_path.exists() {
+                collect_docs_examples(test::parse_tests(&corpus_path)?, &mut examples);
+            }
+            let format = if docs_options.html {
+                generate::DocsFormat::Html
+            } else {
+                generate::DocsFormat::Markdown
+            };
+            let docs = generate::render_reference_docs(
+                &grammar_json,
+                &node_types_json,
+                &examples,
+                format,
+            )?;
+            if let Some(output_path) = docs_options.output {
+                fs::write(&output_path, docs)
+                    .with_context(|| format!("Failed to write {output_path:?}"))?;
+            } else {
+                print!("{docs}");
             }
         }
 
@@ -483,11 +1052,8 @@ fn run() -> Result<()> {
 
             if build_options.wasm {
                 let output_path = build_options.output.map(|path| current_dir.join(path));
-                let root_path = lookup_package_json_for_path(&grammar_path.join("package.json"))
-                    .map(|(p, _)| p.parent().unwrap().to_path_buf())?;
-                wasm::compile_language_to_wasm(
+                build_wasm(
                     &loader,
-                    Some(&root_path),
                     &grammar_path,
                     &current_dir,
                     output_path,
@@ -534,10 +1100,24 @@ fn run() -> Result<()> {
 
         Commands::Parse(parse_options) => {
             let config = Config::load(parse_options.config_path)?;
-            let output = if parse_options.output_dot {
+            let output = if let Some(format) = &parse_options.output_format {
+                match format.as_str() {
+                    "sexp" => ParseOutput::Normal,
+                    "json" => ParseOutput::Json,
+                    "xml" => ParseOutput::Xml,
+                    "dot" => ParseOutput::Dot,
+                    _ => {
+                        return Err(anyhow!(
+                            "Invalid output format `{format}`. It must be `sexp`, `json`, `xml` or `dot`."
+                        ))
+                    }
+                }
+            } else if parse_options.output_dot {
                 ParseOutput::Dot
             } else if parse_options.output_xml {
                 ParseOutput::Xml
+            } else if parse_options.json {
+                ParseOutput::Summary
             } else if parse_options.quiet {
                 ParseOutput::Quiet
             } else {
@@ -556,6 +1136,7 @@ fn run() -> Result<()> {
 
             let time = parse_options.time;
             let edits = parse_options.edits.unwrap_or_default();
+            let ranges = parse_options.ranges.unwrap_or_default();
             let cancellation_flag = util::cancel_on_signal();
             let mut parser = Parser::new();
 
@@ -584,7 +1165,10 @@ fn run() -> Result<()> {
                 (paths, language)
             } else {
                 (
-                    collect_paths(parse_options.paths_file.as_deref(), parse_options.paths)?,
+                    collect_files(collect_paths(
+                        parse_options.paths_file.as_deref(),
+                        parse_options.paths,
+                    )?)?,
                     None,
                 )
             };
@@ -593,9 +1177,15 @@ fn run() -> Result<()> {
             let mut has_error = false;
             let loader_config = config.get()?;
             loader.find_all_languages(&loader_config)?;
+            find_project_languages(&mut loader, project_config.as_ref())?;
+            let language = match parse_options.language_name.as_deref() {
+                Some(name) => Some(select_language_by_name(&loader, name)?),
+                None => language,
+            };
 
             let should_track_stats = parse_options.stat;
             let mut stats = parse::Stats::default();
+            let mut summary = parse::Summary::default();
 
             for path in &paths {
                 let path = Path::new(&path);
@@ -616,12 +1206,19 @@ fn run() -> Result<()> {
                         .iter()
                         .map(std::string::String::as_str)
                         .collect::<Vec<&str>>(),
+                    ranges: &ranges
+                        .iter()
+                        .map(std::string::String::as_str)
+                        .collect::<Vec<&str>>(),
                     max_path_length,
                     output,
+                    byte_ranges: parse_options.byte_ranges,
+                    check_edits: parse_options.check_edits,
                     print_time: time,
                     timeout,
                     debug: parse_options.debug,
                     debug_graph: parse_options.debug_graph,
+                    log_ambiguities: parse_options.log_ambiguities,
                     cancellation_flag: Some(&cancellation_flag),
                     encoding,
                     open_log: parse_options.open_log,
@@ -630,14 +1227,10 @@ fn run() -> Result<()> {
                 let parse_result = parse::parse_file_at_path(&mut parser, &opts)?;
 
                 if should_track_stats {
-                    stats.total_parses += 1;
-                    if parse_result.successful {
-                        stats.successful_parses += 1;
-                    }
-                    if let Some(duration) = parse_result.duration {
-                        stats.total_bytes += parse_result.bytes;
-                        stats.total_duration += duration;
-                    }
+                    stats.add(&path.to_string_lossy(), &parse_result);
+                }
+                if parse_options.json {
+                    summary.add(&path.to_string_lossy(), &parse_result);
                 }
 
                 has_error |= !parse_result.successful;
@@ -646,12 +1239,59 @@ fn run() -> Result<()> {
             if should_track_stats {
                 println!("\n{stats}");
             }
+            if parse_options.json {
+                println!("{}", serde_json::to_string_pretty(&summary)?);
+            }
 
             if has_error {
                 return Err(anyhow!(""));
             }
         }
 
+        Commands::Diff(diff_options) => {
+            let config = Config::load(diff_options.config_path)?;
+            let loader_config = config.get()?;
+            loader.find_all_languages(&loader_config)?;
+            find_project_languages(&mut loader, project_config.as_ref())?;
+
+            // The old version is often a temporary file, so both versions are parsed with
+            // the language of the new one.
+            let language = loader.select_language(
+                &diff_options.new_path,
+                &current_dir,
+                diff_options.scope.as_deref(),
+            )?;
+            let mut parser = Parser::new();
+            parser.set_language(&language)?;
+
+            let old_source = fs::read(&diff_options.old_path).with_context(|| {
+                format!("Error reading source file {:?}", diff_options.old_path)
+            })?;
+            let new_source = fs::read(&diff_options.new_path).with_context(|| {
+                format!("Error reading source file {:?}", diff_options.new_path)
+            })?;
+            let old_tree = parser
+                .parse(&old_source, None)
+                .ok_or_else(|| anyhow!("Failed to parse {:?}", diff_options.old_path))?;
+            let new_tree = parser
+                .parse(&new_source, None)
+                .ok_or_else(|| anyhow!("Failed to parse {:?}", diff_options.new_path))?;
+
+            let changes = tree_diff::diff_trees(&old_tree, &old_source, &new_tree, &new_source);
+            if diff_options.json {
+                println!("{}", serde_json::to_string_pretty(&changes)?);
+            } else if changes.is_empty() {
+                println!("No structural changes");
+            } else {
+                for change in &changes {
+                    println!("{}", change.render(color));
+                }
+            }
+            if !changes.is_empty() {
+                return Err(anyhow!(""));
+            }
+        }
+
         Commands::Test(test_options) => {
             let config = Config::load(test_options.config_path)?;
 
@@ -668,7 +1308,13 @@ fn run() -> Result<()> {
                 loader.use_wasm(&engine);
             }
 
-            let languages = loader.languages_at_path(&current_dir)?;
+            let languages = if let Some(name) = test_options.language_name.as_deref() {
+                loader.find_all_languages(&config.get()?)?;
+                find_project_languages(&mut loader, project_config.as_ref())?;
+                vec![(select_language_by_name(&loader, name)?, name.to_string())]
+            } else {
+                loader.languages_at_path(&current_dir)?
+            };
             let language = &languages
                 .first()
                 .ok_or_else(|| anyhow!("No language found"))?
@@ -676,57 +1322,94 @@ fn run() -> Result<()> {
             parser.set_language(language)?;
 
             let test_dir = current_dir.join("test");
+            let json = test_options.json;
+            let mut summary = test::Summary::default();
+
+            let run_tests = |summary: &mut test::Summary| -> Result<()> {
+                // Run the corpus tests. Look for them in `test/corpus`.
+                let test_corpus_dir = test_dir.join("corpus");
+                if test_corpus_dir.is_dir() {
+                    let mut opts = TestOptions {
+                        path: test_corpus_dir,
+                        debug: test_options.debug,
+                        debug_graph: test_options.debug_graph,
+                        include: test_options.include,
+                        exclude: test_options.exclude,
+                        update: test_options.update,
+                        open_log: test_options.open_log,
+                        languages: languages.iter().map(|(l, n)| (n.as_str(), l)).collect(),
+                        color,
+                        test_num: 1,
+                        show_fields: test_options.show_fields,
+                        jobs: test_options.jobs.unwrap_or(1),
+                        quiet: test_options.quiet || json,
+                        json,
+                    };
+
+                    test::run_tests_at_path(&mut parser, &mut opts, summary)?;
+                }
 
-            // Run the corpus tests. Look for them in `test/corpus`.
-            let test_corpus_dir = test_dir.join("corpus");
-            if test_corpus_dir.is_dir() {
-                let mut opts = TestOptions {
-                    path: test_corpus_dir,
-                    debug: test_options.debug,
-                    debug_graph: test_options.debug_graph,
-                    include: test_options.include,
-                    exclude: test_options.exclude,
-                    update: test_options.update,
-                    open_log: test_options.open_log,
-                    languages: languages.iter().map(|(l, n)| (n.as_str(), l)).collect(),
-                    color,
-                    test_num: 1,
-                    show_fields: test_options.show_fields,
-                };
+                // Check that all of the queries are valid.
+                test::check_queries_at_path(language, &current_dir.join("queries"))?;
 
-                test::run_tests_at_path(&mut parser, &mut opts)?;
-            }
+                let mut out: Box<dyn io::Write> = if test_options.quiet || json {
+                    Box::new(io::sink())
+                } else {
+                    Box::new(io::stdout())
+                };
 
-            // Check that all of the queries are valid.
-            test::check_queries_at_path(language, &current_dir.join("queries"))?;
+                // Run the syntax highlighting tests.
+                let test_highlight_dir = test_dir.join("highlight");
+                #[cfg(feature = "highlight")]
+                if test_highlight_dir.is_dir() {
+                    let mut highlighter = Highlighter::new();
+                    highlighter.parser = parser;
+                    let result = test_highlight::test_highlights(
+                        &loader,
+                        &config.get()?,
+                        &mut highlighter,
+                        &test_highlight_dir,
+                        color,
+                        &mut out,
+                    );
+                    summary.highlight_tests_passed = Some(result.is_ok());
+                    result?;
+                    parser = highlighter.parser;
+                }
+                #[cfg(not(feature = "highlight"))]
+                if test_highlight_dir.is_dir() {
+                    eprintln!("Warning: Skipping the highlight tests, because this build of the CLI doesn't include the `highlight` feature");
+                }
 
-            // Run the syntax highlighting tests.
-            let test_highlight_dir = test_dir.join("highlight");
-            if test_highlight_dir.is_dir() {
-                let mut highlighter = Highlighter::new();
-                highlighter.parser = parser;
-                test_highlight::test_highlights(
-                    &loader,
-                    &config.get()?,
-                    &mut highlighter,
-                    &test_highlight_dir,
-                    color,
-                )?;
-                parser = highlighter.parser;
-            }
+                let test_tag_dir = test_dir.join("tags");
+                if test_tag_dir.is_dir() {
+                    let mut tags_context = TagsContext::new();
+                    tags_context.parser = parser;
+                    let result = test_tags::test_tags(
+                        &loader,
+                        &config.get()?,
+                        &mut tags_context,
+                        &test_tag_dir,
+                        color,
+                        &mut out,
+                    );
+                    summary.tag_tests_passed = Some(result.is_ok());
+                    result?;
+                }
+                Ok(())
+            };
 
-            let test_tag_dir = test_dir.join("tags");
-            if test_tag_dir.is_dir() {
-                let mut tags_context = TagsContext::new();
-                tags_context.parser = parser;
-                test_tags::test_tags(
-                    &loader,
-                    &config.get()?,
-                    &mut tags_context,
-                    &test_tag_dir,
-                    color,
-                )?;
+            let result = run_tests(&mut summary);
+            if json {
+                summary.successful = result.is_ok();
+                summary.error = result
+                    .as_ref()
+                    .err()
+                    .map(ToString::to_string)
+                    .filter(|error| !error.is_empty());
+                println!("{}", serde_json::to_string_pretty(&summary)?);
             }
+            result?;
         }
 
         Commands::Fuzz(fuzz_options) => {
@@ -746,6 +1429,7 @@ fn run() -> Result<()> {
                 exclude: fuzz_options.exclude,
                 log_graphs: fuzz_options.log_graphs || *LOG_GRAPH_ENABLED,
                 log: fuzz_options.log || *LOG_ENABLED,
+                save_failures: fuzz_options.save_failures,
             };
 
             fuzz_language_corpus(
@@ -757,11 +1441,72 @@ fn run() -> Result<()> {
             );
         }
 
+        Commands::Benchmark(benchmark_options) => {
+            let config = Config::load(benchmark_options.config_path)?;
+            let loader_config = config.get()?;
+            loader.find_all_languages(&loader_config)?;
+            find_project_languages(&mut loader, project_config.as_ref())?;
+
+            let baseline = benchmark_options
+                .baseline
+                .as_ref()
+                .map(|path| -> Result<Vec<benchmark::FileBenchmark>> {
+                    let json = fs::read_to_string(path)
+                        .with_context(|| format!("Failed to read baseline {path:?}"))?;
+                    serde_json::from_str(&json)
+                        .with_context(|| format!("Failed to parse baseline {path:?}"))
+                })
+                .transpose()?;
+
+            let options = benchmark::BenchmarkOptions {
+                iterations: benchmark_options.iterations.unwrap_or(10),
+                edits: benchmark_options.edits.unwrap_or(10),
+            };
+            let paths = collect_files(collect_paths(
+                benchmark_options.paths_file.as_deref(),
+                benchmark_options.paths,
+            )?)?;
+            let mut parser = Parser::new();
+            let mut results = Vec::new();
+            for path in &paths {
+                let path = Path::new(path);
+                let language = loader.select_language(
+                    path,
+                    &current_dir,
+                    benchmark_options.scope.as_deref(),
+                )?;
+                results.push(benchmark::benchmark_file(
+                    &mut parser,
+                    &language,
+                    path,
+                    &options,
+                )?);
+            }
+
+            benchmark::print_results(&results, baseline.as_deref());
+            if let Some(path) = &benchmark_options.save_baseline {
+                fs::write(path, serde_json::to_string_pretty(&results)?)
+                    .with_context(|| format!("Failed to write baseline {path:?}"))?;
+            }
+            if let (Some(baseline), Some(max_regression)) =
+                (&baseline, benchmark_options.max_regression)
+            {
+                let regressions = benchmark::find_regressions(&results, baseline, max_regression);
+                if !regressions.is_empty() {
+                    return Err(anyhow!(
+                        "Parsing got slower than the baseline allows:\n  {}",
+                        regressions.join("\n  ")
+                    ));
+                }
+            }
+        }
+
         Commands::Query(query_options) => {
             let config = Config::load(query_options.config_path)?;
             let paths = collect_paths(query_options.paths_file.as_deref(), query_options.paths)?;
             let loader_config = config.get()?;
             loader.find_all_languages(&loader_config)?;
+            find_project_languages(&mut loader, project_config.as_ref())?;
             let language = loader.select_language(
                 Path::new(&paths[0]),
                 &current_dir,
@@ -795,12 +1540,14 @@ fn run() -> Result<()> {
             )?;
         }
 
+        #[cfg(feature = "highlight")]
         Commands::Highlight(highlight_options) => {
             let config = Config::load(highlight_options.config_path)?;
-            let theme_config: tree_sitter_cli::highlight::ThemeConfig = config.get()?;
+            let theme_config: highlight::ThemeConfig = config.get()?;
             loader.configure_highlights(&theme_config.theme.highlight_names);
             let loader_config = config.get()?;
             loader.find_all_languages(&loader_config)?;
+            find_project_languages(&mut loader, project_config.as_ref())?;
 
             let quiet = highlight_options.quiet;
             let html_mode = quiet || highlight_options.html;
@@ -822,6 +1569,14 @@ fn run() -> Result<()> {
                     return Err(anyhow!("Unknown scope '{scope}'"));
                 }
             }
+            if let Some(name) = highlight_options.language_name.as_deref() {
+                language = loader.language_configuration_for_name(name)?;
+                if language.is_none() {
+                    return Err(anyhow!(
+                        "Unknown language '{name}'. It must be the name of a grammar in one of your parser directories."
+                    ));
+                }
+            }
 
             for path in paths {
                 let path = Path::new(&path);
@@ -830,6 +1585,10 @@ fn run() -> Result<()> {
                     None => {
                         if let Some(v) = loader.language_configuration_for_file_name(path)? {
                             v
+                        } else if let Some(v) =
+                            loader.language_configuration_for_first_line_regex(path)?
+                        {
+                            v
                         } else {
                             eprintln!("{}", util::lang_not_found_for_path(path, &loader_config));
                             continue;
@@ -909,6 +1668,17 @@ fn run() -> Result<()> {
             let config = Config::load(tags_options.config_path)?;
             let loader_config = config.get()?;
             loader.find_all_languages(&loader_config)?;
+            find_project_languages(&mut loader, project_config.as_ref())?;
+            let output = match tags_options.output_format.as_deref() {
+                None | Some("text") => tags::TagsOutput::Text,
+                Some("json") => tags::TagsOutput::Json,
+                Some("ctags") => tags::TagsOutput::Ctags,
+                Some(format) => {
+                    return Err(anyhow!(
+                        "Invalid output format `{format}`. It must be `text`, `json` or `ctags`."
+                    ))
+                }
+            };
             let paths = collect_paths(tags_options.paths_file.as_deref(), tags_options.paths)?;
             tags::generate_tags(
                 &loader,
@@ -917,9 +1687,11 @@ fn run() -> Result<()> {
                 &paths,
                 tags_options.quiet,
                 tags_options.time,
+                output,
             )?;
         }
 
+        #[cfg(feature = "playground")]
         Commands::Playground(playground_options) => {
             let open_in_browser = !playground_options.quiet;
             let grammar_path = playground_options
@@ -928,24 +1700,44 @@ fn run() -> Result<()> {
             playground::serve(&grammar_path, open_in_browser)?;
         }
 
+        Commands::Lsp(lsp_options) => {
+            let grammar_path = lsp_options
+                .grammar_path
+                .unwrap_or_else(|| generate::default_grammar_path(&current_dir));
+            lsp::run(&grammar_path, lsp_options.js_runtime.as_deref())?;
+        }
+
         Commands::DumpLanguages(dump_options) => {
             let config = Config::load(dump_options.config_path)?;
             let loader_config = config.get()?;
             loader.find_all_languages(&loader_config)?;
+            find_project_languages(&mut loader, project_config.as_ref())?;
             for (configuration, language_path) in loader.get_all_language_configurations() {
                 println!(
                     concat!(
+                        "name: {}\n",
                         "scope: {}\n",
                         "parser: {:?}\n",
+                        "version: {}\n",
+                        "abi: {}\n",
                         "highlights: {:?}\n",
                         "file_types: {:?}\n",
+                        "first_line_regex: {:?}\n",
                         "content_regex: {:?}\n",
                         "injection_regex: {:?}\n",
                     ),
+                    configuration.language_name,
                     configuration.scope.as_ref().unwrap_or(&String::new()),
                     language_path,
+                    grammar_version(language_path)
+                        .or_else(|| grammar_version(&configuration.root_path))
+                        .unwrap_or_default(),
+                    generated_abi_version(language_path)
+                        .map(|version| version.to_string())
+                        .unwrap_or_default(),
                     configuration.highlights_filenames,
                     configuration.file_types,
+                    configuration.first_line_regex,
                     configuration.content_regex,
                     configuration.injection_regex,
                 );
@@ -988,6 +1780,161 @@ const fn get_styles() -> clap::builder::Styles {
         .placeholder(Style::new().fg_color(Some(Color::Ansi(AnsiColor::White))))
 }
 
+/// The command line arguments, with the default arguments that the project's `tree-sitter.json`
+/// sets for the subcommand inserted after the subcommand's name, so that the arguments that are
+/// passed explicitly take precedence.
+fn args_with_project_defaults(
+    cli: &Command,
+    project_config: Option<&ProjectConfig>,
+) -> Result<Vec<OsString>> {
+    #[derive(Deserialize)]
+    struct ProjectDefaults {
+        #[serde(default)]
+        defaults: HashMap<String, Vec<String>>,
+    }
+
+    let mut args = env::args_os().collect::<Vec<_>>();
+    let Some(project_config) = project_config else {
+        return Ok(args);
+    };
+    let ProjectDefaults { defaults } = project_config.get()?;
+    let subcommand = args
+        .get(1)
+        .and_then(|arg| arg.to_str())
+        .and_then(|name| cli.find_subcommand(name));
+    if let Some(default_args) = subcommand.and_then(|command| defaults.get(command.get_name())) {
+        args.splice(2..2, default_args.iter().map(OsString::from));
+    }
+    Ok(args)
+}
+
+/// Find the grammars that the project's `tree-sitter.json` lists, in addition to the ones in
+/// the configured parser directories.
+fn find_project_languages(
+    loader: &mut loader::Loader,
+    project_config: Option<&ProjectConfig>,
+) -> Result<()> {
+    if let Some(project_config) = project_config {
+        loader
+            .find_language_configurations_at_path(project_config.root(), false)
+            .with_context(|| {
+                format!(
+                    "Failed to load the grammars listed in {:?}",
+                    project_config.location
+                )
+            })?;
+    }
+    Ok(())
+}
+
+/// Load an installed language by its name, for the `--language` option.
+fn select_language_by_name(loader: &loader::Loader, name: &str) -> Result<Language> {
+    loader
+        .language_for_name(name)
+        .with_context(|| format!("Failed to load language '{name}'"))?
+        .ok_or_else(|| {
+            anyhow!(
+                "Unknown language '{name}'. It must be the name of a grammar in one of your parser directories, or of a library in one of your library directories."
+            )
+        })
+}
+
+#[cfg(feature = "playground")]
+fn build_wasm(
+    loader: &loader::Loader,
+    grammar_path: &Path,
+    output_dir: &Path,
+    output_path: Option<PathBuf>,
+    force_docker: bool,
+) -> Result<()> {
+    let root_path = generate::lookup_package_json_for_path(&grammar_path.join("package.json"))
+        .map(|(p, _)| p.parent().unwrap().to_path_buf())?;
+    wasm::compile_language_to_wasm(
+        loader,
+        Some(&root_path),
+        grammar_path,
+        output_dir,
+        output_path,
+        force_docker,
+    )
+}
+
+#[cfg(not(feature = "playground"))]
+fn build_wasm(_: &loader::Loader, _: &Path, _: &Path, _: Option<PathBuf>, _: bool) -> Result<()> {
+    Err(anyhow!(
+        "This build of the CLI can't build WASM modules, because it doesn't include the `playground` feature"
+    ))
+}
+
+/// The version of the grammar's package, from its `package.json`, or else its `Cargo.toml`.
+fn grammar_version(root_path: &Path) -> Option<String> {
+    #[derive(Deserialize)]
+    struct PackageJSON {
+        version: String,
+    }
+
+    #[derive(Deserialize)]
+    struct CargoToml {
+        package: PackageJSON,
+    }
+
+    fs::read_to_string(root_path.join("package.json"))
+        .ok()
+        .and_then(|contents| serde_json::from_str::<PackageJSON>(&contents).ok())
+        .or_else(|| {
+            let contents = fs::read_to_string(root_path.join("Cargo.toml")).ok()?;
+            toml::from_str::<CargoToml>(&contents)
+                .ok()
+                .map(|cargo_toml| cargo_toml.package)
+        })
+        .map(|package| package.version)
+}
+
+/// The ABI version of the parser that was generated in the language's `src` directory, which
+/// is read from the generated code, so that the parser doesn't need to be compiled.
+fn generated_abi_version(language_path: &Path) -> Option<usize> {
+    let parser_c = fs::read_to_string(language_path.join("src").join("parser.c")).ok()?;
+    parser_c.lines().find_map(|line| {
+        line.strip_prefix("#define LANGUAGE_VERSION ")?
+            .trim()
+            .parse()
+            .ok()
+    })
+}
+
+/// Replace each directory in the given paths with the files inside of it, recursively,
+/// skipping hidden files and directories.
+fn collect_files(paths: Vec<String>) -> Result<Vec<String>> {
+    fn helper(dir: &Path, result: &mut Vec<String>) -> Result<()> {
+        let mut entries = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory {dir:?}"))?
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(fs::DirEntry::file_name);
+        for entry in entries {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            if path.is_dir() {
+                helper(&path, result)?;
+            } else if let Some(path) = path.to_str() {
+                result.push(path.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    let mut result = Vec::new();
+    for path in paths {
+        if Path::new(&path).is_dir() {
+            helper(Path::new(&path), &mut result)?;
+        } else {
+            result.push(path);
+        }
+    }
+    Ok(result)
+}
+
 fn collect_paths(paths_file: Option<&str>, paths: Option<Vec<String>>) -> Result<Vec<String>> {
     if let Some(paths_file) = paths_file {
         return Ok(fs::read_to_string(paths_file)
@@ -1040,3 +1987,30 @@ fn collect_paths(paths_file: Option<&str>, paths: Option<Vec<String>>) -> Result
 
     Err(anyhow!("Must provide one or more paths"))
 }
+
+/// The examples in a test corpus that are expected to parse without errors, for
+/// illustrating a grammar's reference.
+fn collect_docs_examples(entry: test::TestEntry, examples: &mut Vec<generate::DocsExample>) {
+    match entry {
+        test::TestEntry::Group { children, .. } => {
+            for child in children {
+                collect_docs_examples(child, examples);
+            }
+        }
+        test::TestEntry::Example {
+            name,
+            input,
+            output,
+            attributes,
+            ..
+        } => {
+            if !attributes.error && !attributes.skip {
+                examples.push(generate::DocsExample {
+                    name,
+                    source: String::from_utf8_lossy(&input).into_owned(),
+                    tree: output,
+                });
+            }
+        }
+    }
+}
@@ -7,10 +7,17 @@ use std::{
 };
 
 use anyhow::{anyhow, Context, Result};
-use tree_sitter::{ffi, InputEdit, Language, LogType, Parser, Point, Tree};
+use serde::Serialize;
+use tree_sitter::{ffi, InputEdit, Language, LogType, Parser, Point, Range, Tree, TreeCursor};
 
 use super::util;
-use crate::fuzz::edits::Edit;
+use crate::{
+    fuzz::edits::Edit,
+    test::{print_diff, print_diff_key},
+};
+
+/// How many of the slowest files are listed in the statistics.
+const SLOWEST_FILE_COUNT: usize = 5;
 
 #[derive(Debug, Default)]
 pub struct Stats {
@@ -18,6 +25,70 @@ pub struct Stats {
     pub total_parses: usize,
     pub total_bytes: usize,
     pub total_duration: Duration,
+    pub total_error_nodes: usize,
+    pub slowest_files: Vec<(String, Duration)>,
+}
+
+impl Stats {
+    pub fn add(&mut self, path: &str, result: &ParseResult) {
+        self.total_parses += 1;
+        if result.successful {
+            self.successful_parses += 1;
+        }
+        self.total_error_nodes += result.error_nodes;
+        if let Some(duration) = result.duration {
+            self.total_bytes += result.bytes;
+            self.total_duration += duration;
+
+            let index = self
+                .slowest_files
+                .partition_point(|(_, slow_duration)| *slow_duration >= duration);
+            if index < SLOWEST_FILE_COUNT {
+                self.slowest_files
+                    .insert(index, (path.to_string(), duration));
+                self.slowest_files.truncate(SLOWEST_FILE_COUNT);
+            }
+        }
+    }
+}
+
+/// A machine-readable summary of the parsed files, printed by `tree-sitter parse --json`.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub successful_parses: usize,
+    pub failed_parses: usize,
+    pub files: Vec<FileSummary>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FileSummary {
+    pub path: String,
+    pub successful: bool,
+    pub bytes: usize,
+    pub nodes: usize,
+    pub error_nodes: usize,
+    /// The parse time in milliseconds, or `None` if the parse timed out.
+    pub duration_ms: Option<f64>,
+}
+
+impl Summary {
+    pub fn add(&mut self, path: &str, result: &ParseResult) {
+        if result.successful {
+            self.successful_parses += 1;
+        } else {
+            self.failed_parses += 1;
+        }
+        self.files.push(FileSummary {
+            path: path.to_string(),
+            successful: result.successful,
+            bytes: result.bytes,
+            nodes: result.nodes,
+            error_nodes: result.error_nodes,
+            duration_ms: result
+                .duration
+                .map(|duration| duration.as_micros() as f64 / 1e3),
+        });
+    }
 }
 
 impl fmt::Display for Stats {
@@ -35,7 +106,19 @@ impl fmt::Display for Stats {
             } else {
                 0
             }
-        )
+        )?;
+        writeln!(
+            f,
+            "Total error and missing nodes: {}",
+            self.total_error_nodes
+        )?;
+        if !self.slowest_files.is_empty() {
+            writeln!(f, "Slowest files:")?;
+            for (path, duration) in &self.slowest_files {
+                writeln!(f, "  {:>9.2} ms\t{path}", duration.as_micros() as f64 / 1e3)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -44,19 +127,27 @@ pub enum ParseOutput {
     Normal,
     Quiet,
     Xml,
+    Json,
     Dot,
+    /// Print nothing for each file, not even its first error, so that the caller can print
+    /// a summary of all of the files instead.
+    Summary,
 }
 
 pub struct ParseFileOptions<'a> {
     pub language: Language,
     pub path: &'a Path,
     pub edits: &'a [&'a str],
+    pub ranges: &'a [&'a str],
     pub max_path_length: usize,
     pub output: ParseOutput,
+    pub byte_ranges: bool,
+    pub check_edits: bool,
     pub print_time: bool,
     pub timeout: u64,
     pub debug: bool,
     pub debug_graph: bool,
+    pub log_ambiguities: bool,
     pub cancellation_flag: Option<&'a AtomicUsize>,
     pub encoding: Option<u32>,
     pub open_log: bool,
@@ -66,9 +157,100 @@ pub struct ParseFileOptions<'a> {
 pub struct ParseResult {
     pub successful: bool,
     pub bytes: usize,
+    pub nodes: usize,
+    pub error_nodes: usize,
     pub duration: Option<Duration>,
 }
 
+/// Follows the parser's debug log to report where it pursued several alternatives at once,
+/// because of a conflict that the grammar declares, and how it chose between them.
+#[derive(Default)]
+struct AmbiguityLog {
+    version: usize,
+    version_count: usize,
+    position: Point,
+    reductions: Vec<String>,
+}
+
+impl AmbiguityLog {
+    fn handle(&mut self, message: &str) -> Option<String> {
+        let (event, fields) = message.split_once(' ').unwrap_or((message, ""));
+        let field = |name: &str| {
+            fields.split(", ").find_map(|field| {
+                field
+                    .strip_prefix(name)
+                    .and_then(|field| field.strip_prefix(':'))
+            })
+        };
+        let number = |name: &str| field(name).and_then(|value| value.parse::<usize>().ok());
+
+        match event {
+            "process" => {
+                let version_count = number("version_count")?;
+                let previous_count = self.version_count;
+                let reductions = std::mem::take(&mut self.reductions);
+                self.version = number("version")?;
+                self.version_count = version_count;
+                let position = self.position;
+                self.position = Point::new(number("row")?, number("col")?);
+                if previous_count == 1 && version_count > 1 {
+                    let mut line = format!(
+                        "{}: split into {version_count} versions",
+                        point_to_string(position)
+                    );
+                    if !reductions.is_empty() {
+                        line += &format!(" after reducing {}", reductions.join(", "));
+                    }
+                    Some(line)
+                } else if previous_count > 1 && version_count == 1 {
+                    Some(format!(
+                        "{}: merged back into one version",
+                        point_to_string(self.position)
+                    ))
+                } else {
+                    None
+                }
+            }
+            "reduce" => {
+                self.reductions.push(format!("`{}`", field("sym")?));
+                None
+            }
+            "detect_error" if self.version_count > 1 => Some(format!(
+                "  version {} hit a syntax error at {}",
+                self.version,
+                point_to_string(self.position)
+            )),
+            "select_smaller_error" => Some(format!(
+                "  chose `{}` over `{}`, which has more errors",
+                field("symbol")?,
+                field("over_symbol")?
+            )),
+            "select_higher_precedence" => Some(format!(
+                "  chose `{}` over `{}` by dynamic precedence ({} > {})",
+                field("symbol")?,
+                field("over_symbol")?,
+                field("prec")?,
+                field("other_prec")?
+            )),
+            "select_earlier" => Some(format!(
+                "  chose `{}` over `{}`, as neither has a higher dynamic precedence",
+                field("symbol")?,
+                field("over_symbol")?
+            )),
+            "select_existing" => Some(format!(
+                "  kept `{}` over the equivalent `{}`",
+                field("symbol")?,
+                field("over_symbol")?
+            )),
+            _ => None,
+        }
+    }
+}
+
+fn point_to_string(point: Point) -> String {
+    format!("{}:{}", point.row, point.column)
+}
+
 pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Result<ParseResult> {
     let mut _log_session = None;
     parser.set_language(&opts.language)?;
@@ -82,10 +264,37 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
     // Set a timeout based on the `--time` flag.
     parser.set_timeout_micros(opts.timeout);
 
+    // Only parse the given ranges of the file if `--range` was passed, and the whole
+    // file otherwise.
+    let included_ranges = opts
+        .ranges
+        .iter()
+        .map(|range| parse_range_flag(&source_code, range))
+        .collect::<Result<Vec<_>>>()?;
+    parser
+        .set_included_ranges(&included_ranges)
+        .map_err(|error| {
+            anyhow!(
+                "Invalid range '{}': the ranges must be in order and must not overlap",
+                opts.ranges[error.0]
+            )
+        })?;
+
     // Render an HTML graph if `--debug-graph` was passed
     if opts.debug_graph {
         _log_session = Some(util::log_graphs(parser, "log.html", opts.open_log)?);
     }
+    // Log the parser's ambiguities to stderr if `--log-ambiguities` was passed
+    else if opts.log_ambiguities {
+        let mut log = AmbiguityLog::default();
+        parser.set_logger(Some(Box::new(move |log_type, message| {
+            if log_type == LogType::Parse {
+                if let Some(line) = log.handle(message) {
+                    eprintln!("{line}");
+                }
+            }
+        })));
+    }
     // Log to stderr if `--debug` was passed
     else if opts.debug {
         parser.set_logger(Some(Box::new(|log_type, message| {
@@ -143,6 +352,28 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
 
         let duration = time.elapsed();
         let duration_ms = duration.as_micros() as f64 / 1e3;
+
+        // Check that parsing the edited text incrementally gave the same tree as parsing
+        // it from scratch.
+        if opts.check_edits && !opts.edits.is_empty() {
+            let fresh_tree = parser.parse(&source_code, None).unwrap();
+            let incremental_outline = tree_outline(&tree);
+            let fresh_outline = tree_outline(&fresh_tree);
+            if incremental_outline != fresh_outline {
+                println!(
+                    "The incremental parse of {} after the edits differs from a parse from scratch:",
+                    opts.path.display()
+                );
+                print_diff_key();
+                print_diff(&incremental_outline, &fresh_outline, true);
+                println!();
+                return Err(anyhow!(
+                    "Incremental parsing of {:?} gave an unexpected tree",
+                    opts.path
+                ));
+            }
+        }
+
         let mut cursor = tree.walk();
 
         if opts.output == ParseOutput::Normal {
@@ -187,6 +418,14 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
                             end.row,
                             end.column
                         )?;
+                        if opts.byte_ranges {
+                            write!(
+                                &mut stdout,
+                                " {{{}..{}}}",
+                                node.start_byte(),
+                                node.end_byte()
+                            )?;
+                        }
                         needs_newline = true;
                     }
                     if cursor.goto_first_child() {
@@ -295,6 +534,12 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
             println!();
         }
 
+        if opts.output == ParseOutput::Json {
+            let json_tree = json_node(&mut cursor, &source_code);
+            writeln!(&mut stdout, "{}", serde_json::to_string_pretty(&json_tree)?)?;
+            cursor.reset(tree.root_node());
+        }
+
         if opts.output == ParseOutput::Dot {
             util::print_tree_graph(&tree, "log.html", opts.open_log).unwrap();
         }
@@ -315,12 +560,13 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
             }
         }
 
-        if first_error.is_some() || opts.print_time {
+        if opts.output != ParseOutput::Summary && (first_error.is_some() || opts.print_time) {
             write!(
                 &mut stdout,
-                "{:width$}\t{duration_ms:>7.2} ms\t{:>6} bytes/ms",
+                "{:width$}\t{duration_ms:>7.2} ms\t{:>6} bytes/ms\t{:>6} nodes",
                 opts.path.to_str().unwrap(),
                 (source_code.len() as u128 * 1_000_000) / duration.as_nanos(),
+                tree.root_node().descendant_count(),
                 width = opts.max_path_length
             )?;
             if let Some(node) = first_error {
@@ -352,11 +598,13 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
         return Ok(ParseResult {
             successful: first_error.is_none(),
             bytes: source_code.len(),
+            nodes: tree.root_node().descendant_count(),
+            error_nodes: count_error_nodes(&tree),
             duration: Some(duration),
         });
     }
 
-    if opts.print_time {
+    if opts.output != ParseOutput::Summary && opts.print_time {
         let duration = time.elapsed();
         let duration_ms = duration.as_micros() as f64 / 1e3;
         writeln!(
@@ -370,10 +618,132 @@ pub fn parse_file_at_path(parser: &mut Parser, opts: &ParseFileOptions) -> Resul
     Ok(ParseResult {
         successful: false,
         bytes: source_code.len(),
+        nodes: 0,
+        error_nodes: 0,
         duration: None,
     })
 }
 
+/// Count the `ERROR` and `MISSING` nodes in the tree, only descending into the nodes
+/// that contain errors.
+fn count_error_nodes(tree: &Tree) -> usize {
+    let mut count = 0;
+    let mut cursor = tree.walk();
+    loop {
+        let node = cursor.node();
+        if node.is_error() || node.is_missing() {
+            count += 1;
+        }
+        if node.has_error() && cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return count;
+            }
+        }
+    }
+}
+
+/// Describe every node in the tree, including the anonymous ones, with its byte range,
+/// one node per line.
+fn tree_outline(tree: &Tree) -> String {
+    let mut outline = String::new();
+    let mut cursor = tree.walk();
+    let mut depth = 0;
+    loop {
+        let node = cursor.node();
+        outline += &"  ".repeat(depth);
+        if let Some(field_name) = cursor.field_name() {
+            outline += &format!("{field_name}: ");
+        }
+        if node.is_missing() {
+            outline += "MISSING ";
+        }
+        outline += &format!(
+            "{:?} [{}..{}]\n",
+            node.kind(),
+            node.start_byte(),
+            node.end_byte()
+        );
+
+        if cursor.goto_first_child() {
+            depth += 1;
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                return outline;
+            }
+            depth -= 1;
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct JsonNode<'a> {
+    #[serde(rename = "type")]
+    kind: &'a str,
+    named: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<&'a str>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    missing: bool,
+    start_byte: usize,
+    end_byte: usize,
+    start_point: JsonPoint,
+    end_point: JsonPoint,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<JsonNode<'a>>,
+}
+
+#[derive(Serialize)]
+struct JsonPoint {
+    row: usize,
+    column: usize,
+}
+
+impl From<Point> for JsonPoint {
+    fn from(point: Point) -> Self {
+        Self {
+            row: point.row,
+            column: point.column,
+        }
+    }
+}
+
+/// Describe the cursor's current node, and all of its descendants, including the
+/// anonymous ones. Leaf nodes also include their text.
+fn json_node<'a>(cursor: &mut TreeCursor<'a>, source_code: &[u8]) -> JsonNode<'a> {
+    let node = cursor.node();
+    let mut children = Vec::new();
+    if cursor.goto_first_child() {
+        loop {
+            children.push(json_node(cursor, source_code));
+            if !cursor.goto_next_sibling() {
+                break;
+            }
+        }
+        cursor.goto_parent();
+    }
+    JsonNode {
+        kind: node.kind(),
+        named: node.is_named(),
+        field: cursor.field_name(),
+        missing: node.is_missing(),
+        start_byte: node.start_byte(),
+        end_byte: node.end_byte(),
+        start_point: node.start_position().into(),
+        end_point: node.end_position().into(),
+        text: children.is_empty().then(|| {
+            String::from_utf8_lossy(&source_code[node.start_byte()..node.end_byte()]).to_string()
+        }),
+        children,
+    }
+}
+
 pub fn perform_edit(tree: &mut Tree, input: &mut Vec<u8>, edit: &Edit) -> Result<InputEdit> {
     let start_byte = edit.position;
     let old_end_byte = edit.position + edit.deleted_length;
@@ -411,19 +781,7 @@ fn parse_edit_flag(source_code: &[u8], flag: &str) -> Result<Edit> {
     let deleted_length = parts.next().ok_or_else(error)?;
     let inserted_text = parts.collect::<Vec<_>>().join(" ").into_bytes();
 
-    // Position can either be a byte_offset or row,column pair, separated by a comma
-    let position = if position == "$" {
-        source_code.len()
-    } else if position.contains(',') {
-        let mut parts = position.split(',');
-        let row = parts.next().ok_or_else(error)?;
-        let row = row.parse::<usize>().map_err(|_| error())?;
-        let column = parts.next().ok_or_else(error)?;
-        let column = column.parse::<usize>().map_err(|_| error())?;
-        offset_for_position(source_code, Point { row, column })?
-    } else {
-        position.parse::<usize>().map_err(|_| error())?
-    };
+    let position = parse_position(source_code, position, error)?;
 
     // Deleted length must be a byte count.
     let deleted_length = deleted_length.parse::<usize>().map_err(|_| error())?;
@@ -435,6 +793,51 @@ fn parse_edit_flag(source_code: &[u8], flag: &str) -> Result<Edit> {
     })
 }
 
+/// Parse a range flag of the form `<START>..<END>`, where both ends are positions like
+/// those of an edit.
+fn parse_range_flag(source_code: &[u8], flag: &str) -> Result<Range> {
+    let error = || {
+        anyhow!(concat!(
+            "Invalid range string '{}'. ",
+            "Range strings must match the pattern '<START_BYTE_OR_POSITION>..<END_BYTE_OR_POSITION>'"
+        ), flag)
+    };
+
+    let (start, end) = flag.split_once("..").ok_or_else(error)?;
+    let start_byte = parse_position(source_code, start, error)?;
+    let end_byte = parse_position(source_code, end, error)?;
+    if start_byte > end_byte || end_byte > source_code.len() {
+        return Err(error());
+    }
+    Ok(Range {
+        start_byte,
+        end_byte,
+        start_point: position_for_offset(source_code, start_byte)?,
+        end_point: position_for_offset(source_code, end_byte)?,
+    })
+}
+
+/// Parse a position, which can either be a byte offset, a row,column pair separated by a
+/// comma, or `$` for the end of the file.
+fn parse_position(
+    source_code: &[u8],
+    position: &str,
+    error: impl Fn() -> anyhow::Error,
+) -> Result<usize> {
+    if position == "$" {
+        Ok(source_code.len())
+    } else if position.contains(',') {
+        let mut parts = position.split(',');
+        let row = parts.next().ok_or_else(&error)?;
+        let row = row.parse::<usize>().map_err(|_| error())?;
+        let column = parts.next().ok_or_else(&error)?;
+        let column = column.parse::<usize>().map_err(|_| error())?;
+        offset_for_position(source_code, Point { row, column })
+    } else {
+        position.parse::<usize>().map_err(|_| error())
+    }
+}
+
 pub fn offset_for_position(input: &[u8], position: Point) -> Result<usize> {
     let mut row = 0;
     let mut offset = 0;
@@ -480,3 +883,61 @@ pub fn position_for_offset(input: &[u8], offset: usize) -> Result<Point> {
     };
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range_flag() {
+        let source_code = b"<p>\n<script>let a = 1;</script>\n</p>\n";
+        assert_eq!(
+            parse_range_flag(source_code, "12..22").unwrap(),
+            Range {
+                start_byte: 12,
+                end_byte: 22,
+                start_point: Point::new(1, 8),
+                end_point: Point::new(1, 18),
+            }
+        );
+        assert_eq!(
+            parse_range_flag(source_code, "1,8..$").unwrap().end_point,
+            Point::new(3, 0)
+        );
+        assert!(parse_range_flag(source_code, "22..12").is_err());
+        assert!(parse_range_flag(source_code, "12..100").is_err());
+        assert!(parse_range_flag(source_code, "12").is_err());
+    }
+
+    #[test]
+    fn test_ambiguity_log() {
+        let mut log = AmbiguityLog::default();
+        let lines = [
+            "new_parse",
+            "process version:0, version_count:1, state:1, row:0, col:0",
+            "lexed_lookahead sym:identifier, size:1",
+            "shift state:5",
+            "process version:0, version_count:1, state:5, row:0, col:1",
+            "reduce sym:type, child_count:1",
+            "reduce sym:expression, child_count:1",
+            "process version:0, version_count:2, state:7, row:0, col:1",
+            "process version:1, version_count:2, state:9, row:0, col:1",
+            "detect_error",
+            "select_higher_precedence symbol:call, prec:1, over_symbol:cast, other_prec:0",
+            "process version:0, version_count:1, state:12, row:0, col:4",
+            "process version:0, version_count:1, state:3, row:0, col:5",
+        ]
+        .into_iter()
+        .filter_map(|message| log.handle(message))
+        .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "0:1: split into 2 versions after reducing `type`, `expression`",
+                "  version 1 hit a syntax error at 0:1",
+                "  chose `call` over `cast` by dynamic precedence (1 > 0)",
+                "0:4: merged back into one version",
+            ]
+        );
+    }
+}
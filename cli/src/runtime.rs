@@ -0,0 +1,886 @@
+//! Parse with the parsers that [`generate`](crate::generate) produces, without writing
+//! separate bindings for them. The parser, tree and cursor types are those of the
+//! `tree-sitter` crate, and are re-exported here so that they don't have to be imported
+//! from two crates. So are the types of its query engine, which matches S-expression
+//! patterns against trees, checking the node types and fields that the patterns name
+//! against the language when they are compiled. Highlighting, which is built on queries,
+//! comes from the `tree-sitter-highlight` crate when the `highlight` feature is enabled.
+//! Documents that mix languages are parsed through a [`LanguageRegistry`], which resolves
+//! the languages of their injections.
+
+use std::{
+    collections::{hash_map::Entry, HashMap, VecDeque},
+    fs, ops,
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+pub use tree_sitter::{
+    InputEdit, Language, LanguageError, Node, Parser, Point, Query, QueryCapture, QueryCursor,
+    QueryError, QueryErrorKind, QueryMatch, QueryPredicate, QueryPredicateArg, Range, Tree,
+    TreeCursor,
+};
+#[cfg(feature = "highlight")]
+pub use tree_sitter_highlight::{
+    Highlight, HighlightConfiguration, HighlightEvent, Highlighter, HtmlRenderer,
+};
+use tree_sitter_loader::{CompileConfig, Loader};
+
+use crate::generate::{GeneratedOutput, ALLOC_HEADER};
+
+/// Compile a generated parser into a shared library in `lib_dir`, and load its language.
+/// The sources are written to `lib_dir/src/<name>`, and the library is only rebuilt when
+/// they change. The parser must have been generated with the default export prefix, and
+/// the grammar's external scanner, if it has one, is given by `scanner_path`.
+pub fn load_language(
+    output: &GeneratedOutput,
+    scanner_path: Option<&Path>,
+    lib_dir: &Path,
+) -> Result<Language> {
+    let src_dir = lib_dir.join("src").join(&output.name);
+    let header_dir = src_dir.join("tree_sitter");
+    fs::create_dir_all(&header_dir)
+        .with_context(|| format!("Failed to create the directory {header_dir:?}"))?;
+
+    let mut paths_to_check = vec![src_dir.join("parser.c")];
+    write_if_changed(&paths_to_check[0], &output.parser_c)?;
+    let lexer_path = src_dir.join("lexer.c");
+    if let Some(lexer_c) = &output.lexer_c {
+        write_if_changed(&lexer_path, lexer_c)?;
+        paths_to_check.push(lexer_path);
+    } else if lexer_path.exists() {
+        fs::remove_file(&lexer_path)?;
+    }
+    if let Some(scanner_path) = scanner_path {
+        let extension = scanner_path
+            .extension()
+            .and_then(|e| e.to_str())
+            .ok_or_else(|| anyhow!("Invalid external scanner path {scanner_path:?}"))?;
+        let scanner_code = fs::read_to_string(scanner_path)
+            .with_context(|| format!("Failed to read the external scanner {scanner_path:?}"))?;
+        let scanner_copy_path = src_dir.join("scanner").with_extension(extension);
+        write_if_changed(&scanner_copy_path, &scanner_code)?;
+        paths_to_check.push(scanner_copy_path);
+    }
+    for (file_name, contents) in [
+        ("alloc.h", ALLOC_HEADER),
+        ("array.h", tree_sitter::ARRAY_HEADER),
+        ("parser.h", tree_sitter::PARSER_HEADER),
+    ] {
+        write_if_changed(&header_dir.join(file_name), contents)?;
+    }
+
+    let loader = Loader::with_parser_lib_path(lib_dir.to_path_buf());
+    let mut config = CompileConfig::new(&src_dir, Some(&paths_to_check), None);
+    config.name.clone_from(&output.name);
+    loader.load_language_at_path_with_name(config)
+}
+
+/// Parse a string with a language, for the common case of parsing a whole document once.
+/// To reparse the document after editing it, pass the edit to [`Tree::edit`] and the tree
+/// to [`Parser::parse`], which reuses the parts of the tree that the edit didn't touch.
+pub fn parse(language: &Language, source: &str) -> Result<Tree> {
+    let mut parser = Parser::new();
+    parser.set_language(language)?;
+    parser
+        .parse(source, None)
+        .ok_or_else(|| anyhow!("Parsing was cancelled"))
+}
+
+/// Build the configuration for highlighting with a generated parser, using the highlights
+/// and injections queries that were generated for its grammar, and the given query for
+/// resolving local variables, which may be empty. The configuration recognizes the given
+/// highlight names, and the [`Highlight`] in each [`HighlightEvent`] is an index into them.
+#[cfg(feature = "highlight")]
+pub fn highlight_configuration(
+    language: Language,
+    output: &GeneratedOutput,
+    locals_query: &str,
+    highlight_names: &[impl AsRef<str>],
+) -> Result<HighlightConfiguration> {
+    let mut config = HighlightConfiguration::new(
+        language,
+        &output.name,
+        output.highlights_query.as_deref().unwrap_or_default(),
+        output.injections_query.as_deref().unwrap_or_default(),
+        locals_query,
+    )?;
+    config.configure(highlight_names);
+    Ok(config)
+}
+
+/// A highlighted region of a document: the byte range of a node that one of the highlights
+/// query's patterns captured, and the index of the capture in [`Query::capture_names`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HighlightSpan {
+    pub range: ops::Range<usize>,
+    pub capture: u32,
+}
+
+/// Keeps a document's syntax tree and highlighted regions between edits, so that after an
+/// edit only the regions that it affected are highlighted again. This doesn't process
+/// injections or locals, which can depend on distant parts of the document; editors that
+/// need them can use a [`Highlighter`] for the whole document in the background.
+pub struct IncrementalHighlighter {
+    parser: Parser,
+    query: Query,
+    tree: Option<Tree>,
+    spans: Vec<HighlightSpan>,
+}
+
+impl IncrementalHighlighter {
+    pub fn new(language: &Language, highlights_query: &str) -> Result<Self> {
+        let mut parser = Parser::new();
+        parser.set_language(language)?;
+        Ok(Self {
+            parser,
+            query: Query::new(language, highlights_query)?,
+            tree: None,
+            spans: Vec::new(),
+        })
+    }
+
+    pub fn query(&self) -> &Query {
+        &self.query
+    }
+
+    /// The highlighted regions of the document, ordered by their start.
+    pub fn spans(&self) -> &[HighlightSpan] {
+        &self.spans
+    }
+
+    /// Parse and highlight the whole document.
+    pub fn highlight(&mut self, source: &str) -> Result<&[HighlightSpan]> {
+        let tree = self
+            .parser
+            .parse(source, None)
+            .ok_or_else(|| anyhow!("Parsing was cancelled"))?;
+        self.spans = query_spans(&self.query, &tree, source, 0..source.len());
+        sort_spans(&mut self.spans);
+        self.tree = Some(tree);
+        Ok(&self.spans)
+    }
+
+    /// Apply an edit to the document, whose new contents are `source`, reparsing it
+    /// incrementally and highlighting again only the regions that the edit changed.
+    /// Returns the byte ranges of the new contents that were highlighted again, which are
+    /// the only parts of the document whose highlighting can differ.
+    pub fn edit(&mut self, edit: &InputEdit, source: &str) -> Result<Vec<ops::Range<usize>>> {
+        let Some(mut old_tree) = self.tree.take() else {
+            self.highlight(source)?;
+            let document = 0..source.len();
+            return Ok(vec![document]);
+        };
+        old_tree.edit(edit);
+        let tree = self
+            .parser
+            .parse(source, Some(&old_tree))
+            .ok_or_else(|| anyhow!("Parsing was cancelled"))?;
+
+        // The regions touching the edit are highlighted again, because their captures
+        // can depend on the text that changed even when the tree's structure didn't.
+        let mut ranges = old_tree
+            .changed_ranges(&tree)
+            .map(|range| range.start_byte..range.end_byte)
+            .collect::<Vec<_>>();
+        ranges.push(edit.start_byte.saturating_sub(1)..(edit.new_end_byte + 1).min(source.len()));
+        ranges.sort_unstable_by_key(|range| range.start);
+        let mut merged_ranges = Vec::<ops::Range<usize>>::new();
+        for range in ranges {
+            match merged_ranges.last_mut() {
+                Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+                _ => merged_ranges.push(range),
+            }
+        }
+
+        let old_spans = std::mem::take(&mut self.spans);
+        for mut span in old_spans {
+            if span.range.start > edit.old_end_byte {
+                span.range.start = span.range.start - edit.old_end_byte + edit.new_end_byte;
+                span.range.end = span.range.end - edit.old_end_byte + edit.new_end_byte;
+            } else if span.range.end >= edit.start_byte {
+                continue;
+            }
+            if !merged_ranges
+                .iter()
+                .any(|range| span.range.start < range.end && span.range.end > range.start)
+            {
+                self.spans.push(span);
+            }
+        }
+        for range in &merged_ranges {
+            self.spans
+                .extend(query_spans(&self.query, &tree, source, range.clone()));
+        }
+        sort_spans(&mut self.spans);
+        self.spans.dedup();
+        self.tree = Some(tree);
+        Ok(merged_ranges)
+    }
+}
+
+fn sort_spans(spans: &mut [HighlightSpan]) {
+    spans.sort_unstable_by_key(|span| (span.range.start, span.range.end, span.capture));
+}
+
+fn query_spans(
+    query: &Query,
+    tree: &Tree,
+    source: &str,
+    range: ops::Range<usize>,
+) -> Vec<HighlightSpan> {
+    let mut cursor = QueryCursor::new();
+    cursor.set_byte_range(range);
+    cursor
+        .matches(query, tree.root_node(), source.as_bytes())
+        .flat_map(|m| {
+            m.captures.iter().map(|capture| HighlightSpan {
+                range: capture.node.byte_range(),
+                capture: capture.index,
+            })
+        })
+        .collect()
+}
+
+/// Languages by name, along with the injections queries that say which parts of their
+/// documents are written in other languages, for parsing documents that mix languages,
+/// like Markdown with fenced code blocks or HTML with scripts and style sheets.
+#[derive(Default)]
+pub struct LanguageRegistry {
+    languages: HashMap<String, RegisteredLanguage>,
+}
+
+struct RegisteredLanguage {
+    language: Language,
+    injections_query: Option<Query>,
+}
+
+/// The part of a document that is written in one language, as parsed by
+/// [`LanguageRegistry::parse`]. The tree's [`Tree::included_ranges`] are the parts of the
+/// document that it covers.
+#[derive(Debug)]
+pub struct DocumentLayer {
+    pub language_name: String,
+    pub tree: Tree,
+    /// How many injections deep the layer is, which is zero for the document's own language.
+    pub depth: usize,
+}
+
+impl LanguageRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a language under the name that injections refer to it by, which is matched
+    /// without regard to case. The injections query uses the same captures and properties
+    /// as in [`HighlightConfiguration`], and may be empty.
+    pub fn add(&mut self, name: &str, language: Language, injections_query: &str) -> Result<()> {
+        let injections_query = if injections_query.trim().is_empty() {
+            None
+        } else {
+            Some(
+                Query::new(&language, injections_query)
+                    .with_context(|| format!("Invalid injections query for `{name}`"))?,
+            )
+        };
+        self.languages.insert(
+            name.to_lowercase(),
+            RegisteredLanguage {
+                language,
+                injections_query,
+            },
+        );
+        Ok(())
+    }
+
+    /// Register a generated language, by its grammar's name and with the injections query
+    /// that was generated for it.
+    pub fn add_generated(&mut self, output: &GeneratedOutput, language: Language) -> Result<()> {
+        self.add(
+            &output.name,
+            language,
+            output.injections_query.as_deref().unwrap_or_default(),
+        )
+    }
+
+    pub fn language(&self, name: &str) -> Option<&Language> {
+        self.languages
+            .get(&name.to_lowercase())
+            .map(|registered| &registered.language)
+    }
+
+    /// Parse a document in the given language, along with the parts of it that the
+    /// language's injections query says are written in other languages, and so on for the
+    /// injected languages. Injections of languages that aren't registered are skipped. The
+    /// document's own layer comes first, followed by the injected layers, from the outermost
+    /// inwards.
+    pub fn parse(&self, language_name: &str, source: &str) -> Result<Vec<DocumentLayer>> {
+        if self.language(language_name).is_none() {
+            return Err(anyhow!("The language `{language_name}` is not registered"));
+        }
+        let mut parser = Parser::new();
+        let mut layers = Vec::new();
+        let mut queue = VecDeque::from([(language_name.to_lowercase(), None, Vec::new(), 0)]);
+        while let Some((name, parent_name, ranges, depth)) = queue.pop_front() {
+            let Some(registered) = self.languages.get(&name) else {
+                continue;
+            };
+            parser.set_language(&registered.language)?;
+            parser.set_included_ranges(&ranges)?;
+            let tree = parser
+                .parse(source, None)
+                .ok_or_else(|| anyhow!("Parsing was cancelled"))?;
+            if let Some(query) = &registered.injections_query {
+                for (injected_name, injected_ranges) in
+                    find_injections(query, &tree, source, &name, parent_name.as_deref())
+                {
+                    queue.push_back((
+                        injected_name,
+                        Some(name.clone()),
+                        injected_ranges,
+                        depth + 1,
+                    ));
+                }
+            }
+            layers.push(DocumentLayer {
+                language_name: name,
+                tree,
+                depth,
+            });
+        }
+        Ok(layers)
+    }
+}
+
+/// The languages and ranges of the injections in a layer of a document. The nodes captured
+/// as `injection.content` by a pattern with the `injection.combined` property are parsed
+/// together, as one document per language.
+fn find_injections(
+    query: &Query,
+    tree: &Tree,
+    source: &str,
+    language_name: &str,
+    parent_name: Option<&str>,
+) -> Vec<(String, Vec<Range>)> {
+    let content_capture_index = query.capture_index_for_name("injection.content");
+    let language_capture_index = query.capture_index_for_name("injection.language");
+    let layer_ranges = tree.included_ranges();
+    let mut injections = Vec::<(String, Vec<Range>)>::new();
+    let mut combined_injections = HashMap::<_, usize>::new();
+    let mut cursor = QueryCursor::new();
+    for query_match in cursor.matches(query, tree.root_node(), source.as_bytes()) {
+        let mut name = None;
+        let mut content_node = None;
+        for capture in query_match.captures {
+            if Some(capture.index) == language_capture_index {
+                name = capture.node.utf8_text(source.as_bytes()).ok();
+            } else if Some(capture.index) == content_capture_index {
+                content_node = Some(capture.node);
+            }
+        }
+
+        let mut include_children = false;
+        let mut combined = false;
+        for property in query.property_settings(query_match.pattern_index) {
+            match property.key.as_ref() {
+                "injection.language" => name = name.or(property.value.as_deref()),
+                "injection.self" => name = name.or(Some(language_name)),
+                "injection.parent" => name = name.or(parent_name),
+                "injection.include-children" => include_children = true,
+                "injection.combined" => combined = true,
+                _ => {}
+            }
+        }
+        let (Some(name), Some(content_node)) = (name, content_node) else {
+            continue;
+        };
+        let ranges = intersect_ranges(
+            &content_ranges(content_node, include_children),
+            &layer_ranges,
+        );
+        if ranges.is_empty() {
+            continue;
+        }
+
+        let name = name.to_lowercase();
+        if combined {
+            match combined_injections.entry((query_match.pattern_index, name.clone())) {
+                Entry::Occupied(entry) => injections[*entry.get()].1.extend(ranges),
+                Entry::Vacant(entry) => {
+                    entry.insert(injections.len());
+                    injections.push((name, ranges));
+                }
+            }
+        } else {
+            injections.push((name, ranges));
+        }
+    }
+    for (_, ranges) in &mut injections {
+        ranges.sort_unstable_by_key(|range| range.start_byte);
+    }
+    injections
+}
+
+/// The ranges of a node's text, without the text of its children unless they are included.
+fn content_ranges(node: Node, include_children: bool) -> Vec<Range> {
+    if include_children {
+        return vec![node.range()];
+    }
+    let mut ranges = Vec::new();
+    let mut start = (node.start_byte(), node.start_position());
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if start.0 < child.start_byte() {
+            ranges.push(Range {
+                start_byte: start.0,
+                start_point: start.1,
+                end_byte: child.start_byte(),
+                end_point: child.start_position(),
+            });
+        }
+        start = (child.end_byte(), child.end_position());
+    }
+    if start.0 < node.end_byte() {
+        ranges.push(Range {
+            start_byte: start.0,
+            start_point: start.1,
+            end_byte: node.end_byte(),
+            end_point: node.end_position(),
+        });
+    }
+    ranges
+}
+
+fn intersect_ranges(ranges: &[Range], layer_ranges: &[Range]) -> Vec<Range> {
+    let mut result = Vec::new();
+    for range in ranges {
+        for layer_range in layer_ranges {
+            let start = if range.start_byte < layer_range.start_byte {
+                layer_range
+            } else {
+                range
+            };
+            let end = if range.end_byte > layer_range.end_byte {
+                layer_range
+            } else {
+                range
+            };
+            if start.start_byte < end.end_byte {
+                result.push(Range {
+                    start_byte: start.start_byte,
+                    start_point: start.start_point,
+                    end_byte: end.end_byte,
+                    end_point: end.end_point,
+                });
+            }
+        }
+    }
+    result
+}
+
+// Rewriting a file that is unchanged would update its modification time, and make the
+// loader recompile the library.
+fn write_if_changed(path: &Path, contents: &str) -> Result<()> {
+    if fs::read_to_string(path).map_or(true, |existing| existing != contents) {
+        fs::write(path, contents).with_context(|| format!("Failed to write {path:?}"))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generate::{generate_parser, GenerateOptions};
+
+    #[test]
+    #[cfg(feature = "highlight")]
+    fn test_highlight_with_generated_language() {
+        let output = generate_parser(
+            r#"{
+                "name": "runtime_highlight_test",
+                "extras": [{"type": "PATTERN", "value": "\\s"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "declaration"}},
+                    "declaration": {"type": "SEQ", "members": [
+                        {"type": "STRING", "value": "let"},
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": "="},
+                        {"type": "SYMBOL", "name": "number"}
+                    ]},
+                    "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                    "number": {"type": "PATTERN", "value": "[0-9]+"}
+                }
+            }"#,
+            &GenerateOptions::default(),
+        )
+        .unwrap();
+        let lib_dir = tempfile::tempdir().unwrap();
+        let language = load_language(&output, None, lib_dir.path()).unwrap();
+        let highlight_names = ["keyword", "number", "operator"];
+        let config = highlight_configuration(language, &output, "", &highlight_names).unwrap();
+
+        let source = "let x = 1";
+        let mut highlighter = Highlighter::new();
+        let mut highlighted = Vec::new();
+        let mut current = None;
+        for event in highlighter
+            .highlight(&config, source.as_bytes(), None, |_| None)
+            .unwrap()
+        {
+            match event.unwrap() {
+                HighlightEvent::HighlightStart(Highlight(index)) => current = Some(index),
+                HighlightEvent::HighlightEnd => current = None,
+                HighlightEvent::Source { start, end } => {
+                    if let Some(index) = current {
+                        highlighted.push((&source[start..end], highlight_names[index]));
+                    }
+                }
+            }
+        }
+        assert_eq!(
+            highlighted,
+            [("let", "keyword"), ("=", "operator"), ("1", "number")]
+        );
+    }
+
+    #[test]
+    fn test_incremental_highlighter() {
+        let output = generate_parser(
+            r#"{
+                "name": "runtime_incremental_test",
+                "extras": [{"type": "PATTERN", "value": "\\s"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "_item"}},
+                    "_item": {"type": "CHOICE", "members": [
+                        {"type": "SYMBOL", "name": "call"},
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "SYMBOL", "name": "number"}
+                    ]},
+                    "call": {"type": "SEQ", "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "STRING", "value": "("},
+                        {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "_item"}},
+                        {"type": "STRING", "value": ")"}
+                    ]},
+                    "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                    "number": {"type": "PATTERN", "value": "[0-9]+"}
+                }
+            }"#,
+            &GenerateOptions::default(),
+        )
+        .unwrap();
+        let lib_dir = tempfile::tempdir().unwrap();
+        let language = load_language(&output, None, lib_dir.path()).unwrap();
+        let query = r#"
+            (call (identifier) @function)
+            (identifier) @variable
+            ((identifier) @constant (#eq? @constant "pi"))
+            (number) @number
+        "#;
+
+        let mut source = "f(x 1) y g(2)".to_string();
+        let mut highlighter = IncrementalHighlighter::new(&language, query).unwrap();
+        highlighter.highlight(&source).unwrap();
+        let mut fresh_highlighter = IncrementalHighlighter::new(&language, query).unwrap();
+
+        // Each edit is a byte range of the old text and its replacement.
+        for (start, old_end, text) in [
+            (7, 8, "h"),
+            (13, 13, " pi"),
+            (4, 5, "pi"),
+            (1, 2, ""),
+            (0, 0, "a "),
+            (9, 10, "(0)"),
+        ] {
+            source.replace_range(start..old_end, text);
+            let new_end = start + text.len();
+            let ranges = highlighter
+                .edit(
+                    &InputEdit {
+                        start_byte: start,
+                        old_end_byte: old_end,
+                        new_end_byte: new_end,
+                        start_position: Point::new(0, start),
+                        old_end_position: Point::new(0, old_end),
+                        new_end_position: Point::new(0, new_end),
+                    },
+                    &source,
+                )
+                .unwrap();
+            assert!(ranges
+                .iter()
+                .any(|range| range.start <= start && range.end >= new_end));
+            assert_eq!(
+                highlighter.spans(),
+                fresh_highlighter.highlight(&source).unwrap(),
+                "after editing the text to {source:?}"
+            );
+        }
+
+        let names = highlighter.query().capture_names();
+        let captured = highlighter
+            .spans()
+            .iter()
+            .map(|span| (&source[span.range.clone()], names[span.capture as usize]))
+            .collect::<Vec<_>>();
+        assert_eq!(source, "a fx pi) (0) g(2) pi");
+        assert!(captured.contains(&("pi", "constant")));
+        assert!(captured.contains(&("g", "function")));
+    }
+
+    #[test]
+    fn test_language_registry_injections() {
+        let outer_output = generate_parser(
+            r#"{
+                "name": "runtime_outer_test",
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "CHOICE", "members": [
+                        {"type": "SYMBOL", "name": "block"},
+                        {"type": "SYMBOL", "name": "fence"}
+                    ]}},
+                    "block": {"type": "SEQ", "members": [
+                        {"type": "STRING", "value": "{"},
+                        {"type": "SYMBOL", "name": "code"},
+                        {"type": "STRING", "value": "}"}
+                    ]},
+                    "fence": {"type": "SEQ", "members": [
+                        {"type": "STRING", "value": "<"},
+                        {"type": "SYMBOL", "name": "name"},
+                        {"type": "STRING", "value": ":"},
+                        {"type": "SYMBOL", "name": "code"},
+                        {"type": "STRING", "value": ">"}
+                    ]},
+                    "name": {"type": "PATTERN", "value": "[a-zA-Z]+"},
+                    "code": {"type": "PATTERN", "value": "[^}>]+"}
+                }
+            }"#,
+            &GenerateOptions::default(),
+        )
+        .unwrap();
+        let inner_output = generate_parser(
+            r#"{
+                "name": "runtime_inner_test",
+                "extras": [{"type": "PATTERN", "value": "\\s"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "CHOICE", "members": [
+                        {"type": "SYMBOL", "name": "number"},
+                        {"type": "SYMBOL", "name": "word"}
+                    ]}},
+                    "number": {"type": "PATTERN", "value": "[0-9]+"},
+                    "word": {"type": "PATTERN", "value": "[a-z]+"}
+                }
+            }"#,
+            &GenerateOptions::default(),
+        )
+        .unwrap();
+        let lib_dir = tempfile::tempdir().unwrap();
+        let outer_language = load_language(&outer_output, None, lib_dir.path()).unwrap();
+        let inner_language = load_language(&inner_output, None, lib_dir.path()).unwrap();
+
+        let mut registry = LanguageRegistry::new();
+        registry
+            .add(
+                "outer",
+                outer_language,
+                r#"
+                ((block (code) @injection.content)
+                 (#set! injection.language "Inner")
+                 (#set! injection.combined))
+                (fence (name) @injection.language (code) @injection.content)
+                "#,
+            )
+            .unwrap();
+        registry.add("inner", inner_language, "").unwrap();
+        assert!(registry.language("INNER").is_some());
+
+        let source = "{1}<inner:2 b>{a}<unknown:zz>";
+        let layers = registry.parse("outer", source).unwrap();
+        assert_eq!(layers[0].language_name, "outer");
+        assert_eq!(layers[0].depth, 0);
+        let described = layers[1..]
+            .iter()
+            .map(|layer| {
+                (
+                    layer.language_name.as_str(),
+                    layer.depth,
+                    layer.tree.root_node().to_sexp(),
+                    layer
+                        .tree
+                        .included_ranges()
+                        .iter()
+                        .map(|range| &source[range.start_byte..range.end_byte])
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            described,
+            [
+                (
+                    "inner",
+                    1,
+                    "(program (number) (word))".to_string(),
+                    vec!["1", "a"]
+                ),
+                (
+                    "inner",
+                    1,
+                    "(program (number) (word))".to_string(),
+                    vec!["2 b"]
+                ),
+            ]
+        );
+
+        assert_eq!(
+            registry.parse("missing", source).unwrap_err().to_string(),
+            "The language `missing` is not registered"
+        );
+    }
+
+    #[test]
+    fn test_load_and_parse_generated_language() {
+        let output = generate_parser(
+            r#"{
+                "name": "runtime_test",
+                "extras": [{"type": "PATTERN", "value": "\\s"}],
+                "rules": {
+                    "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "word"}},
+                    "word": {"type": "PATTERN", "value": "[a-z]+"}
+                }
+            }"#,
+            &GenerateOptions::default(),
+        )
+        .unwrap();
+        let lib_dir = tempfile::tempdir().unwrap();
+        let language = load_language(&output, None, lib_dir.path()).unwrap();
+
+        let mut source = "one two".to_string();
+        let mut tree = parse(&language, &source).unwrap();
+        assert_eq!(tree.root_node().to_sexp(), "(program (word) (word))");
+
+        source.insert_str(4, "and ");
+        tree.edit(&InputEdit {
+            start_byte: 4,
+            old_end_byte: 4,
+            new_end_byte: 8,
+            start_position: Point::new(0, 4),
+            old_end_position: Point::new(0, 4),
+            new_end_position: Point::new(0, 8),
+        });
+        let mut parser = Parser::new();
+        parser.set_language(&language).unwrap();
+        let new_tree = parser.parse(&source, Some(&tree)).unwrap();
+        assert_eq!(
+            new_tree.root_node().to_sexp(),
+            "(program (word) (word) (word))"
+        );
+
+        let mut cursor = new_tree.walk();
+        assert!(cursor.goto_first_child());
+        assert!(cursor.goto_next_sibling());
+        assert_eq!(cursor.node().utf8_text(source.as_bytes()).unwrap(), "and");
+
+        let query = Query::new(
+            &language,
+            r#"((word) @keyword (#eq? @keyword "and")) ((word)+ @words)"#,
+        )
+        .unwrap();
+        let mut query_cursor = QueryCursor::new();
+        let captures = query_cursor
+            .matches(&query, new_tree.root_node(), source.as_bytes())
+            .map(|m| {
+                (
+                    m.pattern_index,
+                    m.captures
+                        .iter()
+                        .map(|c| c.node.utf8_text(source.as_bytes()).unwrap())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(captures, [(0, vec!["and"]), (1, vec!["one", "and", "two"])]);
+        assert_eq!(
+            Query::new(&language, "(number)").unwrap_err().kind,
+            QueryErrorKind::NodeType
+        );
+    }
+
+    #[test]
+    fn test_load_language_with_unused_symbol_ids() {
+        let grammar = |rules: &str| {
+            format!(
+                r#"{{
+                    "name": "runtime_symbol_ids_test",
+                    "extras": [{{"type": "PATTERN", "value": "\\s"}}],
+                    "rules": {{
+                        {rules}
+                        "statement": {{"type": "SEQ", "members": [
+                            {{"type": "SYMBOL", "name": "identifier"}},
+                            {{"type": "STRING", "value": ";"}}
+                        ]}},
+                        "identifier": {{"type": "PATTERN", "value": "[a-z]+"}}
+                    }}
+                }}"#
+            )
+        };
+        let previous_output = generate_parser(
+            &grammar(
+                r#""program": {"type": "REPEAT", "content": {"type": "CHOICE", "members": [
+                    {"type": "SYMBOL", "name": "block"},
+                    {"type": "SYMBOL", "name": "statement"}
+                ]}},
+                "block": {"type": "SEQ", "members": [
+                    {"type": "STRING", "value": "{"},
+                    {"type": "SYMBOL", "name": "statement"},
+                    {"type": "STRING", "value": "}"}
+                ]},"#,
+            ),
+            &GenerateOptions::default(),
+        )
+        .unwrap();
+
+        // Without blocks, the ids of the brace tokens are left unused.
+        let output = generate_parser(
+            &grammar(
+                r#""program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "statement"}},"#,
+            ),
+            &GenerateOptions {
+                previous_symbol_ids: previous_output.symbol_ids.clone(),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let lib_dir = tempfile::tempdir().unwrap();
+        let language = load_language(&output, None, lib_dir.path()).unwrap();
+
+        for (id, name) in output.symbol_ids.iter().enumerate() {
+            let id = id as u16 + 1;
+            if name.is_empty() {
+                assert_eq!(language.node_kind_for_id(id), None);
+            } else {
+                assert_eq!(
+                    previous_output.symbol_ids[usize::from(id) - 1],
+                    *name,
+                    "symbol {id} changed"
+                );
+            }
+        }
+        assert_eq!(
+            language.id_for_node_kind("statement", true),
+            output
+                .symbol_ids
+                .iter()
+                .position(|name| name == "sym_statement")
+                .unwrap() as u16
+                + 1
+        );
+
+        let tree = parse(&language, "a; b;").unwrap();
+        assert_eq!(
+            tree.root_node().to_sexp(),
+            "(program (statement (identifier)) (statement (identifier)))"
+        );
+        let tree = parse(&language, "a; {b;}").unwrap();
+        assert!(tree.root_node().has_error());
+    }
+}
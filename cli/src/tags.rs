@@ -7,11 +7,53 @@ use std::{
 };
 
 use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tree_sitter::Point;
 use tree_sitter_loader::{Config, Loader};
 use tree_sitter_tags::TagsContext;
 
 use super::util;
 
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum TagsOutput {
+    Text,
+    /// A JSON array of all of the tags in all of the files.
+    Json,
+    /// A tags file in the format of Universal Ctags, listing the definitions, sorted by name.
+    Ctags,
+}
+
+/// A tag in the JSON output, whose range is the range of its name.
+#[derive(Serialize)]
+struct JsonTag {
+    path: String,
+    name: String,
+    kind: String,
+    is_definition: bool,
+    start_byte: usize,
+    end_byte: usize,
+    start_point: JsonPoint,
+    end_point: JsonPoint,
+    line: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    docs: Option<String>,
+}
+
+#[derive(Serialize)]
+struct JsonPoint {
+    row: usize,
+    column: usize,
+}
+
+impl From<Point> for JsonPoint {
+    fn from(point: Point) -> Self {
+        Self {
+            row: point.row,
+            column: point.column,
+        }
+    }
+}
+
 pub fn generate_tags(
     loader: &Loader,
     loader_config: &Config,
@@ -19,6 +61,7 @@ pub fn generate_tags(
     paths: &[String],
     quiet: bool,
     time: bool,
+    output: TagsOutput,
 ) -> Result<()> {
     let mut lang = None;
     if let Some(scope) = scope {
@@ -32,6 +75,8 @@ pub fn generate_tags(
     let cancellation_flag = util::cancel_on_signal();
     let stdout = io::stdout();
     let mut stdout = stdout.lock();
+    let mut json_tags = Vec::new();
+    let mut ctags_lines = Vec::new();
 
     for path in paths {
         let path = Path::new(&path);
@@ -40,6 +85,8 @@ pub fn generate_tags(
             None => {
                 if let Some(v) = loader.language_configuration_for_file_name(path)? {
                     v
+                } else if let Some(v) = loader.language_configuration_for_first_line_regex(path)? {
+                    v
                 } else {
                     eprintln!("{}", util::lang_not_found_for_path(path, loader_config));
                     continue;
@@ -48,7 +95,7 @@ pub fn generate_tags(
         };
 
         if let Some(tags_config) = language_config.tags_config(language)? {
-            let indent = if paths.len() > 1 {
+            let indent = if paths.len() > 1 && output == TagsOutput::Text {
                 if !quiet {
                     writeln!(&mut stdout, "{}", path.to_string_lossy())?;
                 }
@@ -64,12 +111,40 @@ pub fn generate_tags(
                 .0
             {
                 let tag = tag?;
-                if !quiet {
+                let name = str::from_utf8(&source[tag.name_range.clone()]).unwrap_or("");
+                let kind = tags_config.syntax_type_name(tag.syntax_type_id);
+                if quiet {
+                    continue;
+                }
+                if output == TagsOutput::Json {
+                    json_tags.push(JsonTag {
+                        path: path.to_string_lossy().to_string(),
+                        name: name.to_string(),
+                        kind: kind.to_string(),
+                        is_definition: tag.is_definition,
+                        start_byte: tag.name_range.start,
+                        end_byte: tag.name_range.end,
+                        start_point: tag.span.start.into(),
+                        end_point: tag.span.end.into(),
+                        line: str::from_utf8(&source[tag.line_range])
+                            .unwrap_or("")
+                            .to_string(),
+                        docs: tag.docs,
+                    });
+                } else if output == TagsOutput::Ctags {
+                    if tag.is_definition {
+                        let row = tag.span.start.row + 1;
+                        ctags_lines.push(format!(
+                            "{name}\t{}\t{row};\"\tkind:{kind}\tline:{row}",
+                            path.to_string_lossy(),
+                        ));
+                    }
+                } else {
                     write!(
                         &mut stdout,
                         "{indent}{:<10}\t | {:<8}\t{} {} - {} `{}`",
-                        str::from_utf8(&source[tag.name_range]).unwrap_or(""),
-                        &tags_config.syntax_type_name(tag.syntax_type_id),
+                        name,
+                        kind,
                         if tag.is_definition { "def" } else { "ref" },
                         tag.span.start,
                         tag.span.end,
@@ -86,7 +161,7 @@ pub fn generate_tags(
                 }
             }
 
-            if time {
+            if time && output == TagsOutput::Text {
                 writeln!(&mut stdout, "{indent}time: {}ms", t0.elapsed().as_millis(),)?;
             }
         } else {
@@ -94,5 +169,23 @@ pub fn generate_tags(
         }
     }
 
+    match output {
+        TagsOutput::Text => {}
+        TagsOutput::Json => {
+            writeln!(&mut stdout, "{}", serde_json::to_string_pretty(&json_tags)?)?;
+        }
+        TagsOutput::Ctags => {
+            ctags_lines.sort_unstable();
+            writeln!(&mut stdout, "!_TAG_FILE_FORMAT\t2\t/extended format/")?;
+            writeln!(
+                &mut stdout,
+                "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/"
+            )?;
+            for line in ctags_lines {
+                writeln!(&mut stdout, "{line}")?;
+            }
+        }
+    }
+
     Ok(())
 }
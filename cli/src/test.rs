@@ -5,6 +5,8 @@ use std::{
     io::{self, Write},
     path::{Path, PathBuf},
     str,
+    sync::Mutex,
+    thread,
 };
 
 use anstyle::{AnsiColor, Color, Style};
@@ -15,6 +17,7 @@ use regex::{
     bytes::{Regex as ByteRegex, RegexBuilder as ByteRegexBuilder},
     Regex,
 };
+use serde::Serialize;
 use similar::{ChangeTag, TextDiff};
 use tree_sitter::{format_sexp, Language, LogType, Parser, Query};
 use walkdir::WalkDir;
@@ -95,6 +98,7 @@ impl Default for TestAttributes {
     }
 }
 
+#[derive(Clone)]
 pub struct TestOptions<'a> {
     pub path: PathBuf,
     pub debug: bool,
@@ -107,9 +111,39 @@ pub struct TestOptions<'a> {
     pub color: bool,
     pub test_num: usize,
     pub show_fields: bool,
+    pub jobs: usize,
+    /// Don't print a line for each test, only the failures.
+    pub quiet: bool,
+    /// Don't print anything, so that the caller can print the summary as JSON.
+    pub json: bool,
 }
 
-pub fn run_tests_at_path(parser: &mut Parser, opts: &mut TestOptions) -> Result<()> {
+/// A machine-readable summary of a test run, printed by `tree-sitter test --json`.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub successful: bool,
+    pub corpus_tests: usize,
+    pub corpus_failures: Vec<Failure>,
+    /// Whether the highlight tests passed, or `None` if there are none.
+    pub highlight_tests_passed: Option<bool>,
+    /// Whether the tag tests passed, or `None` if there are none.
+    pub tag_tests_passed: Option<bool>,
+    /// The error that stopped the test run, if any.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    pub name: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+pub fn run_tests_at_path(
+    parser: &mut Parser,
+    opts: &mut TestOptions,
+    summary: &mut Summary,
+) -> Result<()> {
     let test_entry = parse_tests(&opts.path)?;
     let mut _log_session = None;
 
@@ -127,20 +161,51 @@ pub fn run_tests_at_path(parser: &mut Parser, opts: &mut TestOptions) -> Result<
     let mut failures = Vec::new();
     let mut corrected_entries = Vec::new();
     let mut has_parse_errors = false;
-    run_tests(
-        parser,
-        test_entry,
-        opts,
-        0,
-        &mut failures,
-        &mut corrected_entries,
-        &mut has_parse_errors,
-    )?;
+    let first_test_num = opts.test_num;
+    if opts.jobs > 1 && opts.path.is_dir() {
+        run_tests_in_parallel(
+            parser,
+            test_entry,
+            opts,
+            &mut failures,
+            &mut has_parse_errors,
+        )?;
+    } else {
+        run_tests(
+            parser,
+            test_entry,
+            opts,
+            0,
+            &mut failures,
+            &mut corrected_entries,
+            &mut has_parse_errors,
+            &mut if opts.quiet {
+                Box::new(io::sink()) as Box<dyn Write>
+            } else {
+                Box::new(io::stdout())
+            },
+        )?;
+    }
 
     parser.stop_printing_dot_graphs();
 
+    summary.corpus_tests += opts.test_num - first_test_num;
+    summary
+        .corpus_failures
+        .extend(failures.iter().map(|(name, actual, expected)| Failure {
+            name: name.clone(),
+            expected: expected.clone(),
+            actual: actual.clone(),
+        }));
+
     if failures.is_empty() {
         Ok(())
+    } else if opts.json {
+        if opts.update && !has_parse_errors {
+            Ok(())
+        } else {
+            Err(anyhow!(""))
+        }
     } else {
         println!();
 
@@ -200,6 +265,105 @@ pub fn run_tests_at_path(parser: &mut Parser, opts: &mut TestOptions) -> Result<
     }
 }
 
+/// Run the corpus files and directories at the top level of the test entry on separate
+/// threads, each with its own parser. Their output is buffered, and printed in order once
+/// they have all finished.
+fn run_tests_in_parallel(
+    parser: &Parser,
+    test_entry: TestEntry,
+    opts: &mut TestOptions,
+    failures: &mut Vec<(String, String, String)>,
+    has_parse_errors: &mut bool,
+) -> Result<()> {
+    struct Outcome {
+        output: Vec<u8>,
+        failures: Vec<(String, String, String)>,
+        has_parse_errors: bool,
+        completed: bool,
+    }
+
+    let TestEntry::Group { children, .. } = test_entry else {
+        unreachable!();
+    };
+    let language = parser
+        .language()
+        .ok_or_else(|| anyhow!("The parser has no language"))?;
+
+    let mut test_num = opts.test_num;
+    let mut units = Vec::with_capacity(children.len());
+    for (i, child) in children.into_iter().enumerate() {
+        let child_test_num = test_num;
+        test_num += count_subtests(&child);
+        units.push((i, child_test_num, child));
+    }
+    let thread_count = opts.jobs.min(units.len()).max(1);
+    let units = Mutex::new(units.into_iter());
+
+    let mut outcomes = thread::scope(|scope| {
+        let handles = (0..thread_count)
+            .map(|_| {
+                scope.spawn(|| -> Result<Vec<(usize, Outcome)>> {
+                    let mut parser = Parser::new();
+                    parser.set_language(&language)?;
+                    let mut outcomes = Vec::new();
+                    loop {
+                        let Some((i, test_num, entry)) = units.lock().unwrap().next() else {
+                            break;
+                        };
+                        let mut opts = opts.clone();
+                        opts.test_num = test_num;
+                        let mut outcome = Outcome {
+                            output: Vec::new(),
+                            failures: Vec::new(),
+                            has_parse_errors: false,
+                            completed: true,
+                        };
+                        outcome.completed = run_tests(
+                            &mut parser,
+                            entry,
+                            &mut opts,
+                            1,
+                            &mut outcome.failures,
+                            &mut Vec::new(),
+                            &mut outcome.has_parse_errors,
+                            &mut outcome.output,
+                        )?;
+                        outcomes.push((i, outcome));
+                    }
+                    Ok(outcomes)
+                })
+            })
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|handle| {
+                handle
+                    .join()
+                    .map_err(|_| anyhow!("A test thread panicked"))?
+            })
+            .collect::<Result<Vec<_>>>()
+    })?
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>();
+    outcomes.sort_unstable_by_key(|(i, _)| *i);
+
+    let mut stdout = io::stdout().lock();
+    for (_, outcome) in outcomes {
+        if !opts.quiet {
+            stdout.write_all(&outcome.output)?;
+        }
+        failures.extend(outcome.failures);
+        *has_parse_errors |= outcome.has_parse_errors;
+        if !outcome.completed {
+            // fail fast
+            break;
+        }
+    }
+    opts.test_num = test_num;
+    Ok(())
+}
+
 #[allow(clippy::type_complexity)]
 pub fn get_test_info<'test>(
     test_entry: &'test TestEntry,
@@ -339,6 +503,7 @@ fn run_tests(
     failures: &mut Vec<(String, String, String)>,
     corrected_entries: &mut Vec<(String, String, String, String, usize, usize)>,
     has_parse_errors: &mut bool,
+    out: &mut dyn Write,
 ) -> Result<bool> {
     match test_entry {
         TestEntry::Example {
@@ -351,23 +516,25 @@ fn run_tests(
             attributes_str,
             attributes,
         } => {
-            print!("{}", "  ".repeat(indent_level as usize));
+            write!(out, "{}", "  ".repeat(indent_level as usize))?;
 
             if attributes.skip {
-                println!(
+                writeln!(
+                    out,
                     "{:>3}.  {}",
                     opts.test_num,
                     paint(opts.color.then_some(AnsiColor::Yellow), &name),
-                );
+                )?;
                 return Ok(true);
             }
 
             if !attributes.platform {
-                println!(
+                writeln!(
+                    out,
                     "{:>3}.  {}",
                     opts.test_num,
                     paint(opts.color.then_some(AnsiColor::Magenta), &name),
-                );
+                )?;
                 return Ok(true);
             }
 
@@ -382,12 +549,21 @@ fn run_tests(
                 let tree = parser.parse(&input, None).unwrap();
 
                 if attributes.error {
-                    if tree.root_node().has_error() {
-                        println!(
+                    // The expected tree of an invalid input is optional, but if it is given,
+                    // the parser must recover from the error in the same way.
+                    let mut actual = tree.root_node().to_sexp();
+                    if !(opts.show_fields || has_fields) {
+                        actual = strip_sexp_fields(&actual);
+                    }
+                    let recovered_as_expected = output.is_empty() || actual == output;
+
+                    if tree.root_node().has_error() && recovered_as_expected {
+                        writeln!(
+                            out,
                             "{:>3}.  {}",
                             opts.test_num,
                             paint(opts.color.then_some(AnsiColor::Green), &name)
-                        );
+                        )?;
                         if opts.update {
                             let input = String::from_utf8(input.clone()).unwrap();
                             let output = format_sexp(&output, 0);
@@ -400,6 +576,33 @@ fn run_tests(
                                 divider_delim_len,
                             ));
                         }
+                    } else if tree.root_node().has_error() {
+                        if opts.update {
+                            let input = String::from_utf8(input.clone()).unwrap();
+                            let actual_output = format_sexp(&actual, 0);
+                            corrected_entries.push((
+                                name.clone(),
+                                input,
+                                actual_output,
+                                attributes_str.clone(),
+                                header_delim_len,
+                                divider_delim_len,
+                            ));
+                            writeln!(
+                                out,
+                                "{:>3}. ✓ {}",
+                                opts.test_num,
+                                paint(opts.color.then_some(AnsiColor::Blue), &name),
+                            )?;
+                        } else {
+                            writeln!(
+                                out,
+                                "{:>3}. ✗ {}",
+                                opts.test_num,
+                                paint(opts.color.then_some(AnsiColor::Red), &name),
+                            )?;
+                        }
+                        failures.push((name.clone(), actual, output.clone()));
                     } else {
                         if opts.update {
                             let input = String::from_utf8(input.clone()).unwrap();
@@ -414,11 +617,12 @@ fn run_tests(
                                 divider_delim_len,
                             ));
                         }
-                        println!(
+                        writeln!(
+                            out,
                             "{:>3}.  {}",
                             opts.test_num,
                             paint(opts.color.then_some(AnsiColor::Red), &name)
-                        );
+                        )?;
                         failures.push((
                             name.clone(),
                             tree.root_node().to_sexp(),
@@ -436,11 +640,12 @@ fn run_tests(
                     }
 
                     if actual == output {
-                        println!(
+                        writeln!(
+                            out,
                             "{:>3}. ✓ {}",
                             opts.test_num,
                             paint(opts.color.then_some(AnsiColor::Green), &name)
-                        );
+                        )?;
                         if opts.update {
                             let input = String::from_utf8(input.clone()).unwrap();
                             let output = format_sexp(&output, 0);
@@ -485,18 +690,20 @@ fn run_tests(
                                     header_delim_len,
                                     divider_delim_len,
                                 ));
-                                println!(
+                                writeln!(
+                                    out,
                                     "{:>3}. ✓ {}",
                                     opts.test_num,
                                     paint(opts.color.then_some(AnsiColor::Blue), &name),
-                                );
+                                )?;
                             }
                         } else {
-                            println!(
+                            writeln!(
+                                out,
                                 "{:>3}. ✗ {}",
                                 opts.test_num,
                                 paint(opts.color.then_some(AnsiColor::Red), &name),
-                            );
+                            )?;
                         }
                         failures.push((name.clone(), actual, output.clone()));
 
@@ -580,8 +787,8 @@ fn run_tests(
                 }
                 if !has_printed && indent_level > 1 {
                     has_printed = true;
-                    print!("{}", "  ".repeat((indent_level - 1) as usize));
-                    println!("{name}:");
+                    write!(out, "{}", "  ".repeat((indent_level - 1) as usize))?;
+                    writeln!(out, "{name}:")?;
                 }
                 if !run_tests(
                     parser,
@@ -591,6 +798,7 @@ fn run_tests(
                     failures,
                     corrected_entries,
                     has_parse_errors,
+                    out,
                 )? {
                     // fail fast
                     return Ok(false);
@@ -1,4 +1,4 @@
-use std::{fs, path::Path};
+use std::{fs, io::Write, path::Path};
 
 use anstyle::AnsiColor;
 use anyhow::{anyhow, Result};
@@ -49,9 +49,18 @@ pub fn test_highlights(
     highlighter: &mut Highlighter,
     directory: &Path,
     use_color: bool,
+    out: &mut dyn Write,
 ) -> Result<()> {
-    println!("syntax highlighting:");
-    test_highlights_indented(loader, loader_config, highlighter, directory, use_color, 2)
+    writeln!(out, "syntax highlighting:")?;
+    test_highlights_indented(
+        loader,
+        loader_config,
+        highlighter,
+        directory,
+        use_color,
+        2,
+        out,
+    )
 }
 
 fn test_highlights_indented(
@@ -61,6 +70,7 @@ fn test_highlights_indented(
     directory: &Path,
     use_color: bool,
     indent_level: usize,
+    out: &mut dyn Write,
 ) -> Result<()> {
     let mut failed = false;
 
@@ -68,13 +78,14 @@ fn test_highlights_indented(
         let highlight_test_file = highlight_test_file?;
         let test_file_path = highlight_test_file.path();
         let test_file_name = highlight_test_file.file_name();
-        print!(
+        write!(
+            out,
             "{indent:indent_level$}",
             indent = "",
             indent_level = indent_level * 2
-        );
+        )?;
         if test_file_path.is_dir() && test_file_path.read_dir()?.next().is_some() {
-            println!("{}:", test_file_name.into_string().unwrap());
+            writeln!(out, "{}:", test_file_name.into_string().unwrap())?;
             if test_highlights_indented(
                 loader,
                 loader_config,
@@ -82,6 +93,7 @@ fn test_highlights_indented(
                 &test_file_path,
                 use_color,
                 indent_level + 1,
+                out,
             )
             .is_err()
             {
@@ -106,27 +118,30 @@ fn test_highlights_indented(
                 fs::read(&test_file_path)?.as_slice(),
             ) {
                 Ok(assertion_count) => {
-                    println!(
+                    writeln!(
+                        out,
                         "✓ {} ({assertion_count} assertions)",
                         paint(
                             use_color.then_some(AnsiColor::Green),
                             test_file_name.to_string_lossy().as_ref()
                         ),
-                    );
+                    )?;
                 }
                 Err(e) => {
-                    println!(
+                    writeln!(
+                        out,
                         "✗ {}",
                         paint(
                             use_color.then_some(AnsiColor::Red),
                             test_file_name.to_string_lossy().as_ref()
                         )
-                    );
-                    println!(
+                    )?;
+                    writeln!(
+                        out,
                         "{indent:indent_level$}  {e}",
                         indent = "",
                         indent_level = indent_level * 2
-                    );
+                    )?;
                     failed = true;
                 }
             }
@@ -1,4 +1,4 @@
-use std::{fs, path::Path};
+use std::{fs, io::Write, path::Path};
 
 use anstyle::AnsiColor;
 use anyhow::{anyhow, Result};
@@ -49,9 +49,10 @@ pub fn test_tags(
     tags_context: &mut TagsContext,
     directory: &Path,
     use_color: bool,
+    out: &mut dyn Write,
 ) -> Result<()> {
     let mut failed = false;
-    println!("tags:");
+    writeln!(out, "tags:")?;
     for tag_test_file in fs::read_dir(directory)? {
         let tag_test_file = tag_test_file?;
         let test_file_path = tag_test_file.path();
@@ -73,23 +74,25 @@ pub fn test_tags(
             fs::read(&test_file_path)?.as_slice(),
         ) {
             Ok(assertion_count) => {
-                println!(
+                writeln!(
+                    out,
                     "  ✓ {} ({assertion_count} assertions)",
                     paint(
                         use_color.then_some(AnsiColor::Green),
                         test_file_name.to_string_lossy().as_ref()
                     ),
-                );
+                )?;
             }
             Err(e) => {
-                println!(
+                writeln!(
+                    out,
                     "  ✗ {}",
                     paint(
                         use_color.then_some(AnsiColor::Red),
                         test_file_name.to_string_lossy().as_ref()
                     )
-                );
-                println!("    {e}");
+                )?;
+                writeln!(out, "    {e}")?;
                 failed = true;
             }
         }
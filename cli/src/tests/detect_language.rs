@@ -1,8 +1,11 @@
 use std::{fs, path::Path};
 
-use tree_sitter_loader::Loader;
+use tree_sitter_loader::{Config, Loader};
 
-use crate::tests::helpers::fixtures::scratch_dir;
+use crate::{
+    generate::generate_parser_for_grammar,
+    tests::helpers::fixtures::{get_test_language, scratch_dir},
+};
 
 #[test]
 fn detect_language_by_first_line_regex() {
@@ -83,6 +86,139 @@ fn detect_language_by_first_line_regex() {
     );
 }
 
+#[test]
+fn detect_language_by_name_in_library_directory() {
+    let (parser_name, parser_code) = generate_parser_for_grammar(
+        r#"{
+            "name": "test_library_language",
+            "rules": {"program": {"type": "STRING", "value": "x"}}
+        }"#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+
+    // The test languages are compiled into the scratch directory, so treat it as a directory
+    // of installed libraries.
+    let mut loader = Loader::with_parser_lib_path(scratch_dir().join("unused"));
+    loader
+        .find_all_languages(&Config {
+            parser_directories: Vec::new(),
+            library_directories: vec![scratch_dir().to_path_buf()],
+        })
+        .unwrap();
+    let loaded_language = loader
+        .language_for_name("test_library_language")
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        loaded_language.node_kind_count(),
+        language.node_kind_count()
+    );
+    assert!(loader
+        .language_for_name("test_missing_language")
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn detect_language_by_first_line_regex_among_several() {
+    let mut loader = Loader::with_parser_lib_path(scratch_dir().to_path_buf());
+    let mut dirs = Vec::new();
+    for (name, first_line_regex) in [("alpha", "^#!.*alpha"), ("beta", "^#!.*beta")] {
+        let dir = tree_sitter_dir(
+            &format!(
+                r#"{{
+  "name": "tree-sitter-{name}",
+  "version": "0.0.1",
+  "tree-sitter": [
+    {{
+      "scope": "source.{name}",
+      "first-line-regex": "{first_line_regex}"
+    }}
+  ]
+}}
+"#
+            ),
+            name,
+        );
+        loader
+            .find_language_configurations_at_path(dir.path(), false)
+            .unwrap();
+        dirs.push(dir);
+    }
+
+    // every language is detected, not only the one whose regex happens to be checked last
+    for name in ["alpha", "beta"] {
+        let file_name = dirs[0].path().join("script");
+        fs::write(&file_name, format!("#!/usr/bin/env {name}\n")).unwrap();
+        assert_eq!(
+            get_lang_scope(&loader, &file_name),
+            Some(format!("source.{name}"))
+        );
+    }
+}
+
+#[test]
+fn detect_language_from_project_config() {
+    let project_dir = tree_sitter_dir(
+        r#"{
+  "name": "tree-sitter-project",
+  "version": "0.0.1",
+  "tree-sitter": [
+    {
+      "scope": "source.package",
+      "file-types": [
+        "package"
+      ]
+    }
+  ]
+}
+"#,
+        "project",
+    );
+    fs::write(
+        project_dir.path().join("tree-sitter.json"),
+        r#"{
+  "grammars": [
+    {
+      "scope": "source.project",
+      "file-types": [
+        "project"
+      ]
+    }
+  ],
+  "defaults": {}
+}
+"#,
+    )
+    .unwrap();
+
+    // the tree-sitter.json takes precedence over the package.json
+    let mut loader = Loader::with_parser_lib_path(scratch_dir().to_path_buf());
+    let config = loader
+        .find_language_configurations_at_path(project_dir.path(), false)
+        .unwrap();
+    assert_eq!(config.len(), 1);
+    assert_eq!(config[0].scope.as_ref().unwrap(), "source.project");
+
+    // the languages at a path are only added once
+    let config = loader
+        .find_language_configurations_at_path(project_dir.path(), true)
+        .unwrap();
+    assert_eq!(config.len(), 1);
+    assert_eq!(loader.get_all_language_configurations().len(), 1);
+
+    let file_name = project_dir.path().join("example.project");
+    fs::write(&file_name, "").unwrap();
+    assert_eq!(
+        get_lang_scope(&loader, &file_name),
+        Some("source.project".into())
+    );
+    let file_name = project_dir.path().join("example.package");
+    fs::write(&file_name, "").unwrap();
+    assert!(get_lang_scope(&loader, &file_name).is_none());
+}
+
 fn tree_sitter_dir(package_json: &str, name: &str) -> tempfile::TempDir {
     let temp_dir = tempfile::tempdir().unwrap();
     fs::write(temp_dir.path().join("package.json"), package_json).unwrap();
@@ -0,0 +1,445 @@
+use serde_json::{json, Value};
+use tree_sitter::{Node, Parser, Query, QueryCursor, Tree};
+
+use super::helpers::fixtures::generate_test_language;
+use crate::generate::{generate_parser, read_table_blob, GenerateOptions};
+
+#[test]
+fn test_table_dump() {
+    let (output, language) = generate_test_language(
+        r#"{
+            "name": "table_dump_test",
+            "extras": [{"type": "PATTERN", "value": " "}],
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "word"}},
+                "word": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#,
+        &GenerateOptions {
+            dump_tables: true,
+            ..Default::default()
+        },
+    );
+
+    let dump = output.table_dump.unwrap();
+    assert!(dump.starts_with("Parse table\n===========\n\nstate 0 (lex state 0)\n"));
+    assert!(dump.contains("  word: shift "));
+    assert!(dump.contains("  EOF: reduce program (1)"));
+    assert!(dump.contains("  program: goto "));
+    assert!(dump.contains("\nLex table\n=========\n"));
+    assert!(dump.contains("  accept word\n"));
+    assert!(dump.contains("  ['a'-'z']: advance "));
+    assert!(dump.contains(": skip "));
+
+    // The dump describes the states of the compiled parser: each word is lexed in a state
+    // whose entry shifts it.
+    assert_eq!(
+        dump.matches(" (lex state ").count(),
+        language.parse_state_count()
+    );
+    let tree = parse(&language, "one two three");
+    for node in leaves(&tree) {
+        let heading = format!("state {} (lex state ", node.parse_state());
+        let state = dump[dump.find(&heading).unwrap()..]
+            .split("\n\n")
+            .next()
+            .unwrap();
+        let word_entry = state.lines().find(|line| line.starts_with("  word: "));
+        assert!(word_entry.unwrap().contains("shift "), "{state}");
+    }
+}
+
+#[test]
+fn test_table_blob() {
+    let (output, language) = generate_test_language(
+        r#"{
+            "name": "table_blob_test",
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "word"}},
+                "word": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#,
+        &GenerateOptions {
+            table_blob: true,
+            ..Default::default()
+        },
+    );
+
+    // The blob has the symbols and states of the compiled parser.
+    let blob = read_table_blob(&output.table_blob.unwrap()).unwrap();
+    assert_eq!(blob.name, "table_blob_test");
+    assert_eq!(blob.abi_version as usize, tree_sitter::LANGUAGE_VERSION);
+    assert_eq!(blob.parse_states.len(), language.parse_state_count());
+    assert_eq!(blob.symbols.len(), language.node_kind_count());
+    for (id, symbol) in blob.symbols.iter().enumerate().skip(1) {
+        let id = id as u16;
+        assert_eq!(language.node_kind_for_id(id), Some(symbol.name.as_str()));
+        assert_eq!(language.node_kind_is_visible(id), symbol.visible);
+        assert_eq!(language.node_kind_is_named(id), symbol.named);
+    }
+}
+
+#[test]
+fn test_ascii_bitmap_lexer_with_invalid_bytes() {
+    let (output, language) = generate_test_language(
+        r#"{
+            "name": "ascii_bitmap_test",
+            "extras": [{"type": "PATTERN", "value": " "}],
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "CHOICE", "members": [
+                    {"type": "SYMBOL", "name": "control"},
+                    {"type": "SYMBOL", "name": "word"}
+                ]}},
+                "control": {"type": "PATTERN", "value": "[\\x01\\x03\\x05]+"},
+                "word": {"type": "PATTERN", "value": "[a-fA-F0-9_\\x7f]+"}
+            }
+        }"#,
+        &GenerateOptions {
+            lexer_strategy: Some("ascii-bitmap".to_string()),
+            ..Default::default()
+        },
+    );
+    assert!(output.parser_c.contains("lex_ascii_bitmap_2["));
+
+    // The lexer reads an invalid UTF-8 byte as -1, which isn't in any bitmap.
+    let mut parser = Parser::new();
+    parser.set_language(&language).unwrap();
+    for source in [&b"ab\xffcd"[..], b"\x01\xff\x03", b"\xff"] {
+        let tree = parser.parse(source, None).unwrap();
+        let root = tree.root_node();
+        assert!(root.has_error(), "{}", root.to_sexp());
+        for node in leaves(&tree) {
+            if !node.is_error() && !node.parent().unwrap().is_error() {
+                assert!(
+                    !source[node.byte_range()].contains(&0xff),
+                    "{}",
+                    root.to_sexp()
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn test_source_map() {
+    let (output, language) = generate_test_language(
+        r#"{
+            "name": "source_map_test",
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "assignment"}},
+                "assignment": {"type": "SEQ", "members": [
+                    {"type": "SYMBOL", "name": "identifier"},
+                    {"type": "STRING", "value": "="},
+                    {"type": "SYMBOL", "name": "number"}
+                ]},
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                "number": {"type": "PATTERN", "value": "\\d+"}
+            }
+        }"#,
+        &GenerateOptions {
+            source_map: true,
+            ..Default::default()
+        },
+    );
+
+    let source_map: Value = serde_json::from_str(&output.source_map.unwrap()).unwrap();
+    let parse_states = source_map["parse_states"].as_array().unwrap();
+    assert_eq!(parse_states.len(), language.parse_state_count());
+
+    // The state after an identifier is in the middle of an assignment.
+    let state = parse_states
+        .iter()
+        .find(|state| state["symbol_sequence"] == json!(["identifier"]))
+        .unwrap();
+    assert_eq!(state["rules"], json!(["assignment"]));
+    assert_eq!(
+        state["items"],
+        json!(["assignment → identifier • = number"])
+    );
+
+    // Lex states that don't conflict are merged, so the lex state of that parse
+    // state leads to the `=` token, among others.
+    let lex_state = &source_map["lex_states"][state["lex_state"].as_u64().unwrap() as usize];
+    assert!(lex_state["tokens"]
+        .as_array()
+        .unwrap()
+        .contains(&json!("\"=\"")));
+    assert!(lex_state["parse_states"]
+        .as_array()
+        .unwrap()
+        .contains(&state["id"]));
+    assert!(source_map.get("keyword_lex_states").is_none());
+
+    // Each token of a parsed document is lexed in a lex state that the source map says
+    // leads to it.
+    let tree = parse(&language, "a = 1\nbc = 23");
+    for node in leaves(&tree) {
+        let state = &parse_states[usize::from(node.parse_state())];
+        let lex_state = &source_map["lex_states"][state["lex_state"].as_u64().unwrap() as usize];
+        let token = if node.is_named() {
+            node.kind().to_string()
+        } else {
+            format!("{:?}", node.kind())
+        };
+        assert!(
+            lex_state["tokens"]
+                .as_array()
+                .unwrap()
+                .contains(&json!(token)),
+            "{token} in {lex_state}"
+        );
+    }
+}
+
+#[test]
+fn test_rust_ast() {
+    let (output, language) = generate_test_language(
+        r#"{
+            "name": "rust_ast_test",
+            "supertypes": ["_expression"],
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "_expression"}},
+                "_expression": {"type": "CHOICE", "members": [
+                    {"type": "SYMBOL", "name": "call"},
+                    {"type": "SYMBOL", "name": "identifier"},
+                    {"type": "SYMBOL", "name": "number"}
+                ]},
+                "call": {"type": "SEQ", "members": [
+                    {"type": "FIELD", "name": "function", "content": {"type": "SYMBOL", "name": "identifier"}},
+                    {"type": "STRING", "value": "("},
+                    {"type": "REPEAT", "content": {"type": "FIELD", "name": "type", "content": {"type": "CHOICE", "members": [
+                        {"type": "SYMBOL", "name": "identifier"},
+                        {"type": "SYMBOL", "name": "number"}
+                    ]}}},
+                    {"type": "STRING", "value": ")"}
+                ]},
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                "number": {"type": "PATTERN", "value": "\\d+"}
+            }
+        }"#,
+        &GenerateOptions {
+            rust_ast: true,
+            ..Default::default()
+        },
+    );
+
+    let code = output.rust_ast.unwrap();
+    assert!(code.starts_with("// This file is generated by `tree-sitter generate --rust-ast`."));
+    assert!(code.contains("pub enum Expression<'tree> {\n    Call(Call<'tree>),\n    Identifier(Identifier<'tree>),\n    Number(Number<'tree>),\n}"));
+    assert!(code.contains("(node.is_named() && node.kind() == \"call\").then_some(Self(node))"));
+    assert!(code.contains("pub fn function(&self) -> Option<Identifier<'tree>> {"));
+    assert!(code.contains("pub fn r#type(&self) -> Vec<CallType<'tree>> {"));
+    assert!(code.contains("pub enum CallType<'tree> {\n    Identifier(Identifier<'tree>),\n    Number(Number<'tree>),\n}"));
+    assert!(code.contains("pub fn children(&self) -> Vec<Expression<'tree>> {"));
+
+    // The wrappers only refer to the node kinds and fields of the compiled parser.
+    for (index, _) in code.match_indices("node.kind() == \"") {
+        let kind = code[index + "node.kind() == \"".len()..]
+            .split('"')
+            .next()
+            .unwrap();
+        assert_ne!(language.id_for_node_kind(kind, true), 0, "{kind}");
+    }
+    assert!(language.field_id_for_name("function").is_some());
+    assert!(language.field_id_for_name("type").is_some());
+}
+
+#[test]
+fn test_highlights_query() {
+    let (output, language) = generate_test_language(
+        r#"{
+            "name": "highlights_query_test",
+            "extras": [{"type": "SYMBOL", "name": "line_comment"}, {"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "statement"}},
+                "statement": {"type": "SEQ", "members": [
+                    {"type": "STRING", "value": "let"},
+                    {"type": "SYMBOL", "name": "identifier"},
+                    {"type": "STRING", "value": "="},
+                    {"type": "CHOICE", "members": [
+                        {"type": "SYMBOL", "name": "string_literal"},
+                        {"type": "SYMBOL", "name": "number"},
+                        {"type": "STRING", "value": "true"},
+                        {"type": "SEQ", "members": [
+                            {"type": "STRING", "value": "("},
+                            {"type": "SYMBOL", "name": "identifier"},
+                            {"type": "STRING", "value": ")"}
+                        ]}
+                    ]},
+                    {"type": "STRING", "value": ";"}
+                ]},
+                "string_literal": {"type": "PATTERN", "value": "\"[^\"]*\""},
+                "number": {"type": "PATTERN", "value": "\\d+"},
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"},
+                "line_comment": {"type": "PATTERN", "value": "//.*"}
+            }
+        }"#,
+        &GenerateOptions::default(),
+    );
+
+    let query = output.highlights_query.unwrap();
+    assert_eq!(
+        query,
+        "; This file was generated by `tree-sitter generate` as a starting point for highlighting.
+
+\"let\" @keyword
+
+\"=\" @operator
+
+[
+  \"(\"
+  \")\"
+] @punctuation.bracket
+
+\";\" @punctuation.delimiter
+
+\"true\" @constant.builtin
+
+(number) @number
+
+(string_literal) @string
+
+(line_comment) @comment
+"
+    );
+
+    // The query is valid for the compiled parser, and highlights a parsed document.
+    let source = "let a = 1; // one\nlet b = (a);";
+    assert_eq!(
+        captures(&language, &query, source),
+        [
+            "keyword: let",
+            "operator: =",
+            "number: 1",
+            "punctuation.delimiter: ;",
+            "comment: // one",
+            "keyword: let",
+            "operator: =",
+            "punctuation.bracket: (",
+            "punctuation.bracket: )",
+            "punctuation.delimiter: ;",
+        ]
+    );
+}
+
+#[test]
+fn test_injections_query() {
+    let (output, language) = generate_test_language(
+        r#"{
+            "name": "injections_query_test",
+            "rules": {
+                "document": {"type": "REPEAT", "content": {"type": "CHOICE", "members": [
+                    {"type": "SYMBOL", "name": "code_block"},
+                    {"type": "SYMBOL", "name": "script"}
+                ]}},
+                "code_block": {"type": "SEQ", "members": [
+                    {"type": "STRING", "value": "```"},
+                    {"type": "FIELD", "name": "language", "content": {"type": "SYMBOL", "name": "info"}},
+                    {"type": "FIELD", "name": "body", "content": {"type": "SYMBOL", "name": "text"}},
+                    {"type": "STRING", "value": "```"}
+                ]},
+                "script": {"type": "PATTERN", "value": "<[^>]*>"},
+                "info": {"type": "PATTERN", "value": "[a-z]+"},
+                "text": {"type": "PATTERN", "value": "[^`]+"}
+            },
+            "injections": [
+                {"node": "code_block", "language_field": "language", "content_field": "body"},
+                {"node": "script", "language": "javascript", "include_children": true}
+            ]
+        }"#,
+        &GenerateOptions::default(),
+    );
+
+    let query = output.injections_query.unwrap();
+    assert_eq!(
+        query,
+        "; This file is generated by `tree-sitter generate` from the grammar's `injections`.
+
+(code_block
+  language: _ @injection.language
+  body: _ @injection.content)
+
+((script) @injection.content
+ (#set! injection.language \"javascript\")
+ (#set! injection.include-children))
+"
+    );
+
+    // The query is valid for the compiled parser, and finds the injections in a parsed
+    // document.
+    assert_eq!(
+        captures(&language, &query, "```rust\nfn main() {}\n```<a()>"),
+        [
+            "injection.language: rust",
+            "injection.content: \nfn main() {}\n",
+            "injection.content: <a()>",
+        ]
+    );
+}
+
+#[test]
+fn test_injections_query_with_unknown_field() {
+    let result = generate_parser(
+        r#"{
+            "name": "doc",
+            "rules": {
+                "document": {"type": "SYMBOL", "name": "script"},
+                "script": {"type": "PATTERN", "value": "<[^>]*>"}
+            },
+            "injections": [{"node": "script", "language": "js", "content_field": "body"}]
+        }"#,
+        &GenerateOptions::default(),
+    );
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "Injection node `script` does not have a field named `body`"
+    );
+}
+
+fn parse(language: &tree_sitter::Language, source: &str) -> Tree {
+    let mut parser = Parser::new();
+    parser.set_language(language).unwrap();
+    let tree = parser.parse(source, None).unwrap();
+    assert!(
+        !tree.root_node().has_error(),
+        "{}",
+        tree.root_node().to_sexp()
+    );
+    tree
+}
+
+fn leaves(tree: &Tree) -> Vec<Node<'_>> {
+    let mut leaves = Vec::new();
+    let mut cursor = tree.walk();
+    'outer: loop {
+        let node = cursor.node();
+        if node.child_count() == 0 {
+            leaves.push(node);
+        } else if cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'outer;
+            }
+        }
+    }
+    leaves
+}
+
+fn captures(language: &tree_sitter::Language, query: &str, source: &str) -> Vec<String> {
+    let query = Query::new(language, query).unwrap();
+    let tree = parse(language, source);
+    QueryCursor::new()
+        .captures(&query, tree.root_node(), source.as_bytes())
+        .map(|(m, index)| {
+            let capture = m.captures[index];
+            format!(
+                "{}: {}",
+                query.capture_names()[capture.index as usize],
+                capture.node.utf8_text(source.as_bytes()).unwrap()
+            )
+        })
+        .collect()
+}
@@ -6,11 +6,12 @@ use std::{
 use anyhow::Context;
 use lazy_static::lazy_static;
 use tree_sitter::Language;
+#[cfg(feature = "highlight")]
 use tree_sitter_highlight::HighlightConfiguration;
 use tree_sitter_loader::{CompileConfig, Loader};
 use tree_sitter_tags::TagsConfiguration;
 
-use crate::generate::ALLOC_HEADER;
+use crate::generate::{generate_parser, GenerateOptions, GeneratedOutput, ALLOC_HEADER};
 
 include!("./dirs.rs");
 
@@ -24,6 +25,7 @@ lazy_static! {
     };
 }
 
+#[cfg(feature = "highlight")]
 pub fn test_loader() -> &'static Loader {
     &TEST_LOADER
 }
@@ -47,6 +49,7 @@ pub fn get_language_queries_path(language_name: &str) -> PathBuf {
     GRAMMARS_DIR.join(language_name).join("queries")
 }
 
+#[cfg(feature = "highlight")]
 pub fn get_highlight_config(
     language_name: &str,
     injection_query_filename: Option<&str>,
@@ -135,3 +138,15 @@ pub fn get_test_language(name: &str, parser_code: &str, path: Option<&Path>) ->
 
     TEST_LOADER.load_language_at_path_with_name(config).unwrap()
 }
+
+/// Generate a parser with the given options, and compile and load it, so that the other files
+/// that generation produces can be checked against the parser. The grammar's name must be
+/// unique among the tests, because the compiled parser is cached under it.
+pub fn generate_test_language(
+    grammar_json: &str,
+    options: &GenerateOptions,
+) -> (GeneratedOutput, Language) {
+    let output = generate_parser(grammar_json, options).unwrap();
+    let language = get_test_language(&output.name, &output.parser_c, None);
+    (output, language)
+}
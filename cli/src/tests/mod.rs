@@ -1,7 +1,9 @@
 mod async_context_test;
 mod corpus_test;
 mod detect_language;
+mod generated_outputs_test;
 mod helpers;
+#[cfg(feature = "highlight")]
 mod highlight_test;
 mod language_test;
 mod node_test;
@@ -10,9 +12,12 @@ mod parser_test;
 mod pathological_test;
 mod query_test;
 mod tags_test;
+#[cfg(feature = "highlight")]
 mod test_highlight_test;
 mod test_tags_test;
+mod test_test;
 mod text_provider_test;
+mod tree_diff_test;
 mod tree_test;
 
 #[cfg(feature = "wasm")]
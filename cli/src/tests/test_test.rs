@@ -0,0 +1,122 @@
+use std::fs;
+
+use tree_sitter::Parser;
+
+use super::helpers::fixtures::get_test_language;
+use crate::{
+    generate::generate_parser_for_grammar,
+    test::{run_tests_at_path, Summary, TestOptions},
+};
+
+#[test]
+fn test_corpus_with_error_recovery_expectations() {
+    let (parser_name, parser_code) = generate_parser_for_grammar(
+        r#"{
+            "name": "test_error_recovery",
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "statement"}},
+                "statement": {
+                    "type": "SEQ",
+                    "members": [
+                        {"type": "SYMBOL", "name": "word"},
+                        {"type": "STRING", "value": ";"}
+                    ]
+                },
+                "word": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#,
+    )
+    .unwrap();
+    let language = get_test_language(&parser_name, &parser_code, None);
+
+    let corpus_dir = tempfile::tempdir().unwrap();
+    let corpus_path = corpus_dir.path().join("errors.txt");
+    fs::write(
+        &corpus_path,
+        r#"
+==================
+Expected recovery
+:error
+==================
+
+a
+
+---
+
+(program (ERROR (word)))
+
+==================
+Unexpected recovery
+:error
+==================
+
+a
+
+---
+
+(program (statement (word) (MISSING ";")))
+
+==================
+Any recovery
+:error
+==================
+
+a
+
+---
+
+==================
+Valid input
+:error
+==================
+
+a;
+
+---
+"#,
+    )
+    .unwrap();
+
+    let mut parser = Parser::new();
+    parser.set_language(&language).unwrap();
+    let mut opts = TestOptions {
+        path: corpus_path,
+        debug: false,
+        debug_graph: false,
+        include: None,
+        exclude: None,
+        update: false,
+        open_log: false,
+        languages: [("", &language)].into_iter().collect(),
+        color: false,
+        test_num: 1,
+        show_fields: false,
+        jobs: 1,
+        quiet: true,
+        json: true,
+    };
+    let mut summary = Summary::default();
+    assert!(run_tests_at_path(&mut parser, &mut opts, &mut summary).is_err());
+
+    assert_eq!(summary.corpus_tests, 4);
+    assert_eq!(
+        summary
+            .corpus_failures
+            .iter()
+            .map(|failure| (
+                failure.name.as_str(),
+                failure.actual.as_str(),
+                failure.expected.as_str()
+            ))
+            .collect::<Vec<_>>(),
+        [
+            (
+                "Unexpected recovery",
+                "(program (ERROR (word)))",
+                "(program (statement (word) (MISSING \";\")))",
+            ),
+            ("Valid input", "(program (statement (word)))", "NO ERROR"),
+        ]
+    );
+}
@@ -0,0 +1,88 @@
+use tree_sitter::{Language, Parser};
+
+use super::helpers::fixtures::get_test_language;
+use crate::{
+    generate::generate_parser_for_grammar,
+    tree_diff::{diff_trees, Change},
+};
+
+#[test]
+fn test_diff_trees_ignores_formatting() {
+    let language = get_assignments_language();
+    let changes = diff_sources(&language, "a = b;\nc = d;", "a=b;  c =\n  d;");
+    assert_eq!(changes, []);
+}
+
+#[test]
+fn test_diff_trees_with_changed_added_and_removed_nodes() {
+    let language = get_assignments_language();
+    let changes = diff_sources(&language, "a = b;\nc = d;", "a = e;\nc = d;\nf = g;");
+    assert_eq!(
+        changes
+            .iter()
+            .map(|change| change.render(false))
+            .collect::<Vec<_>>(),
+        [
+            "~ value: (identifier) [0, 4] - [0, 5] -> [0, 4] - [0, 5]: \"b\" -> \"e\"",
+            "+ (assignment) [2, 0] - [2, 6]",
+        ]
+    );
+
+    let changes = diff_sources(&language, "a = b;\nc = d;", "c = d;");
+    assert_eq!(
+        changes
+            .iter()
+            .map(|change| change.render(false))
+            .collect::<Vec<_>>(),
+        ["- (assignment) [0, 0] - [0, 6]"]
+    );
+    assert!(matches!(
+        &changes[0],
+        Change::Removed { range, .. } if range.start_byte == 0 && range.end_byte == 6
+    ));
+}
+
+fn diff_sources(language: &Language, old_source: &str, new_source: &str) -> Vec<Change> {
+    let mut parser = Parser::new();
+    parser.set_language(language).unwrap();
+    let old_tree = parser.parse(old_source, None).unwrap();
+    let new_tree = parser.parse(new_source, None).unwrap();
+    diff_trees(
+        &old_tree,
+        old_source.as_bytes(),
+        &new_tree,
+        new_source.as_bytes(),
+    )
+}
+
+fn get_assignments_language() -> Language {
+    let (parser_name, parser_code) = generate_parser_for_grammar(
+        r#"{
+            "name": "test_tree_diff",
+            "extras": [{"type": "PATTERN", "value": "\\s"}],
+            "rules": {
+                "program": {"type": "REPEAT", "content": {"type": "SYMBOL", "name": "assignment"}},
+                "assignment": {
+                    "type": "SEQ",
+                    "members": [
+                        {
+                            "type": "FIELD",
+                            "name": "name",
+                            "content": {"type": "SYMBOL", "name": "identifier"}
+                        },
+                        {"type": "STRING", "value": "="},
+                        {
+                            "type": "FIELD",
+                            "name": "value",
+                            "content": {"type": "SYMBOL", "name": "identifier"}
+                        },
+                        {"type": "STRING", "value": ";"}
+                    ]
+                },
+                "identifier": {"type": "PATTERN", "value": "[a-z]+"}
+            }
+        }"#,
+    )
+    .unwrap();
+    get_test_language(&parser_name, &parser_code, None)
+}
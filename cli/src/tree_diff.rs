@@ -0,0 +1,303 @@
+use std::{
+    cmp::max,
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+use anstyle::AnsiColor;
+use serde::Serialize;
+use tree_sitter::{Node, Point, Tree};
+
+use crate::test::paint;
+
+/// A structural difference between the syntax trees of two versions of a file.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum Change {
+    /// A node that is only in the new tree.
+    Added { node: String, range: Span },
+    /// A node that is only in the old tree.
+    Removed { node: String, range: Span },
+    /// A leaf node that is in both trees, but whose text differs.
+    Changed {
+        node: String,
+        old_range: Span,
+        new_range: Span,
+        old_text: String,
+        new_text: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_point: SpanPoint,
+    pub end_point: SpanPoint,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SpanPoint {
+    pub row: usize,
+    pub column: usize,
+}
+
+impl From<Point> for SpanPoint {
+    fn from(point: Point) -> Self {
+        Self {
+            row: point.row,
+            column: point.column,
+        }
+    }
+}
+
+impl From<Node<'_>> for Span {
+    fn from(node: Node) -> Self {
+        Self {
+            start_byte: node.start_byte(),
+            end_byte: node.end_byte(),
+            start_point: node.start_position().into(),
+            end_point: node.end_position().into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}, {}] - [{}, {}]",
+            self.start_point.row,
+            self.start_point.column,
+            self.end_point.row,
+            self.end_point.column
+        )
+    }
+}
+
+impl Change {
+    /// Describe the change on a single line, in the style of a diff.
+    pub fn render(&self, use_color: bool) -> String {
+        match self {
+            Self::Added { node, range } => paint(
+                use_color.then_some(AnsiColor::Green),
+                &format!("+ {node} {range}"),
+            ),
+            Self::Removed { node, range } => paint(
+                use_color.then_some(AnsiColor::Red),
+                &format!("- {node} {range}"),
+            ),
+            Self::Changed {
+                node,
+                old_range,
+                new_range,
+                old_text,
+                new_text,
+            } => paint(
+                use_color.then_some(AnsiColor::Yellow),
+                &format!("~ {node} {old_range} -> {new_range}: {old_text:?} -> {new_text:?}"),
+            ),
+        }
+    }
+}
+
+/// A node, along with the name of the field that it has in its parent.
+#[derive(Clone, Copy)]
+struct Child<'tree> {
+    node: Node<'tree>,
+    field_name: Option<&'static str>,
+}
+
+impl Child<'_> {
+    /// Whether the nodes can be matched up with each other, regardless of their contents.
+    fn matches(&self, other: &Self) -> bool {
+        self.node.kind() == other.node.kind()
+            && self.node.is_named() == other.node.is_named()
+            && self.node.is_missing() == other.node.is_missing()
+            && self.field_name == other.field_name
+    }
+
+    fn describe(&self) -> String {
+        let kind = if self.node.is_named() {
+            self.node.kind().to_string()
+        } else {
+            format!("{:?}", self.node.kind())
+        };
+        let node = if self.node.is_missing() {
+            format!("(MISSING {kind})")
+        } else {
+            format!("({kind})")
+        };
+        if let Some(field_name) = self.field_name {
+            format!("{field_name}: {node}")
+        } else {
+            node
+        }
+    }
+
+    /// A hash of the node's kind, its descendants' kinds, and its leaves' text, to quickly
+    /// tell whether two nodes are equal.
+    fn fingerprint(&self, source: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash_into(source, &mut hasher);
+        hasher.finish()
+    }
+
+    fn hash_into(&self, source: &[u8], hasher: &mut DefaultHasher) {
+        self.node.kind().hash(hasher);
+        self.node.is_named().hash(hasher);
+        self.node.is_missing().hash(hasher);
+        self.field_name.hash(hasher);
+        let children = self.children();
+        if children.is_empty() {
+            source[self.node.byte_range()].hash(hasher);
+        } else {
+            children.len().hash(hasher);
+            for child in children {
+                child.hash_into(source, hasher);
+            }
+        }
+    }
+
+    fn children(&self) -> Vec<Self> {
+        let mut children = Vec::new();
+        let mut cursor = self.node.walk();
+        if cursor.goto_first_child() {
+            loop {
+                children.push(Child {
+                    node: cursor.node(),
+                    field_name: cursor.field_name(),
+                });
+                if !cursor.goto_next_sibling() {
+                    break;
+                }
+            }
+        }
+        children
+    }
+}
+
+/// Compare the syntax trees of two versions of a file. The nodes are compared by their
+/// kinds, their field names and the text of the leaves, and not by their positions, so
+/// changes that only move nodes around, like reformatting, leave the trees equal.
+pub fn diff_trees(
+    old_tree: &Tree,
+    old_source: &[u8],
+    new_tree: &Tree,
+    new_source: &[u8],
+) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let old_root = Child {
+        node: old_tree.root_node(),
+        field_name: None,
+    };
+    let new_root = Child {
+        node: new_tree.root_node(),
+        field_name: None,
+    };
+    if old_root.matches(&new_root) {
+        diff_nodes(old_root, old_source, new_root, new_source, &mut changes);
+    } else {
+        changes.push(Change::Removed {
+            node: old_root.describe(),
+            range: old_root.node.into(),
+        });
+        changes.push(Change::Added {
+            node: new_root.describe(),
+            range: new_root.node.into(),
+        });
+    }
+    changes
+}
+
+fn diff_nodes(
+    old: Child,
+    old_source: &[u8],
+    new: Child,
+    new_source: &[u8],
+    changes: &mut Vec<Change>,
+) {
+    let old_children = old.children();
+    let new_children = new.children();
+
+    if old_children.is_empty() && new_children.is_empty() {
+        let old_text = &old_source[old.node.byte_range()];
+        let new_text = &new_source[new.node.byte_range()];
+        if old_text != new_text {
+            changes.push(Change::Changed {
+                node: new.describe(),
+                old_range: old.node.into(),
+                new_range: new.node.into(),
+                old_text: String::from_utf8_lossy(old_text).to_string(),
+                new_text: String::from_utf8_lossy(new_text).to_string(),
+            });
+        }
+        return;
+    }
+
+    // Match up the children along the longest common subsequence of their kinds, preferring
+    // to match children that are equal. The children that aren't matched were removed or
+    // added.
+    let old_fingerprints = old_children
+        .iter()
+        .map(|child| child.fingerprint(old_source))
+        .collect::<Vec<_>>();
+    let new_fingerprints = new_children
+        .iter()
+        .map(|child| child.fingerprint(new_source))
+        .collect::<Vec<_>>();
+    let weight = |i: usize, j: usize| {
+        if !old_children[i].matches(&new_children[j]) {
+            0
+        } else if old_fingerprints[i] == new_fingerprints[j] {
+            2
+        } else {
+            1
+        }
+    };
+
+    let (n, m) = (old_children.len(), new_children.len());
+    let mut lengths = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = max(lengths[i + 1][j], lengths[i][j + 1]);
+            let weight = weight(i, j);
+            if weight > 0 {
+                lengths[i][j] = max(lengths[i][j], lengths[i + 1][j + 1] + weight);
+            }
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n
+            && j < m
+            && weight(i, j) > 0
+            && lengths[i][j] == lengths[i + 1][j + 1] + weight(i, j)
+        {
+            if old_fingerprints[i] != new_fingerprints[j] {
+                diff_nodes(
+                    old_children[i],
+                    old_source,
+                    new_children[j],
+                    new_source,
+                    changes,
+                );
+            }
+            i += 1;
+            j += 1;
+        } else if i < n && (j == m || lengths[i + 1][j] >= lengths[i][j + 1]) {
+            changes.push(Change::Removed {
+                node: old_children[i].describe(),
+                range: old_children[i].node.into(),
+            });
+            i += 1;
+        } else {
+            changes.push(Change::Added {
+                node: new_children[j].describe(),
+                range: new_children[j].node.into(),
+            });
+            j += 1;
+        }
+    }
+}
@@ -28,7 +28,7 @@ pub fn get_grammar_name(language_dir: &Path) -> Result<String> {
         .with_context(|| format!("Failed to read grammar file {grammar_json_path:?}"))?;
     let grammar: GrammarJSON = serde_json::from_str(&grammar_json)
         .with_context(|| format!("Failed to parse grammar file {grammar_json_path:?}"))?;
-    Ok(grammar.name)
+    Ok(grammar.name.into())
 }
 
 pub fn compile_language_to_wasm(
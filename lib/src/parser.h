@@ -7,12 +7,32 @@ extern "C" {
 
 #include <stdbool.h>
 #include <stdint.h>
+
+/* Freestanding parsers only depend on the headers that are available without libc. */
+#ifdef TREE_SITTER_FREESTANDING
+#include <stddef.h>
+#else
 #include <stdlib.h>
+#endif
 
 #define ts_builtin_sym_error ((TSSymbol)-1)
 #define ts_builtin_sym_end 0
 #define TREE_SITTER_SERIALIZATION_BUFFER_SIZE 1024
 
+/* The `inline` keyword is only standard since C99. Parsers can be compiled as C89 by
+   defining `TS_INLINE` as the compiler's equivalent keyword, or as nothing. */
+#ifndef TS_INLINE
+#if defined(__cplusplus) || (defined(__STDC_VERSION__) && __STDC_VERSION__ >= 199901L)
+#define TS_INLINE inline
+#elif defined(_MSC_VER)
+#define TS_INLINE __inline
+#elif defined(__GNUC__)
+#define TS_INLINE __inline__
+#else
+#define TS_INLINE
+#endif
+#endif
+
 #ifndef TREE_SITTER_API_H_
 typedef uint16_t TSStateId;
 typedef uint16_t TSSymbol;
@@ -54,7 +74,7 @@ typedef enum {
   TSParseActionTypeShift,
   TSParseActionTypeReduce,
   TSParseActionTypeAccept,
-  TSParseActionTypeRecover,
+  TSParseActionTypeRecover
 } TSParseActionType;
 
 typedef union {
@@ -131,13 +151,13 @@ struct TSLanguage {
   const TSStateId *primary_state_ids;
 };
 
-static inline bool set_contains(TSCharacterRange *ranges, uint32_t len, int32_t lookahead) {
+static TS_INLINE bool set_contains(const TSCharacterRange *ranges, uint32_t len, int32_t lookahead) {
   uint32_t index = 0;
   uint32_t size = len - index;
   while (size > 1) {
     uint32_t half_size = size / 2;
     uint32_t mid_index = index + half_size;
-    TSCharacterRange *range = &ranges[mid_index];
+    const TSCharacterRange *range = &ranges[mid_index];
     if (lookahead >= range->start && lookahead <= range->end) {
       return true;
     } else if (lookahead > range->end) {
@@ -145,8 +165,7 @@ static inline bool set_contains(TSCharacterRange *ranges, uint32_t len, int32_t
     }
     size -= half_size;
   }
-  TSCharacterRange *range = &ranges[index];
-  return (lookahead >= range->start && lookahead <= range->end);
+  return (lookahead >= ranges[index].start && lookahead <= ranges[index].end);
 }
 
 /*
@@ -181,7 +200,8 @@ static inline bool set_contains(TSCharacterRange *ranges, uint32_t len, int32_t
 #define ADVANCE_MAP(...)                                              \
   {                                                                   \
     static const uint16_t map[] = { __VA_ARGS__ };                    \
-    for (uint32_t i = 0; i < sizeof(map) / sizeof(map[0]); i += 2) {  \
+    uint32_t i;                                                       \
+    for (i = 0; i < sizeof(map) / sizeof(map[0]); i += 2) {           \
       if (map[i] == lookahead) {                                      \
         state = map[i + 1];                                           \
         goto next_state;                                              \
@@ -263,4 +283,4 @@ static inline bool set_contains(TSCharacterRange *ranges, uint32_t len, int32_t
 }
 #endif
 
-#endif  // TREE_SITTER_PARSER_H_
+#endif  /* TREE_SITTER_PARSER_H_ */
@@ -139,6 +139,7 @@ fn tag_next_version(
         "Cargo.lock",
         "cli/Cargo.toml",
         "cli/config/Cargo.toml",
+        "cli/generate-c/Cargo.toml",
         "cli/loader/Cargo.toml",
         "lib/Cargo.toml",
         "highlight/Cargo.toml",